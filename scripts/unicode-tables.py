@@ -0,0 +1,70 @@
+#!/usr/bin/env python3
+"""Generate src/unicode/tables.rs from the Unicode database bundled with Python.
+
+Run from the repository's root: python3 scripts/unicode-tables.py > src/unicode/tables.rs
+"""
+
+import sys
+import unicodedata
+
+HANGUL = range(0xAC00, 0xAC00 + 11172)
+
+
+def canonical(code):
+    """The canonical decomposition mapping of a character, if it has one."""
+    decomposition = unicodedata.decomposition(chr(code))
+    if not decomposition or decomposition.startswith("<"):
+        return None
+    return [int(part, 16) for part in decomposition.split()]
+
+
+def full(code):
+    """The full canonical decomposition of a character."""
+    mapping = canonical(code)
+    if mapping is None:
+        return [code]
+    return [part for code in mapping for part in full(code)]
+
+
+def char(code):
+    return f"'\\u{{{code:04x}}}'"
+
+
+def rows(items, per_line):
+    for start in range(0, len(items), per_line):
+        yield "    " + " ".join(items[start : start + per_line])
+
+
+codes = [code for code in range(0x110000) if code not in HANGUL and not 0xD800 <= code < 0xE000]
+decompositions = [(code, full(code)) for code in codes if canonical(code) is not None]
+combining = [(code, unicodedata.combining(chr(code))) for code in codes]
+combining = [(code, klass) for code, klass in combining if klass]
+compositions = sorted(
+    ((mapping[0], mapping[1]), code)
+    for code in codes
+    if (mapping := canonical(code)) is not None
+    and len(mapping) == 2
+    and unicodedata.normalize("NFC", chr(mapping[0]) + chr(mapping[1])) == chr(code)
+)
+
+print(f"// Generated by scripts/unicode-tables.py from Unicode {unicodedata.unidata_version}. Don't edit.")
+print()
+print("/// The full canonical decompositions of characters, other than Hangul syllables, by character.")
+print("#[rustfmt::skip]")
+print("pub(super) const DECOMPOSITIONS: &[(char, &[char])] = &[")
+items = [f"({char(code)}, &[{', '.join(map(char, mapping))}])," for code, mapping in decompositions]
+print("\n".join(rows(items, 3)))
+print("];")
+print()
+print("/// The canonical combining classes of characters whose class isn't zero, by character.")
+print("#[rustfmt::skip]")
+print("pub(super) const COMBINING_CLASSES: &[(char, u8)] = &[")
+print("\n".join(rows([f"({char(code)}, {klass})," for code, klass in combining], 6)))
+print("];")
+print()
+print("/// The primary composites of pairs of characters, other than Hangul syllables, by pair.")
+print("#[rustfmt::skip]")
+print("pub(super) const COMPOSITIONS: &[((char, char), char)] = &[")
+items = [f"(({char(a)}, {char(b)}), {char(code)})," for (a, b), code in compositions]
+print("\n".join(rows(items, 3)))
+print("];")
@@ -0,0 +1,1241 @@
+/*!
+Applying synchronization plans to trees on disk.
+*/
+
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    diff::difference_kind,
+    journal::Transaction,
+    source::{at, kind_at, same_content, Disk},
+    Comparison, DiffReport, Difference, Operation, Options, Plan, SpecialFiles, Stats,
+};
+
+/**
+Options for applying a [`Plan`], built up one setting at a time.
+
+By default, the files a plan overwrites or removes are gone for good. They can be moved aside
+instead, into a backup directory or, with the `trash` feature, the trash. The copies can also be
+given the metadata of their sources, so that the trees compare equal by more than their contents.
+
+```
+use dir_compare::ApplyOptions;
+
+let options = ApplyOptions::new()
+    .backup_dir("backups")
+    .preserve_times(true);
+```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    backups: Option<Backups>,
+    dry_run: bool,
+    preserve: Preserve,
+    byte_budget: Option<u64>,
+    hooks: Option<Arc<dyn ApplyHooks>>,
+    verify: bool,
+}
+
+/// Where overwritten and removed entries are moved.
+#[derive(Debug, Clone)]
+enum Backups {
+    Directory(PathBuf),
+    #[cfg(feature = "trash")]
+    Trash,
+}
+
+/// The metadata of the source entries to give their copies.
+#[derive(Debug, Clone, Copy, Default)]
+struct Preserve {
+    permissions: bool,
+    times: bool,
+    ownership: bool,
+}
+
+impl ApplyOptions {
+    /// Create the default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Only report the operations that would be applied, without changing anything on disk.
+    */
+    #[must_use]
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /**
+    Move the entries that are overwritten or removed into a new directory inside the given one,
+    named for the time the plan was applied, such as `20240131T235959Z`. Each keeps its path
+    relative to the root of the target. The directories are created as needed.
+    */
+    #[must_use]
+    pub fn backup_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.backups = Some(Backups::Directory(dir.into()));
+        self
+    }
+
+    /**
+    Move the entries that are overwritten or removed into the trash: the one the freedesktop.org
+    specification describes on Linux and other Unix-like systems, or `~/.Trash` on macOS. The trash
+    isn't supported on other platforms, where applying a plan that needs it fails.
+    */
+    #[cfg(feature = "trash")]
+    #[must_use]
+    pub fn move_to_trash(mut self, enabled: bool) -> Self {
+        self.backups = enabled.then_some(Backups::Trash);
+        self
+    }
+
+    /**
+    Once the plan has been applied, compare every path it touched between the source and the
+    target again, to make sure that the target now matches the source there. See
+    [`ApplyReport::verification`]. Files are compared by their bytes, as they were copied.
+    */
+    #[must_use]
+    pub fn verify(mut self, enabled: bool) -> Self {
+        self.verify = enabled;
+        self
+    }
+
+    /**
+    Call the given [`ApplyHooks`] before and after each operation, so that they can log, prompt
+    for, or veto them. Hooks aren't called in a dry run.
+    */
+    #[must_use]
+    pub fn hooks(mut self, hooks: impl ApplyHooks + 'static) -> Self {
+        self.hooks = Some(Arc::new(hooks));
+        self
+    }
+
+    /**
+    Copy no more than the given number of bytes, such as to fit a sync into a maintenance window.
+    The plan is applied up to the first file that would exceed the budget, and the operations from
+    there on are left for later, in [`ApplyReport::remaining`]. A file larger than the whole budget
+    is never copied, so it stops the plan where it is.
+    */
+    #[must_use]
+    pub fn byte_budget(mut self, bytes: u64) -> Self {
+        self.byte_budget = Some(bytes);
+        self
+    }
+
+    /**
+    Give the copied files and directories the permissions of their sources. Files are copied with
+    their permissions regardless, as [`fs::copy`] copies them, but directories are otherwise created
+    with the default permissions.
+    */
+    #[must_use]
+    pub fn preserve_permissions(mut self, enabled: bool) -> Self {
+        self.preserve.permissions = enabled;
+        self
+    }
+
+    /**
+    Give the copied files and directories the modification times of their sources, so that the
+    trees compare equal with [`Options::compare_mtimes`] too.
+    */
+    #[must_use]
+    pub fn preserve_times(mut self, enabled: bool) -> Self {
+        self.preserve.times = enabled;
+        self
+    }
+
+    /**
+    Give the copied files and directories the owner and group of their sources, where possible. On
+    Unix, only the superuser can give files away, so the owner is left as it is if it can't be
+    changed, and so is the group. Ownership isn't preserved on other platforms.
+    */
+    #[must_use]
+    pub fn preserve_ownership(mut self, enabled: bool) -> Self {
+        self.preserve.ownership = enabled;
+        self
+    }
+}
+
+/**
+Callbacks around each operation of a [`Plan`] as it's applied, with paths relative to the roots of
+the trees. See [`ApplyOptions::hooks`].
+
+Every operation is put to the hooks before any is applied: a hook that returns `false` vetoes its
+operation, which is skipped along with those that depend on it, such as the copies of a vetoed
+directory's children, or the removal of the directory a vetoed removal is inside. Once the plan
+has been applied in full, the hooks are called after each operation that was, in order. If applying
+the plan fails, it's rolled back and no hooks are called after.
+
+```
+use std::path::Path;
+
+use dir_compare::{ApplyHooks, ApplyOptions};
+
+/// Keeps every configuration file as it is.
+#[derive(Debug)]
+struct KeepConfig;
+
+impl ApplyHooks for KeepConfig {
+    fn before_overwrite(&self, path: &Path) -> bool {
+        path.extension().is_none_or(|extension| extension != "conf")
+    }
+
+    fn after_delete(&self, path: &Path) {
+        println!("Deleted {path:?}");
+    }
+}
+
+let options = ApplyOptions::new().hooks(KeepConfig);
+```
+*/
+pub trait ApplyHooks: Debug + Send + Sync {
+    /// Whether to copy an entry to the given path, where the target has none.
+    fn before_copy(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// Whether to overwrite the file at the given path with the source's.
+    fn before_overwrite(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// Whether to remove the entry at the given path.
+    fn before_delete(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// Called once an entry has been copied to the given path, where the target had none.
+    fn after_copy(&self, _path: &Path) {}
+
+    /// Called once the file at the given path has been overwritten with the source's.
+    fn after_overwrite(&self, _path: &Path) {}
+
+    /// Called once the entry at the given path has been removed.
+    fn after_delete(&self, _path: &Path) {}
+}
+
+/// What an operation does to the target, as the hooks are told.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Effect {
+    Copy,
+    Overwrite,
+    Delete,
+}
+
+/**
+An entry that was moved aside instead of being overwritten or removed.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    path: PathBuf,
+    location: PathBuf,
+}
+
+impl Backup {
+    /// The path the entry had, relative to the root of the target.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the entry was moved.
+    #[must_use]
+    pub fn location(&self) -> &Path {
+        &self.location
+    }
+}
+
+/**
+What applying a [`Plan`] did.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyReport {
+    applied: Vec<Operation>,
+    backups: Vec<Backup>,
+    bytes_copied: u64,
+    remaining: Plan,
+    skipped: Vec<Operation>,
+    verification: Option<DiffReport>,
+}
+
+impl ApplyReport {
+    /// The operations that were applied, in order. In a dry run, those that would have been.
+    #[must_use]
+    pub fn applied(&self) -> &[Operation] {
+        &self.applied
+    }
+
+    /// The number of bytes of the files that were copied. In a dry run, of those that would have
+    /// been.
+    #[must_use]
+    pub fn bytes_copied(&self) -> u64 {
+        self.bytes_copied
+    }
+
+    /**
+    The operations left for later, once the [byte budget](ApplyOptions::byte_budget) ran out, in
+    the order they must be applied. Apply it between the same trees to resume.
+    */
+    #[must_use]
+    pub fn remaining(&self) -> &Plan {
+        &self.remaining
+    }
+
+    /// The operations that [hooks](ApplyOptions::hooks) vetoed, and those that depended on them, in
+    /// order.
+    #[must_use]
+    pub fn skipped(&self) -> &[Operation] {
+        &self.skipped
+    }
+
+    /**
+    The differences that remained between the target and the source, at the paths the plan
+    touched, once it had been applied, if [verification](ApplyOptions::verify) was enabled and this
+    wasn't a dry run. "Added" and "removed" are relative to the target, so an empty report means
+    that the target matches the source at every path the plan touched.
+
+    ```no_run
+    use dir_compare::{ApplyOptions, Options, Plan};
+
+    let options = ApplyOptions::new().verify(true);
+    let report = Plan::sync("build/site", "/var/www", &Options::new(), &options)?;
+    if let Some(mismatches) = report.verification().filter(|report| !report.is_empty()) {
+        eprintln!("{} paths still differ after syncing", mismatches.differences().len());
+    }
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn verification(&self) -> Option<&DiffReport> {
+        self.verification.as_ref()
+    }
+
+    /// Whether the whole plan was applied, with nothing left for later.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    /// The entries that were moved aside, in the order they were moved.
+    #[must_use]
+    pub fn backups(&self) -> &[Backup] {
+        &self.backups
+    }
+}
+
+impl Plan {
+    /**
+    Apply the plan, making the target tree at the given path match the source tree at the other,
+    as configured by the given [`ApplyOptions`].
+
+    ```no_run
+    use dir_compare::{ApplyOptions, Content};
+
+    let base = Content::of("/var/lib/replicas/last-sync")?;
+    let ours = Content::of("/srv/replica-a")?;
+    let theirs = Content::of("/srv/replica-b")?;
+    let plan = base.three_way(&ours, &theirs).sync_plan();
+
+    let options = ApplyOptions::new().backup_dir("/var/backups/replicas");
+    plan.to_theirs().apply("/srv/replica-a", "/srv/replica-b", &options)?;
+    plan.to_ours().apply("/srv/replica-b", "/srv/replica-a", &options)?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Atomicity
+
+    The plan, or as much of it as fits the byte budget, is applied as a whole or not at all. Every
+    file to be copied is first staged in full, in a directory created next to the target, and the
+    entries to be overwritten or removed are moved aside into it; then every change to the target is
+    a rename. If any operation fails, the renames are undone in reverse order, leaving the target as
+    it was. Only once every operation has succeeded are the entries that were moved aside backed
+    up, as configured, or deleted. The backup directory, or the trash, is created beforehand, so
+    that failing to create it rolls the plan back too. If an entry still can't be backed up, the
+    staging directory is left in place, with the entries not yet backed up, and the error says where
+    it is.
+
+    The metadata of copied files is preserved, as configured, as they're staged. That of copied
+    directories is preserved once the plan has been applied, deepest first, so that their
+    permissions can't stop their children from being placed, nor placing those children change
+    their modification times; an error then leaves the plan applied.
+
+    The target's parent directory must be writable, and the staging directory must be on the same
+    file system as the target.
+
+    # Errors
+
+    - Will return an error of kind [`io::ErrorKind::Unsupported`] if the plan copies a special
+      file, which can't be copied.
+    - Will bubble I/O errors, after rolling back any operations already applied.
+    */
+    pub fn apply(
+        &self,
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        options: &ApplyOptions,
+    ) -> io::Result<ApplyReport> {
+        let (source, target) = (source.as_ref(), target.as_ref());
+        let (within, mut bytes_copied) = self.within_budget(source, options.byte_budget)?;
+        let (mut plan, remaining) = self.split_at(within);
+        if options.dry_run {
+            return Ok(ApplyReport {
+                applied: plan.operations().to_vec(),
+                backups: Vec::new(),
+                bytes_copied,
+                remaining,
+                skipped: Vec::new(),
+                verification: None,
+            });
+        }
+
+        let mut effects = Vec::new();
+        let mut skipped = Vec::new();
+        if let Some(hooks) = &options.hooks {
+            (plan, effects, skipped) = plan.consult(target, hooks.as_ref());
+            if !skipped.is_empty() {
+                bytes_copied = plan.within_budget(source, None)?.1;
+            }
+        }
+
+        let mut transaction = Transaction::begin(target)?;
+
+        let directories = match plan.apply_in(source, &mut transaction, options.preserve) {
+            Ok(directories) => directories,
+            Err(error) => {
+                transaction.roll_back();
+                return Err(error);
+            }
+        };
+        let mut stash = Stash::new(options.backups.as_ref(), target);
+        if !transaction.aside().is_empty() {
+            if let Err(error) = stash.prepare() {
+                transaction.roll_back();
+                return Err(error);
+            }
+        }
+        let committed = transaction.commit();
+
+        let mut report = ApplyReport {
+            applied: plan.operations().to_vec(),
+            backups: Vec::new(),
+            bytes_copied,
+            remaining,
+            skipped,
+            verification: None,
+        };
+        let kept = committed.aside().iter().try_for_each(|(path, aside)| {
+            if let Some(location) = stash.keep(path, aside)? {
+                report.backups.push(Backup {
+                    path: path.clone(),
+                    location,
+                });
+            }
+            Ok::<_, io::Error>(())
+        });
+        if let Err(error) = kept {
+            // The plan has been applied, so the entries not yet kept mustn't be discarded with it.
+            let stage = committed.keep_stage();
+            return Err(io::Error::new(
+                error.kind(),
+                format!("{error}; the entries not yet backed up were left in {stage:?}"),
+            ));
+        }
+        for path in directories.iter().rev() {
+            options
+                .preserve
+                .copy(&at(source, path), &at(target, path))?;
+        }
+        if let Some(hooks) = &options.hooks {
+            for (operation, effect) in plan.iter().zip(effects) {
+                let path = operation.path();
+                match effect {
+                    Effect::Copy => hooks.after_copy(path),
+                    Effect::Overwrite => hooks.after_overwrite(path),
+                    Effect::Delete => hooks.after_delete(path),
+                }
+            }
+        }
+        // The target is verified as it's left, after any hooks have run.
+        if options.verify {
+            report.verification = Some(plan.verify(source, target)?);
+        }
+
+        Ok(report)
+    }
+
+    /**
+    Make the target tree at the given path match the source tree at the other: compare them, as
+    configured by the given [`Options`], then apply the resulting plan, as configured by the given
+    [`ApplyOptions`]. See [`DiffReport::sync_plan`](crate::DiffReport::sync_plan) and
+    [`Plan::apply`].
+
+    ```no_run
+    use dir_compare::{ApplyOptions, Options, Plan};
+
+    let options = Options::new();
+    let dry_run = ApplyOptions::new().dry_run(true);
+    let preview = Plan::sync("build/site", "/var/www", &options, &dry_run)?;
+    for operation in preview.applied() {
+        println!("{operation:?}");
+    }
+    Plan::sync("build/site", "/var/www", &options, &ApplyOptions::new())?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    See [`Comparison::between`] and [`Plan::apply`]. Will also return an error of kind
+    [`io::ErrorKind::InvalidInput`], before changing anything, if an entry to copy was matched with
+    one named differently in the target, by [`Options::normalize_names`] or
+    [`Options::case_insensitive`], since the plan can't rename it.
+    */
+    pub fn sync(
+        source: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+        options: &Options,
+        apply_options: &ApplyOptions,
+    ) -> io::Result<ApplyReport> {
+        let (source, target) = (source.as_ref(), target.as_ref());
+        let plan = Comparison::between(target, source, options)?
+            .into_report()
+            .sync_plan();
+
+        // Entries are reported by their names in the target, where they're in both trees, which
+        // the source lacks if options matched them despite their names differing.
+        if options.matches_names_loosely() {
+            for operation in &plan {
+                let Operation::Copy(path) = operation else {
+                    continue;
+                };
+                if fs::symlink_metadata(at(source, path)).is_err() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "{path:?} is named differently in {source:?}, and can't be renamed \
+                             by syncing"
+                        ),
+                    ));
+                }
+            }
+        }
+
+        plan.apply(source, target, apply_options)
+    }
+
+    /// Compare the paths the plan touches between the target and the source, by their bytes.
+    fn verify(&self, source: &Path, target: &Path) -> io::Result<DiffReport> {
+        let options = Options::new().special_files(SpecialFiles::CompareByType);
+        let (a, b) = (Disk::new(target, &options), Disk::new(source, &options));
+        let kind = |root: &Path, path: &Path| match kind_at(&at(root, path), &options) {
+            Ok(kind) => Ok(Some(kind)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        };
+
+        let mut differences = Vec::new();
+        let mut stats = Stats::default();
+        let paths: BTreeSet<_> = self.iter().map(Operation::path).collect();
+        for path in paths {
+            let (kind_a, kind_b) = (kind(target, path)?, kind(source, path)?);
+            if let Some(kind) = difference_kind(kind_a, kind_b, || {
+                same_content(&a, &b, (path, path), &mut stats)
+            })? {
+                differences.push(Difference::new(path.to_path_buf(), kind));
+            }
+        }
+
+        Ok(DiffReport::new(differences))
+    }
+
+    /**
+    Put each operation to the hooks, returning the plan of those to apply, with what each does,
+    and the operations that are skipped.
+    */
+    fn consult(
+        &self,
+        target: &Path,
+        hooks: &dyn ApplyHooks,
+    ) -> (Self, Vec<Effect>, Vec<Operation>) {
+        let mut operations = Vec::new();
+        let mut effects = Vec::new();
+        let mut skipped: Vec<Operation> = Vec::new();
+
+        for operation in self {
+            let path = operation.path();
+            // An operation depends on those at the paths above and below it.
+            let related = |other: &Operation| {
+                path.starts_with(other.path()) || other.path().starts_with(path)
+            };
+            if skipped.iter().any(related) {
+                skipped.push(operation.clone());
+                continue;
+            }
+
+            let effect = match operation {
+                Operation::Remove(_) => Effect::Delete,
+                // An entry the plan removed first isn't overwritten.
+                Operation::Copy(_)
+                    if fs::symlink_metadata(at(target, path)).is_ok()
+                        && !operations.contains(&Operation::Remove(path.to_path_buf())) =>
+                {
+                    Effect::Overwrite
+                }
+                Operation::Copy(_) => Effect::Copy,
+            };
+            let proceed = match effect {
+                Effect::Copy => hooks.before_copy(path),
+                Effect::Overwrite => hooks.before_overwrite(path),
+                Effect::Delete => hooks.before_delete(path),
+            };
+
+            if proceed {
+                operations.push(operation.clone());
+                effects.push(effect);
+            } else {
+                skipped.push(operation.clone());
+            }
+        }
+
+        (Self::from_operations(operations), effects, skipped)
+    }
+
+    /**
+    The number of leading operations that copy no more than the given number of bytes, if any, and
+    the number of bytes they copy.
+    */
+    fn within_budget(&self, source: &Path, budget: Option<u64>) -> io::Result<(usize, u64)> {
+        let mut spent = 0;
+        for (index, operation) in self.iter().enumerate() {
+            let Operation::Copy(path) = operation else {
+                continue;
+            };
+            let metadata = fs::metadata(at(source, path))?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if budget.is_some_and(|budget| spent + metadata.len() > budget) {
+                return Ok((index, spent));
+            }
+            spent += metadata.len();
+        }
+
+        Ok((self.operations().len(), spent))
+    }
+
+    /**
+    Apply the operations as part of the given transaction, preserving the metadata of the copied
+    files as configured, and returning the paths of the copied directories, in order.
+    */
+    fn apply_in(
+        &self,
+        source: &Path,
+        transaction: &mut Transaction,
+        preserve: Preserve,
+    ) -> io::Result<Vec<PathBuf>> {
+        // Stage every file first, so that an unreadable source leaves the target untouched.
+        let mut staged = Vec::new();
+        for path in self.iter().filter_map(|operation| match operation {
+            Operation::Copy(path) => Some(path),
+            Operation::Remove(_) => None,
+        }) {
+            let from = at(source, path);
+            let file_type = fs::metadata(&from)?.file_type();
+            staged.push(if file_type.is_dir() {
+                None
+            } else if file_type.is_file() {
+                let staged = transaction.stage(&from)?;
+                preserve.copy(&from, &staged)?;
+                Some(staged)
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{from:?} is a special file, which can't be copied"),
+                ));
+            });
+        }
+
+        let mut staged = staged.into_iter();
+        let mut directories = Vec::new();
+        for operation in self {
+            match operation {
+                Operation::Copy(path) => match staged.next().flatten() {
+                    Some(file) => transaction.place(file, path)?,
+                    None => {
+                        transaction.create_directory(path)?;
+                        directories.push(path.clone());
+                    }
+                },
+                Operation::Remove(path) => transaction.remove(path)?,
+            }
+        }
+
+        Ok(directories)
+    }
+}
+
+impl Preserve {
+    /// Give the entry at `to` the metadata of the one at `from`, as configured.
+    fn copy(self, from: &Path, to: &Path) -> io::Result<()> {
+        if !(self.permissions || self.times || self.ownership) {
+            return Ok(());
+        }
+        let metadata = fs::metadata(from)?;
+
+        // Changing the owner can clear permission bits such as setuid, so it comes first.
+        #[cfg(unix)]
+        if self.ownership {
+            use std::os::unix::fs::{chown, MetadataExt};
+
+            let owned = chown(to, Some(metadata.uid()), Some(metadata.gid()))
+                .or_else(|_| chown(to, None, Some(metadata.gid())));
+            match owned {
+                Err(error) if error.kind() != io::ErrorKind::PermissionDenied => return Err(error),
+                _ => {}
+            }
+        }
+        if self.permissions {
+            fs::set_permissions(to, metadata.permissions())?;
+        }
+        // Times come last, as changing anything else about a directory could change them.
+        if self.times {
+            open_for_times(to)?.set_modified(metadata.modified()?)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Open the entry at the given path, even a read-only file or a directory, to set its times.
+#[cfg(windows)]
+fn open_for_times(path: &Path) -> io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    File::options()
+        .access_mode(FILE_WRITE_ATTRIBUTES)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+}
+
+/// Open the entry at the given path, even a read-only file or a directory, to set its times.
+#[cfg(not(windows))]
+fn open_for_times(path: &Path) -> io::Result<File> {
+    // The owner of a file can set its times without being able to write to it.
+    File::open(path)
+}
+
+/// Move the entry at `from` to `to`, copying it across file systems if need be.
+pub(crate) fn relocate(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    // Directories are only moved aside once their children have been, so they're empty, and may
+    // already exist to hold those children.
+    if fs::symlink_metadata(from)?.is_dir() {
+        match fs::create_dir(to) {
+            Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+            _ => {}
+        }
+        fs::remove_dir(from)
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+}
+
+/// Keeps the entries that were overwritten or removed, as configured.
+struct Stash<'a> {
+    backups: Option<&'a Backups>,
+    target: &'a Path,
+    started: SystemTime,
+    /// The timestamped backup directory, once it's been created.
+    dir: Option<PathBuf>,
+}
+
+impl<'a> Stash<'a> {
+    fn new(backups: Option<&'a Backups>, target: &'a Path) -> Self {
+        Self {
+            backups,
+            target,
+            started: SystemTime::now(),
+            dir: None,
+        }
+    }
+
+    /// Create where the entries are kept, if so configured, before any are kept there.
+    fn prepare(&mut self) -> io::Result<()> {
+        match self.backups {
+            None => {}
+            Some(Backups::Directory(dir)) => {
+                self.dir(dir)?;
+            }
+            #[cfg(feature = "trash")]
+            Some(Backups::Trash) => {
+                crate::trash::trash_dir()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+    Keep the entry that was at the given path, and that's now at `from`, if so configured,
+    returning where it went.
+    */
+    fn keep(&mut self, path: &Path, from: &Path) -> io::Result<Option<PathBuf>> {
+        let location = match self.backups {
+            None => return Ok(None),
+            Some(Backups::Directory(dir)) => {
+                // The root has no path relative to itself, so it keeps its name.
+                let relative = if path.as_os_str().is_empty() {
+                    Path::new(self.target.file_name().unwrap_or_default())
+                } else {
+                    path
+                };
+                let location = self.dir(dir)?.join(relative);
+                if let Some(parent) = location.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                relocate(from, &location)?;
+                location
+            }
+            #[cfg(feature = "trash")]
+            Some(Backups::Trash) => crate::trash::trash(from, self.started)?,
+        };
+
+        Ok(Some(location))
+    }
+
+    /// The timestamped directory inside the given one, created on first use.
+    fn dir(&mut self, parent: &Path) -> io::Result<&Path> {
+        if self.dir.is_none() {
+            let stamp = timestamp(self.started);
+            let mut dir = parent.join(&stamp);
+            fs::create_dir_all(parent)?;
+            // Plans applied within the same second get directories of their own.
+            for attempt in 2.. {
+                match fs::create_dir(&dir) {
+                    Ok(()) => break,
+                    Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                        dir = parent.join(format!("{stamp}-{attempt}"));
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            self.dir = Some(dir);
+        }
+
+        Ok(self.dir.as_deref().expect("the directory was just created"))
+    }
+}
+
+/// The date and time, in UTC, as the year, month, day, hour, minute, and second.
+pub(crate) fn utc(time: SystemTime) -> (i64, u32, u32, u32, u32, u32) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // Convert days since the epoch to a date in the proleptic Gregorian calendar, counting years
+    // from March so that leap days fall at their end.
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month as u32,
+        day as u32,
+        (seconds / 3600) as u32,
+        (seconds / 60 % 60) as u32,
+        (seconds % 60) as u32,
+    )
+}
+
+/// The time in the basic format of ISO 8601, in UTC, such as `20240131T235959Z`.
+fn timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc(time);
+    format!("{year:04}{month:02}{day:02}T{hour:02}{minute:02}{second:02}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::timestamp;
+    use crate::{
+        testing::TempDir, ApplyHooks, ApplyOptions, Comparison, Content, DiffReport,
+        DifferenceKind, Operation, Options, Plan,
+    };
+
+    #[test]
+    fn timestamps_should_be_in_utc() {
+        let time = UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!(timestamp(time), "20000229T123456Z");
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101T000000Z");
+    }
+
+    #[test]
+    fn replaced_entries_should_be_backed_up() {
+        let temp = TempDir::new();
+        let [source, target, backups] =
+            ["source", "target", "backups"].map(|name| temp.path().join(name));
+        fs::create_dir_all(source.join("dir")).unwrap();
+        fs::write(source.join("dir/file.txt"), "new").unwrap();
+        fs::create_dir_all(target.join("old")).unwrap();
+        fs::write(target.join("old/file.txt"), "old").unwrap();
+        fs::create_dir_all(target.join("dir")).unwrap();
+        fs::write(target.join("dir/file.txt"), "stale").unwrap();
+
+        let plan = Plan::new([
+            (Path::new("dir/file.txt"), DifferenceKind::Modified),
+            (Path::new("old"), DifferenceKind::Removed),
+            (Path::new("old/file.txt"), DifferenceKind::Removed),
+        ]);
+        let options = ApplyOptions::new().backup_dir(&backups);
+        let report = plan.apply(&source, &target, &options).unwrap();
+
+        assert_eq!(report.applied(), plan.operations());
+        assert_eq!(Content::of(&target).unwrap(), Content::of(&source).unwrap());
+
+        let kept: Vec<_> = report.backups().iter().map(|b| b.path()).collect();
+        assert_eq!(
+            kept,
+            [
+                Path::new("old/file.txt"),
+                Path::new("old"),
+                Path::new("dir/file.txt")
+            ]
+        );
+        let stale = report.backups()[2].location();
+        assert!(stale.starts_with(&backups));
+        assert!(stale.ends_with("dir/file.txt"));
+        assert_eq!(fs::read_to_string(stale).unwrap(), "stale");
+        assert_eq!(
+            fs::read_to_string(report.backups()[0].location()).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn plans_should_be_rolled_back_if_their_backups_cant_be_made() {
+        let temp = TempDir::new();
+        let [source, target, file] =
+            ["source", "target", "file"].map(|name| temp.path().join(name));
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("changed.txt"), "new").unwrap();
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("changed.txt"), "old").unwrap();
+        fs::write(target.join("gone.txt"), "old").unwrap();
+        fs::write(&file, "").unwrap();
+        let before = Content::of(&target).unwrap();
+
+        let options = ApplyOptions::new().backup_dir(file.join("backups"));
+        let error = Plan::sync(&source, &target, &Options::new(), &options).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::NotADirectory);
+        assert_eq!(Content::of(&target).unwrap(), before);
+        let siblings = fs::read_dir(temp.path()).unwrap().count();
+        assert_eq!(siblings, 3);
+    }
+
+    #[test]
+    fn failed_plans_should_be_rolled_back() {
+        let temp = TempDir::new();
+        let [source, target] = ["source", "target"].map(|name| temp.path().join(name));
+        fs::create_dir_all(source.join("new")).unwrap();
+        fs::write(source.join("changed.txt"), "new").unwrap();
+        fs::write(source.join("new/file.txt"), "new").unwrap();
+        fs::create_dir_all(target.join("gone")).unwrap();
+        fs::write(target.join("changed.txt"), "old").unwrap();
+        fs::write(target.join("gone/file.txt"), "old").unwrap();
+        let before = Content::of(&target).unwrap();
+
+        // The plan forgets to create the directory of the new file, so placing it fails last.
+        let plan = Plan::new([
+            (Path::new("changed.txt"), DifferenceKind::Modified),
+            (Path::new("gone"), DifferenceKind::Removed),
+            (Path::new("gone/file.txt"), DifferenceKind::Removed),
+            (Path::new("new/file.txt"), DifferenceKind::Added),
+        ]);
+        let error = plan
+            .apply(&source, &target, &ApplyOptions::new())
+            .unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(Content::of(&target).unwrap(), before);
+        // Nothing is left behind next to the target.
+        let siblings = fs::read_dir(temp.path()).unwrap().count();
+        assert_eq!(siblings, 2);
+    }
+
+    #[test]
+    fn targets_should_be_synced_to_match_their_sources() {
+        let temp = TempDir::new();
+        let target = temp.path().join("target");
+        fs::create_dir(&target).unwrap();
+        let (a, b) = (
+            "fixtures/not-equivalent/dir-a",
+            "fixtures/not-equivalent/dir-b",
+        );
+        let options = Options::new();
+
+        Plan::sync(a, &target, &options, &ApplyOptions::new()).unwrap();
+        assert_eq!(Content::of(&target).unwrap(), Content::of(a).unwrap());
+
+        let dry_run = ApplyOptions::new().dry_run(true);
+        let preview = Plan::sync(b, &target, &options, &dry_run).unwrap();
+        assert_eq!(
+            preview.applied(),
+            [Operation::Copy("subdir/test2.txt".into())]
+        );
+        assert_eq!(Content::of(&target).unwrap(), Content::of(a).unwrap());
+
+        Plan::sync(b, &target, &options, &ApplyOptions::new()).unwrap();
+        assert_eq!(Content::of(&target).unwrap(), Content::of(b).unwrap());
+    }
+
+    // Other platforms' file systems are case-insensitive by default, so names can't differ by case.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn targets_should_be_synced_with_names_compared_case_insensitively() {
+        let temp = TempDir::new();
+        let (source, target) = (temp.path().join("source"), temp.path().join("target"));
+        fs::create_dir_all(source.join("Docs")).unwrap();
+        fs::create_dir(&target).unwrap();
+        fs::write(source.join("Docs/Readme.TXT"), "read me").unwrap();
+        let options = Options::new().case_insensitive(true);
+
+        Plan::sync(&source, &target, &options, &ApplyOptions::new()).unwrap();
+        assert_eq!(Content::of(&target).unwrap(), Content::of(&source).unwrap());
+
+        fs::write(source.join("Docs/Readme.TXT"), "read me again").unwrap();
+        Plan::sync(&source, &target, &options, &ApplyOptions::new()).unwrap();
+        assert_eq!(Content::of(&target).unwrap(), Content::of(&source).unwrap());
+
+        // A file named differently in the target can't be given the source's name.
+        fs::rename(
+            target.join("Docs/Readme.TXT"),
+            target.join("Docs/README.txt"),
+        )
+        .unwrap();
+        fs::write(source.join("Docs/Readme.TXT"), "read me once more").unwrap();
+        let error = Plan::sync(&source, &target, &options, &ApplyOptions::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            fs::read(target.join("Docs/README.txt")).unwrap(),
+            b"read me again"
+        );
+    }
+
+    #[test]
+    fn plans_should_stop_within_their_byte_budgets() {
+        let temp = TempDir::new();
+        let target = temp.path().join("target");
+        fs::create_dir(&target).unwrap();
+        let source = Path::new("fixtures/equivalent/dir-a");
+        let plan = Comparison::between(&target, source, &Options::new())
+            .unwrap()
+            .into_report()
+            .sync_plan();
+
+        // Of the files of 15 and 10 bytes, only the first fits.
+        let options = ApplyOptions::new().byte_budget(20);
+        let report = plan.apply(source, &target, &options).unwrap();
+        assert_eq!(
+            report.applied(),
+            [
+                Operation::Copy("subdir".into()),
+                Operation::Copy("subdir/test2.txt".into())
+            ]
+        );
+        assert_eq!(report.bytes_copied(), 15);
+        assert!(!report.is_complete());
+        assert!(!target.join("test.txt").exists());
+
+        let resumed = report.remaining().apply(source, &target, &options).unwrap();
+        assert!(resumed.is_complete());
+        assert_eq!(Content::of(&target).unwrap(), Content::of(source).unwrap());
+    }
+
+    #[test]
+    fn hooks_should_see_and_veto_operations() {
+        #[derive(Debug, Default)]
+        struct Log(Arc<Mutex<Vec<String>>>);
+
+        impl ApplyHooks for Log {
+            fn before_copy(&self, path: &Path) -> bool {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("before copy {}", path.display()));
+                true
+            }
+
+            fn before_overwrite(&self, path: &Path) -> bool {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("before overwrite {}", path.display()));
+                true
+            }
+
+            fn before_delete(&self, path: &Path) -> bool {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("before delete {}", path.display()));
+                path != Path::new("old/keep.txt")
+            }
+
+            fn after_delete(&self, path: &Path) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("after delete {}", path.display()));
+            }
+        }
+
+        let temp = TempDir::new();
+        let [source, target] = ["source", "target"].map(|name| temp.path().join(name));
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("changed.txt"), "new").unwrap();
+        fs::write(source.join("new.txt"), "new").unwrap();
+        fs::create_dir_all(target.join("old")).unwrap();
+        fs::write(target.join("changed.txt"), "old").unwrap();
+        fs::write(target.join("old/gone.txt"), "old").unwrap();
+        fs::write(target.join("old/keep.txt"), "old").unwrap();
+
+        let log = Arc::default();
+        let options = ApplyOptions::new().hooks(Log(Arc::clone(&log)));
+        let report = Plan::sync(&source, &target, &Options::new(), &options).unwrap();
+
+        assert_eq!(
+            report.skipped(),
+            [
+                Operation::Remove("old/keep.txt".into()),
+                Operation::Remove("old".into())
+            ]
+        );
+        assert!(target.join("old/keep.txt").exists());
+        assert!(!target.join("old/gone.txt").exists());
+        assert_eq!(
+            fs::read_to_string(target.join("changed.txt")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "before delete old/keep.txt",
+                "before delete old/gone.txt",
+                "before overwrite changed.txt",
+                "before copy new.txt",
+                "after delete old/gone.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn applied_plans_should_be_verified() {
+        /// Corrupts every file it sees overwritten.
+        #[derive(Debug)]
+        struct Corrupt(PathBuf);
+
+        impl ApplyHooks for Corrupt {
+            fn after_overwrite(&self, path: &Path) {
+                fs::write(self.0.join(path), "corrupt").unwrap();
+            }
+        }
+
+        let temp = TempDir::new();
+        let target = temp.path().join("target");
+        fs::create_dir(&target).unwrap();
+        let (a, b) = (
+            "fixtures/not-equivalent/dir-a",
+            "fixtures/not-equivalent/dir-b",
+        );
+        let options = ApplyOptions::new().verify(true);
+
+        let report = Plan::sync(a, &target, &Options::new(), &options).unwrap();
+        assert_eq!(report.verification(), Some(&DiffReport::default()));
+
+        let options = options.hooks(Corrupt(target.clone()));
+        let report = Plan::sync(b, &target, &Options::new(), &options).unwrap();
+        let verification = report.verification().unwrap();
+        assert_eq!(verification.differences().len(), 1);
+        assert_eq!(
+            verification.differences()[0].path(),
+            Path::new("subdir/test2.txt")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn metadata_should_be_preserved_when_enabled() {
+        use std::{
+            fs::{File, Permissions},
+            os::unix::fs::PermissionsExt,
+        };
+
+        let temp = TempDir::new();
+        let [source, target] = ["source", "target"].map(|name| temp.path().join(name));
+        fs::create_dir_all(source.join("dir")).unwrap();
+        fs::write(source.join("dir/file.txt"), "data").unwrap();
+        fs::create_dir(&target).unwrap();
+        let time = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for (path, mode) in [("dir/file.txt", 0o440), ("dir", 0o750)] {
+            let path = source.join(path);
+            File::open(&path).unwrap().set_modified(time).unwrap();
+            fs::set_permissions(&path, Permissions::from_mode(mode)).unwrap();
+        }
+
+        let options = Options::new().compare_mtimes(true);
+        let apply_options = ApplyOptions::new()
+            .preserve_permissions(true)
+            .preserve_times(true)
+            .preserve_ownership(true);
+        Plan::sync(&source, &target, &options, &apply_options).unwrap();
+
+        for path in ["dir/file.txt", "dir"] {
+            let (a, b) = (
+                fs::metadata(source.join(path)).unwrap(),
+                fs::metadata(target.join(path)).unwrap(),
+            );
+            assert_eq!(a.permissions(), b.permissions());
+            assert_eq!(b.modified().unwrap(), time);
+        }
+        let comparison = Comparison::between(&source, &target, &options).unwrap();
+        assert!(comparison.report().is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+/*!
+Windows file attributes and alternate data streams, which installers and archivers can change
+without touching an entry's bytes or name.
+*/
+
+use std::{io, path::Path};
+
+/**
+The attributes of an entry on disk that are compared: its read-only, hidden, and system attributes,
+and the names and bytes of its alternate data streams, ordered by name.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Attributes {
+    flags: u32,
+    streams: Vec<(std::ffi::OsString, Vec<u8>)>,
+}
+
+/// The attributes of the entry at the given path on disk, which are only known on Windows.
+#[cfg(not(windows))]
+pub(crate) fn attributes(_path: &Path) -> io::Result<Option<Attributes>> {
+    Ok(None)
+}
+
+/// The attributes of the entry at the given path on disk.
+#[cfg(windows)]
+pub(crate) fn attributes(path: &Path) -> io::Result<Option<Attributes>> {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+
+    let flags = std::fs::metadata(path)?.file_attributes()
+        & (FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM);
+
+    Ok(Some(Attributes {
+        flags,
+        streams: streams::read(path)?,
+    }))
+}
+
+#[cfg(windows)]
+mod streams {
+    use std::{
+        ffi::{c_void, OsString},
+        fs, io,
+        os::windows::ffi::{OsStrExt, OsStringExt},
+        path::Path,
+    };
+
+    type Handle = *mut c_void;
+
+    const INVALID_HANDLE_VALUE: Handle = -1_isize as Handle;
+    const FIND_STREAM_INFO_STANDARD: i32 = 0;
+    const ERROR_HANDLE_EOF: i32 = 38;
+    /// The name of a file's main, unnamed stream, which holds the bytes that are compared anyway.
+    const MAIN_STREAM: &str = "::$DATA";
+
+    /// `WIN32_FIND_STREAM_DATA`: a stream's size, and its name, as `:<name>:$DATA`.
+    #[repr(C)]
+    struct FindStreamData {
+        size: i64,
+        name: [u16; 260 + 36],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn FindFirstStreamW(
+            file_name: *const u16,
+            info_level: i32,
+            data: *mut FindStreamData,
+            flags: u32,
+        ) -> Handle;
+        fn FindNextStreamW(find: Handle, data: *mut FindStreamData) -> i32;
+        fn FindClose(find: Handle) -> i32;
+    }
+
+    /// The names and bytes of the alternate data streams of the entry at the given path, by name.
+    pub(super) fn read(path: &Path) -> io::Result<Vec<(OsString, Vec<u8>)>> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        let mut data = FindStreamData {
+            size: 0,
+            name: [0; 260 + 36],
+        };
+
+        // SAFETY: The path is null-terminated, and the data is a `WIN32_FIND_STREAM_DATA`.
+        let find =
+            unsafe { FindFirstStreamW(wide.as_ptr(), FIND_STREAM_INFO_STANDARD, &mut data, 0) };
+        if find == INVALID_HANDLE_VALUE {
+            let error = io::Error::last_os_error();
+            // An entry without any streams, such as most directories, has nothing to find.
+            return match error.raw_os_error() {
+                Some(ERROR_HANDLE_EOF) => Ok(Vec::new()),
+                _ => Err(error),
+            };
+        }
+
+        let mut names = Vec::new();
+        loop {
+            let length = data.name.iter().position(|&unit| unit == 0).unwrap_or(0);
+            let name = OsString::from_wide(&data.name[..length]);
+            if name != MAIN_STREAM {
+                names.push(name);
+            }
+            // SAFETY: The handle was returned by `FindFirstStreamW` and hasn't been closed.
+            if unsafe { FindNextStreamW(find, &mut data) } == 0 {
+                break;
+            }
+        }
+        // SAFETY: The handle was returned by `FindFirstStreamW` and is closed only once.
+        unsafe {
+            FindClose(find);
+        }
+
+        names.sort_unstable();
+        names
+            .into_iter()
+            .map(|name| {
+                let mut stream = path.as_os_str().to_owned();
+                stream.push(&name);
+                Ok((name, fs::read(stream)?))
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, windows))]
+mod tests {
+    use std::fs;
+
+    use crate::{testing::TempDir, Comparison, DifferenceKind, Options};
+
+    #[test]
+    fn attributes_and_streams_should_be_compared_when_enabled() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for dir in [&a, &b] {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join("setup.ini"), "[setup]").unwrap();
+            fs::write(dir.join("readme.txt"), "read me").unwrap();
+        }
+        let mut permissions = fs::metadata(b.join("setup.ini")).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(b.join("setup.ini"), permissions).unwrap();
+        fs::write(b.join("readme.txt:Zone.Identifier"), "[ZoneTransfer]").unwrap();
+
+        assert!(Comparison::between(&a, &b, &Options::new())
+            .unwrap()
+            .report()
+            .is_empty());
+
+        let options = Options::new().compare_attributes(true);
+        let comparison = Comparison::between(&a, &b, &options).unwrap();
+        let kinds: Vec<_> = comparison
+            .report()
+            .iter()
+            .map(|difference| difference.kind())
+            .collect();
+        assert_eq!(kinds, [DifferenceKind::AttributesChanged; 2]);
+
+        // Read-only files can't be deleted along with the rest of the temporary directory.
+        let mut permissions = fs::metadata(b.join("setup.ini")).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(b.join("setup.ini"), permissions).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+/*!
+A cache of file digests, so that repeated comparisons of the same trees only read the files that
+have changed since.
+*/
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    sha256::{Digest, Sha256},
+    source::{fill, FileInfo, Source},
+    Stats,
+};
+
+/**
+Files modified this recently before they're read aren't cached: a file can be modified again within
+the resolution of its file system's modification times, and the change would go unnoticed.
+*/
+const RACY: Duration = Duration::from_secs(2);
+
+/// A cached digest, and the size and modification time of the file it was computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cached {
+    size: Option<u64>,
+    modified: SystemTime,
+    digest: Digest,
+}
+
+/**
+Digests of the files read by earlier comparisons, keyed by their paths on disk, sizes, and
+modification times. See [`Comparison::between_cached`](crate::Comparison::between_cached).
+
+A file whose size and modification time haven't changed since its digest was cached is compared by
+that digest, without being read again. This suits comparing the same trees over and over, as in a
+watch loop, but relies on modification times: a file changed without its modification time
+changing goes unnoticed.
+
+Digests are of the bytes that are compared, after any [filters](crate::filters) are applied, so a
+cache should only be reused with the same [`Options`](crate::Options).
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Cache {
+    digests: HashMap<PathBuf, Cached>,
+}
+
+impl Cache {
+    /// Create an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of files whose digests are cached.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+
+    /// Whether no digests are cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    /// Forget every cached digest.
+    pub fn clear(&mut self) {
+        self.digests.clear();
+    }
+
+    /// Forget the digests of the files that no longer exist.
+    pub fn prune(&mut self) {
+        self.digests.retain(|path, _| path.exists());
+    }
+
+    /// The digest of the file at the given path in the tree, from the cache if it's unchanged.
+    fn digest(
+        &mut self,
+        source: &dyn Source,
+        path: &Path,
+        info: FileInfo,
+        stats: &mut Stats,
+    ) -> io::Result<Digest> {
+        let location = source.location(path)?;
+        let cached = location
+            .as_ref()
+            .and_then(|location| self.digests.get(location));
+        if let Some(cached) = cached
+            .filter(|cached| cached.size == info.size && Some(cached.modified) == info.modified)
+        {
+            return Ok(cached.digest);
+        }
+
+        let started = SystemTime::now();
+        let digest = digest(source, path, stats)?;
+        let settled = info.modified.filter(|&modified| modified + RACY < started);
+        if let (Some(location), Some(modified)) = (location, settled) {
+            self.digests.insert(
+                location,
+                Cached {
+                    size: info.size,
+                    modified,
+                    digest,
+                },
+            );
+        }
+
+        Ok(digest)
+    }
+
+    /// Whether the file at the given paths in each tree holds the same bytes, by their digests.
+    pub(crate) fn same_content(
+        &mut self,
+        a: (&dyn Source, FileInfo),
+        b: (&dyn Source, FileInfo),
+        paths: (&Path, &Path),
+        stats: &mut Stats,
+    ) -> io::Result<bool> {
+        Ok(self.digest(a.0, paths.0, a.1, stats)? == self.digest(b.0, paths.1, b.1, stats)?)
+    }
+}
+
+/// The digest of the file at the given path in the tree, reading it a chunk at a time.
+fn digest(source: &dyn Source, path: &Path, stats: &mut Stats) -> io::Result<Digest> {
+    const CHUNK: usize = 64 * 1024;
+    let mut reader = source.open(path)?;
+    let mut chunk = vec![0; CHUNK];
+    let mut hasher = Sha256::new();
+    stats.hold(CHUNK);
+
+    loop {
+        let read = fill(&mut reader, &mut chunk)?;
+        stats.read(read);
+        hasher.update(&chunk[..read]);
+        if read < CHUNK {
+            break;
+        }
+    }
+
+    stats.release(CHUNK);
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{testing::TempDir, Cache, Comparison, Options};
+
+    fn write(path: &Path, contents: &str, modified: SystemTime) {
+        fs::write(path, contents).unwrap();
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn only_changed_files_should_be_read_again() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        let past = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for dir in [&a, &b] {
+            fs::create_dir(dir).unwrap();
+            write(&dir.join("one.txt"), "one", past);
+            write(&dir.join("two.txt"), "two", past);
+        }
+
+        let options = Options::new();
+        let mut cache = Cache::new();
+        let first = Comparison::between_cached(&a, &b, &options, &mut cache).unwrap();
+        assert!(first.report().is_empty());
+        assert_eq!(first.stats().bytes_read(), 12);
+        assert_eq!(cache.len(), 4);
+
+        let second = Comparison::between_cached(&a, &b, &options, &mut cache).unwrap();
+        assert!(second.report().is_empty());
+        assert_eq!(second.stats().bytes_read(), 0);
+
+        // A change of the same size is caught by the changed modification time.
+        write(&b.join("two.txt"), "TWO", past + Duration::from_secs(1));
+        let third = Comparison::between_cached(&a, &b, &options, &mut cache).unwrap();
+        assert_eq!(third.report().differences().len(), 1);
+        assert_eq!(third.stats().bytes_read(), 3);
+
+        fs::remove_file(a.join("one.txt")).unwrap();
+        cache.prune();
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn recently_modified_files_should_not_be_cached() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::write(&a, "fresh").unwrap();
+        fs::write(&b, "fresh").unwrap();
+
+        let mut cache = Cache::new();
+        Comparison::between_cached(&a, &b, &Options::new(), &mut cache).unwrap();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+/*!
+A filter that rewrites JSON documents in a canonical form, so that documents that differ only in
+formatting or in the order of their objects' keys compare equal.
+*/
+
+use std::{collections::BTreeMap, io};
+
+use crate::{inflate::invalid, json::push_string, Filter};
+
+/// How deeply arrays and objects may nest, so that hostile documents can't exhaust the stack.
+const MAX_DEPTH: usize = 512;
+
+/**
+Canonicalizes JSON documents: whitespace between tokens is removed, the keys of objects are sorted,
+and strings are rewritten with the fewest escapes. Numbers are kept as written, so `1.0` and `1`
+still differ, as do objects with duplicate keys, whose values are kept in order.
+
+```
+use dir_compare::{filters::Json, Filter};
+
+let a = Json.apply(br#"{ "b": [1, 2], "a": "\u0041" }"#.to_vec())?;
+let b = Json.apply(br#"{"a":"A","b":[1,2]}"#.to_vec())?;
+
+assert_eq!(a, b);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Filter for Json {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        let text = std::str::from_utf8(&bytes).map_err(|_| invalid("JSON must be UTF-8"))?;
+        let mut parser = Parser {
+            text: text.strip_prefix('\u{feff}').unwrap_or(text),
+            position: 0,
+        };
+        let mut canonical = String::with_capacity(bytes.len());
+        parser.skip_whitespace();
+        parser.value(&mut canonical, 0)?;
+        parser.skip_whitespace();
+        if parser.position != parser.text.len() {
+            return Err(invalid("the JSON document continues after its value"));
+        }
+
+        Ok(canonical.into_bytes())
+    }
+}
+
+/// Reads a JSON document, writing each value in canonical form as it goes.
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    /// Read a value, and append it to the output in canonical form.
+    fn value(&mut self, output: &mut String, depth: usize) -> io::Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("the JSON document nests too deeply"));
+        }
+
+        match self.peek() {
+            Some(b'{') => self.object(output, depth),
+            Some(b'[') => self.array(output, depth),
+            Some(b'"') => {
+                let string = self.string()?;
+                push_string(output, &string);
+                Ok(())
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(output),
+            _ => {
+                for literal in ["true", "false", "null"] {
+                    if self.text[self.position..].starts_with(literal) {
+                        self.position += literal.len();
+                        output.push_str(literal);
+                        return Ok(());
+                    }
+                }
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    fn object(&mut self, output: &mut String, depth: usize) -> io::Result<()> {
+        self.position += 1;
+        // Values with the same key are kept in the order they were written.
+        let mut members: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        self.skip_whitespace();
+        if !self.eat(b'}') {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some(b'"') {
+                    return Err(self.unexpected());
+                }
+                let key = self.string()?;
+                self.skip_whitespace();
+                if !self.eat(b':') {
+                    return Err(self.unexpected());
+                }
+                self.skip_whitespace();
+                let mut value = String::new();
+                self.value(&mut value, depth + 1)?;
+                members.entry(key).or_default().push(value);
+                self.skip_whitespace();
+                if self.eat(b'}') {
+                    break;
+                }
+                if !self.eat(b',') {
+                    return Err(self.unexpected());
+                }
+            }
+        }
+
+        output.push('{');
+        let members = members
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)));
+        for (index, (key, value)) in members.enumerate() {
+            if index > 0 {
+                output.push(',');
+            }
+            push_string(output, key);
+            output.push(':');
+            output.push_str(value);
+        }
+        output.push('}');
+
+        Ok(())
+    }
+
+    fn array(&mut self, output: &mut String, depth: usize) -> io::Result<()> {
+        self.position += 1;
+        output.push('[');
+        self.skip_whitespace();
+        if self.eat(b']') {
+            output.push(']');
+            return Ok(());
+        }
+
+        loop {
+            self.skip_whitespace();
+            self.value(output, depth + 1)?;
+            self.skip_whitespace();
+            if self.eat(b']') {
+                output.push(']');
+                return Ok(());
+            }
+            if !self.eat(b',') {
+                return Err(self.unexpected());
+            }
+            output.push(',');
+        }
+    }
+
+    /// Read a string, decoding its escapes.
+    fn string(&mut self) -> io::Result<String> {
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            let rest = &self.text[self.position..];
+            let end = rest
+                .find(['"', '\\'])
+                .ok_or_else(|| invalid("a JSON string is never closed"))?;
+            if rest[..end].chars().any(|character| character < ' ') {
+                return Err(invalid("a JSON string contains a control character"));
+            }
+            string.push_str(&rest[..end]);
+            self.position += end + 1;
+            if rest.as_bytes()[end] == b'"' {
+                return Ok(string);
+            }
+
+            let escape = self.peek().ok_or_else(|| self.unexpected())?;
+            self.position += 1;
+            string.push(match escape {
+                b'"' => '"',
+                b'\\' => '\\',
+                b'/' => '/',
+                b'b' => '\u{8}',
+                b'f' => '\u{c}',
+                b'n' => '\n',
+                b'r' => '\r',
+                b't' => '\t',
+                b'u' => self.unicode_escape()?,
+                _ => return Err(invalid("a JSON string contains an unknown escape")),
+            });
+        }
+    }
+
+    /// Read the digits of a `\u` escape, and those of the low surrogate that may follow it.
+    fn unicode_escape(&mut self) -> io::Result<char> {
+        let high = self.hex()?;
+        let code =
+            if (0xd800..0xdc00).contains(&high) && self.text[self.position..].starts_with("\\u") {
+                self.position += 2;
+                let low = self.hex()?;
+                if !(0xdc00..0xe000).contains(&low) {
+                    return Err(invalid("a JSON string contains an unpaired surrogate"));
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            } else {
+                high
+            };
+
+        char::from_u32(code).ok_or_else(|| invalid("a JSON string contains an unpaired surrogate"))
+    }
+
+    fn hex(&mut self) -> io::Result<u32> {
+        let digits = self
+            .text
+            .get(self.position..self.position + 4)
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| invalid("a JSON string contains a malformed `\\u` escape"))?;
+        self.position += 4;
+
+        Ok(u32::from_str_radix(digits, 16).expect("the digits are hexadecimal"))
+    }
+
+    /// Read a number, which is kept as written.
+    fn number(&mut self, output: &mut String) -> io::Result<()> {
+        let start = self.position;
+        self.eat(b'-');
+        if !self.eat(b'0') && self.digits() == 0 {
+            return Err(self.unexpected());
+        }
+        if self.eat(b'.') && self.digits() == 0 {
+            return Err(self.unexpected());
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            let _ = self.eat(b'+') || self.eat(b'-');
+            if self.digits() == 0 {
+                return Err(self.unexpected());
+            }
+        }
+        output.push_str(&self.text[start..self.position]);
+
+        Ok(())
+    }
+
+    /// Skip a run of digits, returning how many there were.
+    fn digits(&mut self) -> usize {
+        let count = self.text.as_bytes()[self.position..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        self.position += count;
+        count
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    /// Skip the given byte, if it's next.
+    fn eat(&mut self, byte: u8) -> bool {
+        let next = self.peek() == Some(byte);
+        if next {
+            self.position += 1;
+        }
+        next
+    }
+
+    fn unexpected(&self) -> io::Error {
+        match self.text[self.position..].chars().next() {
+            Some(character) => invalid(&format!(
+                "unexpected {character:?} at byte {} of the JSON document",
+                self.position
+            )),
+            None => invalid("the JSON document ends early"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::Json;
+    use crate::Filter;
+
+    #[test]
+    fn json_should_be_canonicalized() {
+        let canonical = |json: &str| String::from_utf8(Json.apply(json.into()).unwrap()).unwrap();
+
+        assert_eq!(
+            canonical("{\n  \"z\": null,\n  \"a\": { \"d\": [true, false, -1.5e3], \"c\": {} }\n}"),
+            r#"{"a":{"c":{},"d":[true,false,-1.5e3]},"z":null}"#
+        );
+        assert_eq!(
+            canonical(r#" "\ud83d\ude00\/\u0009" "#),
+            "\"\u{1f600}/\\t\""
+        );
+        assert_eq!(
+            canonical(r#"{"k":2,"j":0,"k":1}"#),
+            r#"{"j":0,"k":2,"k":1}"#
+        );
+
+        for malformed in [
+            "",
+            "{",
+            "[1,]",
+            "01",
+            "\"\\ud800\"",
+            "{} {}",
+            "nul",
+            "{\"a\" 1}",
+        ] {
+            let error = Json.apply(malformed.into()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{malformed:?}");
+        }
+        let deep = "[".repeat(10_000);
+        assert!(Json.apply(deep.into()).is_err());
+    }
+}
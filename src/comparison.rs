@@ -0,0 +1,445 @@
+/*!
+Comparisons of two trees on disk, with a record of what they cost.
+*/
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    diff::diff_sources, recheck::Recheck, remote::Remote, source::Disk, Cache, DiffReport, Options,
+    Stats, TreeSource,
+};
+
+/**
+The result of comparing two trees on disk: the differences between them and the resources it took
+to find them.
+
+Neither tree is held in memory as a whole. Both are walked one directory at a time, and their files
+are compared a chunk at a time.
+
+```
+use dir_compare::{Comparison, Options};
+
+let comparison = Comparison::between(
+    "fixtures/not-equivalent/dir-a",
+    "fixtures/not-equivalent/dir-b",
+    &Options::new(),
+)?;
+
+assert_eq!(comparison.report().differences().len(), 1);
+println!("Read {} bytes in {:?}.", comparison.stats().bytes_read(), comparison.stats().wall_time());
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
+Files that are hard links to the same file, on either side or across both, such as those of
+snapshots that share unchanged files, match without being read. Files on one side that are linked
+to one another are read once against their counterparts on the other, unless filters apply to them.
+*/
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    report: DiffReport,
+    stats: Stats,
+    hard_links: (Vec<Vec<PathBuf>>, Vec<Vec<PathBuf>>),
+}
+
+impl Comparison {
+    /**
+    Compare the files or directories at the given paths, reading and comparing them as configured
+    by the given [`Options`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn between(
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        options: &Options,
+    ) -> io::Result<Self> {
+        Self::on_disk(a.as_ref(), b.as_ref(), options, None, None)
+    }
+
+    /**
+    Recheck the differences between the files or directories at the given paths, given the report
+    of an earlier comparison between them that started at the time `since`. Only files that differed
+    before, or that either side has modified since, are read. See
+    [`Content::recheck_against_disk`](crate::Content::recheck_against_disk).
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn recheck(
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        previous: &DiffReport,
+        since: SystemTime,
+        options: &Options,
+    ) -> io::Result<Self> {
+        let recheck = Recheck::new(previous, since);
+        Self::on_disk(a.as_ref(), b.as_ref(), options, Some(&recheck), None)
+    }
+
+    /**
+    Compare the files or directories at the given paths, as with [`Comparison::between`], comparing
+    files by the digests in the given [`Cache`] where they're unchanged since they were cached, and
+    caching the digests of the files that are read.
+
+    ```
+    use dir_compare::{Cache, Comparison, Options};
+
+    let options = Options::new();
+    let mut cache = Cache::new();
+    loop {
+        let comparison = Comparison::between_cached(
+            "fixtures/equivalent/dir-a",
+            "fixtures/equivalent/dir-b",
+            &options,
+            &mut cache,
+        )?;
+        println!("Read {} bytes.", comparison.stats().bytes_read());
+        # break;
+    }
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn between_cached(
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        options: &Options,
+        cache: &mut Cache,
+    ) -> io::Result<Self> {
+        Self::on_disk(a.as_ref(), b.as_ref(), options, None, Some(cache))
+    }
+
+    /**
+    Compare only the entries at the given paths, relative to the roots, of the files or
+    directories at the given paths, as configured by the given [`Options`]. The rest of each tree
+    isn't walked, except for listing the directories on the way to the given paths. See
+    [`Options::only_paths`].
+
+    ```
+    use dir_compare::{Comparison, Options};
+
+    let (a, b) = ("fixtures/not-equivalent/dir-a", "fixtures/not-equivalent/dir-b");
+    let comparison = Comparison::between_paths(a, b, ["test.txt"], &Options::new())?;
+
+    assert!(comparison.report().is_empty());
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn between_paths(
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+        options: &Options,
+    ) -> io::Result<Self> {
+        let options = options.clone().only_paths(paths);
+        Self::on_disk(a.as_ref(), b.as_ref(), &options, None, None)
+    }
+
+    /**
+    Compare the file or directory at the given path with a tree from elsewhere, such as another
+    machine, which is listed and read only as the comparison reaches its entries. Both trees are
+    read as configured by the given [`Options`]. See [`TreeSource`].
+
+    # Errors
+
+    Will bubble I/O errors, including those of the [`TreeSource`].
+    */
+    pub fn against(a: impl AsRef<Path>, b: &dyn TreeSource, options: &Options) -> io::Result<Self> {
+        let a = Disk::new(a.as_ref(), options);
+        let b = Remote::new(b, options);
+        let mut stats = Stats::default();
+        let report = stats.time(|stats| diff_sources(&a, &b, options, None, None, stats))?;
+
+        Ok(Self {
+            report,
+            stats,
+            hard_links: (a.link_groups(), Vec::new()),
+        })
+    }
+
+    /**
+    Compare two trees from elsewhere, as with [`Comparison::against`].
+
+    # Errors
+
+    Will bubble I/O errors, including those of either [`TreeSource`].
+    */
+    pub fn between_sources(
+        a: &dyn TreeSource,
+        b: &dyn TreeSource,
+        options: &Options,
+    ) -> io::Result<Self> {
+        let a = Remote::new(a, options);
+        let b = Remote::new(b, options);
+        let mut stats = Stats::default();
+        let report = stats.time(|stats| diff_sources(&a, &b, options, None, None, stats))?;
+
+        Ok(Self {
+            report,
+            stats,
+            hard_links: Default::default(),
+        })
+    }
+
+    pub(crate) fn on_disk(
+        a: &Path,
+        b: &Path,
+        options: &Options,
+        recheck: Option<&Recheck>,
+        cache: Option<&mut Cache>,
+    ) -> io::Result<Self> {
+        let a = Disk::new(a, options);
+        let b = Disk::new(b, options);
+        let mut stats = Stats::default();
+        let report = stats.time(|stats| diff_sources(&a, &b, options, recheck, cache, stats))?;
+
+        Ok(Self {
+            report,
+            stats,
+            hard_links: (a.link_groups(), b.link_groups()),
+        })
+    }
+
+    /// The differences between the trees.
+    #[must_use]
+    pub fn report(&self) -> &DiffReport {
+        &self.report
+    }
+
+    /// The resources the comparison used.
+    #[must_use]
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /**
+    The groups of files in the first and the second tree that are hard links to the same file, by
+    their paths relative to the roots. Links from outside the trees, or from entries that were
+    skipped, aren't listed. Always empty for a [`TreeSource`], and on platforms other than Unix.
+    */
+    #[must_use]
+    pub fn hard_links(&self) -> (&[Vec<PathBuf>], &[Vec<PathBuf>]) {
+        (&self.hard_links.0, &self.hard_links.1)
+    }
+
+    /// The comparison with the given differences, and resources used, in place of its own.
+    pub(crate) fn replaced(mut self, report: DiffReport, stats: Stats) -> Self {
+        self.report = report;
+        self.stats = stats;
+        self
+    }
+
+    /// Discard the statistics, keeping only the differences.
+    #[must_use]
+    pub fn into_report(self) -> DiffReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{testing::TempDir, Comparison, DifferenceKind, Options};
+
+    #[test]
+    fn bytes_read_should_cover_both_sides() {
+        let comparison = Comparison::between(
+            "fixtures/equivalent/dir-a",
+            "fixtures/equivalent/dir-b",
+            &Options::new(),
+        )
+        .unwrap();
+
+        // Two files of 10 and 15 bytes on each side.
+        assert!(comparison.report().is_empty());
+        assert_eq!(comparison.stats().bytes_read(), 50);
+        assert!(comparison.stats().peak_memory() > 0);
+    }
+
+    #[test]
+    fn files_of_different_sizes_should_not_be_read() {
+        let comparison = Comparison::between(
+            "fixtures/not-equivalent/dir-a",
+            "fixtures/not-equivalent/dir-b",
+            &Options::new(),
+        )
+        .unwrap();
+
+        // Only the two files of 10 bytes, which share a size, are read.
+        assert_eq!(comparison.report().differences().len(), 1);
+        assert_eq!(comparison.stats().bytes_read(), 20);
+    }
+
+    #[test]
+    fn directories_and_large_files_should_be_timed_when_enabled() {
+        let (a, b) = ("fixtures/equivalent/dir-a", "fixtures/equivalent/dir-b");
+        let comparison = Comparison::between(a, b, &Options::new()).unwrap();
+        assert!(comparison.stats().entry_times().is_empty());
+
+        // Only the file of 15 bytes on each side is large enough.
+        let options = Options::new().time_entries(30);
+        let comparison = Comparison::between(a, b, &options).unwrap();
+        let stats = comparison.stats();
+        let paths: Vec<_> = stats.entry_times().iter().map(|(path, _)| path).collect();
+        assert_eq!(
+            paths,
+            [
+                &PathBuf::new(),
+                &PathBuf::from("subdir"),
+                &PathBuf::from("subdir/test2.txt")
+            ]
+        );
+        assert!(stats.subtree_time("") >= stats.subtree_time("subdir"));
+        assert!(stats.subtree_time("") <= stats.wall_time());
+    }
+
+    #[test]
+    fn only_the_given_paths_should_be_compared() {
+        let (a, b) = (
+            "fixtures/not-equivalent/dir-a",
+            "fixtures/not-equivalent/dir-b",
+        );
+        let compare = |paths: &[&str]| Comparison::between_paths(a, b, paths, &Options::new());
+
+        let comparison = compare(&["test.txt"]).unwrap();
+        assert!(comparison.report().is_empty());
+        assert_eq!(comparison.stats().bytes_read(), 20);
+
+        // The files of different sizes aren't read, and the file outside the paths isn't either.
+        let comparison = compare(&["subdir/", "missing/file.txt"]).unwrap();
+        let paths: Vec<_> = comparison
+            .report()
+            .iter()
+            .map(|difference| difference.path())
+            .collect();
+        assert_eq!(paths, [PathBuf::from("subdir/test2.txt")]);
+        assert_eq!(comparison.stats().bytes_read(), 0);
+    }
+
+    #[test]
+    fn files_modified_at_different_times_should_differ_when_enabled() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&a)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+        File::options()
+            .write(true)
+            .open(&b)
+            .unwrap()
+            .set_modified(time + Duration::from_secs(1))
+            .unwrap();
+
+        let comparison = Comparison::between(&a, &b, &Options::new()).unwrap();
+        assert!(comparison.report().is_empty());
+
+        let comparison = Comparison::between(&a, &b, &Options::new().compare_mtimes(true)).unwrap();
+        assert_eq!(
+            comparison.report().differences()[0].kind(),
+            DifferenceKind::Modified
+        );
+        assert_eq!(comparison.stats().bytes_read(), 0);
+    }
+
+    #[test]
+    fn modification_times_should_match_within_the_tolerance_and_precision() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::write(&a, "same").unwrap();
+        fs::write(&b, "same").unwrap();
+        let set_modified = |path, time| {
+            let file = File::options().write(true).open(path).unwrap();
+            file.set_modified(time).unwrap();
+        };
+        let differs = |options: Options| {
+            let options = options.compare_mtimes(true);
+            !Comparison::between(&a, &b, &options)
+                .unwrap()
+                .report()
+                .is_empty()
+        };
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        set_modified(&a, time + Duration::from_millis(100));
+        set_modified(&b, time + Duration::from_millis(1900));
+        assert!(differs(Options::new()));
+        assert!(differs(
+            Options::new().mtime_tolerance(Duration::from_secs(1))
+        ));
+        assert!(!differs(
+            Options::new().mtime_tolerance(Duration::from_secs(2))
+        ));
+        assert!(!differs(
+            Options::new().mtime_precision(Duration::from_secs(2))
+        ));
+        assert!(differs(
+            Options::new().mtime_precision(Duration::from_secs(1))
+        ));
+
+        // Truncation can put times that are close together a whole step apart.
+        set_modified(&b, time + Duration::from_millis(2100));
+        assert!(differs(
+            Options::new().mtime_precision(Duration::from_secs(2))
+        ));
+        assert!(!differs(
+            Options::new()
+                .mtime_precision(Duration::from_secs(2))
+                .mtime_tolerance(Duration::from_secs(2))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_linked_files_should_be_read_once() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir_all(a.join("old")).unwrap();
+        fs::create_dir_all(b.join("old")).unwrap();
+        fs::write(a.join("shared"), "unchanged").unwrap();
+        fs::write(a.join("one"), "linked").unwrap();
+        fs::write(b.join("one"), "linked").unwrap();
+        // Snapshots share their unchanged files, and each links to its own files elsewhere.
+        fs::hard_link(a.join("shared"), b.join("shared")).unwrap();
+        fs::hard_link(a.join("one"), a.join("old/one")).unwrap();
+        fs::hard_link(b.join("one"), b.join("old/one")).unwrap();
+
+        let comparison = Comparison::between(&a, &b, &Options::new()).unwrap();
+        assert!(comparison.report().is_empty());
+        // Only the first pair of linked files is read.
+        assert_eq!(comparison.stats().bytes_read(), 12);
+        assert_eq!(comparison.stats().linked_files(), 2);
+        let group = vec![PathBuf::from("old/one"), PathBuf::from("one")];
+        assert_eq!(
+            comparison.hard_links(),
+            (&[group.clone()][..], &[group][..])
+        );
+    }
+}
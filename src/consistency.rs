@@ -0,0 +1,248 @@
+/*!
+Reading trees that change while they're read, such as live log or spool directories.
+*/
+
+use std::{fs, io, path::Path, thread, time::Duration};
+
+/// How long to wait before the first retry. Each later retry waits that much longer.
+const RETRY_PAUSE: Duration = Duration::from_millis(10);
+
+/**
+What to do with an entry that's listed in its directory but vanishes before it's read. See
+[`Options::consistency`](crate::Options::consistency).
+
+The entry at the given path itself is never settled this way: if it's missing, that's an error.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Consistency {
+    /// Fail with the error of kind [`std::io::ErrorKind::NotFound`] that reading the entry failed
+    /// with.
+    #[default]
+    Strict,
+    /// Read the entry again, up to the given number of times, pausing a little longer before each
+    /// attempt, such as for a file that's replaced by deleting and recreating it. Fail as
+    /// [`Consistency::Strict`] does if it's still missing.
+    Retry(u32),
+    /// Leave the entry out, as if it hadn't been listed.
+    Skip,
+    /// Report the entry as [`DifferenceKind::Volatile`](crate::DifferenceKind::Volatile), without
+    /// its descendants, when comparing. Trees read into memory leave it out, as
+    /// [`Consistency::Skip`] does, since they have no way to record it.
+    Volatile,
+}
+
+impl Consistency {
+    /**
+    Run the given operation, which reads an entry, as the policy says. Return `None` if the entry
+    vanished and is to be skipped or reported as volatile. An entry that isn't found only vanished
+    if `vanished` says so, such as by [`missing`]; otherwise, failing to read it is an error.
+    */
+    pub(crate) fn settle<T>(
+        self,
+        mut operation: impl FnMut() -> io::Result<T>,
+        vanished: impl Fn() -> bool,
+    ) -> io::Result<Option<T>> {
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Err(error) if error.kind() == io::ErrorKind::NotFound && vanished() => match self {
+                    Self::Strict => return Err(error),
+                    Self::Retry(attempts) if attempt >= attempts => return Err(error),
+                    Self::Retry(_) => {
+                        attempt += 1;
+                        thread::sleep(RETRY_PAUSE * attempt);
+                    }
+                    Self::Skip | Self::Volatile => return Ok(None),
+                },
+                result => return result.map(Some),
+            }
+        }
+    }
+}
+
+/// Whether the entry at the given path is missing from disk, rather than there but unreadable, as a
+/// link to nothing is.
+pub(crate) fn missing(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        ffi::OsString,
+        io::{self, Read},
+        ops::Range,
+        path::Path,
+    };
+
+    use super::Consistency;
+    use crate::{Comparison, DifferenceKind, EntryKind, Options, TreeSource};
+
+    /// A flat tree of files, one of which vanishes once it's listed.
+    struct Spool {
+        files: Vec<(&'static str, &'static str)>,
+        vanishing: &'static str,
+        /// Which openings of the vanishing file fail, counting from zero.
+        failing: Range<u32>,
+        /// How many times the vanishing file has been opened.
+        opened: Cell<u32>,
+    }
+
+    impl Spool {
+        /// A spool from which the given file vanishes for good.
+        fn new(files: Vec<(&'static str, &'static str)>, vanishing: &'static str) -> Self {
+            Self {
+                files,
+                vanishing,
+                failing: 0..u32::MAX,
+                opened: Cell::new(0),
+            }
+        }
+    }
+
+    impl TreeSource for Spool {
+        fn root(&self) -> io::Result<EntryKind> {
+            Ok(EntryKind::Directory)
+        }
+
+        fn children(&self, _path: &Path) -> io::Result<Vec<(OsString, EntryKind)>> {
+            Ok(self
+                .files
+                .iter()
+                .map(|(name, _)| (name.into(), EntryKind::File))
+                .collect())
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+            let (name, contents) = self
+                .files
+                .iter()
+                .find(|(name, _)| path == Path::new(name))
+                .ok_or(io::ErrorKind::NotFound)?;
+            if *name == self.vanishing {
+                let opened = self.opened.replace(self.opened.get() + 1);
+                if self.failing.contains(&opened) {
+                    return Err(io::ErrorKind::NotFound.into());
+                }
+            }
+            Ok(Box::new(contents.as_bytes()))
+        }
+    }
+
+    /// An operation that fails as if its entry were missing the given number of times.
+    fn vanishing(failures: u32) -> impl FnMut() -> io::Result<u32> {
+        let calls = Cell::new(0);
+        move || {
+            calls.set(calls.get() + 1);
+            match calls.get() > failures {
+                true => Ok(calls.get()),
+                false => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn vanished_entries_should_be_settled_as_configured() {
+        let gone = || true;
+        assert_eq!(
+            Consistency::Strict
+                .settle(vanishing(1), gone)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            Consistency::Retry(2).settle(vanishing(2), gone).unwrap(),
+            Some(3)
+        );
+        assert!(Consistency::Retry(1).settle(vanishing(2), gone).is_err());
+        assert_eq!(Consistency::Skip.settle(vanishing(1), gone).unwrap(), None);
+        assert_eq!(
+            Consistency::Volatile.settle(vanishing(0), gone).unwrap(),
+            Some(1)
+        );
+
+        // Entries that are still there, and other errors, are never settled.
+        assert!(Consistency::Skip.settle(vanishing(1), || false).is_err());
+        let denied = || Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(Consistency::Skip.settle(denied, gone).is_err());
+    }
+
+    #[test]
+    fn entries_that_vanish_mid_comparison_should_be_settled_as_configured() {
+        let files = vec![("kept.txt", "kept"), ("rotated.log", "log")];
+        let before = Spool::new(files.clone(), "");
+        let during = Spool::new(files, "rotated.log");
+        let compare = |policy| {
+            let options = Options::new().consistency(policy);
+            Comparison::between_sources(&before, &during, &options).map(Comparison::into_report)
+        };
+
+        assert_eq!(
+            compare(Consistency::Strict).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(compare(Consistency::Retry(1)).is_err());
+        assert!(compare(Consistency::Skip).unwrap().is_empty());
+
+        let report = compare(Consistency::Volatile).unwrap();
+        let differences: Vec<_> = report
+            .iter()
+            .map(|difference| (difference.path(), difference.kind()))
+            .collect();
+        assert_eq!(
+            differences,
+            [(Path::new("rotated.log"), DifferenceKind::Volatile)]
+        );
+        assert_eq!(report.summary().volatile(), 1);
+        assert!(report.sync_plan().is_empty());
+    }
+
+    #[test]
+    fn retried_entries_should_be_read_once_they_are_found() {
+        let before = Spool::new(vec![("rotated.log", "old")], "");
+        let mut during = Spool::new(vec![("rotated.log", "new")], "rotated.log");
+        // The file is found as it's compared, but not as it's opened again to be diffed.
+        during.failing = 1..2;
+        let options = Options::new()
+            .consistency(Consistency::Retry(1))
+            .unified_diffs(true);
+        let comparison = Comparison::between_sources(&before, &during, &options).unwrap();
+
+        assert!(comparison.report().differences()[0]
+            .unified_diff()
+            .is_some());
+        // Each file is read once to compare it and once to diff it, however often it was opened.
+        assert_eq!(comparison.stats().bytes_read(), 2 * 6);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_to_nothing_should_not_be_taken_for_vanished_entries() {
+        use std::{fs, os::unix::fs::symlink};
+
+        use crate::{testing::TempDir, Content};
+
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for dir in [&a, &b] {
+            fs::create_dir(dir).unwrap();
+            fs::write(dir.join("kept.txt"), "kept").unwrap();
+        }
+        symlink(a.join("rotated.log"), a.join("current.log")).unwrap();
+        fs::write(b.join("current.log"), "log").unwrap();
+
+        for policy in [
+            Consistency::Skip,
+            Consistency::Volatile,
+            Consistency::Retry(1),
+        ] {
+            let options = Options::new().consistency(policy);
+            let error = Content::of_with(&a, &options).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound, "{policy:?}");
+            let error = Comparison::between(&a, &b, &options).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotFound, "{policy:?}");
+        }
+    }
+}
@@ -0,0 +1,993 @@
+/*!
+Path-by-path comparison of two trees.
+
+Where [`PartialEq`] only says _whether_ two trees differ, a [`DiffReport`] says _where_ and _how_.
+*/
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    io::{self, Read},
+    mem,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{
+    attributes::attributes,
+    cache::Cache,
+    recheck::Recheck,
+    remote::Remote,
+    source::{at, same_content, unless_vanished, Child, Disk, EntryKind, FileId, FileInfo, Source},
+    trace::{Evidence, TraceEvent},
+    unified::{as_text, unified_diff},
+    Consistency, Content, Entry, Options, SpecialKind, Stats, TreeSource,
+};
+
+/**
+A single difference between two trees, located by its path relative to their roots.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    path: PathBuf,
+    kind: DifferenceKind,
+    sizes: (Option<u64>, Option<u64>),
+    offset: Option<u64>,
+    unified_diff: Option<String>,
+}
+
+impl Difference {
+    pub(crate) fn new(path: PathBuf, kind: DifferenceKind) -> Self {
+        Self {
+            path,
+            kind,
+            sizes: (None, None),
+            offset: None,
+            unified_diff: None,
+        }
+    }
+
+    pub(crate) fn with_sizes(mut self, sizes: (Option<u64>, Option<u64>)) -> Self {
+        self.sizes = sizes;
+        self
+    }
+
+    pub(crate) fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub(crate) fn with_unified_diff(mut self, unified_diff: Option<String>) -> Self {
+        self.unified_diff = unified_diff;
+        self
+    }
+
+    /// A copy of the difference located by the given path joined with its path.
+    pub(crate) fn relocated(&self, root: &Path) -> Self {
+        Self {
+            path: at(root, &self.path),
+            ..self.clone()
+        }
+    }
+
+    /// The path of the differing entry, relative to the roots of the compared trees.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How the entry differs.
+    #[must_use]
+    pub fn kind(&self) -> DifferenceKind {
+        self.kind
+    }
+
+    /**
+    The sizes of the entry in the first and second trees, where it's a file whose size is known
+    without reading it: not where it's missing or isn't a file, nor where it's a filtered file on
+    disk, whose size says nothing about that of its filtered bytes. A file in only one tree is
+    always known to be empty, if it is. See [`Difference::is_empty_file`].
+    */
+    #[must_use]
+    pub fn sizes(&self) -> (Option<u64>, Option<u64>) {
+        self.sizes
+    }
+
+    /**
+    How many bytes bigger the entry is in the second tree than in the first, or smaller if
+    negative. Sizes that aren't known, as with [`Difference::sizes`], count as zero, so an added
+    file grows by its whole size.
+    */
+    #[must_use]
+    pub fn size_change(&self) -> i64 {
+        let size = |size: Option<u64>| i64::try_from(size.unwrap_or(0)).unwrap_or(i64::MAX);
+        size(self.sizes.1) - size(self.sizes.0)
+    }
+
+    /**
+    Whether the entry is an empty file that exists in only one tree, which
+    [`Options::empty_as_missing`] would treat as missing.
+    */
+    #[must_use]
+    pub fn is_empty_file(&self) -> bool {
+        match self.kind {
+            DifferenceKind::Added => self.sizes.1 == Some(0),
+            DifferenceKind::Removed => self.sizes.0 == Some(0),
+            _ => false,
+        }
+    }
+
+    /**
+    The offset of the first byte at which the two versions of a modified file differ, or the size
+    of the smaller one, if it's the start of the other. Only found by
+    [`Entry::first_difference`] and [`Content::first_difference`].
+    */
+    #[must_use]
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /**
+    A unified diff of the two versions of a modified text file, in the format of `diff -u`. Only
+    computed when enabled with [`Options::unified_diffs`], and only for files that are valid UTF-8.
+    */
+    #[must_use]
+    pub fn unified_diff(&self) -> Option<&str> {
+        self.unified_diff.as_deref()
+    }
+}
+
+/**
+The ways in which an entry may differ between two trees. "Added" and "removed" are relative to the
+first tree; that is, the one the comparison was called on.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DifferenceKind {
+    /// The entry exists only in the second tree.
+    Added,
+    /// The entry exists only in the first tree.
+    Removed,
+    /// The entry is a file in both trees, but their bytes differ.
+    Modified,
+    /// The entry is of a different type in each tree, such as a file in one and a directory in the
+    /// other, or special files of different kinds.
+    TypeChanged,
+    /// The entry vanished from either tree while it was being read, so it's unknown whether it
+    /// differs. Only reported under [`Consistency::Volatile`](crate::Consistency::Volatile).
+    Volatile,
+    /// The entry holds the same bytes in both trees, but its attributes or alternate data streams
+    /// differ. Only reported on Windows, with
+    /// [`Options::compare_attributes`](crate::Options::compare_attributes).
+    AttributesChanged,
+}
+
+impl DifferenceKind {
+    /// A stable name for the kind, for persisting reports.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Modified => "modified",
+            Self::TypeChanged => "type-changed",
+            Self::Volatile => "volatile",
+            Self::AttributesChanged => "attributes-changed",
+        }
+    }
+
+    /// The kind with the given name, as returned by [`DifferenceKind::name`].
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Added,
+            Self::Removed,
+            Self::Modified,
+            Self::TypeChanged,
+            Self::Volatile,
+            Self::AttributesChanged,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+/**
+The differences between two trees, ordered by path.
+
+Every differing path is listed, including the descendants of added or removed directories.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    differences: Vec<Difference>,
+}
+
+impl DiffReport {
+    pub(crate) fn new(differences: Vec<Difference>) -> Self {
+        Self { differences }
+    }
+
+    /// Whether the trees were found to be equivalent.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// The differences, ordered by path.
+    #[must_use]
+    pub fn differences(&self) -> &[Difference] {
+        &self.differences
+    }
+
+    /// Iterate over the differences, ordered by path.
+    pub fn iter(&self) -> std::slice::Iter<'_, Difference> {
+        self.differences.iter()
+    }
+
+    /**
+    Count the differences by kind and total how much the sizes of the differing files changed.
+
+    ```
+    use dir_compare::Content;
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let summary = a.diff(&b).summary();
+
+    assert_eq!(summary.modified(), 1);
+    assert_eq!(summary.size_change(), 4);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn summary(&self) -> DiffSummary {
+        let mut summary = DiffSummary::default();
+        for difference in self {
+            *match difference.kind {
+                DifferenceKind::Added => &mut summary.added,
+                DifferenceKind::Removed => &mut summary.removed,
+                DifferenceKind::Modified => &mut summary.modified,
+                DifferenceKind::TypeChanged => &mut summary.type_changed,
+                DifferenceKind::Volatile => &mut summary.volatile,
+                DifferenceKind::AttributesChanged => &mut summary.attributes_changed,
+            } += 1;
+            let change = difference.size_change();
+            summary.size_change += change;
+            summary.size_churn += change.unsigned_abs();
+        }
+
+        summary
+    }
+
+    /**
+    The differences whose sizes changed the most, in either direction, up to the given number of
+    them, from the greatest change down. Differences whose sizes didn't change, such as those of
+    directories, are left out. See [`Difference::size_change`].
+    */
+    #[must_use]
+    pub fn largest(&self, count: usize) -> Vec<&Difference> {
+        let mut largest: Vec<_> = self
+            .iter()
+            .filter(|difference| difference.size_change() != 0)
+            .collect();
+        // The sort is stable, so equal changes stay ordered by path.
+        largest
+            .sort_by_key(|difference| std::cmp::Reverse(difference.size_change().unsigned_abs()));
+        largest.truncate(count);
+
+        largest
+    }
+}
+
+/**
+Totals over the differences in a [`DiffReport`], from [`DiffReport::summary`].
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    added: usize,
+    removed: usize,
+    modified: usize,
+    type_changed: usize,
+    volatile: usize,
+    attributes_changed: usize,
+    size_churn: u64,
+    size_change: i64,
+}
+
+impl DiffSummary {
+    /// The number of entries that exist only in the second tree.
+    #[must_use]
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// The number of entries that exist only in the first tree.
+    #[must_use]
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// The number of files whose bytes differ.
+    #[must_use]
+    pub fn modified(&self) -> usize {
+        self.modified
+    }
+
+    /// The number of entries of a different type in each tree.
+    #[must_use]
+    pub fn type_changed(&self) -> usize {
+        self.type_changed
+    }
+
+    /// The number of entries that vanished while they were being read.
+    #[must_use]
+    pub fn volatile(&self) -> usize {
+        self.volatile
+    }
+
+    /// The number of entries whose attributes or alternate data streams differ.
+    #[must_use]
+    pub fn attributes_changed(&self) -> usize {
+        self.attributes_changed
+    }
+
+    /**
+    The total of how much the sizes of the differing entries changed, in either direction: an
+    added or removed file counts in full, and a file that grew by 10 bytes counts the same as one
+    that shrank by 10. A file that was modified in place without changing size counts for nothing.
+    */
+    #[must_use]
+    pub fn size_churn(&self) -> u64 {
+        self.size_churn
+    }
+
+    /// How many bytes bigger the second tree is than the first, or smaller if negative.
+    #[must_use]
+    pub fn size_change(&self) -> i64 {
+        self.size_change
+    }
+}
+
+impl IntoIterator for DiffReport {
+    type Item = Difference;
+    type IntoIter = std::vec::IntoIter<Difference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.differences.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a DiffReport {
+    type Item = &'a Difference;
+    type IntoIter = std::slice::Iter<'a, Difference>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.differences.iter()
+    }
+}
+
+impl Content {
+    /**
+    List the differences between this tree and another.
+
+    As with the comparison of [`Content`]s, the names of the top-level entries play no part. The
+    paths in the report are relative to them.
+
+    ```
+    use dir_compare::{Content, DifferenceKind};
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let report = a.diff(&b);
+
+    assert_eq!(report.differences().len(), 1);
+    assert_eq!(report.differences()[0].path(), std::path::Path::new("subdir/test2.txt"));
+    assert_eq!(report.differences()[0].kind(), DifferenceKind::Modified);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> DiffReport {
+        self.diff_with(other, &Options::default())
+    }
+
+    /**
+    List the differences between this tree and another, as configured by the given [`Options`].
+    See [`Content::diff`].
+
+    ```
+    use dir_compare::{Content, Options};
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let report = a.diff_with(&b, &Options::new().unified_diffs(true));
+    let diff = report.differences()[0].unified_diff().unwrap();
+
+    assert!(diff.contains("-more test data\n+this is different!\n"));
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn diff_with(&self, other: &Self, options: &Options) -> DiffReport {
+        diff_sources(self, other, options, None, None, &mut Stats::default())
+            .expect("trees in memory can always be read")
+    }
+
+    /**
+    List the differences between this tree and the file or directory at the given path, as with
+    [`Content::diff`].
+
+    The tree on disk is never held in memory as a whole; it's walked one directory at a time, and
+    its files are compared against this tree's a chunk at a time.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn diff_against_disk(&self, path: impl AsRef<Path>) -> io::Result<DiffReport> {
+        self.diff_against_disk_with(path, &Options::default())
+    }
+
+    /**
+    List the differences between this tree and the file or directory at the given path, reading
+    the latter and comparing them as configured by the given [`Options`]. See
+    [`Content::diff_against_disk`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn diff_against_disk_with(
+        &self,
+        path: impl AsRef<Path>,
+        options: &Options,
+    ) -> io::Result<DiffReport> {
+        diff_sources(
+            self,
+            &Disk::new(path.as_ref(), options),
+            options,
+            None,
+            None,
+            &mut Stats::default(),
+        )
+    }
+
+    /**
+    List the differences between this tree and a tree from elsewhere, such as another machine,
+    reading the latter as configured by the given [`Options`]. See [`TreeSource`].
+
+    # Errors
+
+    Will bubble I/O errors, including those of the [`TreeSource`].
+    */
+    pub fn diff_against_source(
+        &self,
+        source: &dyn TreeSource,
+        options: &Options,
+    ) -> io::Result<DiffReport> {
+        diff_sources(
+            self,
+            &Remote::new(source, options),
+            options,
+            None,
+            None,
+            &mut Stats::default(),
+        )
+    }
+}
+
+impl Entry {
+    /**
+    List the differences between this entry's content and the file or directory at the given path.
+    See [`Content::diff_against_disk`].
+
+    Like [`Content::diff`], and unlike the comparison of [`Entry`]s, this ignores the name of the
+    entry itself.
+
+    ```
+    use dir_compare::Entry;
+
+    let snapshot = Entry::at("fixtures/equivalent/dir-a")?;
+
+    assert!(snapshot.diff_against_disk("fixtures/equivalent/dir-b")?.is_empty());
+    assert!(!snapshot.diff_against_disk("fixtures/not-equivalent/dir-b")?.is_empty());
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn diff_against_disk(&self, path: impl AsRef<Path>) -> io::Result<DiffReport> {
+        self.content.diff_against_disk(path)
+    }
+
+    /**
+    List the differences between this entry's content and the file or directory at the given path,
+    reading the latter and comparing them as configured by the given [`Options`]. See
+    [`Entry::diff_against_disk`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn diff_against_disk_with(
+        &self,
+        path: impl AsRef<Path>,
+        options: &Options,
+    ) -> io::Result<DiffReport> {
+        self.content.diff_against_disk_with(path, options)
+    }
+}
+
+/**
+Compare two trees path by path, reading each file only when it's reached. Files that a recheck
+trusts aren't read at all, and files with cached digests are compared by them.
+*/
+pub(crate) fn diff_sources(
+    a: &dyn Source,
+    b: &dyn Source,
+    options: &Options,
+    recheck: Option<&Recheck>,
+    mut cache: Option<&mut Cache>,
+    stats: &mut Stats,
+) -> io::Result<DiffReport> {
+    // A path pending comparison, with its path in the second tree where the names there differ.
+    type Pending = (
+        PathBuf,
+        Option<PathBuf>,
+        Option<EntryKind>,
+        Option<EntryKind>,
+    );
+    // The memory held by pending paths, or a reported difference, for the statistics.
+    fn footprint<T>(paths: &[Option<&Path>]) -> usize {
+        let paths = paths.iter().flatten().map(|path| path.as_os_str().len());
+        mem::size_of::<T>() + paths.sum::<usize>()
+    }
+
+    let mut differences = Vec::new();
+    let mut stack: Vec<Pending> = vec![(PathBuf::new(), None, Some(a.root()?), Some(b.root()?))];
+    // Whether the files with the given identities matched, for files with several hard links.
+    let mut linked: HashMap<(FileId, FileId), bool> = HashMap::new();
+
+    while let Some((path, other, kind_a, kind_b)) = stack.pop() {
+        stats.release(footprint::<Pending>(&[Some(&path), other.as_deref()]));
+        options.trace(|| TraceEvent::Entered { path: &path });
+        let (start, bytes_before) = (Instant::now(), stats.bytes_read());
+        // Entries are reported by their paths in the first tree, but read by their own in each.
+        let path_b = other.as_deref().unwrap_or(&path);
+
+        // An entry that vanished once it was listed is reported, if the policy says so. One that
+        // vanishes as it's read is retried, left out, or reported, as the sources settle it.
+        let policy = options.consistency_policy();
+        if a.vanished(&path) || b.vanished(path_b) {
+            options.trace(|| TraceEvent::Decided {
+                path: &path,
+                difference: Some(DifferenceKind::Volatile),
+                time: start.elapsed(),
+            });
+            differences.push(Difference::new(path, DifferenceKind::Volatile));
+            continue;
+        }
+        // The sizes of the entry, where it's a file, for the profiles of the trees.
+        let mut profiled = None;
+        let mut read = || {
+            let kind = difference_kind(kind_a, kind_b, || {
+                let (started, read_before) = (Instant::now(), stats.bytes_read());
+                let (same, evidence) = 'compare: {
+                    let (info_a, info_b) = (a.info(&path)?, b.info(path_b)?);
+                    if differ_by_info(info_a, info_b, options) {
+                        break 'compare (false, Evidence::Metadata);
+                    }
+
+                    // Links to the same file match, and links to files already compared match as
+                    // they did, unless filters for their other paths could have decoded them
+                    // differently.
+                    let ids = info_a.id.zip(info_b.id);
+                    let known = ids.and_then(|(id_a, id_b)| match id_a == id_b {
+                        true => Some(true),
+                        false if options.filters(&path) => None,
+                        false => linked.get(&(id_a, id_b)).copied(),
+                    });
+                    if let Some(same) = known {
+                        stats.link();
+                        break 'compare (same, Evidence::Link);
+                    }
+
+                    let trusted =
+                        recheck.is_some_and(|recheck| recheck.trusts(&path, info_a, info_b));
+                    let compared = match cache.as_deref_mut() {
+                        _ if trusted => (true, Evidence::Recheck),
+                        Some(cache) => (
+                            cache.same_content((a, info_a), (b, info_b), (&path, path_b), stats)?,
+                            Evidence::Digest,
+                        ),
+                        None => (
+                            same_content(a, b, (&path, path_b), stats)?,
+                            Evidence::Content,
+                        ),
+                    };
+                    if let Some(ids) = ids.filter(|_| !options.filters(&path)) {
+                        linked.insert(ids, compared.0);
+                    }
+                    compared
+                };
+                options.trace(|| TraceEvent::Compared {
+                    path: &path,
+                    same,
+                    evidence,
+                    bytes_read: stats.bytes_read() - read_before,
+                    time: started.elapsed(),
+                });
+                Ok(same)
+            })?;
+            // Entries that match otherwise may still differ in their attributes, where those are
+            // compared. The names of the roots play no part, so neither do their attributes.
+            let kind = match kind {
+                None if options.compares_attributes()
+                    && matches!(kind_a, Some(EntryKind::File | EntryKind::Directory))
+                    && !path.as_os_str().is_empty() =>
+                {
+                    attributes_differ(a, b, (&path, path_b))?
+                        .then_some(DifferenceKind::AttributesChanged)
+                }
+                kind => kind,
+            };
+
+            let listing = Instant::now();
+            let children = merge_children(
+                list_children(a, &path, kind_a)?,
+                list_children(b, path_b, kind_b)?,
+            );
+            if kind_a == Some(EntryKind::Directory) || kind_b == Some(EntryKind::Directory) {
+                options.trace(|| TraceEvent::Listed {
+                    path: &path,
+                    children: children.len(),
+                    time: listing.elapsed(),
+                });
+            }
+            if options.profiles_trees() {
+                profiled = Some((file_size(a, &path, kind_a)?, file_size(b, path_b, kind_b)?));
+            }
+
+            let Some(kind) = kind else {
+                return Ok((None, children));
+            };
+            let mut sizes = (file_size(a, &path, kind_a)?, file_size(b, path_b, kind_b)?);
+            // A file in only one tree whose size isn't known is read until it's known whether it's
+            // empty, so that it's always told apart.
+            match (kind, sizes) {
+                (DifferenceKind::Added, (_, None)) => sizes.1 = probe_empty(b, path_b, kind_b)?,
+                (DifferenceKind::Removed, (None, _)) => sizes.0 = probe_empty(a, &path, kind_a)?,
+                _ => {}
+            }
+            let mut difference = Difference::new(path.clone(), kind).with_sizes(sizes);
+            if difference.is_empty_file() && options.treats_empty_as_missing() {
+                return Ok((None, children));
+            }
+            if kind == DifferenceKind::Modified && options.wants_unified_diffs() {
+                difference.unified_diff = text_diff(a, b, (&path, path_b), stats)?;
+            }
+            Ok((Some(difference), children))
+        };
+        let read = unless_vanished((a, &path), (b, path_b), read());
+        // Errors don't say which entry they were reading, so the entry is traced.
+        let read =
+            read.inspect_err(|error| options.trace(|| TraceEvent::Failed { path: &path, error }))?;
+        options.trace(|| TraceEvent::Decided {
+            path: &path,
+            difference: match &read {
+                Some((difference, _)) => difference.as_ref().map(Difference::kind),
+                None => (policy == Consistency::Volatile).then_some(DifferenceKind::Volatile),
+            },
+            time: start.elapsed(),
+        });
+        if let Some(sizes) = profiled.filter(|_| read.is_some()) {
+            stats.profile(&path, (kind_a, kind_b), sizes);
+        }
+        if let Some(large_files) = options.entry_time_threshold() {
+            let listed =
+                kind_a == Some(EntryKind::Directory) || kind_b == Some(EntryKind::Directory);
+            if listed || stats.bytes_read() - bytes_before >= large_files {
+                stats.time_entry(path.clone(), start.elapsed());
+            }
+        }
+        let Some((difference, children)) = read else {
+            if policy == Consistency::Volatile {
+                differences.push(Difference::new(path, DifferenceKind::Volatile));
+            }
+            continue;
+        };
+
+        // Push in reverse so that the children are popped, and thus reported, in order.
+        for (name, name_b, kind_a, kind_b) in children.into_iter().rev() {
+            let other = match (&other, name_b) {
+                (None, None) => None,
+                (_, name_b) => Some(path_b.join(name_b.as_ref().unwrap_or(&name))),
+            };
+            let child = path.join(name);
+            stats.hold(footprint::<Pending>(&[Some(&child), other.as_deref()]));
+            stack.push((child, other, kind_a, kind_b));
+        }
+
+        if let Some(difference) = difference {
+            stats.hold(footprint::<Difference>(&[Some(&difference.path)]));
+            differences.push(difference);
+        }
+    }
+
+    Ok(DiffReport { differences })
+}
+
+/**
+How an entry differs between the trees, if it does, given its kinds in each, where it exists.
+Files in both are compared with the given closure, which returns whether they hold the same bytes.
+*/
+pub(crate) fn difference_kind(
+    kind_a: Option<EntryKind>,
+    kind_b: Option<EntryKind>,
+    same_files: impl FnOnce() -> io::Result<bool>,
+) -> io::Result<Option<DifferenceKind>> {
+    Ok(match (kind_a, kind_b) {
+        (Some(EntryKind::File), Some(EntryKind::File)) => {
+            (!same_files()?).then_some(DifferenceKind::Modified)
+        }
+        (Some(EntryKind::Directory), Some(EntryKind::Directory)) | (None, None) => None,
+        // Special files are never read, so they're only compared by their kinds.
+        (Some(EntryKind::Special(a)), Some(EntryKind::Special(b))) if a == b => None,
+        (None, Some(_)) => Some(DifferenceKind::Added),
+        (Some(_), None) => Some(DifferenceKind::Removed),
+        (Some(_), Some(_)) => Some(DifferenceKind::TypeChanged),
+    })
+}
+
+/**
+Whether the attributes of the entry at the given paths in each tree differ, where both are on disk
+and their attributes are known.
+*/
+fn attributes_differ(a: &dyn Source, b: &dyn Source, paths: (&Path, &Path)) -> io::Result<bool> {
+    Ok(match (a.location(paths.0)?, b.location(paths.1)?) {
+        (Some(a), Some(b)) => attributes(&a)? != attributes(&b)?,
+        _ => false,
+    })
+}
+
+/**
+Whether a file is known to differ between the trees without reading it: if its sizes differ or,
+when enabled, its modification times.
+*/
+fn differ_by_info(a: FileInfo, b: FileInfo, options: &Options) -> bool {
+    let sizes_differ = matches!((a.size, b.size), (Some(a), Some(b)) if a != b);
+    let times_differ = options.compares_mtimes()
+        && matches!((a.modified, b.modified), (Some(a), Some(b)) if !options.same_mtimes(a, b));
+
+    sizes_differ || times_differ
+}
+
+/// The size of the entry at the given path, if it's a file whose size is known without reading it.
+fn file_size(source: &dyn Source, path: &Path, kind: Option<EntryKind>) -> io::Result<Option<u64>> {
+    match kind {
+        Some(EntryKind::File) => Ok(source.info(path)?.size),
+        _ => Ok(None),
+    }
+}
+
+/// The size of the entry at the given path, if it's an empty file, found by reading a byte of it.
+fn probe_empty(
+    source: &dyn Source,
+    path: &Path,
+    kind: Option<EntryKind>,
+) -> io::Result<Option<u64>> {
+    match kind {
+        Some(EntryKind::File) => {
+            let empty = source.open(path)?.read(&mut [0])? == 0;
+            Ok(empty.then_some(0))
+        }
+        _ => Ok(None),
+    }
+}
+
+/**
+A unified diff of the file at the given paths in each tree, if it's text in both, headed by its path
+in the first.
+*/
+pub(crate) fn text_diff(
+    a: &dyn Source,
+    b: &dyn Source,
+    paths: (&Path, &Path),
+    stats: &mut Stats,
+) -> io::Result<Option<String>> {
+    let mut old = Vec::new();
+    let mut new = Vec::new();
+    a.open(paths.0)?.read_to_end(&mut old)?;
+    b.open(paths.1)?.read_to_end(&mut new)?;
+    stats.read(old.len() + new.len());
+
+    Ok(as_text(&old)
+        .zip(as_text(&new))
+        .map(|(old, new)| unified_diff(paths.0, old, new)))
+}
+
+fn list_children(
+    source: &dyn Source,
+    path: &Path,
+    kind: Option<EntryKind>,
+) -> io::Result<Vec<Child>> {
+    match kind {
+        Some(EntryKind::Directory) => source.children(path),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/**
+A child of either directory being compared: its name in the first tree, or in the second if it's
+only there, its name in the second where that differs, and its kinds in each.
+*/
+type Merged = (
+    OsString,
+    Option<OsString>,
+    Option<EntryKind>,
+    Option<EntryKind>,
+);
+
+/**
+Join two lists of children, each ordered by key, into a single list ordered by key. Children are
+matched by their keys, and named as the first tree names them, along with their names in the second
+where those differ.
+*/
+fn merge_children(a: Vec<Child>, b: Vec<Child>) -> Vec<Merged> {
+    let mut merged = Vec::with_capacity(a.len().max(b.len()));
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+
+    loop {
+        let order = match (a.peek(), b.peek()) {
+            (None, None) => return merged,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((name_a, key_a, _)), Some((name_b, key_b, _))) => key_a
+                .as_deref()
+                .unwrap_or(name_a)
+                .cmp(key_b.as_deref().unwrap_or(name_b)),
+        };
+
+        merged.push(
+            match order {
+                Ordering::Less => a
+                    .next()
+                    .map(|(name, _, kind)| (name, None, Some(kind), None)),
+                Ordering::Greater => b
+                    .next()
+                    .map(|(name, _, kind)| (name, None, None, Some(kind))),
+                Ordering::Equal => {
+                    a.next()
+                        .zip(b.next())
+                        .map(|((name, _, kind_a), (name_b, _, kind_b))| {
+                            let name_b = (name_b != name).then_some(name_b);
+                            (name, name_b, Some(kind_a), Some(kind_b))
+                        })
+                }
+            }
+            .expect("the peeked children exist"),
+        );
+    }
+}
+
+/// An entry in a flattened tree, stripped of its name and children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Node<'a> {
+    File(&'a [u8]),
+    Directory,
+    Special(SpecialKind),
+}
+
+impl Node<'_> {
+    /// How the entry changed from `before` to `after`, if at all.
+    pub(crate) fn change(before: Option<&Self>, after: Option<&Self>) -> Option<DifferenceKind> {
+        match (before, after) {
+            (None, None) => None,
+            (None, Some(_)) => Some(DifferenceKind::Added),
+            (Some(_), None) => Some(DifferenceKind::Removed),
+            (Some(Self::File(a)), Some(Self::File(b))) => {
+                (a != b).then_some(DifferenceKind::Modified)
+            }
+            (Some(Self::Directory), Some(Self::Directory)) => None,
+            (Some(Self::Special(a)), Some(Self::Special(b))) if a == b => None,
+            (Some(_), Some(_)) => Some(DifferenceKind::TypeChanged),
+        }
+    }
+}
+
+/// Map every entry in the tree, including the root, to its path relative to the root.
+pub(crate) fn flatten(content: &Content) -> BTreeMap<PathBuf, Node<'_>> {
+    let mut nodes = BTreeMap::new();
+    let mut stack = vec![(PathBuf::new(), content)];
+
+    while let Some((path, content)) = stack.pop() {
+        match content {
+            Content::File(bytes) => {
+                nodes.insert(path, Node::File(bytes));
+            }
+            Content::Special(kind) => {
+                nodes.insert(path, Node::Special(*kind));
+            }
+            Content::Entries(entries) => {
+                stack.extend(
+                    entries
+                        .iter()
+                        .map(|entry| (path.join(&entry.name), &entry.content)),
+                );
+                nodes.insert(path, Node::Directory);
+            }
+        }
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{Content, DifferenceKind};
+
+    #[test]
+    fn equivalent_contents_should_not_differ() {
+        let a = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let b = Content::of("fixtures/equivalent/dir-b").unwrap();
+
+        assert!(a.diff(&b).is_empty());
+    }
+
+    #[test]
+    fn missing_entries_should_be_added_or_removed() {
+        let a = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let b = Content::of("fixtures/equivalent/dir-a/subdir").unwrap();
+        let report = a.diff(&b);
+
+        let kinds: Vec<_> = report.iter().map(|d| (d.path(), d.kind())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (Path::new("subdir"), DifferenceKind::Removed),
+                (Path::new("subdir/test2.txt"), DifferenceKind::Removed),
+                (Path::new("test.txt"), DifferenceKind::Removed),
+                (Path::new("test2.txt"), DifferenceKind::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn largest_differences_should_come_first() {
+        let a = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let b = Content::of("fixtures/equivalent/dir-a/subdir").unwrap();
+        let report = a.diff(&b);
+
+        let summary = report.summary();
+        assert_eq!((summary.added(), summary.removed()), (1, 3));
+        // Files of 15 and 10 bytes were removed, and one of 15 bytes added.
+        assert_eq!(summary.size_churn(), 40);
+        assert_eq!(summary.size_change(), -10);
+
+        let largest: Vec<_> = report.largest(2).iter().map(|d| d.path()).collect();
+        assert_eq!(
+            largest,
+            [Path::new("subdir/test2.txt"), Path::new("test2.txt")]
+        );
+        assert_eq!(report.differences()[0].sizes(), (None, None));
+    }
+
+    #[test]
+    fn file_against_directory_on_disk_should_change_type() {
+        let file = Content::File(b"test data\n".to_vec());
+        let report = file.diff_against_disk("fixtures/equivalent/dir-a").unwrap();
+
+        let kinds: Vec<_> = report.iter().map(|d| (d.path(), d.kind())).collect();
+        assert_eq!(kinds[0], (Path::new(""), DifferenceKind::TypeChanged));
+        assert!(kinds[1..]
+            .iter()
+            .all(|(_, kind)| *kind == DifferenceKind::Added));
+    }
+}
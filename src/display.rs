@@ -0,0 +1,78 @@
+/*!
+Rendering of trees for humans.
+*/
+
+use std::fmt::{self, Display};
+
+use crate::{Content, Entry};
+
+/**
+Renders the entry as an indented tree, like `tree -s`, with the size of each file in bytes.
+Children are listed in order of name.
+
+```
+use dir_compare::Entry;
+
+let entry = Entry::at("fixtures/equivalent/dir-a")?;
+
+assert_eq!(
+    entry.to_string(),
+    "\
+dir-a
+├── subdir
+│   └── [15]  test2.txt
+└── [10]  test.txt
+"
+);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", Label(self))?;
+
+        // Each item is a child yet to be rendered, the prefix to render before it, and whether
+        // it's the last of its siblings.
+        let mut stack = Vec::new();
+        push_children(&mut stack, self, "");
+
+        while let Some((entry, prefix, last)) = stack.pop() {
+            let branch = if last { "└── " } else { "├── " };
+            writeln!(f, "{prefix}{branch}{}", Label(entry))?;
+
+            let indent = if last { "    " } else { "│   " };
+            push_children(&mut stack, entry, &format!("{prefix}{indent}"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Push the entry's children, if any, so that they're popped in order.
+fn push_children<'a>(stack: &mut Vec<(&'a Entry, String, bool)>, entry: &'a Entry, prefix: &str) {
+    if let Content::Entries(children) = &entry.content {
+        let last = children.len().saturating_sub(1);
+        stack.extend(
+            children
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(index, child)| (child, prefix.to_owned(), index == last)),
+        );
+    }
+}
+
+/// An entry's name, preceded by its size if it's a file, or its kind if it's a special file.
+struct Label<'a>(&'a Entry);
+
+impl Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.0.name.to_string_lossy();
+        match &self.0.content {
+            Content::File(bytes) => write!(f, "[{}]  {}", bytes.len(), name),
+            Content::Entries(_) => write!(f, "{name}"),
+            Content::Special(kind) => write!(f, "[{kind}]  {name}"),
+        }
+    }
+}
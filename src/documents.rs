@@ -0,0 +1,343 @@
+/*!
+Filters that strip the metadata documents pick up whenever they're generated, such as creation
+dates and the name of the generating application, so that regenerated documents compare equal.
+*/
+
+use std::io;
+
+use crate::{zip, Filter};
+
+/// The elements of the core properties of OOXML documents that change whenever they're saved.
+const OOXML_CORE: [&str; 5] = [
+    "dcterms:created",
+    "dcterms:modified",
+    "cp:lastModifiedBy",
+    "cp:lastPrinted",
+    "cp:revision",
+];
+
+/// The elements of the extended properties of OOXML documents naming the generating application.
+const OOXML_APP: [&str; 3] = ["Application", "AppVersion", "TotalTime"];
+
+/// The keys of the document information dictionary of PDFs that hold dates or generators.
+const PDF_INFO: [&[u8]; 4] = [b"/CreationDate", b"/ModDate", b"/Producer", b"/Creator"];
+
+/// The properties of XMP metadata that hold dates, generators, or identifiers of a single save.
+const XMP: [&str; 7] = [
+    "xmp:CreateDate",
+    "xmp:ModifyDate",
+    "xmp:MetadataDate",
+    "xmp:CreatorTool",
+    "pdf:Producer",
+    "xmpMM:DocumentID",
+    "xmpMM:InstanceID",
+];
+
+/**
+Normalizes Office Open XML documents, such as `.docx`, `.xlsx`, and `.pptx` files, to the contents
+of their parts, ordered by name, without the volatile document properties.
+
+OOXML documents are zip archives, so the archive's own timestamps and compression are ignored too,
+as are the creation and modification dates, last author, revision, and generating application
+recorded in `docProps/core.xml` and `docProps/app.xml`.
+
+```
+use dir_compare::{filters::Ooxml, Content, Options};
+
+let options = Options::new().filter("*.docx", Ooxml);
+let a = Content::of_with("fixtures/documents/dir-a/report.docx", &options)?;
+let b = Content::of_with("fixtures/documents/dir-b/report.docx", &options)?;
+
+assert_eq!(a, b);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ooxml;
+
+impl Filter for Ooxml {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut entries = zip::entries(&bytes)?;
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut normalized = Vec::new();
+        for (name, mut bytes) in entries {
+            match name.as_str() {
+                "docProps/core.xml" => bytes = strip_elements(&bytes, &OOXML_CORE),
+                "docProps/app.xml" => bytes = strip_elements(&bytes, &OOXML_APP),
+                _ => {}
+            }
+            normalized.extend_from_slice(format!("{name} {}\n", bytes.len()).as_bytes());
+            normalized.append(&mut bytes);
+            normalized.push(b'\n');
+        }
+
+        Ok(normalized)
+    }
+}
+
+/**
+Normalizes PDFs by blanking their creation and modification dates, generating applications, and file
+identifiers, in both the document information dictionary and XMP metadata.
+
+Removing metadata shifts the offsets of the objects that follow it, so cross-reference tables,
+`startxref` offsets, and direct stream lengths are removed as well. The objects and stream contents
+themselves are compared as is. Metadata held in compressed object streams isn't found.
+
+```
+use dir_compare::{filters::Pdf, Content, Options};
+
+let options = Options::new().filter("*.pdf", Pdf);
+let a = Content::of_with("fixtures/documents/dir-a/report.pdf", &options)?;
+let b = Content::of_with("fixtures/documents/dir-b/report.pdf", &options)?;
+
+assert_eq!(a, b);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pdf;
+
+impl Filter for Pdf {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        if !bytes.starts_with(b"%PDF-") {
+            return Err(crate::inflate::invalid("the data is not a PDF"));
+        }
+
+        let mut bytes = strip_elements(&bytes, &XMP);
+        bytes = strip_attributes(&bytes, &XMP);
+        for key in PDF_INFO {
+            bytes = blank_values(&bytes, key);
+        }
+        bytes = blank_values(&bytes, b"/ID");
+        bytes = strip_numbers(&bytes, b"/Length");
+        bytes = strip_numbers(&bytes, b"/Prev");
+        bytes = strip_numbers(&bytes, b"startxref");
+
+        Ok(strip_cross_references(&bytes))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| from + position)
+}
+
+/// The index of the first byte at or after `from` that isn't whitespace.
+fn skip_whitespace(bytes: &[u8], from: usize) -> usize {
+    from + bytes[from..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count()
+}
+
+/// Remove the XML elements with the given qualified names, along with their content.
+fn strip_elements(xml: &[u8], names: &[&str]) -> Vec<u8> {
+    let mut xml = xml.to_vec();
+
+    for name in names {
+        let open = format!("<{name}");
+        let close = format!("</{name}>");
+        let mut from = 0;
+
+        while let Some(start) = find(&xml, open.as_bytes(), from) {
+            from = start + open.len();
+            // Skip elements whose names merely start with this one.
+            if !matches!(
+                xml.get(from),
+                Some(b'>' | b'/') | Some(b' ' | b'\t' | b'\r' | b'\n')
+            ) {
+                continue;
+            }
+            let Some(tag_end) = find(&xml, b">", from).map(|end| end + 1) else {
+                break;
+            };
+            let end = if xml[tag_end - 2] == b'/' {
+                tag_end
+            } else {
+                find(&xml, close.as_bytes(), tag_end).map_or(tag_end, |end| end + close.len())
+            };
+
+            xml.drain(start..end);
+            from = start;
+        }
+    }
+
+    xml
+}
+
+/// Remove the XML attributes with the given qualified names.
+fn strip_attributes(xml: &[u8], names: &[&str]) -> Vec<u8> {
+    let mut xml = xml.to_vec();
+
+    for name in names {
+        let pattern = format!("{name}=");
+        let mut from = 0;
+
+        while let Some(start) = find(&xml, pattern.as_bytes(), from) {
+            from = start + pattern.len();
+            if start == 0 || !xml[start - 1].is_ascii_whitespace() {
+                continue;
+            }
+            let Some(&quote @ (b'"' | b'\'')) = xml.get(from) else {
+                continue;
+            };
+            let Some(end) = find(&xml, &[quote], from + 1) else {
+                break;
+            };
+
+            xml.drain(start - 1..=end);
+            from = start - 1;
+        }
+    }
+
+    xml
+}
+
+/// Replace the values of a PDF dictionary key with empty ones: `()` for strings, `[]` for arrays.
+fn blank_values(pdf: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut pdf = pdf.to_vec();
+    let mut from = 0;
+
+    while let Some(start) = find(&pdf, key, from) {
+        from = start + key.len();
+        // Skip keys that merely start with this one.
+        if pdf.get(from).is_some_and(u8::is_ascii_alphanumeric) {
+            continue;
+        }
+
+        let value = skip_whitespace(&pdf, from);
+        let (end, blank): (Option<usize>, &[u8]) = match pdf.get(value) {
+            Some(b'(') => (literal_string_end(&pdf, value), b"()"),
+            Some(b'<') => (find(&pdf, b">", value).map(|end| end + 1), b"()"),
+            Some(b'[') => (find(&pdf, b"]", value).map(|end| end + 1), b"[]"),
+            _ => continue,
+        };
+        let Some(end) = end else {
+            break;
+        };
+
+        pdf.splice(value..end, blank.iter().copied());
+        from = value + blank.len();
+    }
+
+    pdf
+}
+
+/// The index just past the end of the PDF literal string starting at `start`, which may contain
+/// balanced or escaped parentheses.
+fn literal_string_end(pdf: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = start;
+
+    while index < pdf.len() {
+        match pdf[index] {
+            b'\\' => index += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    None
+}
+
+/// Remove the number that directly follows each occurrence of a keyword, unless it's the object
+/// number of an indirect reference, such as `12 0 R`.
+fn strip_numbers(pdf: &[u8], keyword: &[u8]) -> Vec<u8> {
+    let mut pdf = pdf.to_vec();
+    let mut from = 0;
+
+    while let Some(start) = find(&pdf, keyword, from) {
+        from = start + keyword.len();
+        if pdf.get(from).is_some_and(u8::is_ascii_alphanumeric) {
+            continue;
+        }
+        let digits = |from: usize| {
+            pdf[from..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count()
+        };
+
+        let number = skip_whitespace(&pdf, from);
+        let length = digits(number);
+        if length == 0 {
+            continue;
+        }
+
+        let generation = skip_whitespace(&pdf, number + length);
+        let generation_length = digits(generation);
+        let reference = skip_whitespace(&pdf, generation + generation_length);
+        if generation_length > 0 && pdf.get(reference) == Some(&b'R') {
+            continue;
+        }
+
+        pdf.drain(from..number + length);
+    }
+
+    pdf
+}
+
+/// Remove cross-reference tables, which run from an `xref` line up to the trailer.
+fn strip_cross_references(pdf: &[u8]) -> Vec<u8> {
+    let mut pdf = pdf.to_vec();
+    let mut from = 0;
+
+    while let Some(start) = find(&pdf, b"\nxref", from) {
+        from = start + 1;
+        let Some(end) = find(&pdf, b"trailer", start) else {
+            break;
+        };
+        pdf.drain(start + 1..end);
+    }
+
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{blank_values, strip_attributes, strip_elements, Ooxml, Pdf};
+    use crate::Filter;
+
+    #[test]
+    fn volatile_metadata_should_be_stripped() {
+        assert_eq!(
+            strip_elements(b"<a><b:c x=\"1\">2</b:c><b:cd/><b:c/></a>", &["b:c"]),
+            b"<a><b:cd/></a>"
+        );
+        assert_eq!(
+            strip_attributes(b"<a b:c=\"1\" b:cd='2'/>", &["b:c"]),
+            b"<a b:cd='2'/>"
+        );
+        assert_eq!(
+            blank_values(b"<</Producer (a \\) (b)) /ProducerX (c)>>", b"/Producer"),
+            b"<</Producer () /ProducerX (c)>>"
+        );
+    }
+
+    #[test]
+    fn changed_documents_should_differ() {
+        for (name, filter) in [("report.pdf", &Pdf as &dyn Filter), ("report.docx", &Ooxml)] {
+            let read = |dir: &str| {
+                let bytes = fs::read(format!("fixtures/documents/{dir}/{name}")).unwrap();
+                filter.apply(bytes).unwrap()
+            };
+
+            assert_ne!(read("dir-a"), read("changed"));
+        }
+    }
+}
@@ -0,0 +1,393 @@
+/*!
+A filter that masks the fields of executables that differ between otherwise reproducible builds.
+*/
+
+use std::{io, ops::Range};
+
+use crate::Filter;
+
+/// The type of ELF section and program header that holds notes.
+const ELF_NOTE_SECTION: u32 = 7;
+const ELF_NOTE_SEGMENT: u32 = 4;
+/// The type of the GNU note holding a build ID.
+const NT_GNU_BUILD_ID: u32 = 3;
+/// The marker that ends signed Linux kernel modules.
+const MODULE_SIGNATURE: &[u8] = b"~Module signature appended~\n";
+
+/// The indices of the PE data directories of interest.
+const PE_CERTIFICATES: usize = 4;
+const PE_DEBUG: usize = 6;
+/// The types of PE debug data that identify a single build.
+const PE_CODEVIEW: u32 = 2;
+const PE_REPRO: u32 = 16;
+
+/**
+Masks build IDs, timestamps, and signatures in ELF and PE executables and libraries, so that
+reproducible builds can be verified even where only those fields legitimately differ.
+
+- In ELF files, GNU build ID notes are zeroed, as is the checksum of a `.gnu_debuglink` section. The
+  signature appended to a kernel module is removed.
+- In PE files, the timestamps of the COFF header and debug directory, the checksum, and the ID of
+  the program database and reproducible-build hash referred to by the debug directory are zeroed.
+  The Authenticode signature is removed.
+
+Files in neither format are left as they are, so the filter can be registered for whole build
+output directories.
+
+```
+use dir_compare::{filters::Executable, Content, Options};
+
+let options = Options::new().filter("hello*", Executable);
+let a = Content::of_with("fixtures/executables/dir-a", &options)?;
+let b = Content::of_with("fixtures/executables/dir-b", &options)?;
+
+assert_eq!(a, b);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Executable;
+
+impl Filter for Executable {
+    fn apply(&self, mut bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        let (masks, removed) = if bytes.starts_with(b"\x7fELF") {
+            elf(&bytes)
+        } else if bytes.starts_with(b"MZ") {
+            pe(&bytes)
+        } else {
+            (Vec::new(), None)
+        };
+
+        for mask in masks {
+            if let Some(bytes) = bytes.get_mut(mask) {
+                bytes.fill(0);
+            }
+        }
+        if let Some(removed) = removed.filter(|removed| removed.end <= bytes.len()) {
+            bytes.drain(removed);
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Read fixed-size integers of either byte order at offsets into a file, where they're in bounds.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn read<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        let mut array: [u8; N] = self
+            .bytes
+            .get(offset..offset.checked_add(N)?)?
+            .try_into()
+            .ok()?;
+        if self.big_endian {
+            array.reverse();
+        }
+        Some(array)
+    }
+
+    fn u16(&self, offset: usize) -> Option<usize> {
+        self.read(offset).map(u16::from_le_bytes).map(usize::from)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        self.read(offset).map(u32::from_le_bytes)
+    }
+
+    fn usize(&self, offset: usize) -> Option<usize> {
+        self.u32(offset)
+            .and_then(|value| usize::try_from(value).ok())
+    }
+
+    fn u64(&self, offset: usize) -> Option<usize> {
+        self.read(offset)
+            .map(u64::from_le_bytes)
+            .and_then(|value| usize::try_from(value).ok())
+    }
+}
+
+/// The ranges of an ELF file to zero, and the range to remove.
+fn elf(bytes: &[u8]) -> (Vec<Range<usize>>, Option<Range<usize>>) {
+    let wide = bytes.get(4) == Some(&2);
+    let reader = Reader {
+        bytes,
+        big_endian: bytes.get(5) == Some(&2),
+    };
+    let address = |offset| {
+        if wide {
+            reader.u64(offset)
+        } else {
+            reader.usize(offset)
+        }
+    };
+    // The offsets of fields that follow addresses depend on the width of the addresses.
+    let field = |narrow: usize, wide_offset: usize| if wide { wide_offset } else { narrow };
+
+    let mut masks = Vec::new();
+    let mut notes = Vec::new();
+
+    // Notes are found through the program headers, which even stripped files keep.
+    let program_headers = address(field(0x1c, 0x20));
+    let program_header_size = reader.u16(field(0x2a, 0x36));
+    let program_headers_count = reader.u16(field(0x2c, 0x38));
+    if let (Some(start), Some(size), Some(count)) =
+        (program_headers, program_header_size, program_headers_count)
+    {
+        let headers = (0..count).filter_map(|index| start.checked_add(index.checked_mul(size)?));
+        for header in headers {
+            if reader.u32(header) == Some(ELF_NOTE_SEGMENT) {
+                let field_at = |narrow, wide| address(header.checked_add(field(narrow, wide))?);
+                let offset = field_at(0x04, 0x08);
+                let length = field_at(0x10, 0x20);
+                let alignment = field_at(0x1c, 0x30);
+                if let (Some(offset), Some(length)) = (offset, length) {
+                    notes.push((offset, length, alignment.unwrap_or(4)));
+                }
+            }
+        }
+    }
+
+    let section_headers = address(field(0x20, 0x28));
+    let section_header_size = reader.u16(field(0x2e, 0x3a));
+    let section_headers_count = reader.u16(field(0x30, 0x3c));
+    let names_index = reader.u16(field(0x32, 0x3e));
+    if let (Some(start), Some(size), Some(count), Some(names_index)) = (
+        section_headers,
+        section_header_size,
+        section_headers_count,
+        names_index,
+    ) {
+        let section = |index: usize| {
+            let header = start.checked_add(index.checked_mul(size)?)?;
+            let field_at = |narrow, wide| address(header.checked_add(field(narrow, wide))?);
+            Some((
+                reader.usize(header)?,
+                reader.u32(header.checked_add(4)?)?,
+                field_at(0x10, 0x18)?,
+                field_at(0x14, 0x20)?,
+                field_at(0x20, 0x30)?,
+            ))
+        };
+        let names = section(names_index).map(|(_, _, offset, _, _)| offset);
+
+        for (name, kind, offset, length, alignment) in (0..count).filter_map(section) {
+            if kind == ELF_NOTE_SECTION {
+                notes.push((offset, length, alignment));
+            }
+            let name = names.and_then(|names| bytes.get(names.checked_add(name)?..));
+            // The debug link ends with a CRC-32 of the debug file.
+            if name.is_some_and(|name| name.starts_with(b".gnu_debuglink\0")) && length >= 4 {
+                if let Some(end) = offset.checked_add(length) {
+                    masks.push(end - 4..end);
+                }
+            }
+        }
+    }
+
+    for (offset, length, alignment) in notes {
+        masks.extend(build_ids(&reader, offset, length, alignment.max(4)));
+    }
+
+    let removed = bytes
+        .ends_with(MODULE_SIGNATURE)
+        .then(|| module_signature(bytes))
+        .flatten();
+
+    (masks, removed)
+}
+
+/// The ranges of the build IDs among the notes at the given range of an ELF file.
+fn build_ids(reader: &Reader, offset: usize, length: usize, alignment: usize) -> Vec<Range<usize>> {
+    // Notes whose sizes run past the end of the address space end the search.
+    let skip =
+        |start: usize, size: usize| start.checked_add(size.checked_next_multiple_of(alignment)?);
+    let end = offset.saturating_add(length);
+    let mut ids = Vec::new();
+    let mut note = offset;
+
+    while let Some(name) = note.checked_add(12).filter(|&name| name <= end) {
+        let (Some(name_size), Some(descriptor_size), Some(kind)) = (
+            reader.usize(note),
+            reader.usize(note + 4),
+            reader.u32(note + 8),
+        ) else {
+            break;
+        };
+        let Some(descriptor) = skip(name, name_size) else {
+            break;
+        };
+
+        // The header was read, so the name starts within the file, and can't overflow its end.
+        if kind == NT_GNU_BUILD_ID
+            && name_size == 4
+            && reader.bytes.get(name..name + 4) == Some(b"GNU\0")
+        {
+            ids.extend(
+                descriptor
+                    .checked_add(descriptor_size)
+                    .map(|end| descriptor..end),
+            );
+        }
+        let Some(next) = skip(descriptor, descriptor_size) else {
+            break;
+        };
+        note = next;
+    }
+
+    ids
+}
+
+/**
+The range of the signature appended to a Linux kernel module. It's followed by a 12-byte trailer,
+ending with the signature's size as a big-endian integer, and then by the marker.
+*/
+fn module_signature(bytes: &[u8]) -> Option<Range<usize>> {
+    let trailer = bytes.len().checked_sub(MODULE_SIGNATURE.len() + 12)?;
+    let size = u32::from_be_bytes(bytes.get(trailer + 8..trailer + 12)?.try_into().ok()?);
+    let start = trailer.checked_sub(usize::try_from(size).ok()?)?;
+
+    Some(start..bytes.len())
+}
+
+/// The ranges of a PE file to zero, and the range to remove.
+fn pe(bytes: &[u8]) -> (Vec<Range<usize>>, Option<Range<usize>>) {
+    let reader = Reader {
+        bytes,
+        big_endian: false,
+    };
+    let mut masks = Vec::new();
+
+    let Some(header) = reader.usize(0x3c) else {
+        return (masks, None);
+    };
+    if !bytes
+        .get(header..)
+        .is_some_and(|header| header.starts_with(b"PE\0\0"))
+    {
+        return (masks, None);
+    }
+    let coff = header + 4;
+    let optional = coff + 20;
+    let sections_count = reader.u16(coff + 2).unwrap_or(0);
+    let sections = optional + reader.u16(coff + 16).unwrap_or(0);
+    masks.push(coff + 4..coff + 8);
+
+    let directories = match reader.u16(optional) {
+        Some(0x10b) => optional + 96,
+        Some(0x20b) => optional + 112,
+        _ => return (masks, None),
+    };
+    masks.push(optional + 64..optional + 68);
+    let directory = |index: usize| {
+        Some((
+            reader.usize(directories + 8 * index)?,
+            reader.usize(directories + 8 * index + 4)?,
+        ))
+    };
+
+    // Map a relative virtual address to an offset into the file, through the section containing it.
+    let offset_of = |address: usize| {
+        (0..sections_count)
+            .map(|index| sections + 40 * index)
+            .find_map(|section| {
+                let start = reader.usize(section + 12)?;
+                let length = reader.usize(section + 8)?;
+                let raw = reader.usize(section + 20)?;
+                (start..start.checked_add(length)?)
+                    .contains(&address)
+                    .then(|| raw.checked_add(address - start))
+                    .flatten()
+            })
+    };
+
+    if let Some((address, length)) = directory(PE_DEBUG) {
+        if let Some(start) = offset_of(address) {
+            for entry in (start..start.saturating_add(length).min(bytes.len())).step_by(28) {
+                masks.push(entry + 4..entry + 8);
+                let (Some(kind), Some(size), Some(data)) = (
+                    reader.u32(entry + 12),
+                    reader.usize(entry + 16),
+                    reader.usize(entry + 24),
+                ) else {
+                    continue;
+                };
+                match kind {
+                    // The program database's GUID and age follow the `RSDS` signature.
+                    PE_CODEVIEW
+                        if bytes
+                            .get(data..)
+                            .is_some_and(|data| data.starts_with(b"RSDS")) =>
+                    {
+                        masks.push(data + 4..data + 24);
+                    }
+                    PE_REPRO => masks.extend(data.checked_add(size).map(|end| data..end)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // The certificate table is addressed by its offset into the file, not by a virtual address.
+    let removed = directory(PE_CERTIFICATES)
+        .filter(|&(_, length)| length > 0)
+        .and_then(|(offset, length)| {
+            masks.push(directories + 8 * PE_CERTIFICATES..directories + 8 * PE_CERTIFICATES + 8);
+            Some(offset..offset.checked_add(length)?)
+        });
+
+    (masks, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Executable;
+    use crate::Filter;
+
+    #[test]
+    fn changed_executables_should_differ() {
+        for name in ["hello", "hello.exe"] {
+            let read = |dir: &str| {
+                let bytes = fs::read(format!("fixtures/executables/{dir}/{name}")).unwrap();
+                Executable.apply(bytes).unwrap()
+            };
+
+            assert_ne!(read("dir-a"), read("changed"));
+        }
+    }
+
+    #[test]
+    fn other_files_should_be_left_alone() {
+        assert_eq!(Executable.apply(b"MZ".to_vec()).unwrap(), b"MZ");
+        assert_eq!(Executable.apply(b"text".to_vec()).unwrap(), b"text");
+    }
+
+    #[test]
+    fn corrupt_headers_should_not_panic() {
+        for header in [
+            &b"\x7fELF\x02\x01"[..],
+            b"\x7fELF\x01\x02",
+            b"\x7fELF\x02\x02",
+        ] {
+            for length in [0, 16, 64, 256, 4096] {
+                let mut bytes = header.to_vec();
+                bytes.resize(header.len() + length, 0xff);
+                assert_eq!(Executable.apply(bytes.clone()).unwrap(), bytes);
+            }
+        }
+
+        let mut bytes = b"MZ".to_vec();
+        bytes.resize(0x40, 0);
+        bytes[0x3c] = 0x40;
+        bytes.extend(b"PE\0\0");
+        bytes.resize(0x1000, 0xff);
+        bytes[0x58..0x5a].copy_from_slice(&0x20b_u16.to_le_bytes());
+        assert!(Executable.apply(bytes).is_ok());
+    }
+}
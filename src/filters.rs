@@ -0,0 +1,323 @@
+/*!
+Filters that decode files before they're compared, so that files encoded at rest can be compared by
+their logical content.
+
+Filters are registered for a glob pattern with [`Options::filter`](crate::Options::filter). Every
+filter whose pattern matches a file is applied, in the order of registration, so filters can be
+chained: a base64-encoded gzip file is decoded by registering [`Base64`], then [`Gzip`].
+
+```
+use dir_compare::{filters::{Base64, Gzip, Zstd}, Content, Options};
+
+let options = Options::new()
+    .filter("*.gz.b64", Base64)
+    .filter("*.gz.b64", Gzip)
+    .filter("*.gz", Gzip)
+    .filter("*.zst", Zstd)
+    .filter("*.txt.b64", Base64);
+let a = Content::of_with("fixtures/encoded/dir-a", &options)?;
+let b = Content::of_with("fixtures/encoded/dir-b", &options)?;
+
+assert_eq!(a, b);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
+Other encodings can be supported by implementing [`Filter`] on top of a decoder of your choice.
+
+[`Pdf`] and [`Ooxml`] strip the dates and generators that documents record whenever they're
+regenerated, and [`Executable`] masks the build IDs, timestamps, and signatures of executables. With
+the `sqlite` feature, SQLite databases can be compared by their tables' rows with `Sqlite`, rather
+than by their pages.
+
+For formats no filter covers, [`Mask`] removes volatile regions located by offset or by the bytes
+around them, [`Json`] canonicalizes JSON documents, and [`Transform`] turns any function of a file's
+bytes into a filter.
+*/
+
+use std::{
+    fmt::{self, Debug},
+    io,
+    ops::Range,
+};
+
+pub use crate::canonical::Json;
+pub use crate::documents::{Ooxml, Pdf};
+pub use crate::executables::Executable;
+use crate::inflate::{gunzip, invalid};
+#[cfg(feature = "sqlite")]
+pub use crate::sqlite::Sqlite;
+use crate::zstd::unzstd;
+
+/**
+A transformation of a file's bytes, applied before the file is compared.
+*/
+pub trait Filter: Debug + Send + Sync {
+    /**
+    Transform the bytes of a file.
+
+    # Errors
+
+    Should return an error of kind [`io::ErrorKind::InvalidData`] if the bytes can't be decoded.
+    */
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>>;
+}
+
+/**
+Decompresses gzip files, including those of several concatenated members.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gzip;
+
+impl Filter for Gzip {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        gunzip(&bytes)
+    }
+}
+
+/**
+Decompresses Zstandard files, including those of several concatenated frames. Skippable frames are
+skipped, and frames that need a dictionary are rejected.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zstd;
+
+impl Filter for Zstd {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        unzstd(&bytes)
+    }
+}
+
+/**
+Decodes base64 using the standard alphabet. Padding is optional, and whitespace, such as line
+breaks, is ignored.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Base64;
+
+impl Filter for Base64 {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::with_capacity(bytes.len() / 4 * 3);
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        let mut padding = false;
+
+        for byte in bytes {
+            let value = match byte {
+                b'A'..=b'Z' => byte - b'A',
+                b'a'..=b'z' => byte - b'a' + 26,
+                b'0'..=b'9' => byte - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                b'=' => {
+                    padding = true;
+                    continue;
+                }
+                byte if byte.is_ascii_whitespace() => continue,
+                _ => return Err(invalid("the data is not valid base64")),
+            };
+            if padding {
+                return Err(invalid("base64 data continues after its padding"));
+            }
+
+            buffer = buffer << 6 | u32::from(value);
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                decoded.push((buffer >> bits) as u8);
+            }
+        }
+
+        Ok(decoded)
+    }
+}
+
+/**
+Removes regions of files, located by their offsets or by the bytes around them, before the files are
+compared. An escape hatch for formats with volatile fields, such as embedded timestamps, that no
+dedicated filter covers.
+
+All regions are located in the original bytes, then removed together, so one region's removal
+doesn't shift another's offsets. Regions that extend past the end of a file are cut short.
+
+```
+use dir_compare::{filters::Mask, Options};
+
+let mask = Mask::new()
+    .range(8..16)
+    .between(b"<built>", b"</built>")
+    .after(b"Build ID: ", 40);
+let options = Options::new().filter("*.manifest", mask);
+```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Mask {
+    regions: Vec<Region>,
+}
+
+#[derive(Debug, Clone)]
+enum Region {
+    Range(Range<usize>),
+    Between(Vec<u8>, Vec<u8>),
+    After(Vec<u8>, usize),
+}
+
+impl Mask {
+    /// Create a mask without any regions.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove the bytes at the given offsets.
+    #[must_use]
+    pub fn range(mut self, range: Range<usize>) -> Self {
+        self.regions.push(Region::Range(range));
+        self
+    }
+
+    /**
+    Remove the bytes between each occurrence of `start` and the next occurrence of `end`, keeping
+    the markers themselves. The removed regions may differ in length between files.
+    */
+    #[must_use]
+    pub fn between(mut self, start: &[u8], end: &[u8]) -> Self {
+        self.regions
+            .push(Region::Between(start.to_vec(), end.to_vec()));
+        self
+    }
+
+    /// Remove the given number of bytes after each occurrence of `marker`, keeping the marker.
+    #[must_use]
+    pub fn after(mut self, marker: &[u8], length: usize) -> Self {
+        self.regions.push(Region::After(marker.to_vec(), length));
+        self
+    }
+
+    /// The regions of the bytes to remove, in no particular order.
+    fn locate(&self, bytes: &[u8]) -> Vec<Range<usize>> {
+        let mut located = Vec::new();
+
+        for region in &self.regions {
+            match region {
+                Region::Range(range) => located.push(range.clone()),
+                Region::Between(start, end) => {
+                    let mut from = 0;
+                    while let Some(found) = find(bytes, start, from) {
+                        let region = found + start.len();
+                        let Some(end) = find(bytes, end, region) else {
+                            break;
+                        };
+                        located.push(region..end);
+                        from = end;
+                    }
+                }
+                Region::After(marker, length) => {
+                    let mut from = 0;
+                    while let Some(found) = find(bytes, marker, from) {
+                        from = found + marker.len();
+                        located.push(from..from.saturating_add(*length));
+                    }
+                }
+            }
+        }
+
+        located
+    }
+}
+
+impl Filter for Mask {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut removed = vec![false; bytes.len()];
+        for region in self.locate(&bytes) {
+            let end = region.end.min(bytes.len());
+            if let Some(removed) = removed.get_mut(region.start..end) {
+                removed.fill(true);
+            }
+        }
+
+        Ok(bytes
+            .into_iter()
+            .zip(removed)
+            .filter_map(|(byte, removed)| (!removed).then_some(byte))
+            .collect())
+    }
+}
+
+/**
+A filter made of a function of a file's bytes, for transformations too specific to a tree to be
+worth a type of their own, such as stripping a header that records when a file was built. Usually
+registered with [`Options::transform`](crate::Options::transform).
+
+```
+use dir_compare::{filters::Transform, Options};
+
+let strip_header = Transform::new(|bytes: Vec<u8>| {
+    let start = bytes.iter().position(|&byte| byte == b'\n').map_or(0, |end| end + 1);
+    Ok(bytes[start..].to_vec())
+});
+let options = Options::new().filter("*.generated.rs", strip_header);
+```
+*/
+pub struct Transform {
+    function: Box<dyn Fn(Vec<u8>) -> io::Result<Vec<u8>> + Send + Sync>,
+}
+
+impl Transform {
+    /// Make a filter of the given function.
+    pub fn new(function: impl Fn(Vec<u8>) -> io::Result<Vec<u8>> + Send + Sync + 'static) -> Self {
+        Self {
+            function: Box::new(function),
+        }
+    }
+}
+
+impl Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transform").finish_non_exhaustive()
+    }
+}
+
+impl Filter for Transform {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        (self.function)(bytes)
+    }
+}
+
+/// The offset of the first occurrence of `needle` at or after `from`, if any.
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| from + position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Base64, Filter, Mask};
+
+    #[test]
+    fn base64_should_decode_with_or_without_padding() {
+        assert_eq!(Base64.apply(b"aGVsbG8=".to_vec()).unwrap(), b"hello");
+        assert_eq!(Base64.apply(b"aGVs\nbG8".to_vec()).unwrap(), b"hello");
+        assert!(Base64.apply(b"aGVs*".to_vec()).is_err());
+    }
+
+    #[test]
+    fn masked_regions_should_be_removed() {
+        let mask = Mask::new()
+            .range(0..2)
+            .between(b"<t>", b"</t>")
+            .after(b"id=", 4)
+            .range(40..100);
+        let a = b"v1 <t>2024-01-01</t> id=abcd ok".to_vec();
+        let b = b"v2 <t>2025-12-31T23:59</t> id=wxyz ok".to_vec();
+
+        assert_eq!(mask.apply(a).unwrap(), b" <t></t> id= ok");
+        assert_eq!(mask.apply(b).unwrap(), b" <t></t> id= ok");
+    }
+}
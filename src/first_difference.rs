@@ -0,0 +1,231 @@
+/*!
+Fail-fast comparisons, which stop at the first difference between two trees and say exactly where
+it is, for assertions in tests.
+*/
+
+use std::{cmp::Ordering, path::PathBuf};
+
+use crate::{Content, Difference, DifferenceKind, Entry};
+
+impl Entry {
+    /**
+    Find the first difference between this entry and another, in the order of a
+    [`DiffReport`](crate::DiffReport), without looking any further. Returns `None` if, and only if,
+    the entries are equal.
+
+    The difference is located by its path relative to the directory holding the entries, so it
+    starts with their name. Entries with different names differ as siblings would: the one whose
+    name comes first is reported as removed, or added, with its name as its path. For differing
+    files, [`Difference::offset`] is the offset of the first byte at which they differ.
+
+    ```
+    use std::path::Path;
+
+    use dir_compare::{DifferenceKind, Entry};
+
+    let a = Entry::at("fixtures/not-equivalent/dir-a/subdir")?;
+    let b = Entry::at("fixtures/not-equivalent/dir-b/subdir")?;
+    let difference = a.first_difference(&b).expect("the entries differ");
+
+    assert_eq!(difference.path(), Path::new("subdir/test2.txt"));
+    assert_eq!(difference.kind(), DifferenceKind::Modified);
+    assert_eq!(difference.offset(), Some(0));
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn first_difference(&self, other: &Self) -> Option<Difference> {
+        let (kind, name, content) = match self.key().cmp(other.key()) {
+            Ordering::Equal => {
+                let difference = self.content.first_difference(&other.content)?;
+                return Some(difference.relocated(self.name.as_ref()));
+            }
+            Ordering::Less => (DifferenceKind::Removed, &self.name, &self.content),
+            Ordering::Greater => (DifferenceKind::Added, &other.name, &other.content),
+        };
+        let size = file_size(content);
+        let sizes = match kind {
+            DifferenceKind::Removed => (size, None),
+            _ => (None, size),
+        };
+
+        Some(Difference::new(PathBuf::from(name), kind).with_sizes(sizes))
+    }
+}
+
+impl Content {
+    /**
+    Find the first difference between this tree and another, as with [`Entry::first_difference`],
+    but located by its path relative to the roots, whose names play no part.
+
+    Unlike [`Content::diff`], it stops at the first difference, so it's cheaper for checking that
+    two trees are equal, yet still says where they aren't.
+
+    ```
+    use std::path::Path;
+
+    use dir_compare::Content;
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let difference = a.first_difference(&b).expect("the trees differ");
+
+    assert_eq!(difference.path(), Path::new("subdir/test2.txt"));
+    assert_eq!(difference.path(), a.diff(&b).differences()[0].path());
+    assert_eq!(a.first_difference(&a), None);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn first_difference(&self, other: &Self) -> Option<Difference> {
+        // Pairs are popped in the order of their paths, so each directory's children are pushed in
+        // reverse.
+        let mut stack = vec![(PathBuf::new(), Some(self), Some(other))];
+
+        while let Some((path, a, b)) = stack.pop() {
+            let (a, b) = match (a, b) {
+                (Some(a), Some(b)) => (a, b),
+                (Some(a), None) => {
+                    let sizes = (file_size(a), None);
+                    return Some(Difference::new(path, DifferenceKind::Removed).with_sizes(sizes));
+                }
+                (None, b) => {
+                    let sizes = (None, b.and_then(file_size));
+                    return Some(Difference::new(path, DifferenceKind::Added).with_sizes(sizes));
+                }
+            };
+
+            match (a, b) {
+                (Self::File(bytes_a), Self::File(bytes_b)) => {
+                    if bytes_a != bytes_b {
+                        let offset = bytes_a
+                            .iter()
+                            .zip(bytes_b)
+                            .position(|(a, b)| a != b)
+                            .unwrap_or(bytes_a.len().min(bytes_b.len()));
+                        let sizes = (Some(bytes_a.len() as u64), Some(bytes_b.len() as u64));
+                        return Some(
+                            Difference::new(path, DifferenceKind::Modified)
+                                .with_sizes(sizes)
+                                .with_offset(offset as u64),
+                        );
+                    }
+                }
+                (Self::Special(kind_a), Self::Special(kind_b)) if kind_a == kind_b => {}
+                (Self::Entries(entries_a), Self::Entries(entries_b)) => {
+                    let start = stack.len();
+                    let (mut entries_a, mut entries_b) =
+                        (entries_a.iter().peekable(), entries_b.iter().peekable());
+                    loop {
+                        let order = match (entries_a.peek(), entries_b.peek()) {
+                            (None, None) => break,
+                            (Some(_), None) => Ordering::Less,
+                            (None, Some(_)) => Ordering::Greater,
+                            (Some(a), Some(b)) => a.key().cmp(b.key()),
+                        };
+                        let (name, a, b) = match order {
+                            Ordering::Less => entries_a.next().map(|a| (&a.name, Some(a), None)),
+                            Ordering::Greater => entries_b.next().map(|b| (&b.name, None, Some(b))),
+                            Ordering::Equal => entries_a
+                                .next()
+                                .zip(entries_b.next())
+                                .map(|(a, b)| (&a.name, Some(a), Some(b))),
+                        }
+                        .expect("the peeked entries exist");
+                        stack.push((
+                            path.join(name),
+                            a.map(|a| &a.content),
+                            b.map(|b| &b.content),
+                        ));
+                    }
+                    stack[start..].reverse();
+                }
+                _ => {
+                    let sizes = (file_size(a), file_size(b));
+                    return Some(
+                        Difference::new(path, DifferenceKind::TypeChanged).with_sizes(sizes),
+                    );
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The size of the content, if it's a file.
+fn file_size(content: &Content) -> Option<u64> {
+    match content {
+        Content::File(bytes) => Some(bytes.len() as u64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::{testing::TempDir, Content, DifferenceKind, Entry};
+
+    #[test]
+    fn the_first_difference_should_be_the_first_in_the_report() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for (root, log) in [(&a, "started\nfailed\n"), (&b, "started\nstopped\n")] {
+            fs::create_dir_all(root.join("etc/ssl")).unwrap();
+            fs::create_dir_all(root.join("var")).unwrap();
+            fs::write(root.join("etc/hosts"), "localhost").unwrap();
+            fs::write(root.join("var/log"), log).unwrap();
+        }
+        fs::write(a.join("etc/ssl/cert.pem"), "").unwrap();
+        fs::create_dir(b.join("etc/ssl/cert.pem")).unwrap();
+        fs::write(b.join("etc/zones"), "").unwrap();
+
+        let (content_a, content_b) = (Content::of(&a).unwrap(), Content::of(&b).unwrap());
+        let difference = content_a.first_difference(&content_b).unwrap();
+        assert_eq!(difference.path(), Path::new("etc/ssl/cert.pem"));
+        assert_eq!(difference.kind(), DifferenceKind::TypeChanged);
+        assert_eq!(difference.sizes(), (Some(0), None));
+        assert_eq!(difference.offset(), None);
+        assert_eq!(
+            difference.path(),
+            content_a.diff(&content_b).differences()[0].path()
+        );
+
+        fs::remove_dir(b.join("etc/ssl/cert.pem")).unwrap();
+        fs::write(b.join("etc/ssl/cert.pem"), "").unwrap();
+        let content_b = Content::of(&b).unwrap();
+        let difference = content_a.first_difference(&content_b).unwrap();
+        assert_eq!(difference.path(), Path::new("etc/zones"));
+        assert_eq!(difference.kind(), DifferenceKind::Added);
+
+        fs::remove_file(b.join("etc/zones")).unwrap();
+        let content_b = Content::of(&b).unwrap();
+        let difference = content_a.first_difference(&content_b).unwrap();
+        assert_eq!(difference.path(), Path::new("var/log"));
+        assert_eq!(difference.kind(), DifferenceKind::Modified);
+        assert_eq!(difference.offset(), Some(8));
+        assert!(content_a.first_difference(&content_a).is_none());
+
+        let entry_a = Entry::at(&a).unwrap();
+        assert_eq!(entry_a.first_difference(&Entry::at(&a).unwrap()), None);
+        let difference = entry_a.first_difference(&Entry::at(&b).unwrap()).unwrap();
+        assert_eq!(difference.path(), Path::new("a"));
+        assert_eq!(difference.kind(), DifferenceKind::Removed);
+        let entry = Entry::at(a.join("var")).unwrap();
+        let difference = entry.first_difference(&Entry::at(b.join("var")).unwrap());
+        assert_eq!(difference.unwrap().path(), Path::new("var/log"));
+    }
+
+    #[test]
+    fn files_that_extend_others_should_differ_where_the_shorter_ends() {
+        let difference = Content::File(b"data".to_vec())
+            .first_difference(&Content::File(b"database".to_vec()))
+            .unwrap();
+        assert_eq!(difference.path(), Path::new(""));
+        assert_eq!(difference.offset(), Some(4));
+        assert_eq!(difference.sizes(), (Some(4), Some(8)));
+    }
+}
@@ -0,0 +1,152 @@
+/*!
+Glob patterns for selecting entries by path.
+*/
+
+use std::path::{Component, Path};
+
+/**
+A glob pattern, matched against paths relative to the root of a tree.
+
+- `*` matches any run of characters other than `/`, and `?` any single character other than `/`.
+- `**` matches any run of characters, including `/`. Followed by a `/`, it matches zero or more
+  whole directories.
+- `[abc]`, `[a-z]`, and `[!abc]` match a single character in, or not in, the class.
+- `\` escapes the character after it.
+
+A pattern without a `/` is matched against the entry's name, at any depth. A pattern with one is
+matched against the whole path; a leading `/` is ignored.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Glob {
+    tokens: Vec<char>,
+    whole_path: bool,
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Self {
+        Self {
+            tokens: pattern
+                .strip_prefix('/')
+                .unwrap_or(pattern)
+                .chars()
+                .collect(),
+            whole_path: pattern.contains('/'),
+        }
+    }
+
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        let subject = if self.whole_path {
+            slash_separated(path)
+        } else {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+
+        matches(&self.tokens, &subject.chars().collect::<Vec<_>>())
+    }
+}
+
+/// The path's components, joined by `/` regardless of the platform.
+pub(crate) fn slash_separated(path: &Path) -> String {
+    let mut joined = String::new();
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            if !joined.is_empty() {
+                joined.push('/');
+            }
+            joined.push_str(&name.to_string_lossy());
+        }
+    }
+    joined
+}
+
+fn matches(pattern: &[char], subject: &[char]) -> bool {
+    match pattern {
+        [] => subject.is_empty(),
+        ['*', '*', '/', rest @ ..] => (0..=subject.len())
+            .filter(|&i| i == 0 || subject[i - 1] == '/')
+            .any(|i| matches(rest, &subject[i..])),
+        ['*', '*', rest @ ..] => (0..=subject.len()).any(|i| matches(rest, &subject[i..])),
+        ['*', rest @ ..] => (0..=subject.len())
+            .take_while(|&i| i == 0 || subject[i - 1] != '/')
+            .any(|i| matches(rest, &subject[i..])),
+        ['?', rest @ ..] => match subject {
+            [first, subject @ ..] => *first != '/' && matches(rest, subject),
+            [] => false,
+        },
+        ['[', class @ ..] => match (class_end(class), subject) {
+            (Some(end), [first, subject @ ..]) => {
+                in_class(&class[..end], *first) && matches(&class[end + 1..], subject)
+            }
+            (Some(_), []) => false,
+            (None, _) => literal('[', &pattern[1..], subject),
+        },
+        ['\\', escaped, rest @ ..] => literal(*escaped, rest, subject),
+        [character, rest @ ..] => literal(*character, rest, subject),
+    }
+}
+
+fn literal(character: char, rest: &[char], subject: &[char]) -> bool {
+    match subject {
+        [first, subject @ ..] => *first == character && matches(rest, subject),
+        [] => false,
+    }
+}
+
+/// The index of the `]` closing a class, which can't be the class's first character.
+fn class_end(class: &[char]) -> Option<usize> {
+    let start = usize::from(class.first() == Some(&'!'));
+    class
+        .iter()
+        .skip(start + 1)
+        .position(|&character| character == ']')
+        .map(|position| position + start + 1)
+}
+
+fn in_class(class: &[char], character: char) -> bool {
+    let (negated, class) = match class {
+        ['!', class @ ..] => (true, class),
+        class => (false, class),
+    };
+
+    let mut found = false;
+    let mut index = 0;
+    while index < class.len() {
+        if let [start, '-', end, ..] = class[index..] {
+            found |= (start..=end).contains(&character);
+            index += 3;
+        } else {
+            found |= class[index] == character;
+            index += 1;
+        }
+    }
+
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Glob;
+
+    #[test]
+    fn patterns_without_slashes_should_match_names() {
+        let glob = Glob::new("*.gz");
+
+        assert!(glob.matches(Path::new("data.gz")));
+        assert!(glob.matches(Path::new("logs/2024/data.gz")));
+        assert!(!glob.matches(Path::new("data.gz.b64")));
+    }
+
+    #[test]
+    fn patterns_with_slashes_should_match_paths() {
+        assert!(Glob::new("logs/*.log").matches(Path::new("logs/app.log")));
+        assert!(!Glob::new("logs/*.log").matches(Path::new("logs/old/app.log")));
+        assert!(Glob::new("logs/**/*.log").matches(Path::new("logs/app.log")));
+        assert!(Glob::new("logs/**/*.log").matches(Path::new("logs/old/app.log")));
+        assert!(Glob::new("/bin/[a-c]pp?").matches(Path::new("bin/app1")));
+        assert!(!Glob::new("bin/[!a]pp").matches(Path::new("bin/app")));
+    }
+}
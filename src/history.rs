@@ -0,0 +1,408 @@
+/*!
+An append-only log of comparison results, for tracking how trees drift apart over time.
+*/
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{DiffReport, Difference, DifferenceKind, Progress};
+
+/**
+A log file of successive [`DiffReport`]s, each recorded with the time of its comparison.
+
+Runs are only ever appended to the log, so it can be shared by a scheduled job that records and
+tools that query it. Recording a report with no differences is meaningful: it marks the trees as
+having been in sync at that time.
+
+```no_run
+use dir_compare::{Entry, History};
+
+let history = History::at("integrity.log");
+let snapshot = Entry::at("/srv/reference")?;
+history.record(&snapshot.diff_against_disk("/srv/live")?)?;
+
+if let Some(since) = history.diverged_since("etc/passwd")? {
+    println!("etc/passwd has been out of sync since {since:?}");
+}
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
+# Format
+
+The log is a UTF-8 text file. Each run starts with a line of the form `run <seconds>.<nanoseconds>`,
+counted from the Unix epoch, followed by a line of the form `<kind>\t<path>\t<size>\t<size>` for
+each difference, where the sizes are those of [`Difference::sizes`], or `-` where they're unknown.
+The sizes may be missing altogether, as they are in older logs. Backslashes, tabs, and newlines in
+paths are escaped as `\\`, `\t`, and `\n`, and bytes of paths that aren't valid UTF-8 as `\xHH`, so
+that such paths are recorded as they are. Those can only be read back on Unix, where paths are
+bytes; elsewhere, they make the log malformed.
+
+Each run is appended with a single call, but POSIX only guarantees writes of at most `PIPE_BUF`
+bytes to pipes to be atomic, and network file systems such as NFS don't append atomically at all. A
+run recorded as another process records or reads one may be interleaved with it or seen half
+written.
+*/
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+/**
+A single recorded comparison.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    time: SystemTime,
+    report: DiffReport,
+}
+
+impl Run {
+    /// When the comparison was recorded.
+    #[must_use]
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// The result of the comparison.
+    #[must_use]
+    pub fn report(&self) -> &DiffReport {
+        &self.report
+    }
+}
+
+impl History {
+    /// Use the log at the given path. It's created when the first run is recorded.
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /**
+    Append a report to the log, timestamped with the current time.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn record(&self, report: &DiffReport) -> io::Result<()> {
+        self.record_at(SystemTime::now(), report)
+    }
+
+    /**
+    Append a report to the log with the given timestamp.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn record_at(&self, time: SystemTime, report: &DiffReport) -> io::Result<()> {
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+
+        let mut run = format!(
+            "run {}.{:09}\n",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos()
+        );
+        for difference in report {
+            run.push_str(difference.kind().name());
+            run.push('\t');
+            run.push_str(&escape(difference.path()));
+            let (a, b) = difference.sizes();
+            for size in [a, b] {
+                run.push('\t');
+                match size {
+                    Some(size) => run.push_str(&size.to_string()),
+                    None => run.push('-'),
+                }
+            }
+            run.push('\n');
+        }
+
+        // Appending writes at the end of the log, even if it has grown since it was opened. See the
+        // docs of `History` for what isn't guaranteed.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(run.as_bytes())
+    }
+
+    /**
+    Read every recorded run, oldest first. A log that doesn't exist yet has no runs.
+
+    # Errors
+
+    - Will return an error of kind [`io::ErrorKind::InvalidData`] if the log is malformed.
+    - Will bubble I/O errors.
+    */
+    pub fn runs(&self) -> io::Result<Vec<Run>> {
+        let log = match fs::read_to_string(&self.path) {
+            Ok(log) => log,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+
+        let mut runs: Vec<(SystemTime, Vec<Difference>)> = Vec::new();
+        for (number, line) in log.lines().enumerate() {
+            let malformed = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} of {:?} is malformed", number + 1, self.path),
+                )
+            };
+
+            if let Some(time) = line.strip_prefix("run ") {
+                runs.push((parse_time(time).ok_or_else(malformed)?, Vec::new()));
+            } else {
+                let mut fields = line.split('\t');
+                let (Some(kind), Some(path)) = (fields.next(), fields.next()) else {
+                    return Err(malformed());
+                };
+                let kind = DifferenceKind::from_name(kind).ok_or_else(malformed)?;
+                let path = unescape(path).ok_or_else(malformed)?;
+                let sizes = match (fields.next(), fields.next(), fields.next()) {
+                    (None, None, None) => (None, None),
+                    (Some(a), Some(b), None) => (
+                        parse_size(a).ok_or_else(malformed)?,
+                        parse_size(b).ok_or_else(malformed)?,
+                    ),
+                    _ => return Err(malformed()),
+                };
+                let (_, differences) = runs.last_mut().ok_or_else(malformed)?;
+                differences.push(Difference::new(path, kind).with_sizes(sizes));
+            }
+        }
+
+        Ok(runs
+            .into_iter()
+            .map(|(time, differences)| Run {
+                time,
+                report: DiffReport::new(differences),
+            })
+            .collect())
+    }
+
+    /**
+    What changed between the last two recorded runs, if there are two. See
+    [`DiffReport::progress_since`].
+
+    # Errors
+
+    See [`History::runs`].
+    */
+    pub fn progress(&self) -> io::Result<Option<Progress>> {
+        Ok(match self.runs()?.as_slice() {
+            [.., earlier, later] => Some(later.report.progress_since(&earlier.report)),
+            _ => None,
+        })
+    }
+
+    /**
+    When the given path first differed in any recorded run, if ever.
+
+    # Errors
+
+    See [`History::runs`].
+    */
+    pub fn first_diverged(&self, path: impl AsRef<Path>) -> io::Result<Option<SystemTime>> {
+        let path = path.as_ref();
+
+        Ok(self
+            .runs()?
+            .into_iter()
+            .find(|run| differs(run, path))
+            .map(|run| run.time))
+    }
+
+    /**
+    When the given path started to differ without interruption up to the latest run. Returns
+    `None` if the path was in sync in the latest run, or if there are no runs.
+
+    # Errors
+
+    See [`History::runs`].
+    */
+    pub fn diverged_since(&self, path: impl AsRef<Path>) -> io::Result<Option<SystemTime>> {
+        let path = path.as_ref();
+
+        Ok(self
+            .runs()?
+            .iter()
+            .rev()
+            .take_while(|run| differs(run, path))
+            .last()
+            .map(|run| run.time))
+    }
+}
+
+fn differs(run: &Run, path: &Path) -> bool {
+    run.report
+        .iter()
+        .any(|difference| difference.path() == path)
+}
+
+fn parse_time(time: &str) -> Option<SystemTime> {
+    let (seconds, nanoseconds) = time.split_once('.')?;
+    // Nanoseconds that carry into the seconds could overflow them.
+    let nanoseconds = nanoseconds
+        .parse()
+        .ok()
+        .filter(|&nanoseconds| nanoseconds < 1_000_000_000)?;
+    let since_epoch = Duration::new(seconds.parse().ok()?, nanoseconds);
+
+    UNIX_EPOCH.checked_add(since_epoch)
+}
+
+/// A size as recorded: a number, or `-` if it's unknown.
+fn parse_size(size: &str) -> Option<Option<u64>> {
+    match size {
+        "-" => Some(None),
+        size => size.parse().ok().map(Some),
+    }
+}
+
+fn escape(path: &Path) -> String {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for character in chunk.valid().chars() {
+            match character {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                character => escaped.push(character),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{byte:02X}"));
+        }
+    }
+    escaped
+}
+
+fn unescape(path: &str) -> Option<PathBuf> {
+    let mut unescaped = Vec::with_capacity(path.len());
+    let mut characters = path.chars();
+    while let Some(character) = characters.next() {
+        let character = match character {
+            '\\' => match characters.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'n' => '\n',
+                'x' => {
+                    let digits = [characters.next()?, characters.next()?];
+                    let byte = digits
+                        .iter()
+                        .try_fold(0, |byte, digit| Some(byte * 16 + digit.to_digit(16)?))?;
+                    unescaped.push(u8::try_from(byte).ok()?);
+                    continue;
+                }
+                _ => return None,
+            },
+            character => character,
+        };
+        unescaped.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+    from_bytes(unescaped)
+}
+
+/// The path with the given bytes.
+#[cfg(unix)]
+fn from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    Some(OsString::from_vec(bytes).into())
+}
+
+/// The path with the given bytes, if they're valid UTF-8, which paths elsewhere than Unix aren't
+/// made of.
+#[cfg(not(unix))]
+fn from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, io,
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{testing::TempDir, Content, DiffReport, History};
+
+    #[test]
+    fn divergence_should_be_tracked_across_runs() {
+        let dir = TempDir::new();
+        let history = History::at(dir.path().join("history.log"));
+        let a = Content::of("fixtures/not-equivalent/dir-a").unwrap();
+        let b = Content::of("fixtures/not-equivalent/dir-b").unwrap();
+        let time = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+
+        history.record_at(time(1), &a.diff(&b)).unwrap();
+        history.record_at(time(2), &DiffReport::default()).unwrap();
+        history.record_at(time(3), &a.diff(&b)).unwrap();
+        history.record_at(time(4), &a.diff(&b)).unwrap();
+
+        let path = "subdir/test2.txt";
+        assert_eq!(history.runs().unwrap().len(), 4);
+        assert_eq!(history.runs().unwrap()[3].report(), &a.diff(&b));
+        assert_eq!(history.first_diverged(path).unwrap(), Some(time(1)));
+        assert_eq!(history.diverged_since(path).unwrap(), Some(time(3)));
+        assert_eq!(history.first_diverged("test.txt").unwrap(), None);
+        assert!(history.progress().unwrap().unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_logs_should_be_rejected() {
+        let dir = TempDir::new();
+        let history = History::at(dir.path().join("history.log"));
+        for log in [
+            "run 1\n",
+            "run 1.5\nremoved\n",
+            "run 1.5\nrenamed\tetc/hosts\n",
+            "modified\tetc/hosts\n",
+            "run 18446744073709551615.4000000000\n",
+        ] {
+            fs::write(dir.path().join("history.log"), log).unwrap();
+            let error = history.runs().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{log:?}");
+        }
+    }
+
+    #[test]
+    fn paths_should_survive_escaping() {
+        let path = Path::new("tab\there\\new\nline");
+
+        assert_eq!(super::unescape(&super::escape(path)).unwrap(), path);
+        assert_eq!(super::unescape("\\x4"), None);
+        assert_eq!(super::unescape("\\xZZ"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_that_are_not_unicode_should_be_recorded_as_they_are() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        use crate::{Difference, DifferenceKind};
+
+        let dir = TempDir::new();
+        let history = History::at(dir.path().join("history.log"));
+        let paths = [b"caf\xe9".as_slice(), b"caf\xe8", b"caf\\xE9"].map(OsStr::from_bytes);
+        let report = DiffReport::new(
+            paths
+                .iter()
+                .map(|path| Difference::new(path.into(), DifferenceKind::Added))
+                .collect(),
+        );
+
+        history.record_at(UNIX_EPOCH, &report).unwrap();
+        assert_eq!(history.runs().unwrap()[0].report(), &report);
+    }
+}
@@ -0,0 +1,109 @@
+/*!
+Exclusion rules in the format of `.gitignore` files.
+*/
+
+use std::{cell::LazyCell, path::Path};
+
+use crate::glob::Glob;
+
+/// A single exclusion rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    glob: Glob,
+    /// Whether the rule re-includes what an earlier rule excluded.
+    negated: bool,
+    /// Whether the rule only matches directories.
+    directory_only: bool,
+}
+
+/**
+Rules for excluding entries from trees, in the format of `.gitignore` files. See
+[`Options::ignore`](crate::Options::ignore).
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    /// Add the rules listed in the given text, one per line, after the existing ones.
+    pub(crate) fn add(&mut self, rules: &str) {
+        for line in rules.lines() {
+            // Trailing spaces are ignored, unless they're escaped.
+            let mut line = line;
+            while line.ends_with(' ') && !line.ends_with("\\ ") {
+                line = &line[..line.len() - 1];
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, line),
+            };
+            let (directory_only, pattern) = match pattern.strip_suffix('/') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern),
+            };
+            if pattern.is_empty() {
+                continue;
+            }
+
+            self.rules.push(Rule {
+                glob: Glob::new(pattern),
+                negated,
+                directory_only,
+            });
+        }
+    }
+
+    /**
+    Whether the entry at the given path relative to the root is excluded. Whether it's a directory
+    is only checked if a rule for directories matches it.
+    */
+    pub(crate) fn excludes(&self, relative: &Path, is_directory: impl FnOnce() -> bool) -> bool {
+        let is_directory = LazyCell::new(is_directory);
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.glob.matches(relative) && (!rule.directory_only || *is_directory))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Ignore;
+
+    fn ignore(rules: &str) -> Ignore {
+        let mut ignore = Ignore::default();
+        ignore.add(rules);
+        ignore
+    }
+
+    #[test]
+    fn later_rules_should_override_earlier_ones() {
+        let ignore = ignore("# Build output\n*.log\n!keep.log\n\n/target\n");
+
+        assert!(ignore.excludes(Path::new("debug.log"), || false));
+        assert!(ignore.excludes(Path::new("logs/debug.log"), || false));
+        assert!(!ignore.excludes(Path::new("logs/keep.log"), || false));
+        assert!(ignore.excludes(Path::new("target"), || true));
+        assert!(!ignore.excludes(Path::new("src/target"), || true));
+    }
+
+    #[test]
+    fn directory_rules_should_only_match_directories() {
+        let ignore = ignore("build/\n\\#notes  \ntrailing\\ \n");
+
+        assert!(ignore.excludes(Path::new("build"), || true));
+        assert!(ignore.excludes(Path::new("src/build"), || true));
+        assert!(!ignore.excludes(Path::new("build"), || false));
+        assert!(ignore.excludes(Path::new("#notes"), || false));
+        assert!(ignore.excludes(Path::new("trailing "), || false));
+    }
+}
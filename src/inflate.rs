@@ -0,0 +1,376 @@
+/*!
+A decoder for DEFLATE streams ([RFC 1951]), and the gzip ([RFC 1952]) and CRC-32 machinery that
+wraps them, so that compressed files can be compared without taking on a dependency.
+
+It favours simplicity over speed, in the manner of zlib's `puff.c`.
+
+[RFC 1951]: https://www.rfc-editor.org/rfc/rfc1951
+[RFC 1952]: https://www.rfc-editor.org/rfc/rfc1952
+*/
+
+use std::io;
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order in which the code length code lengths of a dynamic block are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+/// Reads a byte slice a bit at a time, least significant bit first.
+struct Bits<'a> {
+    input: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn take(&mut self, count: u32) -> io::Result<u32> {
+        while self.count < count {
+            let byte = *self
+                .input
+                .get(self.position)
+                .ok_or_else(|| invalid("the deflate stream ended unexpectedly"))?;
+            self.position += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+
+        let bits = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(bits)
+    }
+
+    /// Discard the bits left in the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .input
+            .get(self.position..self.position + count)
+            .ok_or_else(|| invalid("the deflate stream ended unexpectedly"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols they encode.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+
+        // Reject over-subscribed codes. Incomplete ones are allowed, as for single-code trees.
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(invalid("a Huffman code is over-subscribed"));
+            }
+        }
+
+        let mut offsets = [0; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid("a Huffman code is invalid"))
+    }
+}
+
+/// Decode a raw DEFLATE stream, returning the data and the number of input bytes consumed.
+pub(crate) fn inflate(input: &[u8]) -> io::Result<(Vec<u8>, usize)> {
+    let mut bits = Bits::new(input);
+    let mut output = Vec::new();
+
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored(&mut bits, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed()?;
+                codes(&mut bits, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic(&mut bits)?;
+                codes(&mut bits, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(invalid("a deflate block has an invalid type")),
+        }
+
+        if last {
+            return Ok((output, bits.position));
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, output: &mut Vec<u8>) -> io::Result<()> {
+    bits.align();
+    let header = bits.bytes(4)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return Err(invalid("a stored deflate block has a corrupt length"));
+    }
+
+    output.extend_from_slice(bits.bytes(usize::from(length))?);
+    Ok(())
+}
+
+fn fixed() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic(bits: &mut Bits) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(invalid("a dynamic deflate block has too many codes"));
+    }
+
+    let mut code_lengths = [0; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_lengths.decode(bits)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *index
+                    .checked_sub(1)
+                    .and_then(|previous| lengths.get(previous))
+                    .ok_or_else(|| invalid("a code length repeats nothing"))?;
+                (previous, 3 + bits.take(2)? as usize)
+            }
+            17 => (0, 3 + bits.take(3)? as usize),
+            _ => (0, 11 + bits.take(7)? as usize),
+        };
+
+        let run = lengths
+            .get_mut(index..index + repeat)
+            .ok_or_else(|| invalid("code lengths overflow their block"))?;
+        run.fill(length);
+        index += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("a dynamic deflate block has no end code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = usize::from(literals.decode(bits)?);
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                let length = *LENGTH_BASE
+                    .get(index)
+                    .ok_or_else(|| invalid("a length code is invalid"))?
+                    as usize
+                    + bits.take(u32::from(LENGTH_EXTRA[index]))? as usize;
+
+                let index = usize::from(distances.decode(bits)?);
+                let distance = *DISTANCE_BASE
+                    .get(index)
+                    .ok_or_else(|| invalid("a distance code is invalid"))?
+                    as usize
+                    + bits.take(u32::from(DISTANCE_EXTRA[index]))? as usize;
+
+                let start = output
+                    .len()
+                    .checked_sub(distance)
+                    .ok_or_else(|| invalid("a distance reaches before the start of the data"))?;
+                // Copy byte by byte, since the source and destination may overlap.
+                for offset in 0..length {
+                    output.push(output[start + offset]);
+                }
+            }
+        }
+    }
+}
+
+/// Decode every member of a gzip file, concatenating their data.
+pub(crate) fn gunzip(mut input: &[u8]) -> io::Result<Vec<u8>> {
+    const TEXT_FLAGS: u8 = 0b1_1110;
+    let mut output = Vec::new();
+
+    loop {
+        let header = input
+            .get(..10)
+            .ok_or_else(|| invalid("the gzip header is truncated"))?;
+        if header[..3] != [0x1f, 0x8b, 8] {
+            return Err(invalid("the data is not gzip-compressed"));
+        }
+        let flags = header[3];
+        if flags & !TEXT_FLAGS & !1 != 0 {
+            return Err(invalid("the gzip header has reserved flags set"));
+        }
+
+        let mut position = 10;
+        if flags & 0b100 != 0 {
+            let length = input
+                .get(position..position + 2)
+                .ok_or_else(|| invalid("the gzip header is truncated"))?;
+            position += 2 + usize::from(u16::from_le_bytes([length[0], length[1]]));
+        }
+        for flag in [0b1000, 0b1_0000] {
+            if flags & flag != 0 {
+                let terminator = input
+                    .get(position..)
+                    .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                    .ok_or_else(|| invalid("the gzip header is truncated"))?;
+                position += terminator + 1;
+            }
+        }
+        if flags & 0b10 != 0 {
+            position += 2;
+        }
+
+        let (data, consumed) = inflate(
+            input
+                .get(position..)
+                .ok_or_else(|| invalid("the gzip header is truncated"))?,
+        )?;
+        position += consumed;
+
+        let trailer = input
+            .get(position..position + 8)
+            .ok_or_else(|| invalid("the gzip trailer is truncated"))?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != crc32(&data) || size != data.len() as u32 {
+            return Err(invalid("the gzip data is corrupt"));
+        }
+
+        output.extend_from_slice(&data);
+        input = &input[position + 8..];
+        if input.is_empty() {
+            return Ok(output);
+        }
+    }
+}
+
+/// The CRC-32 checksum used by gzip and zip.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn gzip_files_should_decompress() {
+        let compressed = std::fs::read("fixtures/encoded/dir-b/data.txt.gz").unwrap();
+        let original = std::fs::read("fixtures/encoded/plain.txt").unwrap();
+
+        assert_eq!(super::gunzip(&compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn crc32_should_match_the_check_value() {
+        assert_eq!(super::crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
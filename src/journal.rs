@@ -0,0 +1,218 @@
+/*!
+Transactional changes to a tree on disk, journaled so that they can be rolled back if any fails.
+
+New files are staged in full before they replace anything, and replaced or removed entries are set
+aside rather than deleted, all in a staging directory next to the tree, on the same file system.
+Every change is then a rename, which either happens or doesn't, and which the journal can undo.
+*/
+
+use std::{
+    fs, io, mem,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::source::at;
+
+/// A single change made to the tree, as the journal records it to undo it.
+#[derive(Debug)]
+enum Step {
+    /// A directory was created at the path.
+    Created(PathBuf),
+    /// A staged file was renamed to the path.
+    Placed { path: PathBuf, staged: PathBuf },
+    /// The entry at the path was renamed aside.
+    SetAside { path: PathBuf, aside: PathBuf },
+    /// The empty directory at the path was removed.
+    RemovedDirectory(PathBuf),
+}
+
+/// Changes in progress to the tree at a root, addressed by paths relative to it.
+#[derive(Debug)]
+pub(crate) struct Transaction<'a> {
+    root: &'a Path,
+    stage: PathBuf,
+    journal: Vec<Step>,
+    /// The number of files staged so far, which names the next.
+    staged: usize,
+    /// The entries set aside so far, by their paths relative to the root.
+    aside: Vec<(PathBuf, PathBuf)>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Begin changing the tree at the given root, creating its staging directory.
+    pub(crate) fn begin(root: &'a Path) -> io::Result<Self> {
+        let parent = root
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let name = root.file_name().unwrap_or_default().to_string_lossy();
+
+        let mut attempt = 0;
+        let stage = loop {
+            let stage = parent.join(format!(".{name}.dir-compare-{}-{attempt}", process::id()));
+            match fs::create_dir(&stage) {
+                Ok(()) => break stage,
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(error) => return Err(error),
+            }
+        };
+        fs::create_dir(stage.join("new"))?;
+
+        Ok(Self {
+            root,
+            stage,
+            journal: Vec::new(),
+            staged: 0,
+            aside: Vec::new(),
+        })
+    }
+
+    /// Copy the file at the given path, from anywhere, into the staging directory.
+    pub(crate) fn stage(&mut self, from: &Path) -> io::Result<PathBuf> {
+        let staged = self.stage.join("new").join(self.staged.to_string());
+        self.staged += 1;
+        fs::copy(from, &staged)?;
+
+        Ok(staged)
+    }
+
+    /// Create a directory at the given path, unless there's one already.
+    pub(crate) fn create_directory(&mut self, path: &Path) -> io::Result<()> {
+        let full = at(self.root, path);
+        if fs::symlink_metadata(&full).is_ok_and(|metadata| metadata.is_dir()) {
+            return Ok(());
+        }
+
+        fs::create_dir(&full)?;
+        self.journal.push(Step::Created(full));
+        Ok(())
+    }
+
+    /// Put a staged file at the given path, setting aside the entry there, if any.
+    pub(crate) fn place(&mut self, staged: PathBuf, path: &Path) -> io::Result<()> {
+        let full = at(self.root, path);
+        if fs::symlink_metadata(&full).is_ok() {
+            self.set_aside(path)?;
+        }
+
+        fs::rename(&staged, &full)?;
+        self.journal.push(Step::Placed { path: full, staged });
+        Ok(())
+    }
+
+    /// Remove the entry at the given path, setting it aside. A directory must already be empty.
+    pub(crate) fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let full = at(self.root, path);
+        if !fs::symlink_metadata(&full)?.is_dir() {
+            return self.set_aside(path);
+        }
+
+        // A directory's children have been set aside already, into a directory of its own there.
+        let aside = self.aside_path(path);
+        fs::create_dir_all(&aside)?;
+        fs::remove_dir(&full)?;
+        self.journal.push(Step::RemovedDirectory(full));
+        self.aside.push((path.to_path_buf(), aside));
+        Ok(())
+    }
+
+    fn set_aside(&mut self, path: &Path) -> io::Result<()> {
+        let full = at(self.root, path);
+        let aside = self.aside_path(path);
+        if let Some(parent) = aside.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::rename(&full, &aside)?;
+        self.journal.push(Step::SetAside {
+            path: full,
+            aside: aside.clone(),
+        });
+        self.aside.push((path.to_path_buf(), aside));
+        Ok(())
+    }
+
+    /// The entries set aside so far, by their paths relative to the root and where they are.
+    pub(crate) fn aside(&self) -> &[(PathBuf, PathBuf)] {
+        &self.aside
+    }
+
+    /**
+    Keep the changes, returning the entries that were set aside, in the order they were, by their
+    paths relative to the root and where they are now. They're discarded with the staging directory
+    once the result is dropped, unless they're moved elsewhere first.
+    */
+    pub(crate) fn commit(mut self) -> Committed {
+        self.journal.clear();
+        Committed {
+            stage: mem::take(&mut self.stage),
+            aside: mem::take(&mut self.aside),
+        }
+    }
+
+    /// Undo the changes, as far as possible, in reverse order, and discard the staging directory.
+    pub(crate) fn roll_back(self) {
+        drop(self);
+    }
+
+    fn undo(&mut self) {
+        for step in self.journal.drain(..).rev() {
+            // Nothing more can be done about a step that can't be undone.
+            let _ = match step {
+                Step::Created(path) => fs::remove_dir(path),
+                Step::Placed { path, staged } => fs::rename(path, staged),
+                Step::SetAside { path, aside } => fs::rename(aside, path),
+                Step::RemovedDirectory(path) => fs::create_dir(path),
+            };
+        }
+    }
+
+    /// Where the entry at the given path relative to the root is set aside.
+    fn aside_path(&self, path: &Path) -> PathBuf {
+        // The root has no path relative to itself, so it keeps its name.
+        let aside = self.stage.join("aside");
+        if path.as_os_str().is_empty() {
+            aside.join(self.root.file_name().unwrap_or_default())
+        } else {
+            aside.join(path)
+        }
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        // A transaction dropped without being committed is rolled back, such as on a panic.
+        self.undo();
+        if !self.stage.as_os_str().is_empty() {
+            let _ = fs::remove_dir_all(&self.stage);
+        }
+    }
+}
+
+/// The outcome of a committed transaction: the entries it set aside, which are discarded with its
+/// staging directory when this is dropped.
+pub(crate) struct Committed {
+    stage: PathBuf,
+    aside: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Committed {
+    /// The entries that were set aside, by their paths relative to the root and where they are.
+    pub(crate) fn aside(&self) -> &[(PathBuf, PathBuf)] {
+        &self.aside
+    }
+
+    /// Leave the staging directory, and whatever it still holds, in place, returning its path.
+    pub(crate) fn keep_stage(mut self) -> PathBuf {
+        mem::take(&mut self.stage)
+    }
+}
+
+impl Drop for Committed {
+    fn drop(&mut self) {
+        if !self.stage.as_os_str().is_empty() {
+            let _ = fs::remove_dir_all(&self.stage);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+/*!
+Machine-readable diff reports, in JSON, for CI systems and tools written in other languages.
+*/
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+use crate::{DiffReport, Difference};
+
+/// The version of the JSON format, which is raised whenever a change to it could break a reader.
+const VERSION: u32 = 1;
+
+impl DiffReport {
+    /**
+    The report as a JSON document. See [`DiffReport::write_json`] for the format.
+
+    ```
+    use dir_compare::Content;
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let json = a.diff(&b).to_json();
+
+    assert!(json.starts_with(r#"{"version":1,"differences":[{"path":"subdir/test2.txt","#));
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = format!(r#"{{"version":{VERSION},"differences":["#);
+        for (index, difference) in self.iter().enumerate() {
+            if index > 0 {
+                json.push(',');
+            }
+            push_difference(&mut json, difference);
+        }
+        json.push_str("]}");
+
+        json
+    }
+
+    /**
+    Write the report as a JSON document, followed by a newline.
+
+    # Format
+
+    The document is an object with two fields:
+
+    - `version`: the version of the format, currently `1`. It's raised whenever a change to the
+      format could break a reader, such as a field being removed or changing meaning. Fields may be
+      added without raising it, so readers should ignore those they don't know.
+    - `differences`: an array of the differences, ordered by path, each an object with the fields:
+      - `path`: the path of the differing entry relative to the roots, with `/` separating its
+        components on every platform. Names that aren't valid Unicode are written with
+        replacement characters.
+      - `kind`: one of `added`, `removed`, `modified`, `type-changed`, `volatile`, or
+        `attributes-changed`. See [`DifferenceKind`](crate::DifferenceKind).
+      - `sizes`: an array of the entry's sizes in the first and second trees, each a number or
+        `null` where it's unknown. See [`Difference::sizes`].
+      - `unified_diff`: a unified diff of the entry, as a string, or `null`. See
+        [`Difference::unified_diff`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", self.to_json())
+    }
+
+    /**
+    Write the report as newline-delimited JSON, with one line per difference, so that readers can
+    process huge reports a line at a time, without parsing them whole.
+
+    The first line is an object holding only the `version`, and each line after it is a difference,
+    as in the `differences` array of [`DiffReport::write_json`].
+
+    ```
+    use dir_compare::Content;
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let mut ndjson = Vec::new();
+    a.diff(&b).write_ndjson(&mut ndjson)?;
+
+    assert_eq!(String::from_utf8(ndjson)?.lines().count(), 2);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn write_ndjson(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, r#"{{"version":{VERSION}}}"#)?;
+        let mut line = String::new();
+        for difference in self {
+            line.clear();
+            push_difference(&mut line, difference);
+            line.push('\n');
+            writer.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Append the difference to the JSON, as an object.
+fn push_difference(json: &mut String, difference: &Difference) {
+    let path = difference
+        .path()
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let (a, b) = difference.sizes();
+
+    json.push_str(r#"{"path":"#);
+    push_string(json, &path);
+    json.push_str(r#","kind":"#);
+    push_string(json, difference.kind().name());
+    json.push_str(r#","sizes":["#);
+    push_size(json, a);
+    json.push(',');
+    push_size(json, b);
+    json.push_str(r#"],"unified_diff":"#);
+    match difference.unified_diff() {
+        Some(diff) => push_string(json, diff),
+        None => json.push_str("null"),
+    }
+    json.push('}');
+}
+
+fn push_size(json: &mut String, size: Option<u64>) {
+    match size {
+        Some(size) => {
+            let _ = write!(json, "{size}");
+        }
+        None => json.push_str("null"),
+    }
+}
+
+/// Append the text to the JSON, as a string, escaping what JSON requires.
+pub(crate) fn push_string(json: &mut String, text: &str) {
+    json.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => json.push_str(r#"\""#),
+            '\\' => json.push_str(r"\\"),
+            '\n' => json.push_str(r"\n"),
+            '\r' => json.push_str(r"\r"),
+            '\t' => json.push_str(r"\t"),
+            control if control < ' ' => {
+                let _ = write!(json, r"\u{:04x}", u32::from(control));
+            }
+            character => json.push(character),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{DiffReport, Difference, DifferenceKind};
+
+    #[test]
+    fn reports_should_be_written_as_json() {
+        let report = DiffReport::new(vec![
+            Difference::new(PathBuf::from("dir"), DifferenceKind::Added),
+            Difference::new(
+                PathBuf::from("dir/\"quoted\"\tname\u{1}"),
+                DifferenceKind::Added,
+            )
+            .with_sizes((None, Some(7))),
+            Difference::new(PathBuf::from("gone.txt"), DifferenceKind::Removed)
+                .with_sizes((Some(0), None)),
+        ]);
+
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            concat!(
+                r#"{"version":1,"differences":["#,
+                r#"{"path":"dir","kind":"added","sizes":[null,null],"unified_diff":null},"#,
+                r#"{"path":"dir/\"quoted\"\tname\u0001","kind":"added","sizes":[null,7],"#,
+                r#""unified_diff":null},"#,
+                r#"{"path":"gone.txt","kind":"removed","sizes":[0,null],"unified_diff":null}"#,
+                "]}\n",
+            )
+        );
+
+        let mut ndjson = Vec::new();
+        report.write_ndjson(&mut ndjson).unwrap();
+        let ndjson = String::from_utf8(ndjson).unwrap();
+        let lines: Vec<_> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], r#"{"version":1}"#);
+        assert_eq!(
+            lines[3],
+            r#"{"path":"gone.txt","kind":"removed","sizes":[0,null],"unified_diff":null}"#
+        );
+    }
+
+    #[test]
+    fn empty_reports_should_be_written_as_json() {
+        assert_eq!(
+            DiffReport::default().to_json(),
+            r#"{"version":1,"differences":[]}"#
+        );
+    }
+}
@@ -1,9 +1,11 @@
 /*!
 A simple, dependency-free crate for comparing a pair of files or directories—or their contents.
 Construct an [`Entry`] or [`Content`] of a file/directory at a given path and compare it with
-another as you would an ordinary Rust data structure; they implement [`PartialEq`] & [`Eq`].
+another as you would an ordinary Rust data structure; they implement [`PartialEq`] & [`Eq`]. They
+also implement [`Hash`](std::hash::Hash), [`Ord`], and [`Clone`], so they can be collected into sets
+and maps, sorted, and deduplicated.
 
-This crate works recursively and compares both names and byte content.
+This crate compares both names and byte content, descending into directories however deep they go.
 
 ```
 use dir_compare::{Content, Entry};
@@ -28,15 +30,161 @@ assert_ne!(a, b);
 In exchange for the (implementation) simplicity, it loses speed. In particular, large directories
 may cause memory usage to balloon. dir-compare was designed for use in tests of applications that
 operate on the file system, so performance was not a priority.
+
+# Comparing
+
+When you need to know _how_ two trees differ, [`Content::diff`] lists the differing paths, and
+[`Entry::first_difference`] stops at the first, for fail-fast assertions. [`Content::similarity`]
+scores how alike two trees are, and [`Content::three_way`] compares two trees against a common
+ancestor.
+
+To compare two trees on disk without reading either whole, and to see what it cost, use a
+[`Comparison`]. Either tree can also live elsewhere, such as on another machine, behind a
+[`TreeSource`]. [`Comparison::shallow`] compares trees by structure and file sizes alone, and
+verifies the rest on demand. A [`Session`] compares several pairs of trees, such as those a
+host-integrity audit covers, as one unit.
+
+A [`Manifest`] describes the expected layout of a tree, with patterns and digests where exact files
+aren't known, and checks trees against it.
+
+# Reporting
+
+Reports can be written as JSON, with [`DiffReport::write_json`], for other tools to read. A
+[`History`] logs successive diffs to track drift over time, and [`DiffReport::progress_since`]
+summarizes what changed between two of them. [`Content::recheck_against_disk`] quickly rechecks
+an earlier comparison, and a [`Cache`] of file digests speeds up comparing the same trees over and
+over.
+
+Entries can be displayed as trees, to see what was read, looked up by path with [`Content::get`],
+and walked with [`Content::walk`].
+
+# Syncing
+
+A [`Plan`], such as one made from a [`DiffReport`], is applied to a tree on disk with
+[`Plan::apply`]. [`Plan::sync`] makes one tree on disk match another, and
+[`ThreeWayReport::sync_plan`] plans the synchronization of two trees that share an ancestor.
+
+# Options
+
+How trees are read, such as whether hidden entries, or those a `.gitignore` file excludes, are
+skipped, or which [`filters`] decode files before they're compared, is configured with [`Options`],
+as is what to do with special files such as FIFOs, sockets, and device nodes.
+
+# Features
+
+With the `mmap` feature, files on disk are compared through memory maps, on Unix, instead of being
+read into buffers. The kernel can then serve both sides from the page cache, which lowers peak memory
+when comparing large files. Mapped files must not be truncated during the comparison.
+
+With the `shared` feature, trees read through the same `Blobs` pool share the bytes of their
+identical files as `SharedContent`, so that many snapshots of similar trees can be held in memory,
+such as to compare one reference tree against dozens of candidates.
+
+With the `watch` feature, a `Watch` compares two trees on disk continuously, reporting the paths
+that drift apart or converge as the trees change.
+
+With the `trash` feature, the entries a [`Plan`] overwrites or removes can be moved into the trash
+instead of being lost, with `ApplyOptions::move_to_trash`.
+
+With the `tracing` feature, a `Tracer` set with `Options::tracer` receives an event for each
+directory listed, each pair of files compared, and each entry decided or failed on, with how long it
+took, to find out where a slow or failing comparison spends its time.
 */
 
+mod apply;
+mod attributes;
+mod cache;
+mod canonical;
+mod comparison;
+mod consistency;
+mod diff;
+mod display;
+mod documents;
+mod executables;
+pub mod filters;
+mod first_difference;
+mod glob;
+mod history;
+mod ignore;
+mod inflate;
+mod journal;
+mod json;
+mod manifest;
+mod merge;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod mounts;
+mod options;
+mod profile;
+mod progress;
+mod read;
+mod recheck;
+mod remote;
+mod session;
+mod sha256;
+mod shallow;
+#[cfg(feature = "shared")]
+mod shared;
+mod similarity;
+mod source;
+mod special;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod sync;
+#[cfg(test)]
+mod testing;
+mod trace;
+mod traits;
+#[cfg(feature = "trash")]
+mod trash;
+mod unicode;
+mod unified;
+mod walk;
+#[cfg(feature = "watch")]
+mod watch;
+mod zip;
+mod zstd;
+
 use core::fmt;
 use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
     fmt::Display,
-    fs, io,
+    io,
     path::{Path, PathBuf},
 };
 
+pub use apply::{ApplyHooks, ApplyOptions, ApplyReport, Backup};
+pub use cache::Cache;
+pub use comparison::Comparison;
+pub use consistency::Consistency;
+pub use diff::{DiffReport, DiffSummary, Difference, DifferenceKind};
+pub use filters::Filter;
+pub use history::{History, Run};
+pub use manifest::Manifest;
+pub use merge::{Change, ChangeKind, ThreeWayReport};
+pub use mounts::VirtualFileSystems;
+pub use options::Options;
+pub use profile::TreeProfile;
+pub use progress::Progress;
+pub use remote::TreeSource;
+pub use session::{Session, SessionReport};
+pub use shallow::ShallowComparison;
+#[cfg(feature = "shared")]
+pub use shared::{Blobs, SharedContent, SharedEntry};
+pub use similarity::{FileSimilarity, Similarity};
+pub use source::EntryKind;
+pub use special::{SpecialFiles, SpecialKind};
+pub use stats::Stats;
+pub use sync::{BidirectionalPlan, Operation, Plan};
+#[cfg(feature = "tracing")]
+pub use trace::{Evidence, TraceEvent, Tracer};
+pub use unicode::NormalForm;
+pub use walk::Walk;
+#[cfg(feature = "watch")]
+pub use watch::{Watch, WatchEvent};
+
 /**
 Represents an entry in a directory. May be either a file or a directory.
 
@@ -44,10 +192,16 @@ The comparison of two entries will return `false` if the two entries have differ
 only care about the contents of the entries, use [`Content`] instead. This applies only to the
 entries at the given paths; the top-level entries, if you will. The names of the children, if any,
 will factor into the comparison.
+
+Names are compared as the operating system returns them, so names that aren't valid Unicode are
+compared by their raw bytes, rather than as lossily converted text. Entries read with
+[`Options::normalize_names`] or [`Options::case_insensitive`] are compared by their normalized or
+lowercased names instead, but keep the names the operating system returned.
 */
-#[derive(Debug, PartialEq, Eq)]
 pub struct Entry {
-    name: String,
+    name: OsString,
+    /// The name the entry is compared by, if options made it differ from its name.
+    key: Option<OsString>,
     content: Content,
 }
 
@@ -94,33 +248,67 @@ impl Entry {
     - Will bubble I/O errors.
     */
     pub fn at(path: impl AsRef<Path>) -> Result<Self, EntryError> {
+        Self::at_with(path, &Options::default())
+    }
+
+    /**
+    Read the entry at a given path, as configured by the given [`Options`].
+
+    # Errors
+
+    - Will return an error if the given paths points to a `..` directory.
+    - Will bubble I/O errors.
+    */
+    pub fn at_with(path: impl AsRef<Path>, options: &Options) -> Result<Self, EntryError> {
         let path = path.as_ref();
+        let name = path
+            .file_name()
+            .ok_or_else(|| EntryError::InvalidPath(PathBuf::from(path)))?;
         let entry = Self {
-            name: path
-                .file_name()
-                .ok_or_else(|| EntryError::InvalidPath(PathBuf::from(path)))?
-                .to_string_lossy()
-                .into_owned(),
-            content: Content::of(path)?,
+            name: name.to_owned(),
+            key: options.distinct_key(name),
+            content: Content::of_with(path, options)?,
         };
 
         Ok(entry)
     }
+
+    /// The entry's name, as the operating system returned it.
+    #[must_use]
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /**
+    The entry's name, converted to text for display. Invalid Unicode is replaced with `U+FFFD`, so
+    different names may convert to the same text; compare [`Entry::name`]s instead.
+    */
+    #[must_use]
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        self.name.to_string_lossy()
+    }
+
+    /// The name the entry is compared and ordered by.
+    pub(crate) fn key(&self) -> &OsStr {
+        self.key.as_deref().unwrap_or(&self.name)
+    }
 }
 
 /**
 The content of an entry. For a file, this is its byte content. For a directory, it is the content
-of its children.
+of its children, ordered by the names they're compared by.
 
 The comparison of the contents will return `true` even if the two files/directories have different
 names. This applies only for the top-level entries. The names of their children matter.
 */
-#[derive(Debug, PartialEq, Eq)]
 pub enum Content {
     /// The byte content of the entry.
     File(Vec<u8>),
     /// The content of the entries in the directory.
     Entries(Vec<Entry>),
+    /// A special file, such as a FIFO or a device node, which is never read. Only read when
+    /// enabled with [`Options::special_files`].
+    Special(SpecialKind),
 }
 
 impl Content {
@@ -132,27 +320,19 @@ impl Content {
     Will bubble I/O errors.
     */
     pub fn of(path: impl AsRef<Path>) -> io::Result<Self> {
-        if path.as_ref().is_file() {
-            let data = fs::read(path)?;
-            Ok(Self::File(data))
-        } else {
-            let entries = fs::read_dir(path)?
-                .map(|entry| {
-                    Entry::at(entry?.path()).map_err(|e| match e {
-                        EntryError::IoError(e) => e,
-                        EntryError::InvalidPath(path) => {
-                            panic!(
-                                "`Content::of` returned `EntryError::InvalidPath`, which \
-                                    shouldn't happen. `std::fs::read_dir` should skip `..`. \
-                                    Path: {path:?}."
-                            );
-                        }
-                    })
-                })
-                .collect::<io::Result<_>>()?;
-
-            Ok(Self::Entries(entries))
-        }
+        Self::of_with(path, &Options::default())
+    }
+
+    /**
+    Read the contents of the file or directory at the given path, as configured by the given
+    [`Options`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn of_with(path: impl AsRef<Path>, options: &Options) -> io::Result<Self> {
+        read::read(path.as_ref(), options)
     }
 }
 
@@ -191,4 +371,27 @@ mod tests {
 
         assert_ne!(a, b);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_names_should_not_collapse() {
+        use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+        use crate::{testing::TempDir, Comparison, Options};
+
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir(&a).unwrap();
+        fs::create_dir(&b).unwrap();
+        fs::write(a.join(OsStr::from_bytes(b"\xff.txt")), "data").unwrap();
+        fs::write(b.join(OsStr::from_bytes(b"\xfe.txt")), "data").unwrap();
+
+        let file = Entry::at(a.join(OsStr::from_bytes(b"\xff.txt"))).unwrap();
+        assert_eq!(file.name().as_bytes(), b"\xff.txt");
+        assert_eq!(file.name_lossy(), "\u{fffd}.txt");
+
+        assert_ne!(Content::of(&a).unwrap(), Content::of(&b).unwrap());
+        let comparison = Comparison::between(&a, &b, &Options::new()).unwrap();
+        assert_eq!(comparison.report().differences().len(), 2);
+    }
 }
@@ -0,0 +1,333 @@
+/*!
+Expected layouts of trees, written as manifests of rules, for validating installs and build outputs
+whose files aren't all known byte for byte.
+*/
+
+use std::{fs, io, path::Path};
+
+use crate::{
+    glob::Glob,
+    sha256::{Digest, Sha256},
+    Content, DiffReport, Difference, DifferenceKind, Options,
+};
+
+/**
+An expected layout of a tree: a list of rules, each naming a path or a glob pattern of paths and
+what's expected of the entries there. Checked against a tree with [`Manifest::check`], which
+reports each rule that isn't met as a [`Difference`], as if the manifest were the first tree.
+
+```
+use dir_compare::Manifest;
+
+let manifest = Manifest::parse(
+    "# The layout of dir-a.
+     test.txt: sha256=0c15e883dee85bb2f3540a47ec58f617a2547117f9096417ba5422268029f501
+     subdir: dir
+     subdir/*.txt: sha256=200fce4e21fd021a809cbcbd0978640a3f92cbdc40034b2623daf0e23a707b0f
+     **/*.log: absent",
+)?;
+
+assert!(manifest.check("fixtures/equivalent/dir-a")?.is_empty());
+assert!(!manifest.check("fixtures/not-equivalent/dir-b")?.is_empty());
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
+# Format
+
+Each line is a rule of the form `<pattern>: <expectation>`. Blank lines and lines starting with `#`
+are ignored, as is whitespace around each line. Patterns are globs, as in
+[`Options::filter`], matched against whole paths relative to the root. The expectations are:
+
+- `exists`: the pattern matches at least one entry, of any type.
+- `file`: it matches at least one entry, and every entry it matches is a regular file.
+- `dir`: it matches at least one entry, and every entry it matches is a directory.
+- `sha256=<hex digest>`: it matches at least one entry, and every entry it matches is a file whose
+  bytes have the given SHA-256 digest.
+- `absent`: it matches no entries.
+
+An entry that several rules match has to meet all of them.
+
+# Differences
+
+- A pattern that matches nothing, but should, is reported as [`DifferenceKind::Removed`], at the
+  pattern's path. For a glob, that's the pattern itself.
+- An entry of the wrong type is reported as [`DifferenceKind::TypeChanged`].
+- A file with the wrong digest is reported as [`DifferenceKind::Modified`].
+- An entry that's meant to be absent, or, with [`Manifest::exhaustive`], one that no rule matches,
+  is reported as [`DifferenceKind::Added`].
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    rules: Vec<Rule>,
+    exhaustive: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: String,
+    glob: Glob,
+    expectation: Expectation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expectation {
+    Exists,
+    File,
+    Directory,
+    Sha256(Digest),
+    Absent,
+}
+
+impl Manifest {
+    /**
+    Parse a manifest. See [`Manifest`] for the format.
+
+    # Errors
+
+    Will return an error of kind [`io::ErrorKind::InvalidData`] if a line is malformed.
+    */
+    pub fn parse(manifest: &str) -> io::Result<Self> {
+        let mut rules = Vec::new();
+        for (number, line) in manifest.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = |reason: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} of the manifest {reason}", number + 1),
+                )
+            };
+            // Expectations never contain the separator, though paths might.
+            let (pattern, expectation) = line
+                .rsplit_once(": ")
+                .ok_or_else(|| malformed("has no `: ` between its pattern and expectation"))?;
+            let pattern = pattern.trim().trim_matches('/');
+            if pattern.is_empty() {
+                return Err(malformed("has no pattern"));
+            }
+            let expectation = match expectation.trim() {
+                "exists" => Expectation::Exists,
+                "file" => Expectation::File,
+                "dir" => Expectation::Directory,
+                "absent" => Expectation::Absent,
+                expectation => expectation
+                    .strip_prefix("sha256=")
+                    .and_then(parse_digest)
+                    .map(Expectation::Sha256)
+                    .ok_or_else(|| malformed("has an unknown expectation"))?,
+            };
+
+            rules.push(Rule {
+                pattern: pattern.to_owned(),
+                // A leading `/` anchors patterns without one to the root.
+                glob: Glob::new(&format!("/{pattern}")),
+                expectation,
+            });
+        }
+
+        Ok(Self {
+            rules,
+            exhaustive: false,
+        })
+    }
+
+    /**
+    Read and parse the manifest at the given path. See [`Manifest::parse`].
+
+    # Errors
+
+    - Will return an error of kind [`io::ErrorKind::InvalidData`] if the manifest is malformed, or
+      isn't valid UTF-8.
+    - Will bubble I/O errors.
+    */
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /**
+    Report the entries that no rule matches as added, so that the manifest lists everything the
+    tree may hold. Directories that hold entries a rule matches needn't be matched themselves.
+    */
+    #[must_use]
+    pub fn exhaustive(mut self, enabled: bool) -> Self {
+        self.exhaustive = enabled;
+        self
+    }
+
+    /**
+    Check the file or directory at the given path against the manifest, reading it as
+    [`Content::of`] does.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn check(&self, path: impl AsRef<Path>) -> io::Result<DiffReport> {
+        self.check_with(path, &Options::new())
+    }
+
+    /**
+    Check the file or directory at the given path against the manifest, reading it as configured
+    by the given [`Options`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn check_with(&self, path: impl AsRef<Path>, options: &Options) -> io::Result<DiffReport> {
+        Ok(self.check_content(&Content::of_with(path, options)?))
+    }
+
+    /// Check a tree in memory against the manifest.
+    #[must_use]
+    pub fn check_content(&self, content: &Content) -> DiffReport {
+        let mut matched = vec![false; self.rules.len()];
+        let mut differences = Vec::new();
+
+        for (path, entry) in content.walk().skip(1) {
+            let mut kind = None;
+            let mut covered = false;
+            for (rule, matched) in self.rules.iter().zip(&mut matched) {
+                if !rule.glob.matches(&path) {
+                    continue;
+                }
+                *matched = true;
+                covered = true;
+                kind = kind.or(match (rule.expectation, entry) {
+                    (Expectation::Absent, _) => Some(DifferenceKind::Added),
+                    (Expectation::File | Expectation::Sha256(_), Content::File(_))
+                    | (Expectation::Directory, Content::Entries(_))
+                    | (Expectation::Exists, _) => None,
+                    (Expectation::File | Expectation::Directory | Expectation::Sha256(_), _) => {
+                        Some(DifferenceKind::TypeChanged)
+                    }
+                });
+                if let (Expectation::Sha256(digest), Content::File(bytes)) =
+                    (rule.expectation, entry)
+                {
+                    let mut hasher = Sha256::new();
+                    hasher.update(bytes);
+                    if hasher.finish() != digest {
+                        kind = kind.or(Some(DifferenceKind::Modified));
+                    }
+                }
+            }
+
+            if !covered && self.exhaustive && !self.covers_descendants(content, &path) {
+                kind = Some(DifferenceKind::Added);
+            }
+            if let Some(kind) = kind {
+                let size = match entry {
+                    Content::File(bytes) => Some(bytes.len() as u64),
+                    _ => None,
+                };
+                differences.push(Difference::new(path, kind).with_sizes((None, size)));
+            }
+        }
+
+        for (rule, _) in self
+            .rules
+            .iter()
+            .zip(matched)
+            .filter(|(rule, matched)| !matched && rule.expectation != Expectation::Absent)
+        {
+            differences.push(Difference::new(
+                rule.pattern.clone().into(),
+                DifferenceKind::Removed,
+            ));
+        }
+        differences.sort_by(|a, b| a.path().cmp(b.path()));
+
+        DiffReport::new(differences)
+    }
+
+    /// Whether a rule matches any descendant of the entry at the given path.
+    fn covers_descendants(&self, content: &Content, path: &Path) -> bool {
+        content.get(path).is_some_and(|entry| {
+            entry.walk().skip(1).any(|(descendant, _)| {
+                let descendant = path.join(descendant);
+                self.rules.iter().any(|rule| {
+                    rule.expectation != Expectation::Absent && rule.glob.matches(&descendant)
+                })
+            })
+        })
+    }
+}
+
+/// The digest written in hexadecimal, if it's well-formed.
+fn parse_digest(hex: &str) -> Option<Digest> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut digest = [0; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, path::Path};
+
+    use crate::{Content, DifferenceKind, Manifest};
+
+    #[test]
+    fn trees_should_be_checked_against_manifests() {
+        let manifest = Manifest::parse(
+            "test.txt: sha256=0C15E883DEE85BB2F3540A47EC58F617A2547117F9096417BA5422268029F501
+             subdir/: file
+             subdir/*.md: exists
+             **/test2.txt: absent
+             bin/app: exists",
+        )
+        .unwrap();
+        let content = Content::of("fixtures/not-equivalent/dir-b").unwrap();
+
+        let report = manifest.check_content(&content);
+        let differences: Vec<_> = report
+            .iter()
+            .map(|difference| (difference.path(), difference.kind()))
+            .collect();
+        assert_eq!(
+            differences,
+            [
+                (Path::new("bin/app"), DifferenceKind::Removed),
+                (Path::new("subdir"), DifferenceKind::TypeChanged),
+                (Path::new("subdir/*.md"), DifferenceKind::Removed),
+                (Path::new("subdir/test2.txt"), DifferenceKind::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn exhaustive_manifests_should_report_unexpected_entries() {
+        let content = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let manifest = Manifest::parse("subdir/*.txt: file").unwrap();
+        assert!(manifest.check_content(&content).is_empty());
+
+        let report = manifest.exhaustive(true).check_content(&content);
+        let paths: Vec<_> = report.iter().map(|difference| difference.path()).collect();
+        assert_eq!(paths, [Path::new("test.txt")]);
+        assert_eq!(report.differences()[0].sizes(), (None, Some(10)));
+    }
+
+    #[test]
+    fn malformed_manifests_should_be_rejected() {
+        for manifest in [
+            "test.txt",
+            "test.txt: sha256=0c15",
+            "test.txt: executable",
+            ": file",
+        ] {
+            let error = Manifest::parse(manifest).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().starts_with("line 1 of the manifest"));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+/*!
+Three-way comparison of two trees against their common ancestor, as needed to merge or
+synchronize them.
+*/
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    diff::{flatten, Node},
+    Content, DifferenceKind,
+};
+
+/**
+A path that changed on at least one side of a three-way comparison.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    path: PathBuf,
+    kind: ChangeKind,
+}
+
+impl Change {
+    /// The path of the changed entry, relative to the roots of the compared trees.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Which side changed the entry and how.
+    #[must_use]
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+}
+
+/**
+How an entry changed relative to the base of a three-way comparison.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// Only "ours" changed the entry.
+    Ours(DifferenceKind),
+    /// Only "theirs" changed the entry.
+    Theirs(DifferenceKind),
+    /// Both sides made the same change.
+    Both(DifferenceKind),
+    /// Both sides changed the entry, but differently.
+    Conflict,
+}
+
+/**
+The changes found by a three-way comparison, ordered by path. Paths left untouched by both sides
+are omitted.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreeWayReport {
+    changes: Vec<Change>,
+}
+
+impl ThreeWayReport {
+    /// The changes, ordered by path.
+    #[must_use]
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Iterate over the changes, ordered by path.
+    pub fn iter(&self) -> std::slice::Iter<'_, Change> {
+        self.changes.iter()
+    }
+
+    /// Iterate over the paths both sides changed differently.
+    pub fn conflicts(&self) -> impl Iterator<Item = &Path> {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == ChangeKind::Conflict)
+            .map(Change::path)
+    }
+
+    /// Whether any path was changed differently by both sides.
+    #[must_use]
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+}
+
+impl IntoIterator for ThreeWayReport {
+    type Item = Change;
+    type IntoIter = std::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ThreeWayReport {
+    type Item = &'a Change;
+    type IntoIter = std::slice::Iter<'a, Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.iter()
+    }
+}
+
+impl Content {
+    /**
+    Compare two descendants of this tree, "ours" and "theirs", against it and each other.
+
+    Every path that either side changed is classified by who changed it. Changing a path in the
+    same way on both sides isn't a conflict.
+
+    ```
+    use dir_compare::{ChangeKind, Content, DifferenceKind};
+
+    let base = Content::of("fixtures/three-way/base")?;
+    let ours = Content::of("fixtures/three-way/ours")?;
+    let theirs = Content::of("fixtures/three-way/theirs")?;
+    let report = base.three_way(&ours, &theirs);
+
+    assert!(report.has_conflicts());
+    assert_eq!(
+        report.changes()[0].kind(),
+        ChangeKind::Ours(DifferenceKind::Modified)
+    );
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn three_way(&self, ours: &Self, theirs: &Self) -> ThreeWayReport {
+        let base = flatten(self);
+        let ours = flatten(ours);
+        let theirs = flatten(theirs);
+
+        let mut paths: Vec<&PathBuf> = base
+            .keys()
+            .chain(ours.keys())
+            .chain(theirs.keys())
+            .collect();
+        paths.sort();
+        paths.dedup();
+
+        let changes = paths
+            .into_iter()
+            .filter_map(|path| {
+                let kind = classify(base.get(path), ours.get(path), theirs.get(path))?;
+                Some(Change {
+                    path: path.clone(),
+                    kind,
+                })
+            })
+            .collect();
+
+        ThreeWayReport { changes }
+    }
+}
+
+/// Classify the change of a single path, if there is one.
+pub(crate) fn classify(
+    base: Option<&Node>,
+    ours: Option<&Node>,
+    theirs: Option<&Node>,
+) -> Option<ChangeKind> {
+    let ours_change = Node::change(base, ours);
+    let theirs_change = Node::change(base, theirs);
+
+    match (ours_change, theirs_change) {
+        (None, None) => None,
+        (Some(kind), None) => Some(ChangeKind::Ours(kind)),
+        (None, Some(kind)) => Some(ChangeKind::Theirs(kind)),
+        (Some(kind), Some(_)) if ours == theirs => Some(ChangeKind::Both(kind)),
+        (Some(_), Some(_)) => Some(ChangeKind::Conflict),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{ChangeKind, Content, DifferenceKind};
+
+    #[test]
+    fn changes_should_be_attributed_to_their_side() {
+        let base = Content::of("fixtures/three-way/base").unwrap();
+        let ours = Content::of("fixtures/three-way/ours").unwrap();
+        let theirs = Content::of("fixtures/three-way/theirs").unwrap();
+        let report = base.three_way(&ours, &theirs);
+
+        let changes: Vec<_> = report.iter().map(|c| (c.path(), c.kind())).collect();
+        assert_eq!(
+            changes,
+            [
+                (
+                    Path::new("a.txt"),
+                    ChangeKind::Ours(DifferenceKind::Modified)
+                ),
+                (
+                    Path::new("b.txt"),
+                    ChangeKind::Theirs(DifferenceKind::Modified)
+                ),
+                (Path::new("c.txt"), ChangeKind::Conflict),
+                (
+                    Path::new("d.txt"),
+                    ChangeKind::Both(DifferenceKind::Modified)
+                ),
+                (
+                    Path::new("e.txt"),
+                    ChangeKind::Theirs(DifferenceKind::Added)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_trees_should_not_change() {
+        let base = Content::of("fixtures/three-way/base").unwrap();
+
+        assert!(base.three_way(&base, &base).changes().is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+/*!
+Read-only memory maps of files, so that files on disk can be compared in place, through the page
+cache, rather than through buffers of the crate's own.
+
+Only Unix is supported. Elsewhere, files are never mapped, and are read as without the `mmap`
+feature.
+*/
+
+use std::{fs::File, io, ops::Deref, path::Path};
+
+/// The bytes of a file, either borrowed from a tree in memory or mapped from disk.
+pub(crate) enum Bytes<'a> {
+    Borrowed(&'a [u8]),
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Mapped(Map),
+}
+
+impl Deref for Bytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(bytes) => bytes,
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+/**
+A private, read-only map of a whole file.
+
+If the file is truncated while it's mapped, reading past its new end raises `SIGBUS`. Comparisons
+that map files should only be run on trees that aren't being written to.
+*/
+pub(crate) struct Map {
+    pointer: *const u8,
+    length: usize,
+}
+
+// The map is never written to, so it can be shared and sent like a `&[u8]`.
+unsafe impl Send for Map {}
+unsafe impl Sync for Map {}
+
+impl Deref for Map {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: The pointer refers to `length` readable bytes until the map is dropped.
+        unsafe { std::slice::from_raw_parts(self.pointer, self.length) }
+    }
+}
+
+/// Map the file at the given path, unless mapping isn't supported.
+#[cfg(unix)]
+pub(crate) fn map(path: &Path) -> io::Result<Option<Bytes<'static>>> {
+    use std::os::{
+        fd::AsRawFd,
+        raw::{c_int, c_void},
+    };
+
+    #[cfg(target_pointer_width = "64")]
+    type Offset = i64;
+    #[cfg(not(target_pointer_width = "64"))]
+    type Offset = i32;
+
+    extern "C" {
+        fn mmap(
+            address: *mut c_void,
+            length: usize,
+            protection: c_int,
+            flags: c_int,
+            descriptor: c_int,
+            offset: Offset,
+        ) -> *mut c_void;
+    }
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+    const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    let file = File::open(path)?;
+    let length = usize::try_from(file.metadata()?.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "the file is too large to map"))?;
+    // Empty maps are invalid.
+    if length == 0 {
+        return Ok(Some(Bytes::Borrowed(&[])));
+    }
+
+    // SAFETY: A new private, read-only map is requested, and the result is checked for failure.
+    let pointer = unsafe {
+        mmap(
+            std::ptr::null_mut(),
+            length,
+            PROT_READ,
+            MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if pointer == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    // The map outlives the file's descriptor.
+    Ok(Some(Bytes::Mapped(Map {
+        pointer: pointer.cast(),
+        length,
+    })))
+}
+
+/// Map the file at the given path, unless mapping isn't supported.
+#[cfg(not(unix))]
+pub(crate) fn map(_path: &Path) -> io::Result<Option<Bytes<'static>>> {
+    Ok(None)
+}
+
+#[cfg(unix)]
+impl Drop for Map {
+    fn drop(&mut self) {
+        use std::os::raw::{c_int, c_void};
+
+        extern "C" {
+            fn munmap(address: *mut c_void, length: usize) -> c_int;
+        }
+
+        // SAFETY: The map was created by `mmap` with this length, and is no longer borrowed.
+        unsafe {
+            munmap(self.pointer as *mut c_void, self.length);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map;
+
+    #[test]
+    fn mapped_files_should_hold_their_bytes() {
+        let expected = std::fs::read("fixtures/equivalent/dir-a/test.txt").unwrap();
+        let mapped = map("fixtures/equivalent/dir-a/test.txt".as_ref()).unwrap();
+
+        if let Some(mapped) = mapped {
+            assert_eq!(*mapped, *expected);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+/*!
+Virtual file systems, such as procfs and sysfs, whose pseudo-files are generated on demand and can
+be endless or change on every read, so whole-system comparisons shouldn't read them.
+*/
+
+use std::{collections::HashSet, io, path::Path};
+
+use crate::source::device;
+
+/// The types of the virtual file systems, as the kernel names them.
+#[cfg(target_os = "linux")]
+const VIRTUAL_TYPES: &[&str] = &[
+    "proc",
+    "sysfs",
+    "devtmpfs",
+    "devpts",
+    "debugfs",
+    "tracefs",
+    "securityfs",
+    "cgroup",
+    "cgroup2",
+    "pstore",
+    "bpf",
+    "configfs",
+    "fusectl",
+    "mqueue",
+    "efivarfs",
+    "binfmt_misc",
+];
+
+/**
+What to do with directories where virtual file systems, such as procfs, sysfs, and devtmpfs, are
+mounted. See [`Options::virtual_file_systems`](crate::Options::virtual_file_systems).
+
+They're only detected on Linux, from `/proc/self/mountinfo`.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VirtualFileSystems {
+    /// Read them as any other directory.
+    #[default]
+    Read,
+    /// Leave them out of the tree, as if they didn't exist.
+    Skip,
+    /// Read them as empty directories, which marks where they're mounted without reading them.
+    Empty,
+}
+
+/// The devices of the mounted virtual file systems, which every entry on them is on.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VirtualMounts {
+    devices: HashSet<u64>,
+}
+
+impl VirtualMounts {
+    /// The virtual file systems mounted now. None are known if the mount table can't be read.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn load() -> Self {
+        std::fs::read_to_string("/proc/self/mountinfo")
+            .map(|table| Self::parse(&table))
+            .unwrap_or_default()
+    }
+
+    /// The virtual file systems mounted now, which are only known on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn load() -> Self {
+        Self::default()
+    }
+
+    /**
+    The virtual file systems in the given mount table, in the format of `/proc/self/mountinfo`:
+    each line lists a mount's ID, its parent's ID, its device as `<major>:<minor>`, its root, its
+    mount point, and its options, then optional fields ending with a `-`, then its type.
+    */
+    #[cfg(target_os = "linux")]
+    fn parse(table: &str) -> Self {
+        let devices = table
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let device = fields.nth(2)?;
+                let kind = fields.skip_while(|&field| field != "-").nth(1)?;
+                VIRTUAL_TYPES.contains(&kind).then_some(device)?;
+
+                let (major, minor) = device.split_once(':')?;
+                Some(device_id(major.parse().ok()?, minor.parse().ok()?))
+            })
+            .collect();
+
+        Self { devices }
+    }
+
+    /// Whether the directory at the given path on disk is on a virtual file system.
+    pub(crate) fn contain(&self, path: &Path) -> io::Result<bool> {
+        if self.devices.is_empty() {
+            return Ok(false);
+        }
+
+        Ok(device(path)?.is_some_and(|device| self.devices.contains(&device)))
+    }
+}
+
+/// The ID of the device with the given major and minor numbers, as `stat` reports it on Linux.
+#[cfg(target_os = "linux")]
+fn device_id(major: u64, minor: u64) -> u64 {
+    (minor & 0xff) | ((major & 0xfff) << 8) | ((minor & !0xff) << 12) | ((major & !0xfff) << 32)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{fs, os::unix::fs::symlink, path::Path};
+
+    use super::VirtualMounts;
+    use crate::{testing::TempDir, Comparison, Content, Options, VirtualFileSystems};
+
+    #[test]
+    fn virtual_file_systems_should_be_found_in_the_mount_table() {
+        let mounts = VirtualMounts::parse(concat!(
+            "23 28 0:22 / /proc rw,relatime - proc proc rw\n",
+            "26 25 0:24 / /dev/shm rw,relatime - tmpfs tmpfs rw,size=6158152k\n",
+            "31 24 0:28 / /sys/fs/cgroup rw,nosuid shared:9 - cgroup2 cgroup2 rw\n",
+            "malformed\n",
+        ));
+
+        assert_eq!(mounts.devices.len(), 2);
+        assert!(mounts.devices.contains(&22));
+        assert!(mounts.devices.contains(&28));
+        assert!(VirtualMounts::load().contain(Path::new("/proc")).unwrap());
+    }
+
+    #[test]
+    fn virtual_file_systems_should_be_skipped_or_emptied_when_enabled() {
+        // The link leads to the proc file system.
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir_all(a.join("data")).unwrap();
+        fs::create_dir_all(b.join("data")).unwrap();
+        symlink("/proc", a.join("proc")).unwrap();
+
+        let options = Options::new().virtual_file_systems(VirtualFileSystems::Skip);
+        assert_eq!(
+            Content::of_with(&a, &options).unwrap(),
+            Content::of(&b).unwrap()
+        );
+        assert!(Comparison::between(&a, &b, &options)
+            .unwrap()
+            .report()
+            .is_empty());
+
+        fs::create_dir(b.join("proc")).unwrap();
+        let options = Options::new().virtual_file_systems(VirtualFileSystems::Empty);
+        assert_eq!(
+            Content::of_with(&a, &options).unwrap(),
+            Content::of(&b).unwrap()
+        );
+        assert!(Comparison::between(&a, &b, &options)
+            .unwrap()
+            .report()
+            .is_empty());
+    }
+}
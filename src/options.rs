@@ -0,0 +1,937 @@
+/*!
+Configuration of how trees are read and compared.
+*/
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "tracing")]
+use crate::Tracer;
+use crate::{
+    filters::Transform,
+    glob::Glob,
+    ignore::Ignore,
+    trace::TraceEvent,
+    unicode::{normalize, NormalForm},
+    Consistency, Filter, SpecialFiles, VirtualFileSystems,
+};
+
+/**
+Options for reading and comparing trees, built up one setting at a time. The defaults match the
+behaviour of [`Entry::at`](crate::Entry::at) and [`Content::of`](crate::Content::of).
+
+```
+use dir_compare::{Content, Options};
+
+let options = Options::new().skip_hidden(true);
+let content = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Clone, Default)]
+pub struct Options {
+    skip_hidden: bool,
+    ignore: Ignore,
+    only_paths: Vec<PathBuf>,
+    filters: Vec<(Glob, Arc<dyn Filter>)>,
+    unified_diffs: bool,
+    compare_mtimes: bool,
+    mtime_tolerance: Duration,
+    mtime_precision: Option<Duration>,
+    empty_as_missing: bool,
+    special_files: SpecialFiles,
+    normal_form: Option<NormalForm>,
+    case_insensitive: bool,
+    consistency: Consistency,
+    one_file_system: bool,
+    virtual_file_systems: VirtualFileSystems,
+    compare_attributes: bool,
+    entry_times: Option<u64>,
+    profile_trees: bool,
+    #[cfg(feature = "tracing")]
+    tracer: Option<Arc<dyn Tracer>>,
+}
+
+impl Options {
+    /// Create the default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Skip hidden entries: those whose names start with a `.` on Unix, or that have the hidden
+    attribute on Windows. The entry at the given path itself is never skipped.
+    */
+    #[must_use]
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /**
+    Skip the entries that the given rules exclude, as the lines of a `.gitignore` file would. The
+    rules are added after any added before, and can be negated with `!` to re-include what those
+    excluded. The entry at the given path itself is never skipped.
+
+    Rules are matched against paths relative to the root of the tree, as filters' patterns are.
+    Each line is a pattern: blank lines and those starting with `#` are ignored, as are trailing
+    spaces unless escaped with `\`, a leading `!` negates the pattern, and a trailing `/` only
+    matches directories. A leading `\` escapes a literal `#` or `!`. The last rule to match an
+    entry decides whether it's skipped. The children of a skipped directory are never read, so
+    they can't be re-included.
+
+    ```
+    use dir_compare::{Content, Options};
+
+    let options = Options::new().ignore("target/\n*.log\n!important.log\n");
+    let content = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn ignore(mut self, rules: &str) -> Self {
+        self.ignore.add(rules);
+        self
+    }
+
+    /**
+    Read only the entries at the given paths, relative to the root, with their descendants and the
+    directories on the way to them, so that the rest of the tree isn't walked at all. Calling this
+    again adds to the paths. See [`Comparison::between_paths`](crate::Comparison::between_paths).
+
+    ```
+    use dir_compare::{Content, Options};
+
+    let options = Options::new().only_paths(["subdir/"]);
+    let content = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+
+    assert!(content.get("subdir/test2.txt").is_some());
+    assert!(content.get("test.txt").is_none());
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn only_paths(mut self, paths: impl IntoIterator<Item = impl AsRef<Path>>) -> Self {
+        self.only_paths
+            .extend(paths.into_iter().map(|path| path.as_ref().to_path_buf()));
+        self
+    }
+
+    /**
+    Skip the entries that the rules in the file at the given path, such as a `.gitignore` file,
+    exclude. See [`Options::ignore`].
+
+    Only the rules in the given file apply, to the whole tree; files of rules inside the tree aren't
+    read.
+
+    # Errors
+
+    Will bubble I/O errors from reading the file, including if it isn't valid UTF-8.
+    */
+    pub fn ignore_file(self, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(self.ignore(&fs::read_to_string(path)?))
+    }
+
+    /**
+    Decode the files matching a glob pattern with the given [`Filter`] before comparing them. See
+    the [`filters`](crate::filters) module.
+
+    Patterns are matched against paths relative to the root of the tree. A pattern without a `/`,
+    such as `*.gz`, is matched against the names of entries at any depth; one with a `/`, such as
+    `logs/**/*.gz`, against the whole path. `*` and `?` don't match `/`, but `**` does.
+    */
+    #[must_use]
+    pub fn filter(mut self, pattern: &str, filter: impl Filter + 'static) -> Self {
+        self.filters.push((Glob::new(pattern), Arc::new(filter)));
+        self
+    }
+
+    /**
+    Transform the bytes of the files matching a glob pattern with the given function before
+    comparing them, as if it were a [`Filter`] registered with [`Options::filter`]. The function
+    should return an error of kind [`io::ErrorKind::InvalidData`] if it can't transform a file.
+
+    ```
+    use dir_compare::{Content, Options};
+
+    // Compare text files regardless of line endings.
+    let options = Options::new().transform("*.txt", |bytes| {
+        Ok(bytes.into_iter().filter(|&byte| byte != b'\r').collect())
+    });
+    let a = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+    let b = Content::of_with("fixtures/equivalent/dir-b", &options)?;
+
+    assert_eq!(a, b);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn transform(
+        self,
+        pattern: &str,
+        function: impl Fn(Vec<u8>) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+    ) -> Self {
+        self.filter(pattern, Transform::new(function))
+    }
+
+    /**
+    Include a unified diff of each modified text file in the report. See
+    [`Difference::unified_diff`](crate::Difference::unified_diff).
+
+    Each diff requires reading both versions of the file into memory in full.
+    */
+    #[must_use]
+    pub fn unified_diffs(mut self, enabled: bool) -> Self {
+        self.unified_diffs = enabled;
+        self
+    }
+
+    /**
+    Report files whose modification times differ as modified, without reading them.
+
+    Files are always compared by size before they're read, and reported as modified without being
+    read if their sizes differ. With this enabled, their modification times are compared next, and
+    only the files whose sizes and modification times both match are read. This suits mirrors that
+    preserve modification times, where reading every byte on both sides would dominate the cost.
+    Trees in memory have no modification times, so this only applies to comparisons between trees
+    on disk.
+    */
+    #[must_use]
+    pub fn compare_mtimes(mut self, enabled: bool) -> Self {
+        self.compare_mtimes = enabled;
+        self
+    }
+
+    /**
+    Take modification times that differ by at most the given duration to match, when they're
+    compared, such as 2 seconds for trees copied to or from FAT file systems or zip archives, which
+    store times in steps of 2 seconds. See [`Options::compare_mtimes`].
+    */
+    #[must_use]
+    pub fn mtime_tolerance(mut self, tolerance: Duration) -> Self {
+        self.mtime_tolerance = tolerance;
+        self
+    }
+
+    /**
+    Truncate modification times to whole multiples of the given duration since the Unix epoch
+    before they're compared, such as 1 second for trees copied from file systems with nanosecond
+    timestamps to ones with whole seconds. See [`Options::compare_mtimes`]. A duration of zero
+    leaves them as they are.
+
+    ```
+    use std::time::Duration;
+
+    use dir_compare::{Comparison, Options};
+
+    let options = Options::new()
+        .compare_mtimes(true)
+        .mtime_precision(Duration::from_secs(1))
+        .mtime_tolerance(Duration::from_secs(2));
+    let comparison = Comparison::between(
+        "fixtures/equivalent/dir-a",
+        "fixtures/equivalent/dir-b",
+        &options,
+    )?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn mtime_precision(mut self, precision: Duration) -> Self {
+        self.mtime_precision = Some(precision).filter(|precision| !precision.is_zero());
+        self
+    }
+
+    /**
+    Treat an empty file as equivalent to a missing one, so that an empty file that exists in only
+    one tree, such as a placeholder some generators emit, isn't reported. Otherwise, it's reported
+    as added or removed, and [`Difference::is_empty_file`](crate::Difference::is_empty_file) tells
+    it apart.
+
+    ```
+    use dir_compare::{Content, Options};
+
+    let a = Content::of("fixtures/equivalent/dir-a")?;
+    let report = a.diff_against_disk_with(
+        "fixtures/equivalent/dir-b",
+        &Options::new().empty_as_missing(true),
+    )?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn empty_as_missing(mut self, enabled: bool) -> Self {
+        self.empty_as_missing = enabled;
+        self
+    }
+
+    /**
+    Choose what to do with special files, such as FIFOs, sockets, and device nodes, which can't be
+    read like regular files. By default, they're an error. See [`SpecialFiles`].
+
+    The entry at the given path itself is never skipped; under [`SpecialFiles::Skip`], a special
+    file there is compared by type.
+    */
+    #[must_use]
+    pub fn special_files(mut self, policy: SpecialFiles) -> Self {
+        self.special_files = policy;
+        self
+    }
+
+    /**
+    Normalize the names of entries to the given Unicode normal form before they're compared, so that
+    trees copied between file systems that store names in different forms, such as from macOS to
+    Linux, compare equal. Names that aren't valid Unicode are left as they are.
+
+    Only the comparison uses the normalized names: entries keep their names on disk, and reports
+    name entries as the first tree has them, or as the second does if the first lacks them.
+
+    ```
+    use dir_compare::{Content, NormalForm, Options};
+
+    let options = Options::new().normalize_names(NormalForm::Nfc);
+    let content = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn normalize_names(mut self, form: NormalForm) -> Self {
+        self.normal_form = Some(form);
+        self
+    }
+
+    /**
+    Don't descend into directories on other file systems than the one the given path is on, as
+    `rsync -x` and `find -xdev` don't, so that comparing `/` against a backup doesn't wander into
+    `/proc`, `/sys`, or network mounts. The directories where other file systems are mounted are
+    read as empty. Only supported on Unix, where file systems are told apart by their device IDs.
+    */
+    #[must_use]
+    pub fn one_file_system(mut self, enabled: bool) -> Self {
+        self.one_file_system = enabled;
+        self
+    }
+
+    /**
+    Choose what to do with directories where virtual file systems, such as procfs, sysfs, and
+    devtmpfs, are mounted, whose pseudo-files can be endless or change on every read. By default,
+    they're read as any other directory. See [`VirtualFileSystems`].
+
+    The entry at the given path itself is never skipped; under [`VirtualFileSystems::Skip`], it's
+    read as empty if it's on a virtual file system.
+
+    ```
+    use dir_compare::{Content, Options, VirtualFileSystems};
+
+    let options = Options::new().virtual_file_systems(VirtualFileSystems::Skip);
+    let content = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn virtual_file_systems(mut self, policy: VirtualFileSystems) -> Self {
+        self.virtual_file_systems = policy;
+        self
+    }
+
+    /**
+    Record how long each directory took to list, and each file that was read in at least the given
+    number of bytes, from both sides, took to compare, in the statistics of comparisons. See
+    [`Stats::entry_times`](crate::Stats::entry_times).
+
+    ```
+    use dir_compare::{Comparison, Options};
+
+    let options = Options::new().time_entries(1 << 20);
+    let comparison = Comparison::between(
+        "fixtures/not-equivalent/dir-a",
+        "fixtures/not-equivalent/dir-b",
+        &options,
+    )?;
+    let times = comparison.stats().entry_times();
+
+    // Only the directories are timed, since the files are small.
+    assert_eq!(times.len(), 2);
+    assert!(comparison.stats().subtree_time("subdir") <= comparison.stats().wall_time());
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn time_entries(mut self, large_files: u64) -> Self {
+        self.entry_times = Some(large_files);
+        self
+    }
+
+    /**
+    Send the events of comparisons, such as each directory listed and each pair of files compared,
+    with how long they took, to the given [`Tracer`]. See [`TraceEvent`].
+
+    Enabled by the `tracing` feature.
+    */
+    #[cfg(feature = "tracing")]
+    #[must_use]
+    pub fn tracer(mut self, tracer: impl Tracer + 'static) -> Self {
+        self.tracer = Some(Arc::new(tracer));
+        self
+    }
+
+    /**
+    Profile the trees of comparisons by the sizes and extensions of their files, in their
+    statistics. See [`Stats::profiles`](crate::Stats::profiles).
+
+    Each file's size is looked up even where it isn't needed to compare it, such as when it's in
+    only one tree.
+    */
+    #[must_use]
+    pub fn profile_trees(mut self, enabled: bool) -> Self {
+        self.profile_trees = enabled;
+        self
+    }
+
+    /**
+    Report files and directories whose read-only, hidden, or system attributes differ, or whose
+    alternate data streams differ in name or bytes, as
+    [`DifferenceKind::AttributesChanged`](crate::DifferenceKind::AttributesChanged), such as the
+    `Zone.Identifier` stream Windows adds to downloaded files. Only supported on Windows, and only
+    between trees on disk; elsewhere, this has no effect.
+    */
+    #[must_use]
+    pub fn compare_attributes(mut self, enabled: bool) -> Self {
+        self.compare_attributes = enabled;
+        self
+    }
+
+    /**
+    Choose what to do with entries that vanish between being listed in their directories and being
+    read, as entries of directories that change during a comparison do. By default, that's an
+    error. See [`Consistency`].
+
+    ```
+    use dir_compare::{Comparison, Consistency, Options};
+
+    let options = Options::new().consistency(Consistency::Retry(3));
+    let comparison = Comparison::between(
+        "fixtures/equivalent/dir-a",
+        "fixtures/equivalent/dir-b",
+        &options,
+    )?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn consistency(mut self, policy: Consistency) -> Self {
+        self.consistency = policy;
+        self
+    }
+
+    /**
+    Compare the names of entries case-insensitively, as file systems such as those of Windows and
+    macOS do by default, by comparing them in lowercase. Names that aren't valid Unicode are only
+    lowercased in their ASCII letters. Entries keep their names on disk, and reports name them as
+    [`normalize_names`](Self::normalize_names) describes.
+
+    A directory with several entries whose names differ only by case can't be compared this way,
+    since there's no telling which of them matches an entry on the other side. Reading one is an
+    error of kind [`io::ErrorKind::InvalidData`] that names the colliding entries.
+
+    ```
+    use dir_compare::{Content, Options};
+
+    let options = Options::new().case_insensitive(true);
+    let content = Content::of_with("fixtures/equivalent/dir-a", &options)?;
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// The name an entry with the given name on disk is matched and compared by. Names that aren't
+    /// valid Unicode can't be normalized, and are compared as they are.
+    pub(crate) fn key<'a>(&self, name: &'a OsStr) -> Cow<'a, OsStr> {
+        let key = match (self.normal_form, name.to_str()) {
+            (Some(form), Some(name)) => Cow::Owned(normalize(name, form).into()),
+            _ => Cow::Borrowed(name),
+        };
+        if !self.case_insensitive {
+            return key;
+        }
+
+        Cow::Owned(match key.to_str() {
+            Some(key) => key.to_lowercase().into(),
+            None => key.to_ascii_lowercase(),
+        })
+    }
+
+    /// The key to store for an entry with the given name on disk, if it differs from the name.
+    pub(crate) fn distinct_key(&self, name: &OsStr) -> Option<OsString> {
+        match self.key(name) {
+            Cow::Owned(key) if key != name => Some(key),
+            _ => None,
+        }
+    }
+
+    /**
+    Sort the children of the directory at the given path by the keys they're compared by, given by
+    `names` along with their names on disk, which break ties. Refuse them if any two share a key,
+    which happens when their names differ only by case or normal form.
+    */
+    pub(crate) fn sort_children<T>(
+        &self,
+        directory: &Path,
+        children: &mut [T],
+        names: impl Fn(&T) -> (&OsStr, &OsStr),
+    ) -> io::Result<()> {
+        children.sort_unstable_by(|a, b| names(a).cmp(&names(b)));
+
+        match children
+            .windows(2)
+            .map(|pair| (names(&pair[0]), names(&pair[1])))
+            .find(|((a, _), (b, _))| a == b)
+        {
+            Some(((_, a), (_, b))) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{a:?} and {b:?} in {directory:?} have the same name once it's normalized \
+                     or compared case-insensitively"
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /**
+    Whether to read the child with the given name at the given path on disk, if any, and relative
+    to the root. Whether it's a directory is only checked if an ignore rule for directories matches
+    it.
+    */
+    pub(crate) fn admits(
+        &self,
+        name: &OsStr,
+        path: &Path,
+        relative: &Path,
+        is_directory: impl FnOnce() -> bool,
+    ) -> bool {
+        let hidden = self.skip_hidden && is_hidden(name, path);
+        let selected = self.only_paths.is_empty()
+            || self
+                .only_paths
+                .iter()
+                .any(|only| only.starts_with(relative) || relative.starts_with(only));
+        !hidden && selected && !self.ignore.excludes(relative, is_directory)
+    }
+
+    pub(crate) fn wants_unified_diffs(&self) -> bool {
+        self.unified_diffs
+    }
+
+    pub(crate) fn compares_mtimes(&self) -> bool {
+        self.compare_mtimes
+    }
+
+    /// Whether the given modification times match, at the configured precision and tolerance.
+    pub(crate) fn same_mtimes(&self, a: SystemTime, b: SystemTime) -> bool {
+        let (a, b) = match self.mtime_precision {
+            Some(precision) => (truncate(a, precision), truncate(b, precision)),
+            None => (a, b),
+        };
+        let difference = a.duration_since(b).unwrap_or_else(|error| error.duration());
+
+        difference <= self.mtime_tolerance
+    }
+
+    pub(crate) fn treats_empty_as_missing(&self) -> bool {
+        self.empty_as_missing
+    }
+
+    pub(crate) fn special_file_policy(&self) -> SpecialFiles {
+        self.special_files
+    }
+
+    pub(crate) fn consistency_policy(&self) -> Consistency {
+        self.consistency
+    }
+
+    pub(crate) fn stays_on_one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+
+    pub(crate) fn virtual_file_system_policy(&self) -> VirtualFileSystems {
+        self.virtual_file_systems
+    }
+
+    pub(crate) fn compares_attributes(&self) -> bool {
+        self.compare_attributes
+    }
+
+    /// Whether entries with different names may be matched by their normalized or lowercased names.
+    pub(crate) fn matches_names_loosely(&self) -> bool {
+        self.normal_form.is_some() || self.case_insensitive
+    }
+
+    /// Send the event to the tracer, if there is one, building it only then.
+    pub(crate) fn trace<'a>(&self, event: impl FnOnce() -> TraceEvent<'a>) {
+        #[cfg(feature = "tracing")]
+        if let Some(tracer) = &self.tracer {
+            tracer.event(&event());
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = event;
+    }
+
+    pub(crate) fn profiles_trees(&self) -> bool {
+        self.profile_trees
+    }
+
+    /// The number of bytes from which files are timed, if entries are timed at all.
+    pub(crate) fn entry_time_threshold(&self) -> Option<u64> {
+        self.entry_times
+    }
+
+    /// Whether any filter applies to the file at the given path, relative to the root.
+    pub(crate) fn filters(&self, relative: &Path) -> bool {
+        self.filters.iter().any(|(glob, _)| glob.matches(relative))
+    }
+
+    /// Apply the filters for the file at the given path, relative to the root, to its bytes.
+    pub(crate) fn apply_filters(&self, relative: &Path, mut bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        for (_, filter) in self
+            .filters
+            .iter()
+            .filter(|(glob, _)| glob.matches(relative))
+        {
+            bytes = filter.apply(bytes)?;
+        }
+        Ok(bytes)
+    }
+}
+
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = f.debug_struct("Options");
+        options
+            .field("skip_hidden", &self.skip_hidden)
+            .field("ignore", &self.ignore)
+            .field("only_paths", &self.only_paths)
+            .field("filters", &self.filters)
+            .field("unified_diffs", &self.unified_diffs)
+            .field("compare_mtimes", &self.compare_mtimes)
+            .field("mtime_tolerance", &self.mtime_tolerance)
+            .field("mtime_precision", &self.mtime_precision)
+            .field("empty_as_missing", &self.empty_as_missing)
+            .field("special_files", &self.special_files)
+            .field("normal_form", &self.normal_form)
+            .field("case_insensitive", &self.case_insensitive)
+            .field("consistency", &self.consistency)
+            .field("one_file_system", &self.one_file_system)
+            .field("virtual_file_systems", &self.virtual_file_systems)
+            .field("compare_attributes", &self.compare_attributes)
+            .field("entry_times", &self.entry_times)
+            .field("profile_trees", &self.profile_trees);
+        #[cfg(feature = "tracing")]
+        options.field("tracer", &self.tracer.is_some());
+        options.finish()
+    }
+}
+
+/// The latest time at or before the given one that's a whole multiple of the given precision since
+/// the Unix epoch.
+fn truncate(time: SystemTime, precision: Duration) -> SystemTime {
+    // The remainders are less than the precision, which is never anywhere near `u64::MAX`
+    // nanoseconds.
+    let precision = precision.as_nanos();
+    let excess = |since: Duration| Duration::from_nanos((since.as_nanos() % precision) as u64);
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => time - excess(since),
+        // Times before the epoch are truncated away from it, to the multiple before them.
+        Err(error) => match excess(error.duration()) {
+            excess if excess.is_zero() => time,
+            excess => time - (Duration::from_nanos(precision as u64) - excess),
+        },
+    }
+}
+
+#[cfg(windows)]
+fn is_hidden(_name: &OsStr, path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    std::fs::symlink_metadata(path)
+        .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+}
+
+#[cfg(not(windows))]
+fn is_hidden(name: &OsStr, _path: &Path) -> bool {
+    name.as_encoded_bytes().starts_with(b".")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io, path::Path};
+
+    use crate::{
+        testing::TempDir, Comparison, Content, Difference, DifferenceKind, Entry, NormalForm,
+        Options,
+    };
+
+    #[cfg(not(windows))]
+    #[test]
+    fn hidden_entries_should_be_skipped() {
+        let options = Options::new().skip_hidden(true);
+        let a = Content::of_with("fixtures/hidden/dir-a", &options).unwrap();
+        let b = Content::of_with("fixtures/hidden/dir-b", &options).unwrap();
+
+        assert_eq!(a, b);
+        assert!(a
+            .diff_against_disk_with("fixtures/hidden/dir-b", &options)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn transforms_should_apply_in_order_with_filters() {
+        use crate::filters::Json;
+
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        fs::write(
+            a.join("build.json"),
+            "built: 1700000000\n{\"b\": 1, \"a\": 2}",
+        )
+        .unwrap();
+        fs::write(
+            b.join("build.json"),
+            "built: 1800000000\n{ \"a\": 2, \"b\": 1 }\n",
+        )
+        .unwrap();
+
+        let options = Options::new()
+            .transform("*.json", |bytes| {
+                let start = bytes.iter().position(|&byte| byte == b'\n');
+                Ok(bytes[start.map_or(0, |end| end + 1)..].to_vec())
+            })
+            .filter("*.json", Json);
+        let comparison = Comparison::between(&a, &b, &options).unwrap();
+        assert!(comparison.report().is_empty());
+
+        let options = Options::new().transform("*.json", |_| {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "not a build"))
+        });
+        assert!(Content::of_with(&a, &options).is_err());
+    }
+
+    #[test]
+    fn hidden_entries_should_be_read_by_default() {
+        let a = Content::of("fixtures/hidden/dir-a").unwrap();
+        let b = Content::of("fixtures/hidden/dir-b").unwrap();
+
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn names_should_be_normalized_when_enabled() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir_all(a.join("caf\u{e9}")).unwrap();
+        fs::create_dir_all(b.join("cafe\u{301}")).unwrap();
+        fs::write(a.join("caf\u{e9}/menu.txt"), "cr\u{e8}me").unwrap();
+        fs::write(b.join("cafe\u{301}/menu.txt"), "cr\u{e8}me").unwrap();
+
+        assert_ne!(Content::of(&a).unwrap(), Content::of(&b).unwrap());
+
+        let options = Options::new().normalize_names(NormalForm::Nfc);
+        let content = Content::of_with(&a, &options).unwrap();
+        assert_eq!(content, Content::of_with(&b, &options).unwrap());
+        assert!(content
+            .diff_against_disk_with(&b, &options)
+            .unwrap()
+            .is_empty());
+
+        let options = Options::new().normalize_names(NormalForm::Nfd);
+        let comparison = Comparison::between(&a, &b, &options).unwrap();
+        assert!(comparison.report().is_empty());
+
+        // Only comparisons see the normalized names: entries and reports keep the names on disk.
+        let options = Options::new().normalize_names(NormalForm::Nfc);
+        let Content::Entries(entries) = Content::of_with(&b, &options).unwrap() else {
+            panic!("the tree is a directory");
+        };
+        assert_eq!(entries[0].name(), "cafe\u{301}");
+        fs::write(b.join("cafe\u{301}/menu.txt"), "soupe").unwrap();
+        fs::write(a.join("caf\u{e9}/cr\u{e8}me.txt"), "br\u{fb}l\u{e9}e").unwrap();
+        let comparison = Comparison::between(&b, &a, &options).unwrap();
+        let paths: Vec<_> = comparison
+            .report()
+            .differences()
+            .iter()
+            .map(Difference::path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("cafe\u{301}/cr\u{e8}me.txt"),
+                Path::new("cafe\u{301}/menu.txt")
+            ]
+        );
+    }
+
+    // Other platforms' file systems are case-insensitive by default, so names can't collide.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn names_should_be_compared_case_insensitively_when_enabled() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir_all(a.join("Docs")).unwrap();
+        fs::create_dir_all(b.join("docs")).unwrap();
+        fs::write(a.join("Docs/README.md"), "read me").unwrap();
+        fs::write(b.join("docs/readme.md"), "read me").unwrap();
+
+        assert!(!Comparison::between(&a, &b, &Options::new())
+            .unwrap()
+            .report()
+            .is_empty());
+
+        let options = Options::new().case_insensitive(true);
+        let content = Content::of_with(&a, &options).unwrap();
+        assert_eq!(content, Content::of_with(&b, &options).unwrap());
+        assert!(content
+            .diff_against_disk_with(&b, &options)
+            .unwrap()
+            .is_empty());
+        assert!(Comparison::between(&a, &b, &options)
+            .unwrap()
+            .report()
+            .is_empty());
+
+        // Entries and reports keep the names on disk.
+        let entry = Entry::at_with(a.join("Docs"), &options).unwrap();
+        assert_eq!(entry.name(), "Docs");
+        assert_eq!(entry, Entry::at_with(b.join("docs"), &options).unwrap());
+        fs::write(a.join("Docs/README.md"), "read me again").unwrap();
+        let comparison = Comparison::between(&a, &b, &options).unwrap();
+        let differences = comparison.report().differences();
+        assert_eq!(differences[0].path(), Path::new("Docs/README.md"));
+
+        // Names that differ only by case collide, rather than either being matched.
+        fs::write(b.join("docs/README.md"), "read me too").unwrap();
+        let error = Comparison::between(&a, &b, &options).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error
+            .to_string()
+            .contains("\"README.md\" and \"readme.md\""));
+        assert_eq!(
+            Content::of_with(&b, &options).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn empty_files_should_be_treated_as_missing_when_enabled() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for dir in [&a, &b] {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join("data.csv"), "1,2,3").unwrap();
+        }
+        fs::write(a.join("placeholder"), "").unwrap();
+        fs::write(b.join("output.lock"), "").unwrap();
+        fs::write(b.join("output.csv"), "4,5,6").unwrap();
+
+        let kinds = |options: &Options| {
+            let report = Comparison::between(&a, &b, options).unwrap().into_report();
+            let in_memory = Content::of(&a)
+                .unwrap()
+                .diff_with(&Content::of(&b).unwrap(), options);
+            assert_eq!(report, in_memory);
+            report
+                .iter()
+                .map(|difference| {
+                    let name = difference.path().to_string_lossy().into_owned();
+                    (name, difference.kind(), difference.is_empty_file())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds(&Options::new()),
+            [
+                ("output.csv".to_owned(), DifferenceKind::Added, false),
+                ("output.lock".to_owned(), DifferenceKind::Added, true),
+                ("placeholder".to_owned(), DifferenceKind::Removed, true),
+            ]
+        );
+        assert_eq!(
+            kinds(&Options::new().empty_as_missing(true)),
+            [("output.csv".to_owned(), DifferenceKind::Added, false)]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn other_file_systems_should_not_be_descended_into_when_enabled() {
+        use std::os::unix::fs::symlink;
+
+        // The link leads to the proc file system, which is always mounted apart from the rest.
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(b.join("proc")).unwrap();
+        symlink("/proc", a.join("proc")).unwrap();
+
+        let options = Options::new().one_file_system(true);
+        let content = Content::of_with(&a, &options).unwrap();
+        assert_eq!(content, Content::of(&b).unwrap());
+        assert!(Comparison::between(&a, &b, &options)
+            .unwrap()
+            .report()
+            .is_empty());
+    }
+
+    #[test]
+    fn ignored_entries_should_be_skipped() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for dir in [&a, &b] {
+            fs::create_dir_all(dir.join("src")).unwrap();
+            fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        }
+        fs::create_dir_all(b.join("target/debug")).unwrap();
+        fs::write(b.join("target/debug/main"), "binary").unwrap();
+        fs::write(b.join("build.log"), "noise").unwrap();
+        fs::write(b.join("keep.log"), "signal").unwrap();
+        let gitignore = temp.path().join(".gitignore");
+        fs::write(&gitignore, "# Build output\ntarget/\n*.log\n!keep.log\n").unwrap();
+
+        let options = Options::new().ignore_file(&gitignore).unwrap();
+        let comparison = Comparison::between(&a, &b, &options).unwrap();
+        let paths: Vec<_> = comparison.report().iter().map(|d| d.path()).collect();
+        assert_eq!(paths, [std::path::Path::new("keep.log")]);
+
+        let content = Content::of_with(&b, &options).unwrap();
+        assert!(content.get("target").is_none());
+        assert!(content.get("keep.log").is_some());
+    }
+}
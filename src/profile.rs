@@ -0,0 +1,190 @@
+/*!
+Profiles of what trees hold, by the sizes and extensions of their files, to give the differences
+between them context.
+*/
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{Content, EntryKind};
+
+/**
+How many files and directories a tree holds, how big its files are, and of which types, from
+[`Content::profile`] or, for the trees of comparisons, [`Stats::profiles`](crate::Stats::profiles).
+Special files aren't counted.
+
+```
+use dir_compare::Content;
+
+let profile = Content::of("fixtures/equivalent/dir-a")?.profile();
+
+assert_eq!(profile.files(), 2);
+assert_eq!(profile.bytes(), 25);
+assert_eq!(profile.extensions(), [("txt", 2, 25)]);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeProfile {
+    files: u64,
+    directories: u64,
+    bytes: u64,
+    unknown_sizes: u64,
+    sizes: Vec<u64>,
+    /// The number of files with each extension, and their total size.
+    extensions: BTreeMap<String, (u64, u64)>,
+}
+
+impl TreeProfile {
+    /// The number of files.
+    #[must_use]
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// The number of directories, including the root, if it's one.
+    #[must_use]
+    pub fn directories(&self) -> u64 {
+        self.directories
+    }
+
+    /// The total size of the files whose sizes are known, in bytes.
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /**
+    The number of files whose sizes aren't known, such as filtered files, whose sizes on disk say
+    nothing about the sizes of their filtered bytes. They're left out of the sizes and histogram.
+    */
+    #[must_use]
+    pub fn unknown_sizes(&self) -> u64 {
+        self.unknown_sizes
+    }
+
+    /**
+    The number of files of each size, by powers of two: the count at index 0 is of empty files, and
+    the count at index `i` above it is of files of at least 2<sup>`i`-1</sup> and less than
+    2<sup>`i`</sup> bytes. It ends with the largest files' count.
+    */
+    #[must_use]
+    pub fn size_histogram(&self) -> &[u64] {
+        &self.sizes
+    }
+
+    /**
+    The number of files with each extension and their total size, in bytes, from the greatest
+    total size down. Extensions are lowercased, and files without one are counted under `""`.
+    */
+    #[must_use]
+    pub fn extensions(&self) -> Vec<(&str, u64, u64)> {
+        let mut extensions: Vec<_> = self
+            .extensions
+            .iter()
+            .map(|(extension, &(files, bytes))| (extension.as_str(), files, bytes))
+            .collect();
+        // The sort is stable, so equal totals stay ordered by extension.
+        extensions.sort_by_key(|&(_, _, bytes)| std::cmp::Reverse(bytes));
+
+        extensions
+    }
+
+    /// Count the entry at the given path, of the given kind and, if it's a file, size.
+    pub(crate) fn count(&mut self, path: &Path, kind: EntryKind, size: Option<u64>) {
+        match kind {
+            EntryKind::File => self.files += 1,
+            EntryKind::Directory => {
+                self.directories += 1;
+                return;
+            }
+            EntryKind::Special(_) => return,
+        }
+
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let (files, bytes) = self.extensions.entry(extension).or_default();
+        *files += 1;
+        let Some(size) = size else {
+            self.unknown_sizes += 1;
+            return;
+        };
+        *bytes += size;
+        self.bytes += size;
+
+        let bucket = (u64::BITS - size.leading_zeros()) as usize;
+        if self.sizes.len() <= bucket {
+            self.sizes.resize(bucket + 1, 0);
+        }
+        self.sizes[bucket] += 1;
+    }
+
+    /// Add the counts of another profile to these.
+    pub(crate) fn add(&mut self, other: &Self) {
+        self.files += other.files;
+        self.directories += other.directories;
+        self.bytes += other.bytes;
+        self.unknown_sizes += other.unknown_sizes;
+        if self.sizes.len() < other.sizes.len() {
+            self.sizes.resize(other.sizes.len(), 0);
+        }
+        for (count, other) in self.sizes.iter_mut().zip(&other.sizes) {
+            *count += other;
+        }
+        for (extension, &(files, bytes)) in &other.extensions {
+            let tally = self.extensions.entry(extension.clone()).or_default();
+            tally.0 += files;
+            tally.1 += bytes;
+        }
+    }
+}
+
+impl Content {
+    /// Profile the tree: the number, sizes, and extensions of its files. See [`TreeProfile`].
+    #[must_use]
+    pub fn profile(&self) -> TreeProfile {
+        let mut profile = TreeProfile::default();
+        for (path, content) in self.walk() {
+            let (kind, size) = match content {
+                Self::File(bytes) => (EntryKind::File, Some(bytes.len() as u64)),
+                Self::Entries(_) => (EntryKind::Directory, None),
+                Self::Special(kind) => (EntryKind::Special(*kind), None),
+            };
+            profile.count(&path, kind, size);
+        }
+
+        profile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Comparison, Content, Options};
+
+    #[test]
+    fn trees_should_be_profiled_when_enabled() {
+        let (a, b) = (
+            "fixtures/not-equivalent/dir-a",
+            "fixtures/not-equivalent/dir-b",
+        );
+        let comparison = Comparison::between(a, b, &Options::new()).unwrap();
+        assert!(comparison.stats().profiles().is_none());
+
+        let options = Options::new().profile_trees(true);
+        let comparison = Comparison::between(a, b, &options).unwrap();
+        let (profile_a, profile_b) = comparison.stats().profiles().unwrap();
+        assert_eq!(profile_a, &Content::of(a).unwrap().profile());
+        assert_eq!(profile_b, &Content::of(b).unwrap().profile());
+
+        // Files of 10 and 19 bytes.
+        assert_eq!(profile_b.files(), 2);
+        assert_eq!(profile_b.directories(), 2);
+        assert_eq!(profile_b.size_histogram(), [0, 0, 0, 0, 1, 1]);
+
+        let mut stats = comparison.stats().clone();
+        stats.add(comparison.stats());
+        assert_eq!(stats.profiles().unwrap().1.extensions(), [("txt", 4, 58)]);
+    }
+}
@@ -0,0 +1,183 @@
+/*!
+Comparisons of reports from successive runs, for tracking how drift is being fixed.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    path::Path,
+};
+
+use crate::{DiffReport, Difference};
+
+/**
+What changed between two reports of the same trees, from [`DiffReport::progress_since`]: which
+differences were resolved, which appeared, and which changed, such as a file that's still modified
+but by a different amount. Each list is ordered by path.
+
+Renders as a one-line summary:
+
+```
+use dir_compare::{Content, DiffReport};
+
+let a = Content::of("fixtures/not-equivalent/dir-a")?;
+let b = Content::of("fixtures/not-equivalent/dir-b")?;
+let progress = DiffReport::default().progress_since(&a.diff(&b));
+
+assert_eq!(progress.resolved().len(), 1);
+assert_eq!(
+    progress.to_string(),
+    "1 previously-differing entry is now equal; 0 new differences appeared; 0 differences \
+     changed; 0 unchanged",
+);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    resolved: Vec<Difference>,
+    appeared: Vec<Difference>,
+    changed: Vec<(Difference, Difference)>,
+    unchanged: usize,
+}
+
+impl Progress {
+    /// The differences in the earlier report whose entries no longer differ.
+    #[must_use]
+    pub fn resolved(&self) -> &[Difference] {
+        &self.resolved
+    }
+
+    /// The differences in the later report whose entries didn't differ before.
+    #[must_use]
+    pub fn appeared(&self) -> &[Difference] {
+        &self.appeared
+    }
+
+    /**
+    The differences of entries that differ in both reports, but not in the same way: of another
+    kind, with other sizes, or with another unified diff. Each is paired as earlier, then later.
+    */
+    #[must_use]
+    pub fn changed(&self) -> &[(Difference, Difference)] {
+        &self.changed
+    }
+
+    /// The number of differences that are the same in both reports.
+    #[must_use]
+    pub fn unchanged(&self) -> usize {
+        self.unchanged
+    }
+
+    /// Whether the reports hold the same differences.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.resolved.is_empty() && self.appeared.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, one: &'static str, many: &'static str| match count {
+            1 => one,
+            _ => many,
+        };
+        let (resolved, appeared, changed) =
+            (self.resolved.len(), self.appeared.len(), self.changed.len());
+
+        write!(
+            f,
+            "{resolved} previously-differing {} now equal; {appeared} new {} appeared; \
+             {changed} {} changed; {} unchanged",
+            plural(resolved, "entry is", "entries are"),
+            plural(appeared, "difference", "differences"),
+            plural(changed, "difference", "differences"),
+            self.unchanged,
+        )
+    }
+}
+
+impl DiffReport {
+    /**
+    Compare this report with an earlier one of the same trees, such as one recorded in a
+    [`History`](crate::History), to see what changed between the runs. Differences are matched by
+    path.
+
+    # Examples
+
+    See [`Progress`].
+    */
+    #[must_use]
+    pub fn progress_since(&self, earlier: &DiffReport) -> Progress {
+        let mut earlier: BTreeMap<&Path, &Difference> = earlier
+            .iter()
+            .map(|difference| (difference.path(), difference))
+            .collect();
+
+        let mut progress = Progress::default();
+        for difference in self {
+            match earlier.remove(difference.path()) {
+                None => progress.appeared.push(difference.clone()),
+                Some(before) if before == difference => progress.unchanged += 1,
+                Some(before) => progress.changed.push((before.clone(), difference.clone())),
+            }
+        }
+        progress.resolved = earlier.into_values().cloned().collect();
+
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::{DiffReport, Difference, DifferenceKind};
+
+    fn report(differences: &[(&str, DifferenceKind, Option<u64>)]) -> DiffReport {
+        DiffReport::new(
+            differences
+                .iter()
+                .map(|&(path, kind, size)| {
+                    Difference::new(PathBuf::from(path), kind).with_sizes((size, size))
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn progress_should_be_tracked_between_reports() {
+        let earlier = report(&[
+            ("etc/hosts", DifferenceKind::Modified, Some(10)),
+            ("etc/motd", DifferenceKind::Modified, Some(5)),
+            ("etc/passwd", DifferenceKind::Modified, Some(100)),
+            ("srv/old", DifferenceKind::Removed, None),
+        ]);
+        let later = report(&[
+            ("etc/motd", DifferenceKind::Modified, Some(5)),
+            ("etc/passwd", DifferenceKind::Modified, Some(120)),
+            ("srv/new", DifferenceKind::Added, None),
+        ]);
+
+        let progress = later.progress_since(&earlier);
+        fn paths(differences: &[Difference]) -> Vec<&str> {
+            differences
+                .iter()
+                .map(|difference| difference.path().to_str().unwrap())
+                .collect()
+        }
+        assert_eq!(paths(progress.resolved()), ["etc/hosts", "srv/old"]);
+        assert_eq!(paths(progress.appeared()), ["srv/new"]);
+        assert_eq!(progress.changed().len(), 1);
+        assert_eq!(progress.changed()[0].1.path(), Path::new("etc/passwd"));
+        assert_eq!(progress.unchanged(), 1);
+        assert_eq!(
+            progress.to_string(),
+            "2 previously-differing entries are now equal; 1 new difference appeared; \
+             1 difference changed; 1 unchanged"
+        );
+
+        assert!(later.progress_since(&later).is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+/*!
+Reading trees from disk.
+
+Directories are walked with an explicit work stack instead of recursion, so pathologically deep
+trees can't overflow the call stack.
+*/
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    vec,
+};
+
+use crate::{
+    consistency::missing,
+    mounts::VirtualMounts,
+    source::{device, kind_at, EntryKind},
+    Content, Entry, Options, SpecialFiles, VirtualFileSystems,
+};
+
+/// A directory whose children are still being read.
+struct Frame {
+    /// The directory's name and the key it's compared by, or `None` for the root.
+    name: Option<(OsString, Option<OsString>)>,
+    /// The directory's path relative to the root.
+    relative: PathBuf,
+    /// The children yet to be read, with their keys, ordered by key.
+    pending: vec::IntoIter<(OsString, Option<OsString>, PathBuf, EntryKind)>,
+    /// The children read so far.
+    entries: Vec<Entry>,
+}
+
+impl Frame {
+    fn open(
+        name: Option<(OsString, Option<OsString>)>,
+        relative: PathBuf,
+        path: &Path,
+        options: &Options,
+    ) -> io::Result<Self> {
+        let mut children = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let path = entry.path();
+            if !options.admits(&name, &path, &relative.join(&name), || path.is_dir()) {
+                continue;
+            }
+            match options
+                .consistency_policy()
+                .settle(|| kind_at(&path, options), || missing(&path))?
+            {
+                Some(EntryKind::Special(_))
+                    if options.special_file_policy() == SpecialFiles::Skip => {}
+                Some(kind) => {
+                    let key = options.distinct_key(&name);
+                    children.push((name, key, path, kind));
+                }
+                // An entry that vanished once it was listed is left out.
+                None => {}
+            }
+        }
+        options.sort_children(path, &mut children, |(name, key, _, _)| {
+            (key.as_deref().unwrap_or(name), name)
+        })?;
+
+        Ok(Self {
+            name,
+            relative,
+            pending: children.into_iter(),
+            entries: Vec::new(),
+        })
+    }
+}
+
+/// Read the file or directory at the given path.
+pub(crate) fn read(path: &Path, options: &Options) -> io::Result<Content> {
+    match kind_at(path, options)? {
+        EntryKind::File => {
+            // Filters are matched against the root's name, since it has no path relative to itself.
+            let relative = path.file_name().map(PathBuf::from).unwrap_or_default();
+            return Ok(Content::File(read_file(path, &relative, options)?));
+        }
+        EntryKind::Special(kind) => return Ok(Content::Special(kind)),
+        EntryKind::Directory => {}
+    }
+
+    // Directories on other file systems than the root are read as empty, if the options say so.
+    let root_device = match options.stays_on_one_file_system() {
+        true => device(path)?,
+        false => None,
+    };
+    let virtual_policy = options.virtual_file_system_policy();
+    let mounts = match virtual_policy {
+        VirtualFileSystems::Read => VirtualMounts::default(),
+        VirtualFileSystems::Skip | VirtualFileSystems::Empty => VirtualMounts::load(),
+    };
+    if mounts.contain(path)? {
+        return Ok(Content::Entries(Vec::new()));
+    }
+    let mut stack = vec![Frame::open(None, PathBuf::new(), path, options)?];
+
+    loop {
+        let frame = stack.last_mut().expect("the root frame is popped last");
+
+        if let Some((name, key, path, kind)) = frame.pending.next() {
+            let relative = frame.relative.join(&name);
+            let (policy, vanished) = (options.consistency_policy(), || missing(&path));
+            let content = match kind {
+                EntryKind::File => {
+                    match policy.settle(|| read_file(&path, &relative, options), vanished)? {
+                        Some(bytes) => Content::File(bytes),
+                        None => continue,
+                    }
+                }
+                EntryKind::Special(kind) => Content::Special(kind),
+                EntryKind::Directory if root_device.is_some() && device(&path)? != root_device => {
+                    Content::Entries(Vec::new())
+                }
+                EntryKind::Directory if mounts.contain(&path)? => match virtual_policy {
+                    VirtualFileSystems::Skip => continue,
+                    _ => Content::Entries(Vec::new()),
+                },
+                EntryKind::Directory => {
+                    let name = Some((name.clone(), key.clone()));
+                    let open = || Frame::open(name.clone(), relative.clone(), &path, options);
+                    stack.extend(policy.settle(open, vanished)?);
+                    continue;
+                }
+            };
+            frame.entries.push(Entry { name, key, content });
+            continue;
+        }
+
+        let frame = stack
+            .pop()
+            .expect("the stack was just checked to be non-empty");
+        let content = Content::Entries(frame.entries);
+
+        match (stack.last_mut(), frame.name) {
+            (Some(parent), Some((name, key))) => parent.entries.push(Entry { name, key, content }),
+            _ => return Ok(content),
+        }
+    }
+}
+
+/// Read the file at the given path, applying the filters for its path relative to the root.
+pub(crate) fn read_file(path: &Path, relative: &Path, options: &Options) -> io::Result<Vec<u8>> {
+    options.apply_filters(relative, fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::hash_map::DefaultHasher, fs, hash::Hash, path::PathBuf, thread};
+
+    use crate::{testing::TempDir, Comparison, Content, Entry, Options};
+
+    /// How many directories deep the trees on disk are. Their paths have to fit within `PATH_MAX`
+    /// on Linux.
+    const DISK_DEPTH: usize = 2_000;
+    /// How many directories deep the trees built in memory are, which no path length limits.
+    const DEPTH: usize = 10_000;
+
+    /// A tree of directories nested `depth` deep, the deepest holding a file with the given bytes.
+    fn nested(depth: usize, bytes: &[u8]) -> Content {
+        let file = Content::File(bytes.to_vec());
+        let mut content = Content::Entries(vec![entry("file.txt", file)]);
+        for _ in 0..depth {
+            content = Content::Entries(vec![entry("d", content)]);
+        }
+
+        content
+    }
+
+    fn entry(name: &str, content: Content) -> Entry {
+        Entry {
+            name: name.into(),
+            key: None,
+            content,
+        }
+    }
+
+    #[test]
+    fn deep_trees_should_not_overflow_the_stack() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        let deepest_on_disk: PathBuf = ["d"; DISK_DEPTH].iter().collect();
+        for (root, contents) in [(&a, "a"), (&b, "b")] {
+            fs::create_dir_all(root.join(&deepest_on_disk)).unwrap();
+            fs::write(root.join(&deepest_on_disk).join("file.txt"), contents).unwrap();
+        }
+
+        // A thread with a small stack overflows unless each traversal keeps its own stack.
+        thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || {
+                let entry = entry("a", nested(DEPTH, b"a"));
+                let content = nested(DEPTH, b"b");
+                assert_eq!(entry.clone(), entry);
+                assert!(entry.content < content);
+                entry.hash(&mut DefaultHasher::new());
+                assert_eq!(content.walk().count(), DEPTH + 2);
+                assert_eq!(format!("{content:?}").matches("Entry {").count(), DEPTH + 1);
+                let deepest: PathBuf = ["d"; DEPTH].iter().collect();
+                let difference = entry.content.first_difference(&content).unwrap();
+                assert_eq!(difference.path(), deepest.join("file.txt"));
+
+                #[cfg(feature = "shared")]
+                {
+                    let mut blobs = crate::Blobs::new();
+                    let shared = blobs.intern(&content);
+                    let copy = shared.clone();
+                    assert_eq!(copy, shared);
+                    assert_ne!(blobs.intern(&entry.content), shared);
+                    shared.hash(&mut DefaultHasher::new());
+                    assert_eq!(format!("{shared:?}").matches("Entry {").count(), DEPTH + 1);
+                    assert_eq!(shared.to_content(), content);
+                    drop(copy);
+                }
+
+                // Reading, displaying and diffing, whose costs grow with the length of each path,
+                // are checked on trees as deep as paths on disk can go.
+                let entry = Entry::at(&a).unwrap();
+                let content = Content::of(&b).unwrap();
+                assert!(entry.to_string().lines().count() > DISK_DEPTH);
+                let report = entry.content.diff(&content);
+                assert_eq!(
+                    report.differences()[0].path(),
+                    deepest_on_disk.join("file.txt")
+                );
+                let comparison = Comparison::between(&a, &b, &Options::new()).unwrap();
+                assert_eq!(comparison.report(), &report);
+                #[cfg(feature = "shared")]
+                assert_eq!(
+                    crate::Blobs::new()
+                        .read(&b, &Options::new())
+                        .unwrap()
+                        .to_content(),
+                    content
+                );
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+/*!
+Rechecks of earlier comparisons, which read only the files that differed before or that have been
+modified since, to quickly confirm whether drift has been fixed.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    diff::diff_sources,
+    source::{Disk, FileInfo},
+    Content, DiffReport, Options, Stats,
+};
+
+/// The files a recheck can trust to be unchanged since an earlier comparison.
+pub(crate) struct Recheck {
+    /// The paths that differed in the earlier comparison.
+    differed: HashSet<PathBuf>,
+    /// When the earlier comparison started, or `None` to trust files however recently they were
+    /// modified.
+    since: Option<SystemTime>,
+    /// The paths of the files trusted so far, in the order they were.
+    trusted: RefCell<Vec<PathBuf>>,
+}
+
+impl Recheck {
+    pub(crate) fn new(previous: &DiffReport, since: SystemTime) -> Self {
+        Self {
+            differed: previous
+                .iter()
+                .map(|difference| difference.path().to_path_buf())
+                .collect(),
+            since: Some(since),
+            trusted: RefCell::default(),
+        }
+    }
+
+    /// Trust every file whose size matches, without any earlier comparison, as a shallow
+    /// comparison does.
+    pub(crate) fn everything() -> Self {
+        Self {
+            differed: HashSet::new(),
+            since: None,
+            trusted: RefCell::default(),
+        }
+    }
+
+    /**
+    Whether the file at the given path can be assumed to be the same in both trees without reading
+    it: it was the same in the earlier comparison, and neither side has been modified since. A side
+    without a modification time, such as a tree in memory, is taken not to have been modified.
+    */
+    pub(crate) fn trusts(&self, path: &Path, a: FileInfo, b: FileInfo) -> bool {
+        let unmodified = |info: FileInfo| {
+            info.modified
+                .zip(self.since)
+                .is_none_or(|(time, since)| time < since)
+        };
+        let trusted = !self.differed.contains(path) && unmodified(a) && unmodified(b);
+        if trusted {
+            self.trusted.borrow_mut().push(path.to_path_buf());
+        }
+
+        trusted
+    }
+
+    /// The paths of the files that were trusted, in the order they were.
+    pub(crate) fn into_trusted(self) -> Vec<PathBuf> {
+        self.trusted.into_inner()
+    }
+}
+
+impl Content {
+    /**
+    Recheck the differences between this tree and the file or directory at the given path, given
+    the report of an earlier comparison between them that started at the time `since`.
+
+    Every directory is still listed, so added, removed, and retyped entries are all found, but only
+    files that differed before, or that have been modified since, are read. The rest are assumed to
+    still be the same. This makes it quick to check whether drift has been fixed, but relies on
+    modification times: a file changed without its modification time changing goes unnoticed.
+
+    ```
+    use std::time::SystemTime;
+    use dir_compare::{Content, Options};
+
+    let snapshot = Content::of("fixtures/not-equivalent/dir-a")?;
+    let options = Options::new();
+    let since = SystemTime::now();
+    let report = snapshot.diff_against_disk_with("fixtures/not-equivalent/dir-b", &options)?;
+
+    // Later, after attempting to fix the drift:
+    let recheck =
+        snapshot.recheck_against_disk("fixtures/not-equivalent/dir-b", &report, since, &options)?;
+    assert_eq!(recheck, report);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn recheck_against_disk(
+        &self,
+        path: impl AsRef<Path>,
+        previous: &DiffReport,
+        since: SystemTime,
+        options: &Options,
+    ) -> io::Result<DiffReport> {
+        diff_sources(
+            self,
+            &Disk::new(path.as_ref(), options),
+            options,
+            Some(&Recheck::new(previous, since)),
+            None,
+            &mut Stats::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{testing::TempDir, Comparison, Content, Options};
+
+    #[test]
+    fn only_differing_or_modified_files_should_be_read() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        let past = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for dir in [&a, &b] {
+            fs::create_dir(dir).unwrap();
+            fs::write(dir.join("same.txt"), "same").unwrap();
+            let file = File::options().write(true).open(dir.join("same.txt"));
+            file.unwrap().set_modified(past).unwrap();
+        }
+        fs::write(a.join("drift.txt"), "expected").unwrap();
+        fs::write(b.join("drift.txt"), "drifted!").unwrap();
+
+        let options = Options::new();
+        let since = SystemTime::now();
+        let previous = Comparison::between(&a, &b, &options).unwrap();
+        assert_eq!(previous.stats().bytes_read(), 24);
+
+        fs::write(b.join("drift.txt"), "expected").unwrap();
+        let recheck = Comparison::recheck(&a, &b, previous.report(), since, &options).unwrap();
+        assert!(recheck.report().is_empty());
+        // Only the file that differed was read.
+        assert_eq!(recheck.stats().bytes_read(), 16);
+
+        let snapshot = Content::of(&a).unwrap();
+        fs::write(b.join("new.txt"), "new").unwrap();
+        let report = snapshot
+            .recheck_against_disk(&b, recheck.report(), since, &options)
+            .unwrap();
+        let paths: Vec<_> = report.iter().map(|d| d.path()).collect();
+        assert_eq!(paths, [Path::new("new.txt")]);
+    }
+}
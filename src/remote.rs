@@ -0,0 +1,364 @@
+/*!
+Trees that live somewhere other than the local disk, such as on another machine, read through a
+[`TreeSource`] that lists their entries and opens their files on demand.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    ffi::OsString,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[cfg(feature = "mmap")]
+use crate::mmap::Bytes;
+use crate::{
+    source::{vanish, Child, EntryKind, FileInfo, Source},
+    Options, SpecialFiles,
+};
+
+/**
+A tree whose entries are listed, and whose files are read, only as a comparison reaches them, by
+their paths relative to its root. The empty path is the root itself.
+
+Implement this to compare against a tree that isn't on the local disk, such as one listed over SFTP
+or HTTP, or from a manifest served by an agent on another machine, without copying it locally
+first. See [`Comparison::against`](crate::Comparison::against).
+
+```
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use dir_compare::{Comparison, EntryKind, Options, TreeSource};
+
+/// The files of a tree, by their paths, as an agent on another machine might list them.
+struct Listing(BTreeMap<PathBuf, Vec<u8>>);
+
+impl TreeSource for Listing {
+    fn root(&self) -> io::Result<EntryKind> {
+        Ok(EntryKind::Directory)
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<(OsString, EntryKind)>> {
+        let mut children = BTreeMap::new();
+        for file in self.0.keys() {
+            let mut rest = file.strip_prefix(path).into_iter().flat_map(Path::components);
+            if let Some(name) = rest.next() {
+                let kind = match rest.next() {
+                    Some(_) => EntryKind::Directory,
+                    None => EntryKind::File,
+                };
+                children.insert(name.as_os_str().to_owned(), kind);
+            }
+        }
+        Ok(children.into_iter().collect())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let bytes = self.0.get(path).ok_or(io::ErrorKind::NotFound)?;
+        Ok(Box::new(bytes.as_slice()))
+    }
+}
+
+let remote = Listing(BTreeMap::from([
+    ("test.txt".into(), b"test data\n".to_vec()),
+    ("subdir/test2.txt".into(), b"this is different!\n".to_vec()),
+]));
+let comparison = Comparison::against("fixtures/equivalent/dir-a", &remote, &Options::new())?;
+
+assert_eq!(comparison.report().differences().len(), 1);
+assert_eq!(comparison.report().differences()[0].path(), Path::new("subdir/test2.txt"));
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+pub trait TreeSource {
+    /**
+    The kind of the root of the tree.
+
+    # Errors
+
+    Should return an error if the tree can't be reached.
+    */
+    fn root(&self) -> io::Result<EntryKind>;
+
+    /**
+    The names and kinds of the children of the directory at the given path, in any order.
+
+    # Errors
+
+    Should return an error if the directory can't be listed.
+    */
+    fn children(&self, path: &Path) -> io::Result<Vec<(OsString, EntryKind)>>;
+
+    /**
+    Open the file at the given path for reading. Only files whose sizes match, or aren't known, are
+    opened, and they're read a chunk at a time.
+
+    # Errors
+
+    Should return an error if the file can't be opened.
+    */
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /**
+    The size of the file at the given path, if it's known without reading it. Files whose sizes
+    differ are known to differ without being opened. Unknown by default.
+
+    # Errors
+
+    Should return an error if the file's size can't be looked up.
+    */
+    fn size(&self, _path: &Path) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /**
+    The time the file at the given path was last modified, if it's known, for
+    [`Options::compare_mtimes`]. Unknown by default.
+
+    # Errors
+
+    Should return an error if the file's modification time can't be looked up.
+    */
+    fn modified(&self, _path: &Path) -> io::Result<Option<SystemTime>> {
+        Ok(None)
+    }
+}
+
+/// A [`TreeSource`], read as configured by the given [`Options`], as the trees on disk are.
+pub(crate) struct Remote<'a> {
+    tree: &'a dyn TreeSource,
+    options: &'a Options,
+    /// The paths of the entries that vanished as they were read. See [`Source::vanished`].
+    vanished: RefCell<HashSet<PathBuf>>,
+}
+
+impl<'a> Remote<'a> {
+    pub(crate) fn new(tree: &'a dyn TreeSource, options: &'a Options) -> Self {
+        Self {
+            tree,
+            options,
+            vanished: RefCell::default(),
+        }
+    }
+
+    /**
+    Read the entry at the given path, settling it as the consistency policy says if it's not found,
+    as `Disk` does. There's no telling a link to nothing from an entry that vanished here.
+    */
+    fn settle<T>(&self, path: &Path, read: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let settled = self.options.consistency_policy().settle(read, || true);
+
+        settled?.ok_or_else(|| vanish(&self.vanished, path))
+    }
+
+    /// Refuse a special file if the policy for them is [`SpecialFiles::Error`].
+    fn check(&self, path: &Path, kind: EntryKind) -> io::Result<EntryKind> {
+        match kind {
+            EntryKind::Special(special)
+                if self.options.special_file_policy() == SpecialFiles::Error =>
+            {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{path:?} is a {special}, which can't be read. See `Options::special_files`."
+                    ),
+                ))
+            }
+            kind => Ok(kind),
+        }
+    }
+}
+
+impl Source for Remote<'_> {
+    fn root(&self) -> io::Result<EntryKind> {
+        self.check(Path::new(""), self.tree.root()?)
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<Child>> {
+        let mut children = Vec::new();
+        for (name, kind) in self.settle(path, || self.tree.children(path))? {
+            let child = path.join(&name);
+            let is_directory = || kind == EntryKind::Directory;
+            if !self.options.admits(&name, &child, &child, is_directory) {
+                continue;
+            }
+            match self.check(&child, kind)? {
+                EntryKind::Special(_)
+                    if self.options.special_file_policy() == SpecialFiles::Skip => {}
+                kind => {
+                    let key = self.options.distinct_key(&name);
+                    children.push((name, key, kind));
+                }
+            }
+        }
+        self.options
+            .sort_children(path, &mut children, |(name, key, _)| {
+                (key.as_deref().unwrap_or(name), name)
+            })?;
+
+        Ok(children)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let mut reader = self.settle(path, || self.tree.open(path))?;
+        if !self.options.filters(path) {
+            return Ok(reader);
+        }
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(Box::new(io::Cursor::new(
+            self.options.apply_filters(path, bytes)?,
+        )))
+    }
+
+    fn info(&self, path: &Path) -> io::Result<FileInfo> {
+        self.settle(path, || {
+            Ok(FileInfo {
+                // A filtered file's size says nothing about the size of its filtered bytes.
+                size: if self.options.filters(path) {
+                    None
+                } else {
+                    self.tree.size(path)?
+                },
+                modified: self.tree.modified(path)?,
+                id: None,
+            })
+        })
+    }
+
+    fn location(&self, _path: &Path) -> io::Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    fn vanished(&self, path: &Path) -> bool {
+        self.vanished.borrow().contains(path)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn bytes(&self, _path: &Path) -> io::Result<Option<Bytes<'_>>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        ffi::OsString,
+        io::{self, Read},
+        path::{Path, PathBuf},
+    };
+
+    use crate::{Comparison, Content, DifferenceKind, EntryKind, Options, TreeSource};
+
+    /// A tree of files held in memory, which records the files it opens.
+    #[derive(Default)]
+    struct Files {
+        files: BTreeMap<PathBuf, Vec<u8>>,
+        opened: RefCell<Vec<PathBuf>>,
+    }
+
+    impl TreeSource for Files {
+        fn root(&self) -> io::Result<EntryKind> {
+            Ok(EntryKind::Directory)
+        }
+
+        fn children(&self, path: &Path) -> io::Result<Vec<(OsString, EntryKind)>> {
+            let mut children = BTreeMap::new();
+            for file in self.files.keys() {
+                let mut rest = file
+                    .strip_prefix(path)
+                    .into_iter()
+                    .flat_map(Path::components);
+                if let Some(name) = rest.next() {
+                    let kind = match rest.next() {
+                        Some(_) => EntryKind::Directory,
+                        None => EntryKind::File,
+                    };
+                    children.insert(name.as_os_str().to_owned(), kind);
+                }
+            }
+            // Listed in reverse, to check that the order doesn't matter.
+            Ok(children.into_iter().rev().collect())
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+            self.opened.borrow_mut().push(path.to_path_buf());
+            let bytes = self.files.get(path).ok_or(io::ErrorKind::NotFound)?;
+            Ok(Box::new(bytes.as_slice()))
+        }
+
+        fn size(&self, path: &Path) -> io::Result<Option<u64>> {
+            Ok(self.files.get(path).map(|bytes| bytes.len() as u64))
+        }
+    }
+
+    fn files(files: &[(&str, &str)]) -> Files {
+        Files {
+            files: files
+                .iter()
+                .map(|(path, contents)| (path.into(), contents.as_bytes().to_vec()))
+                .collect(),
+            ..Files::default()
+        }
+    }
+
+    #[test]
+    fn remote_trees_should_be_compared_lazily() {
+        let remote = files(&[
+            ("test.txt", "test data\n"),
+            ("subdir/test2.txt", "this is different!\n"),
+            ("extra/new.txt", "new"),
+        ]);
+
+        let comparison =
+            Comparison::against("fixtures/not-equivalent/dir-a", &remote, &Options::new()).unwrap();
+        let differences: Vec<_> = comparison
+            .report()
+            .differences()
+            .iter()
+            .map(|difference| (difference.path(), difference.kind()))
+            .collect();
+        assert_eq!(
+            differences,
+            [
+                (Path::new("extra"), DifferenceKind::Added),
+                (Path::new("extra/new.txt"), DifferenceKind::Added),
+                (Path::new("subdir/test2.txt"), DifferenceKind::Modified),
+            ]
+        );
+
+        // The file whose size differs is known to differ without being opened.
+        assert_eq!(*remote.opened.borrow(), [Path::new("test.txt")]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn remote_trees_should_be_read_as_configured() {
+        let snapshot = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let remote = files(&[
+            ("test.txt", "test data\n"),
+            ("subdir/test2.txt", "more test data\n"),
+            (".cache/state", "anything"),
+        ]);
+
+        assert!(!snapshot
+            .diff_against_source(&remote, &Options::new())
+            .unwrap()
+            .is_empty());
+        assert!(snapshot
+            .diff_against_source(&remote, &Options::new().skip_hidden(true))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+/*!
+Comparisons of several pairs of trees as one unit, such as the directories a host-integrity audit
+covers.
+*/
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Cache, Comparison, DiffReport, Options, Stats};
+
+/**
+Several pairs of trees on disk, compared as one unit with the same [`Options`] and a shared
+[`Cache`], into a combined report.
+
+```
+use dir_compare::{Options, Session};
+
+let mut session = Session::new(Options::new())
+    .pair("fixtures/equivalent/dir-a", "fixtures/equivalent/dir-b")
+    .pair("fixtures/not-equivalent/dir-a", "fixtures/not-equivalent/dir-b");
+let report = session.run()?;
+
+assert_eq!(report.comparisons().len(), 2);
+assert_eq!(
+    report.differences().differences()[0].path(),
+    std::path::Path::new("fixtures/not-equivalent/dir-a/subdir/test2.txt"),
+);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone)]
+pub struct Session {
+    options: Options,
+    cache: Cache,
+    pairs: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Session {
+    /// Create a session with no pairs of trees, which compares them as configured by the given
+    /// [`Options`].
+    #[must_use]
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            cache: Cache::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Add a pair of files or directories to compare, after those added already.
+    #[must_use]
+    pub fn pair(mut self, a: impl Into<PathBuf>, b: impl Into<PathBuf>) -> Self {
+        self.pairs.push((a.into(), b.into()));
+        self
+    }
+
+    /// The pairs of files or directories the session compares, in the order they were added.
+    #[must_use]
+    pub fn pairs(&self) -> &[(PathBuf, PathBuf)] {
+        &self.pairs
+    }
+
+    /**
+    The digests of the files the session has read, which later runs compare unchanged files by. See
+    [`Comparison::between_cached`].
+    */
+    #[must_use]
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /**
+    Compare every pair, in order, sharing the session's cache between them and with later runs.
+
+    # Errors
+
+    Will bubble I/O errors, stopping at the first pair that fails.
+    */
+    pub fn run(&mut self) -> io::Result<SessionReport> {
+        let comparisons = self
+            .pairs
+            .iter()
+            .map(|(a, b)| Comparison::between_cached(a, b, &self.options, &mut self.cache))
+            .collect::<io::Result<_>>()?;
+
+        Ok(SessionReport {
+            roots: self.pairs.iter().map(|(a, _)| a.clone()).collect(),
+            comparisons,
+        })
+    }
+}
+
+/**
+The result of a [`Session`]'s run: the comparison of each of its pairs, and their differences and
+costs combined.
+*/
+#[derive(Debug, Clone)]
+pub struct SessionReport {
+    /// The first tree of each pair, which locates its differences in the combined report.
+    roots: Vec<PathBuf>,
+    comparisons: Vec<Comparison>,
+}
+
+impl SessionReport {
+    /// The comparison of each pair, in the order the pairs were added.
+    #[must_use]
+    pub fn comparisons(&self) -> &[Comparison] {
+        &self.comparisons
+    }
+
+    /// The comparison of the pair whose first tree is at the given path, if any.
+    #[must_use]
+    pub fn comparison(&self, root: impl AsRef<Path>) -> Option<&Comparison> {
+        let root = root.as_ref();
+        self.roots
+            .iter()
+            .position(|candidate| candidate == root)
+            .map(|index| &self.comparisons[index])
+    }
+
+    /**
+    The differences between every pair, each located by the path of the pair's first tree joined
+    with its path relative to it. They're ordered by pair, then by path.
+    */
+    #[must_use]
+    pub fn differences(&self) -> DiffReport {
+        DiffReport::new(
+            self.roots
+                .iter()
+                .zip(&self.comparisons)
+                .flat_map(|(root, comparison)| {
+                    comparison
+                        .report()
+                        .iter()
+                        .map(|difference| difference.relocated(root))
+                })
+                .collect(),
+        )
+    }
+
+    /// Whether every pair was found to be equivalent.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.comparisons
+            .iter()
+            .all(|comparison| comparison.report().is_empty())
+    }
+
+    /// The resources the comparisons used, in total.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for comparison in &self.comparisons {
+            stats.add(comparison.stats());
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{testing::TempDir, Options, Session};
+
+    #[test]
+    fn sessions_should_combine_their_comparisons() {
+        let temp = TempDir::new();
+        let (live, golden) = (temp.path().join("live"), temp.path().join("golden"));
+        let past = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        for (root, motd) in [(&live, "hello"), (&golden, "HELLO")] {
+            for (dir, name, contents) in [("etc", "motd", motd), ("srv", "index.html", "<p>")] {
+                fs::create_dir_all(root.join(dir)).unwrap();
+                let path = root.join(dir).join(name);
+                fs::write(&path, contents).unwrap();
+                let file = File::options().write(true).open(&path).unwrap();
+                file.set_modified(past).unwrap();
+            }
+        }
+
+        let mut session = Session::new(Options::new())
+            .pair(live.join("etc"), golden.join("etc"))
+            .pair(live.join("srv"), golden.join("srv"));
+        let report = session.run().unwrap();
+        assert!(!report.is_empty());
+        assert!(report
+            .comparison(live.join("srv"))
+            .unwrap()
+            .report()
+            .is_empty());
+        let differences = report.differences();
+        let paths: Vec<_> = differences
+            .iter()
+            .map(|difference| difference.path())
+            .collect();
+        assert_eq!(paths, [live.join("etc/motd")]);
+        assert_eq!(report.stats().bytes_read(), 16);
+        assert_eq!(session.cache().len(), 4);
+
+        // The shared cache spares later runs reading the files that haven't changed.
+        let report = session.run().unwrap();
+        assert_eq!(report.differences().differences().len(), 1);
+        assert_eq!(report.stats().bytes_read(), 0);
+    }
+}
@@ -0,0 +1,228 @@
+/*!
+Shallow comparisons, which compare trees by structure and file sizes alone, and verify the files
+that might match only when asked to.
+*/
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diff::text_diff,
+    recheck::Recheck,
+    source::{same_content, unless_vanished, Disk, Source},
+    Comparison, Consistency, DiffReport, Difference, DifferenceKind, Options, Stats,
+};
+
+/**
+The result of comparing two trees on disk by their structure and file sizes alone, without reading
+any files. See [`Comparison::shallow`].
+
+Its report lists the entries that were added, removed, or changed type, and the files whose sizes
+differ. The trees are candidates for equality if it's empty, and only [`ShallowComparison::verify`]
+can confirm that they are, by reading the files whose sizes match.
+*/
+#[derive(Debug, Clone)]
+pub struct ShallowComparison {
+    a: PathBuf,
+    b: PathBuf,
+    options: Options,
+    comparison: Comparison,
+    candidates: Vec<PathBuf>,
+}
+
+impl Comparison {
+    /**
+    Compare the files or directories at the given paths by their structure and file sizes alone,
+    reading no files, as configured by the given [`Options`]. This finds most differences quickly,
+    and leaves confirming that the files whose sizes match hold the same bytes to
+    [`ShallowComparison::verify`], which a CI workflow might only call on the rare runs that find
+    no differences.
+
+    ```
+    use dir_compare::{Comparison, Options};
+
+    let shallow = Comparison::shallow(
+        "fixtures/not-equivalent/dir-a",
+        "fixtures/not-equivalent/dir-b",
+        &Options::new(),
+    )?;
+    assert!(!shallow.is_candidate_equal());
+
+    let shallow = Comparison::shallow(
+        "fixtures/equivalent/dir-a",
+        "fixtures/equivalent/dir-b",
+        &Options::new(),
+    )?;
+    assert!(shallow.is_candidate_equal());
+    assert!(shallow.verify()?.report().is_empty());
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn shallow(
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        options: &Options,
+    ) -> io::Result<ShallowComparison> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        let recheck = Recheck::everything();
+        let comparison = Self::on_disk(a, b, options, Some(&recheck), None)?;
+
+        Ok(ShallowComparison {
+            a: a.to_path_buf(),
+            b: b.to_path_buf(),
+            options: options.clone(),
+            comparison,
+            candidates: recheck.into_trusted(),
+        })
+    }
+}
+
+impl ShallowComparison {
+    /// The differences found by structure and size alone.
+    #[must_use]
+    pub fn report(&self) -> &DiffReport {
+        self.comparison.report()
+    }
+
+    /// The resources the shallow comparison used.
+    #[must_use]
+    pub fn stats(&self) -> &Stats {
+        self.comparison.stats()
+    }
+
+    /// Whether the trees might be equivalent, since no differences were found by structure or size.
+    #[must_use]
+    pub fn is_candidate_equal(&self) -> bool {
+        self.report().is_empty()
+    }
+
+    /**
+    The paths of the files whose sizes match, which weren't read, ordered by path. Files known to
+    match without being read, such as hard links to the same file, aren't listed.
+    */
+    #[must_use]
+    pub fn candidates(&self) -> &[PathBuf] {
+        &self.candidates
+    }
+
+    /**
+    Read the candidate files, to complete the comparison. The result's report adds those that
+    differ to the shallow comparison's, and its statistics cover both.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn verify(&self) -> io::Result<Comparison> {
+        let a = Disk::new(&self.a, &self.options);
+        let b = Disk::new(&self.b, &self.options);
+        let policy = self.options.consistency_policy();
+
+        let mut stats = Stats::default();
+        let mut differences = stats.time(|stats| {
+            let mut differences = self.report().differences().to_vec();
+            for path in &self.candidates {
+                let read = verify_file(&a, &b, path, &self.options, stats);
+                let difference = unless_vanished((&a, path), (&b, path), read)?;
+                match difference {
+                    Some(Some(difference)) => differences.push(difference),
+                    Some(None) => {}
+                    None if policy == Consistency::Volatile => {
+                        differences.push(Difference::new(path.clone(), DifferenceKind::Volatile))
+                    }
+                    None => {}
+                }
+            }
+            io::Result::Ok(differences)
+        })?;
+        differences.sort_by(|a, b| a.path().cmp(b.path()));
+        stats.add(self.stats());
+
+        Ok(self
+            .comparison
+            .clone()
+            .replaced(DiffReport::new(differences), stats))
+    }
+}
+
+/// How the file at the given path differs between the trees, if it does, by its bytes.
+fn verify_file(
+    a: &dyn Source,
+    b: &dyn Source,
+    path: &Path,
+    options: &Options,
+    stats: &mut Stats,
+) -> io::Result<Option<Difference>> {
+    if same_content(a, b, (path, path), stats)? {
+        return Ok(None);
+    }
+
+    let sizes = (a.info(path)?.size, b.info(path)?.size);
+    let unified_diff = match options.wants_unified_diffs() {
+        true => text_diff(a, b, (path, path), stats)?,
+        false => None,
+    };
+    Ok(Some(
+        Difference::new(path.to_path_buf(), DifferenceKind::Modified)
+            .with_sizes(sizes)
+            .with_unified_diff(unified_diff),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::{testing::TempDir, Comparison, DifferenceKind, Options};
+
+    #[test]
+    fn only_candidate_files_should_be_verified() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for dir in [&a, &b] {
+            fs::create_dir_all(dir.join("src")).unwrap();
+            fs::write(dir.join("src/lib.rs"), "pub fn f() {}").unwrap();
+        }
+        fs::write(a.join("Cargo.lock"), "version = 3").unwrap();
+        fs::write(b.join("Cargo.lock"), "version = 4").unwrap();
+        fs::write(a.join("README.md"), "short").unwrap();
+        fs::write(b.join("README.md"), "longer").unwrap();
+
+        let shallow = Comparison::shallow(&a, &b, &Options::new()).unwrap();
+        assert!(!shallow.is_candidate_equal());
+        assert_eq!(shallow.stats().bytes_read(), 0);
+        assert_eq!(
+            shallow.candidates(),
+            [Path::new("Cargo.lock"), Path::new("src/lib.rs")]
+        );
+
+        let verified = shallow.verify().unwrap();
+        let differences: Vec<_> = verified
+            .report()
+            .iter()
+            .map(|difference| (difference.path(), difference.kind()))
+            .collect();
+        assert_eq!(
+            differences,
+            [
+                (Path::new("Cargo.lock"), DifferenceKind::Modified),
+                (Path::new("README.md"), DifferenceKind::Modified),
+            ]
+        );
+        // Only the candidates were read: two files of 11 bytes and two of 13.
+        assert_eq!(verified.stats().bytes_read(), 48);
+        assert_eq!(
+            verified.report(),
+            Comparison::between(&a, &b, &Options::new())
+                .unwrap()
+                .report()
+        );
+    }
+}
@@ -0,0 +1,567 @@
+/*!
+Trees whose files share their bytes, for holding many snapshots of similar trees in memory at once.
+
+As with [`Content`], trees are traversed with explicit stacks rather than by recursion, so that
+arbitrarily deep trees can't overflow the call stack.
+*/
+
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fmt,
+    hash::{Hash, Hasher},
+    io::{self, Read},
+    mem,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+#[cfg(feature = "mmap")]
+use crate::mmap::Bytes;
+use crate::{
+    diff::diff_sources,
+    source::{Child, FileInfo, Source},
+    traits::{debug_content, debug_entry, DebugTree},
+    Content, DiffReport, Entry, EntryKind, Options, SpecialKind, Stats,
+};
+
+/**
+A pool of file contents, each held once, however many files in however many trees hold it. Trees
+read or interned through the same pool share the bytes of their identical files, so that memory
+scales with the distinct contents of the trees rather than with how many trees there are.
+
+```
+use dir_compare::{Blobs, Options};
+
+let mut blobs = Blobs::new();
+let reference = blobs.read("fixtures/equivalent/dir-a", &Options::new())?;
+let candidates = ["fixtures/equivalent/dir-b", "fixtures/not-equivalent/dir-b"]
+    .map(|path| blobs.read(path, &Options::new()))
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+
+assert!(reference.diff(&candidates[0]).is_empty());
+assert!(!reference.diff(&candidates[1]).is_empty());
+// Of the 6 files, 3 hold distinct bytes.
+assert_eq!(blobs.len(), 3);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
+Enabled by the `shared` feature. Requires no dependencies.
+*/
+#[derive(Debug, Default)]
+pub struct Blobs {
+    blobs: HashSet<Arc<[u8]>>,
+}
+
+impl Blobs {
+    /// Make an empty pool.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Read the file or directory at the given path, as configured by the given [`Options`], sharing
+    the bytes of its files with those already in the pool.
+
+    The tree is read whole before its files are interned, so reading it takes as much memory as
+    [`Content::of_with`] does, on top of the pool's.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn read(&mut self, path: impl AsRef<Path>, options: &Options) -> io::Result<SharedContent> {
+        Ok(self.intern(&Content::of_with(path, options)?))
+    }
+
+    /// Copy a tree in memory into a [`SharedContent`], sharing its files' bytes through the pool.
+    pub fn intern(&mut self, content: &Content) -> SharedContent {
+        convert(
+            content,
+            |content| match content {
+                Content::File(bytes) => Ok(SharedContent::File(self.blob(bytes))),
+                Content::Entries(entries) => Err(entries),
+                Content::Special(kind) => Ok(SharedContent::Special(*kind)),
+            },
+            |entry| (&entry.name, &entry.key, &entry.content),
+            SharedContent::from_entries,
+        )
+    }
+
+    /// The number of distinct file contents in the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.blobs.len()
+    }
+
+    /// Whether the pool holds no file contents.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+
+    /// The total size of the distinct file contents in the pool, in bytes.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.blobs.iter().map(|blob| blob.len() as u64).sum()
+    }
+
+    /**
+    Release the file contents that no tree holds any longer. The pool keeps every content it's
+    given until then, even after the trees holding it are dropped.
+    */
+    pub fn purge(&mut self) {
+        self.blobs.retain(|blob| Arc::strong_count(blob) > 1);
+    }
+
+    /// The pool's copy of the given bytes, added if it has none.
+    fn blob(&mut self, bytes: &[u8]) -> Arc<[u8]> {
+        if let Some(blob) = self.blobs.get(bytes) {
+            return Arc::clone(blob);
+        }
+        let blob: Arc<[u8]> = Arc::from(bytes);
+        self.blobs.insert(Arc::clone(&blob));
+
+        blob
+    }
+}
+
+/**
+The content of an entry, as with [`Content`], but with the bytes of its files shared, through
+[`Blobs`], with every other file that holds the same bytes. Cloning it copies only its structure.
+
+Enabled by the `shared` feature. Requires no dependencies.
+*/
+pub enum SharedContent {
+    /// The byte content of the entry.
+    File(Arc<[u8]>),
+    /// The entries in the directory, ordered by name.
+    Entries(Vec<SharedEntry>),
+    /// A special file, such as a FIFO or a device node.
+    Special(SpecialKind),
+}
+
+/**
+An entry in a directory of a [`SharedContent`]: its name and its content.
+
+Enabled by the `shared` feature. Requires no dependencies.
+*/
+pub struct SharedEntry {
+    name: OsString,
+    /// The name the entry is compared by, if options made it differ from its name.
+    key: Option<OsString>,
+    content: SharedContent,
+}
+
+impl SharedEntry {
+    /// The name of the entry.
+    #[must_use]
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// The content of the entry.
+    #[must_use]
+    pub fn content(&self) -> &SharedContent {
+        &self.content
+    }
+
+    /// The name the entry is compared and ordered by.
+    fn key(&self) -> &OsStr {
+        self.key.as_deref().unwrap_or(&self.name)
+    }
+}
+
+impl SharedContent {
+    /// The entry at the given path, relative to the root of the tree, if there is one.
+    #[must_use]
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Self> {
+        path.as_ref()
+            .components()
+            .try_fold(self, |content, component| {
+                let Component::Normal(name) = component else {
+                    return None;
+                };
+                let Self::Entries(entries) = content else {
+                    return None;
+                };
+                // As with `Content`, entries are ordered by their keys rather than their names.
+                let entry = match entries.binary_search_by(|entry| entry.key().cmp(name)) {
+                    Ok(index) if entries[index].name == name => &entries[index],
+                    _ => entries.iter().find(|entry| entry.name == name)?,
+                };
+
+                Some(&entry.content)
+            })
+    }
+
+    /// List the differences between this tree and another, as with [`Content::diff`].
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> DiffReport {
+        self.diff_with(other, &Options::default())
+    }
+
+    /**
+    List the differences between this tree and another, as configured by the given [`Options`],
+    as with [`Content::diff_with`].
+    */
+    #[must_use]
+    pub fn diff_with(&self, other: &Self, options: &Options) -> DiffReport {
+        diff_sources(self, other, options, None, None, &mut Stats::default())
+            .expect("trees in memory can always be read")
+    }
+
+    /// Copy the tree into a [`Content`], with a buffer of its own for each file.
+    #[must_use]
+    pub fn to_content(&self) -> Content {
+        convert(
+            self,
+            |content| match content {
+                Self::File(bytes) => Ok(Content::File(bytes.to_vec())),
+                Self::Entries(entries) => Err(entries),
+                Self::Special(kind) => Ok(Content::Special(*kind)),
+            },
+            |entry| (&entry.name, &entry.key, &entry.content),
+            |entries| {
+                Content::Entries(
+                    entries
+                        .into_iter()
+                        .map(|(name, key, content)| Entry { name, key, content })
+                        .collect(),
+                )
+            },
+        )
+    }
+
+    fn from_entries(entries: Vec<(OsString, Option<OsString>, Self)>) -> Self {
+        Self::Entries(
+            entries
+                .into_iter()
+                .map(|(name, key, content)| SharedEntry { name, key, content })
+                .collect(),
+        )
+    }
+
+    fn kind(&self) -> EntryKind {
+        match self {
+            Self::File(_) => EntryKind::File,
+            Self::Entries(_) => EntryKind::Directory,
+            Self::Special(kind) => EntryKind::Special(*kind),
+        }
+    }
+
+    fn lookup_or_not_found(&self, path: &Path) -> io::Result<&Self> {
+        self.get(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{path:?} is not in the tree"),
+            )
+        })
+    }
+}
+
+impl Source for SharedContent {
+    fn root(&self) -> io::Result<EntryKind> {
+        Ok(self.kind())
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<Child>> {
+        match self.lookup_or_not_found(path)? {
+            Self::File(_) | Self::Special(_) => Ok(Vec::new()),
+            Self::Entries(entries) => Ok(entries
+                .iter()
+                .map(|entry| (entry.name.clone(), entry.key.clone(), entry.content.kind()))
+                .collect()),
+        }
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.lookup_or_not_found(path)? {
+            Self::File(bytes) => Ok(Box::new(&bytes[..])),
+            Self::Entries(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is a directory"),
+            )),
+            Self::Special(kind) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is a {kind}"),
+            )),
+        }
+    }
+
+    fn info(&self, path: &Path) -> io::Result<FileInfo> {
+        Ok(FileInfo {
+            size: match self.lookup_or_not_found(path)? {
+                Self::File(bytes) => Some(bytes.len() as u64),
+                Self::Entries(_) | Self::Special(_) => None,
+            },
+            modified: None,
+            id: None,
+        })
+    }
+
+    fn location(&self, _path: &Path) -> io::Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn bytes(&self, path: &Path) -> io::Result<Option<Bytes<'_>>> {
+        match self.lookup_or_not_found(path)? {
+            Self::File(bytes) => Ok(Some(Bytes::Borrowed(bytes))),
+            Self::Entries(_) | Self::Special(_) => Ok(None),
+        }
+    }
+}
+
+impl PartialEq for SharedContent {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (Self::File(a), Self::File(b)) => {
+                    if a != b {
+                        return false;
+                    }
+                }
+                (Self::Special(a), Self::Special(b)) => {
+                    if a != b {
+                        return false;
+                    }
+                }
+                (Self::Entries(a), Self::Entries(b)) => {
+                    if a.len() != b.len() {
+                        return false;
+                    }
+                    for (a, b) in a.iter().zip(b) {
+                        if a.key() != b.key() {
+                            return false;
+                        }
+                        stack.push((&a.content, &b.content));
+                    }
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl Eq for SharedContent {}
+
+impl PartialEq for SharedEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key() && self.content == other.content
+    }
+}
+
+impl Eq for SharedEntry {}
+
+impl Hash for SharedContent {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut stack = vec![self];
+
+        while let Some(content) = stack.pop() {
+            match content {
+                Self::File(bytes) => {
+                    state.write_u8(0);
+                    bytes.hash(state);
+                }
+                Self::Special(kind) => {
+                    state.write_u8(2);
+                    kind.hash(state);
+                }
+                Self::Entries(entries) => {
+                    state.write_u8(1);
+                    state.write_usize(entries.len());
+                    for entry in entries {
+                        entry.key().hash(state);
+                    }
+                    stack.extend(entries.iter().rev().map(|entry| &entry.content));
+                }
+            }
+        }
+    }
+}
+
+impl Hash for SharedEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+        self.content.hash(state);
+    }
+}
+
+impl Clone for SharedContent {
+    fn clone(&self) -> Self {
+        convert(
+            self,
+            |content| match content {
+                Self::File(bytes) => Ok(Self::File(Arc::clone(bytes))),
+                Self::Entries(entries) => Err(entries),
+                Self::Special(kind) => Ok(Self::Special(*kind)),
+            },
+            |entry| (&entry.name, &entry.key, &entry.content),
+            Self::from_entries,
+        )
+    }
+}
+
+impl Clone for SharedEntry {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            key: self.key.clone(),
+            content: self.content.clone(),
+        }
+    }
+}
+
+impl DebugTree for SharedContent {
+    type Entry = SharedEntry;
+    const ENTRY: &'static str = "SharedEntry";
+
+    fn leaf(&self, f: &mut fmt::Formatter<'_>) -> Result<fmt::Result, &[SharedEntry]> {
+        match self {
+            Self::File(bytes) => Ok(write!(f, "File({bytes:?})")),
+            Self::Special(kind) => Ok(write!(f, "Special({kind:?})")),
+            Self::Entries(entries) => Err(entries),
+        }
+    }
+
+    fn parts(entry: &SharedEntry) -> (&OsStr, Option<&OsStr>, &Self) {
+        (&entry.name, entry.key.as_deref(), &entry.content)
+    }
+}
+
+impl fmt::Debug for SharedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_entry::<SharedContent>(f, self)
+    }
+}
+
+impl fmt::Debug for SharedContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_content(f, self)
+    }
+}
+
+impl Drop for SharedEntry {
+    fn drop(&mut self) {
+        // As with `Entry`, descendants are moved out of their directories before they're dropped.
+        let SharedContent::Entries(entries) = &mut self.content else {
+            return;
+        };
+        let mut stack = mem::take(entries);
+        while let Some(mut entry) = stack.pop() {
+            if let SharedContent::Entries(entries) = &mut entry.content {
+                stack.append(entries);
+            }
+        }
+    }
+}
+
+/**
+Copy a tree into one of another type. `split` copies a file or special file, or returns the entries
+of a directory, `entry` gives the name, key and content of each, and `join` makes a directory of
+the copied entries.
+*/
+fn convert<'a, T, E: 'a, U>(
+    root: &'a T,
+    mut split: impl FnMut(&'a T) -> Result<U, &'a [E]>,
+    entry: impl Fn(&'a E) -> (&'a OsString, &'a Option<OsString>, &'a T),
+    join: impl Fn(Vec<(OsString, Option<OsString>, U)>) -> U,
+) -> U {
+    let entries = match split(root) {
+        Ok(copied) => return copied,
+        Err(entries) => entries,
+    };
+
+    // Each frame is a directory being copied: its name and key, its entries yet to be copied, and
+    // its entries copied so far.
+    let mut stack = vec![(None, entries.iter(), Vec::with_capacity(entries.len()))];
+
+    loop {
+        let (_, pending, copied) = stack.last_mut().expect("the root frame is popped last");
+
+        if let Some(next) = pending.next() {
+            let (name, key, content) = entry(next);
+            match split(content) {
+                Ok(content) => copied.push((name.clone(), key.clone(), content)),
+                Err(entries) => stack.push((
+                    Some((name, key)),
+                    entries.iter(),
+                    Vec::with_capacity(entries.len()),
+                )),
+            }
+            continue;
+        }
+
+        let (name, _, copied) = stack
+            .pop()
+            .expect("the stack was just checked to be non-empty");
+        let content = join(copied);
+
+        match (stack.last_mut(), name) {
+            (Some((_, _, parent)), Some((name, key))) => {
+                parent.push((name.clone(), key.clone(), content));
+            }
+            _ => return content,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use super::{Blobs, SharedContent};
+    use crate::{Content, Options};
+
+    #[test]
+    fn identical_files_should_share_their_bytes() {
+        let mut blobs = Blobs::new();
+        let a = blobs
+            .read("fixtures/not-equivalent/dir-a", &Options::new())
+            .unwrap();
+        let b = blobs
+            .read("fixtures/not-equivalent/dir-b", &Options::new())
+            .unwrap();
+        assert_eq!(blobs.len(), 3);
+        assert_eq!(blobs.size(), 10 + 15 + 19);
+
+        let (Some(SharedContent::File(file_a)), Some(SharedContent::File(file_b))) =
+            (a.get("test.txt"), b.get("test.txt"))
+        else {
+            panic!("test.txt should be a file in both trees");
+        };
+        assert!(Arc::ptr_eq(file_a, file_b));
+
+        let report = a.diff(&b);
+        assert_eq!(report.differences().len(), 1);
+        assert_eq!(
+            report.differences()[0].path(),
+            Path::new("subdir/test2.txt")
+        );
+        assert_eq!(
+            a.to_content(),
+            Content::of("fixtures/not-equivalent/dir-a").unwrap()
+        );
+        let SharedContent::Entries(entries) = a.clone() else {
+            panic!("the tree should be a directory");
+        };
+        assert_eq!(entries[0].name(), "subdir");
+        assert_eq!(entries[0].content(), a.get("subdir").unwrap());
+        drop(entries);
+
+        drop(b);
+        blobs.purge();
+        assert_eq!(blobs.len(), 2);
+        drop(a);
+        blobs.purge();
+        assert!(blobs.is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+/*!
+Scoring how similar two trees are, for when equality is too blunt an instrument.
+*/
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    diff::{flatten, Node},
+    Content,
+};
+
+/**
+How similar two trees are, by file count and by bytes.
+
+Files are matched by path. Two files at the same path that differ are "near-matches" and are scored
+individually by the bytes they share at their start and end.
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Similarity {
+    matching_files: usize,
+    total_files: usize,
+    matching_bytes: u64,
+    total_bytes: u64,
+    near_matches: Vec<FileSimilarity>,
+}
+
+impl Similarity {
+    /// The number of files identical in both trees.
+    #[must_use]
+    pub fn matching_files(&self) -> usize {
+        self.matching_files
+    }
+
+    /// The number of distinct file paths across both trees.
+    #[must_use]
+    pub fn total_files(&self) -> usize {
+        self.total_files
+    }
+
+    /// The fraction of files that are identical in both trees, from `0.0` to `1.0`.
+    #[must_use]
+    pub fn file_ratio(&self) -> f64 {
+        ratio(self.matching_files as f64, self.total_files as f64)
+    }
+
+    /// The number of bytes the files of both trees have in common.
+    #[must_use]
+    pub fn matching_bytes(&self) -> u64 {
+        self.matching_bytes
+    }
+
+    /// The number of bytes in the larger of each pair of files, or in the file if it's unpaired.
+    #[must_use]
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// The fraction of bytes the trees have in common, from `0.0` to `1.0`.
+    #[must_use]
+    pub fn byte_ratio(&self) -> f64 {
+        ratio(self.matching_bytes as f64, self.total_bytes as f64)
+    }
+
+    /// The files present in both trees that differ, ordered by path.
+    #[must_use]
+    pub fn near_matches(&self) -> &[FileSimilarity] {
+        &self.near_matches
+    }
+}
+
+/**
+The similarity of a pair of differing files at the same path.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSimilarity {
+    path: PathBuf,
+    matching_bytes: u64,
+    score: f64,
+}
+
+impl FileSimilarity {
+    /// The path of the files, relative to the roots of the compared trees.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of bytes in the common prefix and suffix of the files.
+    #[must_use]
+    pub fn matching_bytes(&self) -> u64 {
+        self.matching_bytes
+    }
+
+    /// The matching bytes as a fraction of the larger file, from `0.0` to `1.0`.
+    #[must_use]
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+}
+
+impl Content {
+    /**
+    Score the similarity of this tree and another.
+
+    ```
+    use dir_compare::Content;
+
+    let a = Content::of("fixtures/not-equivalent/dir-a")?;
+    let b = Content::of("fixtures/not-equivalent/dir-b")?;
+    let similarity = a.similarity(&b);
+
+    assert_eq!(similarity.matching_files(), 1);
+    assert_eq!(similarity.file_ratio(), 0.5);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn similarity(&self, other: &Self) -> Similarity {
+        let ours = flatten(self);
+        let theirs = flatten(other);
+        let mut similarity = Similarity::default();
+
+        for (path, node) in &ours {
+            let Node::File(a) = node else { continue };
+            similarity.total_files += 1;
+
+            let Some(Node::File(b)) = theirs.get(path) else {
+                similarity.total_bytes += a.len() as u64;
+                continue;
+            };
+
+            let total = a.len().max(b.len()) as u64;
+            similarity.total_bytes += total;
+
+            if a == b {
+                similarity.matching_files += 1;
+                similarity.matching_bytes += total;
+            } else {
+                let matching = common_bytes(a, b) as u64;
+                similarity.matching_bytes += matching;
+                similarity.near_matches.push(FileSimilarity {
+                    path: path.clone(),
+                    matching_bytes: matching,
+                    score: ratio(matching as f64, total as f64),
+                });
+            }
+        }
+
+        for (path, node) in &theirs {
+            let Node::File(b) = node else { continue };
+            if !matches!(ours.get(path), Some(Node::File(_))) {
+                similarity.total_files += 1;
+                similarity.total_bytes += b.len() as u64;
+            }
+        }
+
+        similarity
+    }
+}
+
+/// The length of the common prefix plus that of the common suffix, without overlapping.
+fn common_bytes(a: &[u8], b: &[u8]) -> usize {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    prefix + suffix
+}
+
+/// Divide, treating `0 / 0` as a perfect match.
+fn ratio(part: f64, whole: f64) -> f64 {
+    if whole == 0.0 {
+        1.0
+    } else {
+        part / whole
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Content;
+
+    #[test]
+    fn equivalent_contents_should_be_fully_similar() {
+        let a = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let b = Content::of("fixtures/equivalent/dir-b").unwrap();
+        let similarity = a.similarity(&b);
+
+        assert_eq!(similarity.file_ratio(), 1.0);
+        assert_eq!(similarity.byte_ratio(), 1.0);
+        assert!(similarity.near_matches().is_empty());
+    }
+
+    #[test]
+    fn near_matches_should_score_common_bytes() {
+        let a = Content::File(b"header, old body, footer".to_vec());
+        let b = Content::File(b"header, new body, footer".to_vec());
+        let similarity = a.similarity(&b);
+
+        let [near_match] = similarity.near_matches() else {
+            panic!("expected a single near-match");
+        };
+        assert_eq!(near_match.matching_bytes(), 21);
+        assert_eq!(similarity.matching_files(), 0);
+    }
+}
@@ -0,0 +1,539 @@
+/*!
+Uniform access to trees, whether they're held in memory or live on disk, so that the same
+comparison can be run against either without first reading the whole tree.
+*/
+
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[cfg(feature = "mmap")]
+use crate::mmap::{map, Bytes};
+use crate::{
+    consistency::missing, mounts::VirtualMounts, read::read_file, Consistency, Content, Options,
+    SpecialFiles, SpecialKind, Stats, VirtualFileSystems,
+};
+
+/// The kind of an entry in a tree, as a [`TreeSource`](crate::TreeSource) lists it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    /// A regular file, whose bytes can be read.
+    File,
+    /// A directory, whose children can be listed.
+    Directory,
+    /// A special file, which is never read, only compared by its kind.
+    Special(SpecialKind),
+}
+
+/**
+The kind of the entry at the given path, following symbolic links.
+
+# Errors
+
+Will return an error for a special file if the policy for them is [`SpecialFiles::Error`].
+*/
+pub(crate) fn kind_at(path: &Path, options: &Options) -> io::Result<EntryKind> {
+    let file_type = fs::metadata(path)?.file_type();
+
+    if file_type.is_file() {
+        Ok(EntryKind::File)
+    } else if file_type.is_dir() {
+        Ok(EntryKind::Directory)
+    } else if options.special_file_policy() == SpecialFiles::Error {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{path:?} is a {}, which can't be read. See `Options::special_files`.",
+                SpecialKind::of(file_type)
+            ),
+        ))
+    } else {
+        Ok(EntryKind::Special(SpecialKind::of(file_type)))
+    }
+}
+
+/// The path on disk of the entry at the given path relative to the root.
+pub(crate) fn at(root: &Path, path: &Path) -> PathBuf {
+    // Joining an empty path would add a trailing separator, which a file can't be opened with.
+    if path.as_os_str().is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(path)
+    }
+}
+
+/// The ID of the device holding the file system the entry at the given path is on, on Unix.
+#[cfg(unix)]
+pub(crate) fn device(path: &Path) -> io::Result<Option<u64>> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(Some(fs::metadata(path)?.dev()))
+}
+
+/// The ID of the device holding the file system the entry at the given path is on, which is only
+/// known on Unix.
+#[cfg(not(unix))]
+pub(crate) fn device(_path: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// What's known about a file without reading it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct FileInfo {
+    /// The number of bytes [`Source::open`] would yield, if known.
+    pub(crate) size: Option<u64>,
+    /// The time the file was last modified, if known.
+    pub(crate) modified: Option<SystemTime>,
+    /// The file's identity on disk, if it's known and the file has more than one hard link.
+    pub(crate) id: Option<FileId>,
+}
+
+/// The identity of a file on disk, which every hard link to it shares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct FileId {
+    device: u64,
+    inode: u64,
+}
+
+impl FileId {
+    /// The identity of the file with the given metadata, if it has more than one hard link.
+    #[cfg(unix)]
+    fn of(metadata: &fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        (metadata.nlink() > 1).then(|| Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    /// The identity of the file with the given metadata, which is only known on Unix.
+    #[cfg(not(unix))]
+    fn of(_metadata: &fs::Metadata) -> Option<Self> {
+        None
+    }
+}
+
+/**
+A child of a directory, as a [`Source`] lists it: its name, the key it's compared by if options made
+that differ from its name, and its kind.
+*/
+pub(crate) type Child = (OsString, Option<OsString>, EntryKind);
+
+/// A tree whose entries are addressed by their paths relative to its root.
+pub(crate) trait Source {
+    /// The kind of the root of the tree.
+    fn root(&self) -> io::Result<EntryKind>;
+
+    /// The children of the directory at the given path, ordered by the keys they're compared by.
+    fn children(&self, path: &Path) -> io::Result<Vec<Child>>;
+
+    /// Open the file at the given path for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// What's known about the file at the given path without reading it.
+    fn info(&self, path: &Path) -> io::Result<FileInfo>;
+
+    /// The path on disk of the entry at the given path, if the tree lives on disk.
+    fn location(&self, path: &Path) -> io::Result<Option<PathBuf>>;
+
+    /**
+    Whether the entry at the given path vanished as it was read, under [`Consistency::Skip`] or
+    [`Consistency::Volatile`], or was listed in its directory, but vanished before its kind was
+    known, under [`Consistency::Volatile`]. Its kind in the listing is then meaningless.
+    */
+    fn vanished(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// The bytes of the file at the given path, if they can be accessed in place.
+    #[cfg(feature = "mmap")]
+    fn bytes(&self, path: &Path) -> io::Result<Option<Bytes<'_>>>;
+}
+
+impl Content {
+    fn lookup_or_not_found(&self, path: &Path) -> io::Result<&Self> {
+        self.get(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{path:?} is not in the tree"),
+            )
+        })
+    }
+}
+
+impl Source for Content {
+    fn root(&self) -> io::Result<EntryKind> {
+        Ok(self.kind())
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<Child>> {
+        match self.lookup_or_not_found(path)? {
+            Self::File(_) | Self::Special(_) => Ok(Vec::new()),
+            Self::Entries(entries) => Ok(entries
+                .iter()
+                .map(|entry| (entry.name.clone(), entry.key.clone(), entry.content.kind()))
+                .collect()),
+        }
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.lookup_or_not_found(path)? {
+            Self::File(bytes) => Ok(Box::new(bytes.as_slice())),
+            Self::Entries(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is a directory"),
+            )),
+            Self::Special(kind) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is a {kind}"),
+            )),
+        }
+    }
+
+    fn info(&self, path: &Path) -> io::Result<FileInfo> {
+        Ok(FileInfo {
+            size: match self.lookup_or_not_found(path)? {
+                Self::File(bytes) => Some(bytes.len() as u64),
+                Self::Entries(_) | Self::Special(_) => None,
+            },
+            modified: None,
+            id: None,
+        })
+    }
+
+    fn location(&self, _path: &Path) -> io::Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn bytes(&self, path: &Path) -> io::Result<Option<Bytes<'_>>> {
+        match self.lookup_or_not_found(path)? {
+            Self::File(bytes) => Ok(Some(Bytes::Borrowed(bytes))),
+            Self::Entries(_) | Self::Special(_) => Ok(None),
+        }
+    }
+}
+
+impl Content {
+    fn kind(&self) -> EntryKind {
+        match self {
+            Self::File(_) => EntryKind::File,
+            Self::Entries(_) => EntryKind::Directory,
+            Self::Special(kind) => EntryKind::Special(*kind),
+        }
+    }
+}
+
+/// A tree read lazily from disk, one directory listing or file at a time.
+pub(crate) struct Disk<'a> {
+    root: PathBuf,
+    options: &'a Options,
+    /// The paths of the files with more than one hard link that have been looked at, by identity.
+    links: RefCell<HashMap<FileId, Vec<PathBuf>>>,
+    /// The paths of the entries that vanished as they were read. See [`Source::vanished`].
+    vanished: RefCell<HashSet<PathBuf>>,
+    /// The device the root is on, once it's needed. See [`Options::one_file_system`].
+    device: OnceCell<Option<u64>>,
+    /// The virtual file systems mounted, once they're needed. See
+    /// [`Options::virtual_file_systems`].
+    mounts: OnceCell<VirtualMounts>,
+}
+
+impl<'a> Disk<'a> {
+    pub(crate) fn new(root: impl Into<PathBuf>, options: &'a Options) -> Self {
+        Self {
+            root: root.into(),
+            options,
+            links: RefCell::default(),
+            vanished: RefCell::default(),
+            device: OnceCell::new(),
+            mounts: OnceCell::new(),
+        }
+    }
+
+    /**
+    The groups of paths that have been looked at that are hard links to the same file, each
+    ordered by path, and ordered by their first paths. Links from outside the tree aren't counted.
+    */
+    pub(crate) fn link_groups(self) -> Vec<Vec<PathBuf>> {
+        let mut groups: Vec<_> = self
+            .links
+            .into_inner()
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort_unstable();
+                paths
+            })
+            .collect();
+        groups.sort_unstable();
+
+        groups
+    }
+
+    /**
+    The path on disk of the entry at the given path.
+
+    Entries are matched by their normalized or lowercased names, if so configured, so a path from
+    the other tree may not name an entry here as it is. Each directory along such a path is then
+    searched for the entry whose name matches once it's normalized or lowercased.
+    */
+    fn full(&self, path: &Path) -> io::Result<PathBuf> {
+        let joined = at(&self.root, path);
+        if path.as_os_str().is_empty() || fs::symlink_metadata(&joined).is_ok() {
+            return Ok(joined);
+        }
+
+        let mut full = self.root.clone();
+        for component in path.components() {
+            let name = component.as_os_str();
+            let candidate = full.join(name);
+            if fs::symlink_metadata(&candidate).is_ok() {
+                full = candidate;
+                continue;
+            }
+
+            full = fs::read_dir(&full)?
+                .filter_map(Result::ok)
+                .find(|entry| self.options.key(&entry.file_name()) == self.options.key(name))
+                .map(|entry| entry.path())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("{path:?} doesn't exist"))
+                })?;
+        }
+
+        Ok(full)
+    }
+
+    /**
+    Read the entry at the given path with `read`, given its path on disk, settling it as the
+    consistency policy says if it vanishes. One that's skipped or reported is recorded as vanished,
+    and fails to read with an error of kind [`io::ErrorKind::NotFound`].
+    */
+    fn settle<T>(
+        &self,
+        path: &Path,
+        mut read: impl FnMut(&Path) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let policy = self.options.consistency_policy();
+        let settled = policy.settle(|| read(&self.full(path)?), || self.full(path).is_err());
+
+        settled?.ok_or_else(|| vanish(&self.vanished, path))
+    }
+
+    /// Whether the entry at the given path on disk is on another file system than the root.
+    fn crosses_file_systems(&self, full: &Path) -> io::Result<bool> {
+        let root = *self
+            .device
+            .get_or_init(|| device(&self.root).ok().flatten());
+        Ok(root.is_some() && device(full)? != root)
+    }
+
+    /// Whether the directory at the given path on disk is on a virtual file system.
+    fn is_virtual(&self, full: &Path) -> io::Result<bool> {
+        self.mounts.get_or_init(VirtualMounts::load).contain(full)
+    }
+
+    /// The path to match filters against. The root has no path relative to itself, so its filters
+    /// are matched against its name.
+    fn relative<'p>(&'p self, path: &'p Path) -> &'p Path {
+        if path.as_os_str().is_empty() {
+            Path::new(self.root.file_name().unwrap_or_default())
+        } else {
+            path
+        }
+    }
+}
+
+impl Source for Disk<'_> {
+    fn root(&self) -> io::Result<EntryKind> {
+        kind_at(&self.root, self.options)
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<Child>> {
+        let (directory, entries) =
+            self.settle(path, |full| Ok((full.to_path_buf(), fs::read_dir(full)?)))?;
+        if self.options.stays_on_one_file_system() && self.crosses_file_systems(&directory)? {
+            return Ok(Vec::new());
+        }
+        // A virtual file system is read as empty, unless it's skipped as its parent is listed.
+        let virtual_policy = self.options.virtual_file_system_policy();
+        if virtual_policy != VirtualFileSystems::Read && self.is_virtual(&directory)? {
+            return Ok(Vec::new());
+        }
+
+        let mut children = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let full = entry.path();
+            if !self
+                .options
+                .admits(&name, &full, &path.join(&name), || full.is_dir())
+            {
+                continue;
+            }
+            let policy = self.options.consistency_policy();
+            let kind = match policy.settle(|| kind_at(&full, self.options), || missing(&full))? {
+                Some(kind) => kind,
+                // Listed as a file, so that it's compared with whatever the other tree holds there.
+                None if policy == Consistency::Volatile => {
+                    self.vanished.borrow_mut().insert(path.join(&name));
+                    EntryKind::File
+                }
+                None => continue,
+            };
+            match kind {
+                EntryKind::Special(_)
+                    if self.options.special_file_policy() == SpecialFiles::Skip => {}
+                EntryKind::Directory
+                    if virtual_policy == VirtualFileSystems::Skip && self.is_virtual(&full)? => {}
+                kind => {
+                    let key = self.options.distinct_key(&name);
+                    children.push((name, key, kind));
+                }
+            }
+        }
+        self.options
+            .sort_children(&directory, &mut children, |(name, key, _)| {
+                (key.as_deref().unwrap_or(name), name)
+            })?;
+
+        Ok(children)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let relative = self.relative(path);
+
+        if self.options.filters(relative) {
+            let bytes = self.settle(path, |full| read_file(full, relative, self.options))?;
+            Ok(Box::new(io::Cursor::new(bytes)))
+        } else {
+            Ok(Box::new(self.settle(path, |full| File::open(full))?))
+        }
+    }
+
+    fn info(&self, path: &Path) -> io::Result<FileInfo> {
+        let metadata = self.settle(path, |full| fs::metadata(full))?;
+        let id = FileId::of(&metadata);
+        if let Some(id) = id {
+            let mut links = self.links.borrow_mut();
+            let paths = links.entry(id).or_default();
+            if !paths.iter().any(|linked| linked == path) {
+                paths.push(path.to_path_buf());
+            }
+        }
+
+        Ok(FileInfo {
+            // A filtered file's size on disk says nothing about the size of its filtered bytes.
+            size: (!self.options.filters(self.relative(path))).then_some(metadata.len()),
+            modified: metadata.modified().ok(),
+            id,
+        })
+    }
+
+    fn location(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        self.settle(path, |full| Ok(Some(full.to_path_buf())))
+    }
+
+    fn vanished(&self, path: &Path) -> bool {
+        self.vanished.borrow().contains(path)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn bytes(&self, path: &Path) -> io::Result<Option<Bytes<'_>>> {
+        // Filtered files have to be read to be filtered.
+        if self.options.filters(self.relative(path)) {
+            return Ok(None);
+        }
+        self.settle(path, map)
+    }
+}
+
+/// Record the entry at the given path as vanished, returning the error reading it fails with.
+pub(crate) fn vanish(vanished: &RefCell<HashSet<PathBuf>>, path: &Path) -> io::Error {
+    vanished.borrow_mut().insert(path.to_path_buf());
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{path:?} vanished as it was read"),
+    )
+}
+
+/**
+What reading the entry at the given paths in each tree returned, or `None` if it vanished from
+either as it was read, and the consistency policy skips or reports it. See [`Source::vanished`].
+*/
+pub(crate) fn unless_vanished<T>(
+    a: (&dyn Source, &Path),
+    b: (&dyn Source, &Path),
+    read: io::Result<T>,
+) -> io::Result<Option<T>> {
+    match read {
+        Err(_) if a.0.vanished(a.1) || b.0.vanished(b.1) => Ok(None),
+        read => read.map(Some),
+    }
+}
+
+/// Whether the file at the given paths in each tree holds the same bytes.
+pub(crate) fn same_content(
+    a: &dyn Source,
+    b: &dyn Source,
+    paths: (&Path, &Path),
+    stats: &mut Stats,
+) -> io::Result<bool> {
+    // Files that can be accessed in place are compared without buffers of their own.
+    #[cfg(feature = "mmap")]
+    if let (Some(a), Some(b)) = (a.bytes(paths.0)?, b.bytes(paths.1)?) {
+        stats.read(a.len() + b.len());
+        return Ok(*a == *b);
+    }
+
+    same_bytes(a.open(paths.0)?, b.open(paths.1)?, stats)
+}
+
+/// Whether two readers yield the same bytes, reading both a chunk at a time.
+fn same_bytes(mut a: impl Read, mut b: impl Read, stats: &mut Stats) -> io::Result<bool> {
+    const CHUNK: usize = 64 * 1024;
+    let mut chunk_a = vec![0; CHUNK];
+    let mut chunk_b = vec![0; CHUNK];
+    stats.hold(2 * CHUNK);
+
+    let same = loop {
+        let read_a = fill(&mut a, &mut chunk_a)?;
+        let read_b = fill(&mut b, &mut chunk_b)?;
+        stats.read(read_a + read_b);
+
+        if chunk_a[..read_a] != chunk_b[..read_b] {
+            break false;
+        }
+        if read_a < CHUNK {
+            break true;
+        }
+    };
+
+    stats.release(2 * CHUNK);
+    Ok(same)
+}
+
+/// Read until the buffer is full or the reader is exhausted, returning the number of bytes read.
+pub(crate) fn fill(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(filled)
+}
@@ -0,0 +1,119 @@
+/*!
+Special files: entries that are neither regular files nor directories, such as FIFOs, sockets, and
+device nodes. Reading them as files could block forever or never end, so they're never read.
+*/
+
+use std::{fmt, fs::FileType};
+
+/**
+The kinds of special files.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SpecialKind {
+    /// A named pipe.
+    Fifo,
+    /// A Unix domain socket.
+    Socket,
+    /// A block device node, such as a disk.
+    BlockDevice,
+    /// A character device node, such as a terminal.
+    CharacterDevice,
+    /// Anything else that's neither a regular file nor a directory.
+    Other,
+}
+
+impl SpecialKind {
+    /// The kind of special file of the given type.
+    #[cfg(unix)]
+    pub(crate) fn of(file_type: FileType) -> Self {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_fifo() {
+            Self::Fifo
+        } else if file_type.is_socket() {
+            Self::Socket
+        } else if file_type.is_block_device() {
+            Self::BlockDevice
+        } else if file_type.is_char_device() {
+            Self::CharacterDevice
+        } else {
+            Self::Other
+        }
+    }
+
+    /// The kind of special file of the given type.
+    #[cfg(not(unix))]
+    pub(crate) fn of(_file_type: FileType) -> Self {
+        Self::Other
+    }
+}
+
+impl fmt::Display for SpecialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Fifo => "fifo",
+            Self::Socket => "socket",
+            Self::BlockDevice => "block device",
+            Self::CharacterDevice => "character device",
+            Self::Other => "special file",
+        })
+    }
+}
+
+/**
+How to treat special files when reading a tree. See [`Options::special_files`].
+
+[`Options::special_files`]: crate::Options::special_files
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpecialFiles {
+    /// Fail with an error of kind [`std::io::ErrorKind::InvalidInput`] naming the special file.
+    #[default]
+    Error,
+    /// Leave special files out of the tree, as if they didn't exist.
+    Skip,
+    /// Read special files as [`Content::Special`](crate::Content::Special), which are equal if
+    /// they're of the same [`SpecialKind`].
+    CompareByType,
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, io, os::unix::net::UnixListener, path::Path};
+
+    use crate::{testing::TempDir, Content, DifferenceKind, Options, SpecialFiles, SpecialKind};
+
+    #[test]
+    fn special_files_should_follow_the_policy() {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        fs::create_dir(&a).unwrap();
+        fs::create_dir(&b).unwrap();
+        fs::write(a.join("file"), "data").unwrap();
+        fs::write(b.join("file"), "data").unwrap();
+        fs::write(b.join("socket"), "data").unwrap();
+        let _listener = UnixListener::bind(a.join("socket")).unwrap();
+
+        let error = Content::of(&a).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let options = Options::new().special_files(SpecialFiles::Skip);
+        let skipped = Content::of_with(&a, &options).unwrap();
+        assert_eq!(skipped.get("socket"), None);
+
+        let options = Options::new().special_files(SpecialFiles::CompareByType);
+        let content = Content::of_with(&a, &options).unwrap();
+        assert_eq!(
+            content.get("socket"),
+            Some(&Content::Special(SpecialKind::Socket))
+        );
+
+        let report = content.diff_against_disk_with(&b, &options).unwrap();
+        let kinds: Vec<_> = report.iter().map(|d| (d.path(), d.kind())).collect();
+        assert_eq!(kinds, [(Path::new("socket"), DifferenceKind::TypeChanged)]);
+        assert!(content
+            .diff_against_disk_with(&a, &options)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,567 @@
+/*!
+A reader of the [SQLite database file format], enough to dump a database's logical content.
+
+Only the main database file is read. A database in WAL mode should be checkpointed first, or recent
+changes still in its `-wal` file will be missed.
+
+[SQLite database file format]: https://www.sqlite.org/fileformat.html
+*/
+
+use std::{cmp::Ordering, collections::HashSet, fmt::Write, io};
+
+use crate::{inflate::invalid, Filter};
+
+/**
+Dumps SQLite databases as text: their schema, then the rows of each table in sorted order.
+
+Physically different but logically identical databases, such as a database before and after
+`VACUUM`, or two databases with different page sizes, dump to the same text. Indexes are left out,
+since they're derived from the tables. So are the rowids of tables without an `INTEGER PRIMARY KEY`,
+since `VACUUM` may renumber them.
+
+Enabled by the `sqlite` feature. Requires no dependencies.
+
+```
+use dir_compare::{filters::Sqlite, Content, Options};
+
+let options = Options::new().filter("*.db", Sqlite);
+let a = Content::of_with("fixtures/sqlite/dir-a", &options)?;
+let b = Content::of_with("fixtures/sqlite/dir-b", &options)?;
+
+assert_eq!(a, b);
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sqlite;
+
+impl Filter for Sqlite {
+    fn apply(&self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        Ok(Database::new(&bytes)?.dump()?.into_bytes())
+    }
+}
+
+const LEAF_TABLE: u8 = 0x0d;
+const INTERIOR_TABLE: u8 = 0x05;
+const LEAF_INDEX: u8 = 0x0a;
+const INTERIOR_INDEX: u8 = 0x02;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    /**
+    Order values by type, then by value. Reals are ordered totally, with NaNs of either sign at
+    either end, so that rows holding them still sort.
+    */
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (Self::Real(a), Self::Real(b)) => a.total_cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (Self::Blob(a), Self::Blob(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::Integer(_) => 1,
+            Self::Real(_) => 2,
+            Self::Text(_) => 3,
+            Self::Blob(_) => 4,
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("NULL"),
+            Self::Integer(integer) => write!(out, "{integer}").expect("writing to a string"),
+            Self::Real(real) => write!(out, "{real:?}").expect("writing to a string"),
+            Self::Text(text) => {
+                out.push('\'');
+                out.push_str(&text.replace('\'', "''"));
+                out.push('\'');
+            }
+            Self::Blob(blob) => {
+                out.push_str("X'");
+                for byte in blob {
+                    write!(out, "{byte:02X}").expect("writing to a string");
+                }
+                out.push('\'');
+            }
+        }
+    }
+}
+
+/// A row of a table, with its rowid if the table has one.
+struct Row {
+    rowid: Option<i64>,
+    values: Vec<Value>,
+}
+
+struct Database<'a> {
+    bytes: &'a [u8],
+    page_size: usize,
+    usable_size: usize,
+    encoding: u32,
+}
+
+impl<'a> Database<'a> {
+    fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        let header = bytes
+            .get(..100)
+            .filter(|header| header.starts_with(b"SQLite format 3\0"))
+            .ok_or_else(|| invalid("the data is not a SQLite database"))?;
+
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            size => usize::from(size),
+        };
+        let usable_size = Some(page_size)
+            .filter(|&page_size| page_size >= 512)
+            .and_then(|page_size| page_size.checked_sub(usize::from(header[20])))
+            .filter(|&usable_size| usable_size >= 480)
+            .ok_or_else(|| invalid("the SQLite database has an invalid page size"))?;
+        let encoding = u32::from_be_bytes([header[56], header[57], header[58], header[59]]);
+
+        Ok(Self {
+            bytes,
+            page_size,
+            usable_size,
+            encoding,
+        })
+    }
+
+    fn page(&self, number: u32) -> io::Result<&'a [u8]> {
+        let start = (number as usize)
+            .checked_sub(1)
+            .ok_or_else(|| invalid("a SQLite page number is zero"))?
+            * self.page_size;
+        self.bytes
+            .get(start..start + self.page_size)
+            .ok_or_else(|| invalid("a SQLite page is out of bounds"))
+    }
+
+    fn dump(&self) -> io::Result<String> {
+        let mut schema: Vec<(Value, Value, Value, i64)> = self
+            .rows(1)?
+            .into_iter()
+            .map(|row| {
+                let mut values = row.values.into_iter();
+                let mut next = || values.next().unwrap_or(Value::Null);
+                let (kind, name, _, root, sql) = (next(), next(), next(), next(), next());
+                let root = match root {
+                    Value::Integer(root) => root,
+                    _ => 0,
+                };
+                (kind, name, sql, root)
+            })
+            .collect();
+        schema.sort_by(|a, b| {
+            (a.0.total_cmp(&b.0))
+                .then_with(|| a.1.total_cmp(&b.1))
+                .then_with(|| a.2.total_cmp(&b.2))
+                .then_with(|| a.3.cmp(&b.3))
+        });
+
+        let mut out = String::new();
+        for (kind, name, sql, _) in &schema {
+            // Indexes created for constraints have no SQL, and are derived anyway.
+            if *sql == Value::Null {
+                continue;
+            }
+            out.push_str("schema ");
+            for value in [kind, name, sql] {
+                value.write(&mut out);
+                out.push(' ');
+            }
+            out.pop();
+            out.push('\n');
+        }
+
+        for (kind, name, sql, root) in &schema {
+            if *kind != Value::Text("table".to_owned()) || *root <= 0 {
+                continue;
+            }
+            let alias = match sql {
+                Value::Text(sql) => rowid_alias(sql),
+                _ => None,
+            };
+
+            let mut rows: Vec<Vec<Value>> = self
+                .rows(*root as u32)?
+                .into_iter()
+                .map(|mut row| {
+                    // The column aliasing the rowid is stored as NULL, with its value in the rowid.
+                    if let (Some(alias), Some(rowid)) = (alias, row.rowid) {
+                        if let Some(value) = row.values.get_mut(alias) {
+                            *value = Value::Integer(rowid);
+                        }
+                    }
+                    row.values
+                })
+                .collect();
+            rows.sort_by(|a, b| compare_rows(a, b));
+
+            for row in rows {
+                out.push_str("row ");
+                name.write(&mut out);
+                out.push_str(" (");
+                for (index, value) in row.iter().enumerate() {
+                    if index > 0 {
+                        out.push_str(", ");
+                    }
+                    value.write(&mut out);
+                }
+                out.push_str(")\n");
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Read every row of the table or index whose b-tree is rooted at the given page.
+    fn rows(&self, root: u32) -> io::Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        let mut stack = vec![root];
+        let mut visited = HashSet::new();
+
+        while let Some(number) = stack.pop() {
+            if !visited.insert(number) {
+                return Err(invalid("a SQLite b-tree has a cycle"));
+            }
+            let page = self.page(number)?;
+            // The first page starts with the database header.
+            let offset = if number == 1 { 100 } else { 0 };
+            let header = &page[offset..];
+            let kind = header[0];
+            let cells = usize::from(u16::from_be_bytes([header[3], header[4]]));
+            let interior = kind == INTERIOR_TABLE || kind == INTERIOR_INDEX;
+            let pointers = offset + if interior { 12 } else { 8 };
+
+            if interior {
+                stack.push(read_u32(header, 8)?);
+            }
+
+            for cell in 0..cells {
+                let pointer = pointers + 2 * cell;
+                let cell = page
+                    .get(pointer..pointer + 2)
+                    .map(|pointer| usize::from(u16::from_be_bytes([pointer[0], pointer[1]])))
+                    .ok_or_else(|| invalid("a SQLite cell pointer is out of bounds"))?;
+                let cell = page
+                    .get(cell..)
+                    .ok_or_else(|| invalid("a SQLite cell is out of bounds"))?;
+
+                match kind {
+                    LEAF_TABLE => {
+                        let (size, read) = varint(cell)?;
+                        let (rowid, read_rowid) = varint(&cell[read..])?;
+                        let payload = self.payload(&cell[read + read_rowid..], size, true)?;
+                        rows.push(Row {
+                            rowid: Some(rowid as i64),
+                            values: self.record(&payload)?,
+                        });
+                    }
+                    INTERIOR_TABLE => stack.push(read_u32(cell, 0)?),
+                    LEAF_INDEX => {
+                        let (size, read) = varint(cell)?;
+                        let payload = self.payload(&cell[read..], size, false)?;
+                        rows.push(Row {
+                            rowid: None,
+                            values: self.record(&payload)?,
+                        });
+                    }
+                    INTERIOR_INDEX => {
+                        stack.push(read_u32(cell, 0)?);
+                        let (size, read) = varint(&cell[4..])?;
+                        let payload = self.payload(&cell[4 + read..], size, false)?;
+                        rows.push(Row {
+                            rowid: None,
+                            values: self.record(&payload)?,
+                        });
+                    }
+                    _ => return Err(invalid("a SQLite page has an invalid type")),
+                }
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// Gather a cell's payload of the given size, following its overflow pages if it has any.
+    fn payload(&self, local: &[u8], size: u64, table: bool) -> io::Result<Vec<u8>> {
+        let size = usize::try_from(size).map_err(|_| invalid("a SQLite payload is too large"))?;
+        let usable = self.usable_size;
+        let max_local = if table {
+            usable - 35
+        } else {
+            (usable - 12) * 64 / 255 - 23
+        };
+
+        if size <= max_local {
+            return local
+                .get(..size)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| invalid("a SQLite payload is out of bounds"));
+        }
+
+        let min_local = (usable - 12) * 32 / 255 - 23;
+        let spilled = min_local + (size - min_local) % (usable - 4);
+        let local_size = if spilled <= max_local {
+            spilled
+        } else {
+            min_local
+        };
+
+        let mut payload = local
+            .get(..local_size)
+            .ok_or_else(|| invalid("a SQLite payload is out of bounds"))?
+            .to_vec();
+        let mut next = read_u32(local, local_size)?;
+        let mut visited = HashSet::new();
+
+        while payload.len() < size {
+            if next == 0 || !visited.insert(next) {
+                return Err(invalid("a SQLite overflow chain is broken"));
+            }
+            let page = self.page(next)?;
+            let take = (size - payload.len()).min(usable - 4);
+            payload.extend_from_slice(&page[4..4 + take]);
+            next = read_u32(page, 0)?;
+        }
+
+        Ok(payload)
+    }
+
+    /// Decode a record into its values.
+    fn record(&self, record: &[u8]) -> io::Result<Vec<Value>> {
+        let (header_size, mut position) = varint(record)?;
+        let header_size = header_size as usize;
+        let mut body = header_size;
+        let mut values = Vec::new();
+
+        while position < header_size {
+            let (serial, read) = varint(
+                record
+                    .get(position..)
+                    .ok_or_else(|| invalid("a SQLite record is truncated"))?,
+            )?;
+            position += read;
+
+            let size = match serial {
+                0 | 8 | 9 => 0,
+                1..=4 => serial as usize,
+                5 => 6,
+                6 | 7 => 8,
+                10 | 11 => return Err(invalid("a SQLite record has a reserved type")),
+                _ => (serial as usize - 12) / 2,
+            };
+            let bytes = record
+                .get(body..body + size)
+                .ok_or_else(|| invalid("a SQLite record is truncated"))?;
+            body += size;
+
+            values.push(match serial {
+                0 => Value::Null,
+                8 => Value::Integer(0),
+                9 => Value::Integer(1),
+                1..=6 => {
+                    // Sign-extend the big-endian integer.
+                    let mut integer = if bytes[0] & 0x80 == 0 { 0 } else { -1i64 };
+                    for &byte in bytes {
+                        integer = integer << 8 | i64::from(byte);
+                    }
+                    Value::Integer(integer)
+                }
+                7 => Value::Real(f64::from_be_bytes(
+                    bytes.try_into().expect("the size was checked"),
+                )),
+                _ if serial % 2 == 0 => Value::Blob(bytes.to_vec()),
+                _ => Value::Text(self.text(bytes)),
+            });
+        }
+
+        Ok(values)
+    }
+
+    fn text(&self, bytes: &[u8]) -> String {
+        let units = bytes.chunks_exact(2).map(|pair| [pair[0], pair[1]]);
+        match self.encoding {
+            2 => String::from_utf16_lossy(&units.map(u16::from_le_bytes).collect::<Vec<_>>()),
+            3 => String::from_utf16_lossy(&units.map(u16::from_be_bytes).collect::<Vec<_>>()),
+            _ => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid("a SQLite page number is out of bounds"))
+}
+
+/// Decode a SQLite varint, returning it and the number of bytes it took.
+fn varint(bytes: &[u8]) -> io::Result<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, &byte) in bytes.iter().enumerate().take(9) {
+        if index == 8 {
+            return Ok((value << 8 | u64::from(byte), 9));
+        }
+        value = value << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            return Ok((value, index + 1));
+        }
+    }
+    Err(invalid("a SQLite varint is truncated"))
+}
+
+/// The index of the column aliasing the rowid in a `CREATE TABLE` statement, if any: the one
+/// declared `INTEGER PRIMARY KEY`, or named by a table constraint `PRIMARY KEY` of one column of
+/// type `INTEGER`.
+fn rowid_alias(sql: &str) -> Option<usize> {
+    let upper = sql.to_ascii_uppercase();
+    if upper.contains("WITHOUT ROWID") {
+        return None;
+    }
+
+    let body = &upper[upper.find('(')? + 1..upper.rfind(')')?];
+    let mut definitions = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, character) in body.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                definitions.push(body[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    definitions.push(body[start..].trim());
+
+    let is_constraint = |definition: &str| {
+        ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+            .iter()
+            .any(|keyword| definition.split_whitespace().next() == Some(keyword))
+    };
+    let columns: Vec<&str> = definitions
+        .iter()
+        .copied()
+        .filter(|definition| !is_constraint(definition))
+        .collect();
+    let column_type = |column: &str| column.split_whitespace().nth(1).map(str::to_owned);
+    let unquote = |name: &str| {
+        name.trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'))
+            .to_owned()
+    };
+
+    if let Some(index) = columns.iter().position(|column| {
+        column_type(column).as_deref() == Some("INTEGER") && column.contains("PRIMARY KEY")
+    }) {
+        return Some(index);
+    }
+
+    let constraint = definitions
+        .iter()
+        .filter(|definition| is_constraint(definition))
+        .find_map(|definition| definition.split_once("PRIMARY KEY"))?
+        .1;
+    let key = constraint[constraint.find('(')? + 1..constraint.find(')')?].trim();
+    if key.contains(',') {
+        return None;
+    }
+    let key = unquote(key.split_whitespace().next()?);
+
+    columns.iter().position(|column| {
+        column
+            .split_whitespace()
+            .next()
+            .is_some_and(|name| unquote(name) == key)
+            && column_type(column).as_deref() == Some("INTEGER")
+    })
+}
+
+/// Order rows lexicographically, by [`Value::total_cmp`].
+fn compare_rows(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.total_cmp(b))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare_rows, rowid_alias, Database, Value};
+
+    #[test]
+    fn rowid_aliases_should_be_found() {
+        assert_eq!(
+            rowid_alias("CREATE TABLE t(a TEXT, id INTEGER PRIMARY KEY)"),
+            Some(1)
+        );
+        assert_eq!(
+            rowid_alias("CREATE TABLE t(id INTEGER, PRIMARY KEY(id))"),
+            Some(0)
+        );
+        assert_eq!(rowid_alias("CREATE TABLE t(id INT PRIMARY KEY)"), None);
+        assert_eq!(rowid_alias("CREATE TABLE t(a, b)"), None);
+    }
+
+    #[test]
+    fn changed_rows_should_be_dumped_differently() {
+        let original = std::fs::read("fixtures/sqlite/dir-a/app.db").unwrap();
+        let changed = std::fs::read("fixtures/sqlite/changed/app.db").unwrap();
+        let original = Database::new(&original).unwrap().dump().unwrap();
+        let changed = Database::new(&changed).unwrap().dump().unwrap();
+
+        assert!(original.contains("row 'users' (3, 'user3', "));
+        assert!(changed.contains("row 'users' (3, 'renamed', "));
+    }
+
+    #[test]
+    fn invalid_page_sizes_should_be_rejected() {
+        let mut database = std::fs::read("fixtures/sqlite/dir-a/app.db").unwrap();
+        for (page_size, reserved) in [(0u16, 0), (2, 0), (256, 0), (512, 64)] {
+            database[16..18].copy_from_slice(&page_size.to_be_bytes());
+            database[20] = reserved;
+            let error = Database::new(&database).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rows_with_nans_should_sort() {
+        let mut rows = [
+            vec![Value::Real(f64::NAN), Value::Integer(1)],
+            vec![Value::Real(1.5), Value::Null],
+            vec![Value::Text("a".to_owned())],
+            vec![Value::Real(f64::NAN), Value::Integer(0)],
+            vec![Value::Null],
+        ];
+        rows.sort_by(|a, b| compare_rows(a, b));
+
+        assert_eq!(rows[0], [Value::Null]);
+        assert_eq!(rows[1], [Value::Real(1.5), Value::Null]);
+        assert_eq!(rows[2][1], Value::Integer(0));
+        assert_eq!(rows[4], [Value::Text("a".to_owned())]);
+        assert_eq!(
+            Value::Real(f64::NAN).total_cmp(&Value::Real(f64::NAN)),
+            Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,184 @@
+/*!
+Resource usage of comparisons.
+*/
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{EntryKind, TreeProfile};
+
+/**
+The resources a comparison used.
+
+Memory is an estimate of the crate's own allocations for the comparison—paths, buffers, and the
+report—at their peak. It doesn't include the allocator's overhead or memory used by the
+operating system on the crate's behalf, such as the page cache.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    bytes_read: u64,
+    linked_files: u64,
+    held_memory: u64,
+    peak_memory: u64,
+    wall_time: Duration,
+    cpu_time: Option<Duration>,
+    entry_times: Vec<(PathBuf, Duration)>,
+    profiles: Option<(TreeProfile, TreeProfile)>,
+}
+
+impl Stats {
+    /// The number of file bytes read from both sides.
+    #[must_use]
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /**
+    The number of files that were found to match without being read, because they're hard links
+    to the same file, or to files that were already compared.
+    */
+    #[must_use]
+    pub fn linked_files(&self) -> u64 {
+        self.linked_files
+    }
+
+    /// The estimated peak memory, in bytes.
+    #[must_use]
+    pub fn peak_memory(&self) -> u64 {
+        self.peak_memory
+    }
+
+    /// The time that elapsed during the comparison.
+    #[must_use]
+    pub fn wall_time(&self) -> Duration {
+        self.wall_time
+    }
+
+    /**
+    The CPU time the comparing thread spent, in user and kernel mode. Only available on Linux,
+    where it's read from `/proc/thread-self/schedstat`.
+    */
+    #[must_use]
+    pub fn cpu_time(&self) -> Option<Duration> {
+        self.cpu_time
+    }
+
+    /**
+    How long each directory took to list, and each large file took to compare, in the order they
+    were reached, if enabled with [`Options::time_entries`](crate::Options::time_entries). Paths are
+    relative to the roots. A directory's time covers only listing its own children.
+    */
+    #[must_use]
+    pub fn entry_times(&self) -> &[(PathBuf, Duration)] {
+        &self.entry_times
+    }
+
+    /**
+    The total of the [`Stats::entry_times`] of the entry at the given path, relative to the roots,
+    and of its descendants: roughly how long the comparison spent in that subtree.
+    */
+    #[must_use]
+    pub fn subtree_time(&self, path: impl AsRef<Path>) -> Duration {
+        let path = path.as_ref();
+        self.entry_times
+            .iter()
+            .filter(|(entry, _)| entry.starts_with(path))
+            .map(|(_, time)| *time)
+            .sum()
+    }
+
+    /**
+    Profiles of the first and second trees, by the sizes and extensions of their files, if enabled
+    with [`Options::profile_trees`](crate::Options::profile_trees).
+    */
+    #[must_use]
+    pub fn profiles(&self) -> Option<(&TreeProfile, &TreeProfile)> {
+        self.profiles.as_ref().map(|(a, b)| (a, b))
+    }
+
+    pub(crate) fn read(&mut self, bytes: usize) {
+        self.bytes_read += bytes as u64;
+    }
+
+    /// Add the given statistics, of a comparison made after these, to these.
+    pub(crate) fn add(&mut self, other: &Self) {
+        self.bytes_read += other.bytes_read;
+        self.linked_files += other.linked_files;
+        // Comparisons made one after another hold their memory one after another.
+        self.peak_memory = self.peak_memory.max(other.peak_memory);
+        self.wall_time += other.wall_time;
+        self.cpu_time = self.cpu_time.zip(other.cpu_time).map(|(a, b)| a + b);
+        self.entry_times.extend_from_slice(&other.entry_times);
+        if let Some((other_a, other_b)) = &other.profiles {
+            let (a, b) = self.profiles.get_or_insert_with(Default::default);
+            a.add(other_a);
+            b.add(other_b);
+        }
+    }
+
+    pub(crate) fn time_entry(&mut self, path: PathBuf, time: Duration) {
+        self.entry_times.push((path, time));
+    }
+
+    /// Count the entry at the given path in the profile of each tree it's in.
+    pub(crate) fn profile(
+        &mut self,
+        path: &Path,
+        kinds: (Option<EntryKind>, Option<EntryKind>),
+        sizes: (Option<u64>, Option<u64>),
+    ) {
+        let (a, b) = self.profiles.get_or_insert_with(Default::default);
+        if let Some(kind) = kinds.0 {
+            a.count(path, kind, sizes.0);
+        }
+        if let Some(kind) = kinds.1 {
+            b.count(path, kind, sizes.1);
+        }
+    }
+
+    pub(crate) fn link(&mut self) {
+        self.linked_files += 1;
+    }
+
+    pub(crate) fn hold(&mut self, bytes: usize) {
+        self.held_memory += bytes as u64;
+        self.peak_memory = self.peak_memory.max(self.held_memory);
+    }
+
+    pub(crate) fn release(&mut self, bytes: usize) {
+        self.held_memory = self.held_memory.saturating_sub(bytes as u64);
+    }
+
+    /// Run the given operation, adding its wall and CPU time to the statistics.
+    pub(crate) fn time<T>(&mut self, operation: impl FnOnce(&mut Self) -> T) -> T {
+        let cpu_start = thread_cpu_time();
+        let start = Instant::now();
+        let result = operation(self);
+
+        self.wall_time += start.elapsed();
+        self.cpu_time = match (cpu_start, thread_cpu_time()) {
+            (Some(start), Some(end)) => {
+                Some(self.cpu_time.unwrap_or_default() + end.saturating_sub(start))
+            }
+            _ => None,
+        };
+
+        result
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn thread_cpu_time() -> Option<Duration> {
+    // The first field is the time spent on the CPU, in nanoseconds.
+    let schedstat = std::fs::read_to_string("/proc/thread-self/schedstat").ok()?;
+    let nanoseconds = schedstat.split_whitespace().next()?.parse().ok()?;
+
+    Some(Duration::from_nanos(nanoseconds))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_cpu_time() -> Option<Duration> {
+    None
+}
@@ -0,0 +1,331 @@
+/*!
+Planning the operations that synchronize trees, in one direction or both.
+*/
+
+use std::path::{Path, PathBuf};
+
+use crate::{ChangeKind, DiffReport, DifferenceKind, ThreeWayReport};
+
+/**
+A single step in making a target tree match a source tree, at a path relative to their roots.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Copy the entry at the path from the source to the target, replacing the file there, if any.
+    /// A directory is copied as a new, empty directory; its children are copied by their own
+    /// operations.
+    Copy(PathBuf),
+    /// Remove the entry at the path from the target. A directory's children are removed by their
+    /// own operations first.
+    Remove(PathBuf),
+}
+
+impl Operation {
+    /// The path the operation applies to, relative to the roots of the trees.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Copy(path) | Self::Remove(path) => path,
+        }
+    }
+}
+
+/**
+The operations that make a target tree match a source tree, in the order they must be applied:
+removals first, deepest first, and then copies, parents first.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    operations: Vec<Operation>,
+}
+
+impl Plan {
+    /**
+    Plan the operations for the given differences, each located by its path and ordered by path.
+    "Added" and "removed" are relative to the target; that is, an added entry exists only in the
+    source.
+    */
+    pub(crate) fn new<'a>(
+        differences: impl IntoIterator<Item = (&'a Path, DifferenceKind)>,
+    ) -> Self {
+        let mut removals = Vec::new();
+        let mut copies = Vec::new();
+
+        for (path, kind) in differences {
+            // There's no telling what to do with an entry that was changing as it was compared.
+            if kind == DifferenceKind::Volatile {
+                continue;
+            }
+            if matches!(kind, DifferenceKind::Removed | DifferenceKind::TypeChanged) {
+                removals.push(Operation::Remove(path.to_path_buf()));
+            }
+            if kind != DifferenceKind::Removed {
+                copies.push(Operation::Copy(path.to_path_buf()));
+            }
+        }
+
+        removals.reverse();
+        removals.append(&mut copies);
+        Self {
+            operations: removals,
+        }
+    }
+
+    /// Whether the trees already match, so there's nothing to do.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// The operations, in the order they must be applied.
+    #[must_use]
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Iterate over the operations, in the order they must be applied.
+    pub fn iter(&self) -> std::slice::Iter<'_, Operation> {
+        self.operations.iter()
+    }
+
+    /// A plan of the given operations, which must already be in the order they must be applied.
+    pub(crate) fn from_operations(operations: Vec<Operation>) -> Self {
+        Self { operations }
+    }
+
+    /// Split the plan in two at the given index: the operations before it, and those from it on.
+    pub(crate) fn split_at(&self, index: usize) -> (Self, Self) {
+        let (before, after) = self.operations.split_at(index);
+        (
+            Self {
+                operations: before.to_vec(),
+            },
+            Self {
+                operations: after.to_vec(),
+            },
+        )
+    }
+}
+
+impl IntoIterator for Plan {
+    type Item = Operation;
+    type IntoIter = std::vec::IntoIter<Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.operations.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Plan {
+    type Item = &'a Operation;
+    type IntoIter = std::slice::Iter<'a, Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.operations.iter()
+    }
+}
+
+/**
+The plans that bring two trees, "ours" and "theirs", back in sync after both changed from a common
+base: the changes only one side made are copied to the other, and the paths both sides changed
+differently are left for the caller to resolve.
+
+See [`ThreeWayReport::sync_plan`].
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BidirectionalPlan {
+    to_theirs: Plan,
+    to_ours: Plan,
+    conflicts: Vec<PathBuf>,
+}
+
+impl BidirectionalPlan {
+    /// The plan that copies the changes only "ours" made to "theirs".
+    #[must_use]
+    pub fn to_theirs(&self) -> &Plan {
+        &self.to_theirs
+    }
+
+    /// The plan that copies the changes only "theirs" made to "ours".
+    #[must_use]
+    pub fn to_ours(&self) -> &Plan {
+        &self.to_ours
+    }
+
+    /**
+    The paths that can't be synchronized safely, ordered by path: those both sides changed
+    differently, and the directories that one side removed or replaced while the other changed
+    something inside them. Nothing at or beneath these paths is planned.
+    */
+    #[must_use]
+    pub fn conflicts(&self) -> &[PathBuf] {
+        &self.conflicts
+    }
+
+    /// Whether any path can't be synchronized safely.
+    #[must_use]
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+}
+
+impl DiffReport {
+    /**
+    Plan the operations that make the first of the compared trees match the second: copying the
+    entries that were added or modified, and removing those that were removed. Entries that changed
+    type are removed, then copied.
+
+    ```
+    use dir_compare::{Content, Operation};
+
+    let target = Content::of("fixtures/not-equivalent/dir-a")?;
+    let source = Content::of("fixtures/not-equivalent/dir-b")?;
+    let plan = target.diff(&source).sync_plan();
+
+    assert_eq!(plan.operations(), [Operation::Copy("subdir/test2.txt".into())]);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn sync_plan(&self) -> Plan {
+        Plan::new(
+            self.iter()
+                .map(|difference| (difference.path(), difference.kind())),
+        )
+    }
+}
+
+impl ThreeWayReport {
+    /**
+    Plan the synchronization of "ours" and "theirs": each change only one side made is safe to copy
+    to the other side, while each conflict is withheld from both plans.
+
+    ```
+    use std::path::Path;
+    use dir_compare::{Content, Operation};
+
+    let base = Content::of("fixtures/three-way/base")?;
+    let ours = Content::of("fixtures/three-way/ours")?;
+    let theirs = Content::of("fixtures/three-way/theirs")?;
+    let plan = base.three_way(&ours, &theirs).sync_plan();
+
+    assert_eq!(plan.to_theirs().operations(), [Operation::Copy("a.txt".into())]);
+    assert_eq!(plan.conflicts(), [Path::new("c.txt")]);
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn sync_plan(&self) -> BidirectionalPlan {
+        let conflicts: Vec<&Path> = self.conflicts().collect();
+        let beneath_conflict = |path: &Path| {
+            conflicts
+                .iter()
+                .any(|conflict| path != *conflict && path.starts_with(conflict))
+        };
+        let above_conflict = |path: &Path| {
+            conflicts
+                .iter()
+                .any(|conflict| path != *conflict && conflict.starts_with(path))
+        };
+
+        let mut to_theirs = Vec::new();
+        let mut to_ours = Vec::new();
+        let mut withheld = Vec::new();
+
+        for change in self
+            .iter()
+            .filter(|change| !beneath_conflict(change.path()))
+        {
+            let path = change.path();
+            let (plan, kind) = match change.kind() {
+                ChangeKind::Ours(kind) => (&mut to_theirs, kind),
+                ChangeKind::Theirs(kind) => (&mut to_ours, kind),
+                ChangeKind::Both(_) => continue,
+                ChangeKind::Conflict => {
+                    withheld.push(path.to_path_buf());
+                    continue;
+                }
+            };
+
+            // Removing or replacing a directory would discard the other side's changes inside it.
+            let destructive = matches!(kind, DifferenceKind::Removed | DifferenceKind::TypeChanged);
+            if destructive && above_conflict(path) {
+                withheld.push(path.to_path_buf());
+            } else {
+                plan.push((path, kind));
+            }
+        }
+
+        BidirectionalPlan {
+            to_theirs: Plan::new(to_theirs),
+            to_ours: Plan::new(to_ours),
+            conflicts: withheld,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::Operation;
+    use crate::{testing::TempDir, Content};
+
+    #[test]
+    fn one_sided_changes_should_be_copied_to_the_other_side() {
+        let base = Content::of("fixtures/three-way/base").unwrap();
+        let ours = Content::of("fixtures/three-way/ours").unwrap();
+        let theirs = Content::of("fixtures/three-way/theirs").unwrap();
+        let plan = base.three_way(&ours, &theirs).sync_plan();
+
+        assert_eq!(
+            plan.to_theirs().operations(),
+            [Operation::Copy("a.txt".into())]
+        );
+        assert_eq!(
+            plan.to_ours().operations(),
+            [
+                Operation::Copy("b.txt".into()),
+                Operation::Copy("e.txt".into())
+            ]
+        );
+        assert_eq!(plan.conflicts(), [Path::new("c.txt")]);
+    }
+
+    #[test]
+    fn removals_should_be_ordered_and_withheld_around_conflicts() {
+        let temp = TempDir::new();
+        let [base, ours, theirs] = ["base", "ours", "theirs"].map(|name| temp.path().join(name));
+        for root in [&base, &ours, &theirs] {
+            fs::create_dir_all(root.join("gone/deep")).unwrap();
+            fs::write(root.join("gone/deep/file.txt"), "file").unwrap();
+            fs::create_dir_all(root.join("kept")).unwrap();
+            fs::write(root.join("kept/file.txt"), "file").unwrap();
+        }
+        // Ours removes both directories, but theirs changes a file in one of them.
+        fs::remove_dir_all(ours.join("gone")).unwrap();
+        fs::remove_dir_all(ours.join("kept")).unwrap();
+        fs::write(theirs.join("kept/file.txt"), "changed").unwrap();
+
+        let base = Content::of(&base).unwrap();
+        let ours = Content::of(&ours).unwrap();
+        let theirs = Content::of(&theirs).unwrap();
+        let plan = base.three_way(&ours, &theirs).sync_plan();
+
+        assert_eq!(
+            plan.to_theirs().operations(),
+            [
+                Operation::Remove("gone/deep/file.txt".into()),
+                Operation::Remove("gone/deep".into()),
+                Operation::Remove("gone".into()),
+            ]
+        );
+        assert!(plan.to_ours().is_empty());
+        assert_eq!(
+            plan.conflicts(),
+            [Path::new("kept"), Path::new("kept/file.txt")]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+/*!
+Instrumentation of comparisons, for finding out which entries are slow, or which one a comparison
+failed on.
+*/
+
+use std::{
+    fmt::{self, Display},
+    io,
+    path::Path,
+    time::Duration,
+};
+
+use crate::DifferenceKind;
+
+/**
+Receives the [`TraceEvent`]s of comparisons, as configured with
+[`Options::tracer`](crate::Options::tracer). Implemented for closures.
+
+Events are received on the comparing thread, as they happen, so a tracer that's slow slows down the
+comparison. One can forward them to a logging or tracing framework, such as `tracing`:
+
+```
+use dir_compare::{Comparison, Options, TraceEvent};
+
+let options = Options::new().tracer(|event: &TraceEvent<'_>| eprintln!("dir-compare: {event}"));
+let comparison = Comparison::between(
+    "fixtures/not-equivalent/dir-a",
+    "fixtures/not-equivalent/dir-b",
+    &options,
+)?;
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
+Enabled by the `tracing` feature. Requires no dependencies.
+*/
+#[cfg(feature = "tracing")]
+pub trait Tracer: Send + Sync {
+    /// Receive an event.
+    fn event(&self, event: &TraceEvent<'_>);
+}
+
+#[cfg(feature = "tracing")]
+impl<F: Fn(&TraceEvent<'_>) + Send + Sync> Tracer for F {
+    fn event(&self, event: &TraceEvent<'_>) {
+        self(event);
+    }
+}
+
+/**
+Something that happened while comparing an entry, at the given path relative to the roots. For
+each entry, it's first [`TraceEvent::Entered`], then any of the others, ending with
+[`TraceEvent::Decided`] or [`TraceEvent::Failed`].
+
+Renders as a line for a log, such as `compared "test.txt": same, by content, 20 bytes read in 41µs`.
+*/
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum TraceEvent<'a> {
+    /// The comparison reached the entry.
+    Entered { path: &'a Path },
+    /// The entry's children were listed, in either tree or both, and merged by name.
+    Listed {
+        path: &'a Path,
+        children: usize,
+        time: Duration,
+    },
+    /// The entry is a file in both trees, and they were compared.
+    Compared {
+        path: &'a Path,
+        same: bool,
+        evidence: Evidence,
+        bytes_read: u64,
+        time: Duration,
+    },
+    /// The entry was found to differ, or not, from when it was reached.
+    Decided {
+        path: &'a Path,
+        difference: Option<DifferenceKind>,
+        time: Duration,
+    },
+    /// Reading the entry failed, which fails the comparison.
+    Failed {
+        path: &'a Path,
+        error: &'a io::Error,
+    },
+}
+
+/// What two files were found to match or differ by. See [`TraceEvent::Compared`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Evidence {
+    /// Their sizes or, where they're compared, their modification times, which differ.
+    Metadata,
+    /// Their identities, as hard links to the same file or to files already compared.
+    Link,
+    /// A recheck, which trusts them to be as they were in an earlier comparison.
+    Recheck,
+    /// Their digests, through a cache, for which only the files not in it were read.
+    Digest,
+    /// Their bytes.
+    Content,
+}
+
+impl Display for TraceEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Entered { path } => write!(f, "entered {path:?}"),
+            Self::Listed {
+                path,
+                children,
+                time,
+            } => write!(f, "listed {path:?}: {children} children in {time:?}"),
+            Self::Compared {
+                path,
+                same,
+                evidence,
+                bytes_read,
+                time,
+            } => write!(
+                f,
+                "compared {path:?}: {}, by {evidence}, {bytes_read} bytes read in {time:?}",
+                if same { "same" } else { "different" }
+            ),
+            Self::Decided {
+                path,
+                difference,
+                time,
+            } => write!(
+                f,
+                "decided {path:?}: {} in {time:?}",
+                difference.map_or("equal", DifferenceKind::name)
+            ),
+            Self::Failed { path, error } => write!(f, "failed {path:?}: {error}"),
+        }
+    }
+}
+
+impl Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Metadata => "metadata",
+            Self::Link => "link",
+            Self::Recheck => "recheck",
+            Self::Digest => "digest",
+            Self::Content => "content",
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{Evidence, TraceEvent};
+    use crate::{Comparison, Options};
+
+    #[test]
+    fn comparisons_should_be_traced_when_enabled() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        let options = Options::new().tracer(move |event: &TraceEvent<'_>| {
+            let summary = match *event {
+                TraceEvent::Listed { path, children, .. } => format!("{path:?} {children}"),
+                TraceEvent::Compared {
+                    path,
+                    same,
+                    evidence,
+                    ..
+                } => format!("{path:?} {same} {evidence}"),
+                TraceEvent::Decided {
+                    path, difference, ..
+                } => format!("{path:?} {difference:?}"),
+                event => event.to_string(),
+            };
+            log.lock().unwrap().push(summary);
+        });
+        Comparison::between(
+            "fixtures/not-equivalent/dir-a",
+            "fixtures/not-equivalent/dir-b",
+            &options,
+        )
+        .unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            [
+                r#"entered """#,
+                r#""" 2"#,
+                r#""" None"#,
+                r#"entered "subdir""#,
+                r#""subdir" 1"#,
+                r#""subdir" None"#,
+                r#"entered "subdir/test2.txt""#,
+                r#""subdir/test2.txt" false metadata"#,
+                r#""subdir/test2.txt" Some(Modified)"#,
+                r#"entered "test.txt""#,
+                r#""test.txt" true content"#,
+                r#""test.txt" None"#,
+            ]
+        );
+        assert_eq!(Evidence::Digest.to_string(), "digest");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_entry_a_comparison_fails_on_should_be_traced() {
+        use std::{fs, os::unix::fs::symlink};
+
+        use crate::testing::TempDir;
+
+        // A link to nothing can't be listed.
+        let temp = TempDir::new();
+        fs::create_dir_all(temp.path().join("logs")).unwrap();
+        symlink(
+            temp.path().join("rotated.log"),
+            temp.path().join("logs/current.log"),
+        )
+        .unwrap();
+
+        let failed = Arc::new(Mutex::new(None));
+        let log = Arc::clone(&failed);
+        let options = Options::new().tracer(move |event: &TraceEvent<'_>| {
+            if let TraceEvent::Failed { path, .. } = event {
+                *log.lock().unwrap() = Some(path.to_path_buf());
+            }
+        });
+        assert!(Comparison::between(temp.path(), temp.path(), &options).is_err());
+        assert_eq!(failed.lock().unwrap().as_deref(), Some(Path::new("logs")));
+    }
+}
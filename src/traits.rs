@@ -0,0 +1,412 @@
+/*!
+The standard traits of [`Entry`] and [`Content`].
+
+Each is implemented with an explicit stack rather than by recursion, so that arbitrarily deep trees
+can't overflow the call stack.
+*/
+
+use std::{
+    cmp::Ordering,
+    ffi::OsStr,
+    fmt,
+    hash::{Hash, Hasher},
+    mem, slice,
+};
+
+use crate::{Content, Entry};
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key() && self.content == other.content
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialEq for Content {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (Self::File(a), Self::File(b)) => {
+                    if a != b {
+                        return false;
+                    }
+                }
+                (Self::Special(a), Self::Special(b)) => {
+                    if a != b {
+                        return false;
+                    }
+                }
+                (Self::Entries(a), Self::Entries(b)) => {
+                    if a.len() != b.len() {
+                        return false;
+                    }
+                    for (a, b) in a.iter().zip(b) {
+                        if a.key() != b.key() {
+                            return false;
+                        }
+                        stack.push((&a.content, &b.content));
+                    }
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl Eq for Content {}
+
+/// A comparison yet to be made while ordering two trees.
+enum Pending<'a> {
+    Names(&'a OsStr, &'a OsStr),
+    Contents(&'a Content, &'a Content),
+    Lengths(usize, usize),
+}
+
+/// Order the pending comparisons lexicographically, stopping at the first that isn't equal.
+fn order(mut stack: Vec<Pending>) -> Ordering {
+    while let Some(pending) = stack.pop() {
+        let ordering = match pending {
+            Pending::Names(a, b) => a.cmp(b),
+            Pending::Lengths(a, b) => a.cmp(&b),
+            Pending::Contents(Content::File(a), Content::File(b)) => a.cmp(b),
+            Pending::Contents(Content::Special(a), Content::Special(b)) => a.cmp(b),
+            Pending::Contents(Content::File(_), _) => Ordering::Less,
+            Pending::Contents(_, Content::File(_)) => Ordering::Greater,
+            Pending::Contents(Content::Special(_), Content::Entries(_)) => Ordering::Less,
+            Pending::Contents(Content::Entries(_), Content::Special(_)) => Ordering::Greater,
+            Pending::Contents(Content::Entries(a), Content::Entries(b)) => {
+                // Compare the entries pairwise and, if one list is a prefix of the other, by
+                // length. The stack pops last-in-first-out, so push everything in reverse.
+                stack.push(Pending::Lengths(a.len(), b.len()));
+                for (a, b) in a.iter().zip(b).rev() {
+                    stack.push(Pending::Contents(&a.content, &b.content));
+                    stack.push(Pending::Names(a.key(), b.key()));
+                }
+                Ordering::Equal
+            }
+        };
+
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/**
+Entries are ordered by name, then by content.
+*/
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        order(vec![
+            Pending::Contents(&self.content, &other.content),
+            Pending::Names(self.key(), other.key()),
+        ])
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/**
+Files are ordered before special files, and special files before directories. Files are ordered by
+their bytes, special files by their kinds, and directories by their entries, lexicographically.
+*/
+impl Ord for Content {
+    fn cmp(&self, other: &Self) -> Ordering {
+        order(vec![Pending::Contents(self, other)])
+    }
+}
+
+impl PartialOrd for Content {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Entry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+        self.content.hash(state);
+    }
+}
+
+impl Hash for Content {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut stack = vec![self];
+
+        while let Some(content) = stack.pop() {
+            match content {
+                Self::File(bytes) => {
+                    state.write_u8(0);
+                    bytes.hash(state);
+                }
+                Self::Special(kind) => {
+                    state.write_u8(2);
+                    kind.hash(state);
+                }
+                Self::Entries(entries) => {
+                    state.write_u8(1);
+                    state.write_usize(entries.len());
+                    for entry in entries {
+                        entry.key().hash(state);
+                    }
+                    stack.extend(entries.iter().rev().map(|entry| &entry.content));
+                }
+            }
+        }
+    }
+}
+
+impl Clone for Entry {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            key: self.key.clone(),
+            content: self.content.clone(),
+        }
+    }
+}
+
+impl Clone for Content {
+    fn clone(&self) -> Self {
+        let entries = match self {
+            Self::File(bytes) => return Self::File(bytes.clone()),
+            Self::Special(kind) => return Self::Special(*kind),
+            Self::Entries(entries) => entries,
+        };
+
+        // Each frame is a directory being copied: its entry, its entries yet to be copied, and its
+        // entries copied so far.
+        let mut stack: Vec<(Option<&Entry>, slice::Iter<Entry>, Vec<Entry>)> =
+            vec![(None, entries.iter(), Vec::with_capacity(entries.len()))];
+
+        loop {
+            let (_, pending, copied) = stack.last_mut().expect("the root frame is popped last");
+
+            if let Some(entry) = pending.next() {
+                match &entry.content {
+                    Self::File(bytes) => copied.push(Entry {
+                        name: entry.name.clone(),
+                        key: entry.key.clone(),
+                        content: Self::File(bytes.clone()),
+                    }),
+                    Self::Special(kind) => copied.push(Entry {
+                        name: entry.name.clone(),
+                        key: entry.key.clone(),
+                        content: Self::Special(*kind),
+                    }),
+                    Self::Entries(entries) => stack.push((
+                        Some(entry),
+                        entries.iter(),
+                        Vec::with_capacity(entries.len()),
+                    )),
+                }
+                continue;
+            }
+
+            let (entry, _, copied) = stack
+                .pop()
+                .expect("the stack was just checked to be non-empty");
+            let content = Self::Entries(copied);
+
+            match (stack.last_mut(), entry) {
+                (Some((_, _, parent)), Some(entry)) => parent.push(Entry {
+                    name: entry.name.clone(),
+                    key: entry.key.clone(),
+                    content,
+                }),
+                _ => return content,
+            }
+        }
+    }
+}
+
+/// A tree that can be written for [`Debug`](fmt::Debug) without recursing into it.
+pub(crate) trait DebugTree: Sized {
+    /// The type of the entries in a directory.
+    type Entry;
+    /// The name of that type.
+    const ENTRY: &'static str;
+
+    /// Write the content, if it's a file or a special file, or return its entries.
+    fn leaf(&self, f: &mut fmt::Formatter<'_>) -> Result<fmt::Result, &[Self::Entry]>;
+
+    /// The name of the entry, the key it's compared by if that differs, and its content.
+    fn parts(entry: &Self::Entry) -> (&OsStr, Option<&OsStr>, &Self);
+}
+
+/// A piece of a tree yet to be written for [`Debug`](fmt::Debug).
+enum Piece<'a, T: DebugTree> {
+    Entry(&'a T::Entry),
+    Content(&'a T),
+    Text(&'static str),
+}
+
+/// Write the entry as `#[derive(Debug)]` would, but on one line, even when `{:#?}` asks otherwise.
+pub(crate) fn debug_entry<T: DebugTree>(
+    f: &mut fmt::Formatter<'_>,
+    entry: &T::Entry,
+) -> fmt::Result {
+    debug_pieces::<T>(f, Piece::Entry(entry))
+}
+
+/// Write the content as [`debug_entry`] does an entry.
+pub(crate) fn debug_content<T: DebugTree>(f: &mut fmt::Formatter<'_>, content: &T) -> fmt::Result {
+    debug_pieces(f, Piece::Content(content))
+}
+
+fn debug_pieces<T: DebugTree>(f: &mut fmt::Formatter<'_>, root: Piece<'_, T>) -> fmt::Result {
+    let mut stack = vec![root];
+
+    while let Some(piece) = stack.pop() {
+        match piece {
+            Piece::Text(text) => f.write_str(text)?,
+            Piece::Entry(entry) => {
+                let (name, key, content) = T::parts(entry);
+                write!(f, "{} {{ name: {name:?}, ", T::ENTRY)?;
+                if let Some(key) = key {
+                    write!(f, "key: {key:?}, ")?;
+                }
+                f.write_str("content: ")?;
+                stack.push(Piece::Text(" }"));
+                stack.push(Piece::Content(content));
+            }
+            Piece::Content(content) => match content.leaf(f) {
+                Ok(written) => written?,
+                Err(entries) => {
+                    f.write_str("Entries([")?;
+                    stack.push(Piece::Text("])"));
+                    for (index, entry) in entries.iter().enumerate().rev() {
+                        stack.push(Piece::Entry(entry));
+                        if index > 0 {
+                            stack.push(Piece::Text(", "));
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+impl DebugTree for Content {
+    type Entry = Entry;
+    const ENTRY: &'static str = "Entry";
+
+    fn leaf(&self, f: &mut fmt::Formatter<'_>) -> Result<fmt::Result, &[Entry]> {
+        match self {
+            Self::File(bytes) => Ok(write!(f, "File({bytes:?})")),
+            Self::Special(kind) => Ok(write!(f, "Special({kind:?})")),
+            Self::Entries(entries) => Err(entries),
+        }
+    }
+
+    fn parts(entry: &Entry) -> (&OsStr, Option<&OsStr>, &Self) {
+        (&entry.name, entry.key.as_deref(), &entry.content)
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_entry::<Content>(f, self)
+    }
+}
+
+impl fmt::Debug for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_content(f, self)
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        // The descendants are moved out of their directories before they're dropped, so that each
+        // is dropped with no entries left to recurse into. `Content` has no `Drop` of its own, so
+        // that it can still be destructured, but its entries are dropped through this.
+        let Content::Entries(entries) = &mut self.content else {
+            return;
+        };
+        let mut stack = mem::take(entries);
+        while let Some(mut entry) = stack.pop() {
+            if let Content::Entries(entries) = &mut entry.content {
+                stack.append(entries);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{Content, Entry};
+
+    #[test]
+    fn identical_trees_should_be_deduplicated() {
+        let trees = [
+            Content::of("fixtures/equivalent/dir-a").unwrap(),
+            Content::of("fixtures/equivalent/dir-b").unwrap(),
+            Content::of("fixtures/not-equivalent/dir-b").unwrap(),
+        ];
+        let unique: HashSet<_> = trees.iter().cloned().collect();
+
+        assert_eq!(unique.len(), 2);
+    }
+
+    #[test]
+    fn entries_should_be_ordered_by_name_then_content() {
+        let mut entries = [
+            Entry::at("fixtures/not-equivalent/dir-b").unwrap(),
+            Entry::at("fixtures/equivalent/dir-b").unwrap(),
+            Entry::at("fixtures/equivalent/dir-a").unwrap(),
+        ];
+        entries.sort();
+
+        assert_eq!(entries[0], Entry::at("fixtures/equivalent/dir-a").unwrap());
+        // "more test data" < "this is different!"
+        assert_eq!(entries[1], Entry::at("fixtures/equivalent/dir-b").unwrap());
+        assert_eq!(entries[2].clone(), entries[2]);
+    }
+
+    #[test]
+    fn contents_should_be_destructurable() {
+        let Content::Entries(entries) = Content::of("fixtures/equivalent/dir-a").unwrap() else {
+            panic!("the fixture should be a directory");
+        };
+
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn entries_should_be_debugged_as_if_derived() {
+        let entry = |name: &str, content| Entry {
+            name: name.into(),
+            key: None,
+            content,
+        };
+        let content = Content::Entries(vec![
+            entry("a", Content::File(vec![1, 2])),
+            entry(
+                "b",
+                Content::Entries(vec![entry("c", Content::File(vec![]))]),
+            ),
+        ]);
+
+        assert_eq!(
+            format!("{content:?}"),
+            "Entries([Entry { name: \"a\", content: File([1, 2]) }, \
+             Entry { name: \"b\", content: Entries([Entry { name: \"c\", content: File([]) }]) }])"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+/*!
+Moving entries into the trash, where the user can restore them from.
+*/
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Move the entry at the given path into the trash, returning where it was moved.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn trash(path: &Path, time: SystemTime) -> io::Result<PathBuf> {
+    trash_into(&trash_dir()?, path, time)
+}
+
+/// Locate the home trash, as the freedesktop.org trash specification does, creating it if need be.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn trash_dir() -> io::Result<PathBuf> {
+    use std::{env, fs};
+
+    let data = env::var_os("XDG_DATA_HOME")
+        .filter(|data| !data.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    let trash = data.join("Trash");
+    fs::create_dir_all(trash.join("files"))?;
+    fs::create_dir_all(trash.join("info"))?;
+
+    Ok(trash)
+}
+
+/**
+Move the entry at the given path into the given freedesktop.org trash directory, recording where it
+came from and when so that it can be restored.
+*/
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trash_into(trash: &Path, path: &Path, time: SystemTime) -> io::Result<PathBuf> {
+    use std::{
+        ffi::OsString,
+        fs::{self, OpenOptions},
+        io::Write,
+        os::unix::ffi::OsStrExt,
+    };
+
+    use crate::apply::{relocate, utc};
+
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    // The trash records absolute paths, but the entry itself mustn't be resolved if it's a link.
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    let original = fs::canonicalize(parent.unwrap_or(Path::new(".")))?
+        .join(path.file_name().unwrap_or_default());
+
+    // Claim a name by creating its info file, which fails if another entry already has it.
+    let (year, month, day, hour, minute, second) = utc(time);
+    let name = path.file_name().unwrap_or_default();
+    for attempt in 1.. {
+        let mut unique = OsString::from(name);
+        if attempt > 1 {
+            unique.push(format!(".{attempt}"));
+        }
+        let mut info_name = unique.clone();
+        info_name.push(".trashinfo");
+
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(info.join(&info_name))
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        };
+        write!(
+            file,
+            "[Trash Info]\nPath={}\nDeletionDate={year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}\n",
+            escape(original.as_os_str().as_bytes()),
+        )?;
+
+        let location = files.join(&unique);
+        relocate(path, &location)?;
+        return Ok(location);
+    }
+
+    unreachable!("names are tried until one is free")
+}
+
+/// Percent-encode a path as a URL path, as the trash specification requires.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn escape(path: &[u8]) -> String {
+    path.iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                char::from(byte).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Move the entry at the given path into the trash, returning where it was moved.
+#[cfg(target_os = "macos")]
+pub(crate) fn trash(path: &Path, _time: SystemTime) -> io::Result<PathBuf> {
+    use std::{ffi::OsString, fs};
+
+    use crate::apply::relocate;
+
+    let trash = trash_dir()?;
+    let name = path.file_name().unwrap_or_default();
+    let mut location = trash.join(name);
+    for attempt in 2.. {
+        if fs::symlink_metadata(&location).is_err() {
+            break;
+        }
+        let mut unique = OsString::from(name);
+        unique.push(format!(" {attempt}"));
+        location = trash.join(unique);
+    }
+
+    relocate(path, &location)?;
+    Ok(location)
+}
+
+/// Locate the user's trash, creating it if need be.
+#[cfg(target_os = "macos")]
+pub(crate) fn trash_dir() -> io::Result<PathBuf> {
+    use std::{env, fs};
+
+    let trash = env::var_os("HOME")
+        .map(|home| Path::new(&home).join(".Trash"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    fs::create_dir_all(&trash)?;
+
+    Ok(trash)
+}
+
+/// Move the entry at the given path into the trash, returning where it was moved.
+#[cfg(not(unix))]
+pub(crate) fn trash(path: &Path, _time: SystemTime) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{path:?} can't be moved to the trash on this platform"),
+    ))
+}
+
+/// Locate the user's trash, which there's none of on this platform.
+#[cfg(not(unix))]
+pub(crate) fn trash_dir() -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "there's no trash on this platform",
+    ))
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::{
+        fs,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use super::trash_into;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn trashed_entries_should_be_restorable() {
+        let temp = TempDir::new();
+        let trash = temp.path().join("Trash");
+        let time = UNIX_EPOCH + Duration::from_secs(951_827_696);
+
+        let mut locations = Vec::new();
+        for _ in 0..2 {
+            let file = temp.path().join("my file.txt");
+            fs::write(&file, "data").unwrap();
+            locations.push(trash_into(&trash, &file, time).unwrap());
+            assert!(!file.exists());
+        }
+
+        assert_eq!(locations[0], trash.join("files/my file.txt"));
+        assert_eq!(locations[1], trash.join("files/my file.txt.2"));
+        let info = fs::read_to_string(trash.join("info/my file.txt.trashinfo")).unwrap();
+        assert!(info.contains("/my%20file.txt\n"));
+        assert!(info.ends_with("DeletionDate=2000-02-29T12:34:56\n"));
+    }
+}
@@ -0,0 +1,170 @@
+/*!
+Unicode canonical normalization of names, so that names that look identical compare equal whichever
+form the file system stores them in.
+
+The tables are generated by `scripts/unicode-tables.py`.
+*/
+
+mod tables;
+
+use tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+
+/**
+The Unicode normal forms that names can be normalized to. See [`Options::normalize_names`].
+
+[`Options::normalize_names`]: crate::Options::normalize_names
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NormalForm {
+    /// Canonical composition, which most Linux and Windows software produces.
+    Nfc,
+    /// Canonical decomposition, which macOS file systems have historically stored.
+    Nfd,
+}
+
+// The constants of the algorithmic decomposition of Hangul syllables.
+const S_BASE: u32 = 0xac00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11a7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// Normalize the text to the given form.
+pub(crate) fn normalize(text: &str, form: NormalForm) -> String {
+    // Most names are ASCII, which every normal form leaves as it is.
+    if text.is_ascii() {
+        return text.to_owned();
+    }
+
+    let decomposed = decompose(text);
+    match form {
+        NormalForm::Nfd => decomposed.into_iter().collect(),
+        NormalForm::Nfc => compose(decomposed).into_iter().collect(),
+    }
+}
+
+fn combining_class(character: char) -> u8 {
+    COMBINING_CLASSES
+        .binary_search_by_key(&character, |&(character, _)| character)
+        .map_or(0, |index| COMBINING_CLASSES[index].1)
+}
+
+/// Fully decompose the text, then put its combining marks in canonical order.
+fn decompose(text: &str) -> Vec<char> {
+    let mut decomposed = Vec::with_capacity(text.len());
+
+    for character in text.chars() {
+        let code = u32::from(character);
+        if (S_BASE..S_BASE + S_COUNT).contains(&code) {
+            let index = code - S_BASE;
+            let jamo = [
+                L_BASE + index / N_COUNT,
+                V_BASE + index % N_COUNT / T_COUNT,
+                T_BASE + index % T_COUNT,
+            ];
+            let length = if jamo[2] == T_BASE { 2 } else { 3 };
+            decomposed.extend(
+                jamo[..length]
+                    .iter()
+                    .filter_map(|&code| char::from_u32(code)),
+            );
+        } else if let Ok(index) =
+            DECOMPOSITIONS.binary_search_by_key(&character, |&(character, _)| character)
+        {
+            decomposed.extend_from_slice(DECOMPOSITIONS[index].1);
+        } else {
+            decomposed.push(character);
+        }
+    }
+
+    // Sort each run of combining marks by class, stably, with an insertion sort; runs are short.
+    for index in 1..decomposed.len() {
+        let class = combining_class(decomposed[index]);
+        if class == 0 {
+            continue;
+        }
+        let mut position = index;
+        while position > 0 && combining_class(decomposed[position - 1]) > class {
+            decomposed.swap(position - 1, position);
+            position -= 1;
+        }
+    }
+
+    decomposed
+}
+
+/// The primary composite of two characters, if they have one.
+fn composite(first: char, second: char) -> Option<char> {
+    let (first_code, second_code) = (u32::from(first), u32::from(second));
+
+    if (L_BASE..L_BASE + L_COUNT).contains(&first_code)
+        && (V_BASE..V_BASE + V_COUNT).contains(&second_code)
+    {
+        let index = (first_code - L_BASE) * N_COUNT + (second_code - V_BASE) * T_COUNT;
+        return char::from_u32(S_BASE + index);
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&first_code)
+        && (first_code - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&second_code)
+    {
+        return char::from_u32(first_code + second_code - T_BASE);
+    }
+
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(pair, _)| pair)
+        .ok()
+        .map(|index| COMPOSITIONS[index].1)
+}
+
+/// Canonically compose decomposed, canonically ordered characters.
+fn compose(decomposed: Vec<char>) -> Vec<char> {
+    let mut composed: Vec<char> = Vec::with_capacity(decomposed.len());
+    // The index of the last starter in `composed`, and the class of the last character after it.
+    let mut starter: Option<usize> = None;
+    let mut last_class = 0;
+
+    for character in decomposed {
+        let class = combining_class(character);
+
+        if let Some(starter) = starter {
+            // A character is blocked from the starter by an intervening character of the same or
+            // a higher class, or by an intervening starter.
+            let blocked = composed.len() > starter + 1 && (last_class == 0 || last_class >= class);
+            if !blocked {
+                if let Some(composite) = composite(composed[starter], character) {
+                    composed[starter] = composite;
+                    continue;
+                }
+            }
+        }
+
+        if class == 0 {
+            starter = Some(composed.len());
+        }
+        last_class = class;
+        composed.push(character);
+    }
+
+    composed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, NormalForm};
+
+    #[test]
+    fn names_should_be_normalized() {
+        let composed = "caf\u{e9} \u{1e69} \u{d55c}";
+        let decomposed = "cafe\u{301} s\u{323}\u{307} \u{1112}\u{1161}\u{11ab}";
+
+        assert_eq!(normalize(composed, NormalForm::Nfd), decomposed);
+        assert_eq!(normalize(decomposed, NormalForm::Nfc), composed);
+        // Combining marks are reordered canonically before they're composed.
+        assert_eq!(normalize("s\u{307}\u{323}", NormalForm::Nfc), "\u{1e69}");
+        assert_eq!(normalize("plain.txt", NormalForm::Nfc), "plain.txt");
+    }
+}
@@ -0,0 +1,1169 @@
+// Generated by scripts/unicode-tables.py from Unicode 14.0.0. Don't edit.
+
+/// The full canonical decompositions of characters, other than Hangul syllables, by character.
+#[rustfmt::skip]
+pub(super) const DECOMPOSITIONS: &[(char, &[char])] = &[
+    ('\u{00c0}', &['\u{0041}', '\u{0300}']), ('\u{00c1}', &['\u{0041}', '\u{0301}']), ('\u{00c2}', &['\u{0041}', '\u{0302}']),
+    ('\u{00c3}', &['\u{0041}', '\u{0303}']), ('\u{00c4}', &['\u{0041}', '\u{0308}']), ('\u{00c5}', &['\u{0041}', '\u{030a}']),
+    ('\u{00c7}', &['\u{0043}', '\u{0327}']), ('\u{00c8}', &['\u{0045}', '\u{0300}']), ('\u{00c9}', &['\u{0045}', '\u{0301}']),
+    ('\u{00ca}', &['\u{0045}', '\u{0302}']), ('\u{00cb}', &['\u{0045}', '\u{0308}']), ('\u{00cc}', &['\u{0049}', '\u{0300}']),
+    ('\u{00cd}', &['\u{0049}', '\u{0301}']), ('\u{00ce}', &['\u{0049}', '\u{0302}']), ('\u{00cf}', &['\u{0049}', '\u{0308}']),
+    ('\u{00d1}', &['\u{004e}', '\u{0303}']), ('\u{00d2}', &['\u{004f}', '\u{0300}']), ('\u{00d3}', &['\u{004f}', '\u{0301}']),
+    ('\u{00d4}', &['\u{004f}', '\u{0302}']), ('\u{00d5}', &['\u{004f}', '\u{0303}']), ('\u{00d6}', &['\u{004f}', '\u{0308}']),
+    ('\u{00d9}', &['\u{0055}', '\u{0300}']), ('\u{00da}', &['\u{0055}', '\u{0301}']), ('\u{00db}', &['\u{0055}', '\u{0302}']),
+    ('\u{00dc}', &['\u{0055}', '\u{0308}']), ('\u{00dd}', &['\u{0059}', '\u{0301}']), ('\u{00e0}', &['\u{0061}', '\u{0300}']),
+    ('\u{00e1}', &['\u{0061}', '\u{0301}']), ('\u{00e2}', &['\u{0061}', '\u{0302}']), ('\u{00e3}', &['\u{0061}', '\u{0303}']),
+    ('\u{00e4}', &['\u{0061}', '\u{0308}']), ('\u{00e5}', &['\u{0061}', '\u{030a}']), ('\u{00e7}', &['\u{0063}', '\u{0327}']),
+    ('\u{00e8}', &['\u{0065}', '\u{0300}']), ('\u{00e9}', &['\u{0065}', '\u{0301}']), ('\u{00ea}', &['\u{0065}', '\u{0302}']),
+    ('\u{00eb}', &['\u{0065}', '\u{0308}']), ('\u{00ec}', &['\u{0069}', '\u{0300}']), ('\u{00ed}', &['\u{0069}', '\u{0301}']),
+    ('\u{00ee}', &['\u{0069}', '\u{0302}']), ('\u{00ef}', &['\u{0069}', '\u{0308}']), ('\u{00f1}', &['\u{006e}', '\u{0303}']),
+    ('\u{00f2}', &['\u{006f}', '\u{0300}']), ('\u{00f3}', &['\u{006f}', '\u{0301}']), ('\u{00f4}', &['\u{006f}', '\u{0302}']),
+    ('\u{00f5}', &['\u{006f}', '\u{0303}']), ('\u{00f6}', &['\u{006f}', '\u{0308}']), ('\u{00f9}', &['\u{0075}', '\u{0300}']),
+    ('\u{00fa}', &['\u{0075}', '\u{0301}']), ('\u{00fb}', &['\u{0075}', '\u{0302}']), ('\u{00fc}', &['\u{0075}', '\u{0308}']),
+    ('\u{00fd}', &['\u{0079}', '\u{0301}']), ('\u{00ff}', &['\u{0079}', '\u{0308}']), ('\u{0100}', &['\u{0041}', '\u{0304}']),
+    ('\u{0101}', &['\u{0061}', '\u{0304}']), ('\u{0102}', &['\u{0041}', '\u{0306}']), ('\u{0103}', &['\u{0061}', '\u{0306}']),
+    ('\u{0104}', &['\u{0041}', '\u{0328}']), ('\u{0105}', &['\u{0061}', '\u{0328}']), ('\u{0106}', &['\u{0043}', '\u{0301}']),
+    ('\u{0107}', &['\u{0063}', '\u{0301}']), ('\u{0108}', &['\u{0043}', '\u{0302}']), ('\u{0109}', &['\u{0063}', '\u{0302}']),
+    ('\u{010a}', &['\u{0043}', '\u{0307}']), ('\u{010b}', &['\u{0063}', '\u{0307}']), ('\u{010c}', &['\u{0043}', '\u{030c}']),
+    ('\u{010d}', &['\u{0063}', '\u{030c}']), ('\u{010e}', &['\u{0044}', '\u{030c}']), ('\u{010f}', &['\u{0064}', '\u{030c}']),
+    ('\u{0112}', &['\u{0045}', '\u{0304}']), ('\u{0113}', &['\u{0065}', '\u{0304}']), ('\u{0114}', &['\u{0045}', '\u{0306}']),
+    ('\u{0115}', &['\u{0065}', '\u{0306}']), ('\u{0116}', &['\u{0045}', '\u{0307}']), ('\u{0117}', &['\u{0065}', '\u{0307}']),
+    ('\u{0118}', &['\u{0045}', '\u{0328}']), ('\u{0119}', &['\u{0065}', '\u{0328}']), ('\u{011a}', &['\u{0045}', '\u{030c}']),
+    ('\u{011b}', &['\u{0065}', '\u{030c}']), ('\u{011c}', &['\u{0047}', '\u{0302}']), ('\u{011d}', &['\u{0067}', '\u{0302}']),
+    ('\u{011e}', &['\u{0047}', '\u{0306}']), ('\u{011f}', &['\u{0067}', '\u{0306}']), ('\u{0120}', &['\u{0047}', '\u{0307}']),
+    ('\u{0121}', &['\u{0067}', '\u{0307}']), ('\u{0122}', &['\u{0047}', '\u{0327}']), ('\u{0123}', &['\u{0067}', '\u{0327}']),
+    ('\u{0124}', &['\u{0048}', '\u{0302}']), ('\u{0125}', &['\u{0068}', '\u{0302}']), ('\u{0128}', &['\u{0049}', '\u{0303}']),
+    ('\u{0129}', &['\u{0069}', '\u{0303}']), ('\u{012a}', &['\u{0049}', '\u{0304}']), ('\u{012b}', &['\u{0069}', '\u{0304}']),
+    ('\u{012c}', &['\u{0049}', '\u{0306}']), ('\u{012d}', &['\u{0069}', '\u{0306}']), ('\u{012e}', &['\u{0049}', '\u{0328}']),
+    ('\u{012f}', &['\u{0069}', '\u{0328}']), ('\u{0130}', &['\u{0049}', '\u{0307}']), ('\u{0134}', &['\u{004a}', '\u{0302}']),
+    ('\u{0135}', &['\u{006a}', '\u{0302}']), ('\u{0136}', &['\u{004b}', '\u{0327}']), ('\u{0137}', &['\u{006b}', '\u{0327}']),
+    ('\u{0139}', &['\u{004c}', '\u{0301}']), ('\u{013a}', &['\u{006c}', '\u{0301}']), ('\u{013b}', &['\u{004c}', '\u{0327}']),
+    ('\u{013c}', &['\u{006c}', '\u{0327}']), ('\u{013d}', &['\u{004c}', '\u{030c}']), ('\u{013e}', &['\u{006c}', '\u{030c}']),
+    ('\u{0143}', &['\u{004e}', '\u{0301}']), ('\u{0144}', &['\u{006e}', '\u{0301}']), ('\u{0145}', &['\u{004e}', '\u{0327}']),
+    ('\u{0146}', &['\u{006e}', '\u{0327}']), ('\u{0147}', &['\u{004e}', '\u{030c}']), ('\u{0148}', &['\u{006e}', '\u{030c}']),
+    ('\u{014c}', &['\u{004f}', '\u{0304}']), ('\u{014d}', &['\u{006f}', '\u{0304}']), ('\u{014e}', &['\u{004f}', '\u{0306}']),
+    ('\u{014f}', &['\u{006f}', '\u{0306}']), ('\u{0150}', &['\u{004f}', '\u{030b}']), ('\u{0151}', &['\u{006f}', '\u{030b}']),
+    ('\u{0154}', &['\u{0052}', '\u{0301}']), ('\u{0155}', &['\u{0072}', '\u{0301}']), ('\u{0156}', &['\u{0052}', '\u{0327}']),
+    ('\u{0157}', &['\u{0072}', '\u{0327}']), ('\u{0158}', &['\u{0052}', '\u{030c}']), ('\u{0159}', &['\u{0072}', '\u{030c}']),
+    ('\u{015a}', &['\u{0053}', '\u{0301}']), ('\u{015b}', &['\u{0073}', '\u{0301}']), ('\u{015c}', &['\u{0053}', '\u{0302}']),
+    ('\u{015d}', &['\u{0073}', '\u{0302}']), ('\u{015e}', &['\u{0053}', '\u{0327}']), ('\u{015f}', &['\u{0073}', '\u{0327}']),
+    ('\u{0160}', &['\u{0053}', '\u{030c}']), ('\u{0161}', &['\u{0073}', '\u{030c}']), ('\u{0162}', &['\u{0054}', '\u{0327}']),
+    ('\u{0163}', &['\u{0074}', '\u{0327}']), ('\u{0164}', &['\u{0054}', '\u{030c}']), ('\u{0165}', &['\u{0074}', '\u{030c}']),
+    ('\u{0168}', &['\u{0055}', '\u{0303}']), ('\u{0169}', &['\u{0075}', '\u{0303}']), ('\u{016a}', &['\u{0055}', '\u{0304}']),
+    ('\u{016b}', &['\u{0075}', '\u{0304}']), ('\u{016c}', &['\u{0055}', '\u{0306}']), ('\u{016d}', &['\u{0075}', '\u{0306}']),
+    ('\u{016e}', &['\u{0055}', '\u{030a}']), ('\u{016f}', &['\u{0075}', '\u{030a}']), ('\u{0170}', &['\u{0055}', '\u{030b}']),
+    ('\u{0171}', &['\u{0075}', '\u{030b}']), ('\u{0172}', &['\u{0055}', '\u{0328}']), ('\u{0173}', &['\u{0075}', '\u{0328}']),
+    ('\u{0174}', &['\u{0057}', '\u{0302}']), ('\u{0175}', &['\u{0077}', '\u{0302}']), ('\u{0176}', &['\u{0059}', '\u{0302}']),
+    ('\u{0177}', &['\u{0079}', '\u{0302}']), ('\u{0178}', &['\u{0059}', '\u{0308}']), ('\u{0179}', &['\u{005a}', '\u{0301}']),
+    ('\u{017a}', &['\u{007a}', '\u{0301}']), ('\u{017b}', &['\u{005a}', '\u{0307}']), ('\u{017c}', &['\u{007a}', '\u{0307}']),
+    ('\u{017d}', &['\u{005a}', '\u{030c}']), ('\u{017e}', &['\u{007a}', '\u{030c}']), ('\u{01a0}', &['\u{004f}', '\u{031b}']),
+    ('\u{01a1}', &['\u{006f}', '\u{031b}']), ('\u{01af}', &['\u{0055}', '\u{031b}']), ('\u{01b0}', &['\u{0075}', '\u{031b}']),
+    ('\u{01cd}', &['\u{0041}', '\u{030c}']), ('\u{01ce}', &['\u{0061}', '\u{030c}']), ('\u{01cf}', &['\u{0049}', '\u{030c}']),
+    ('\u{01d0}', &['\u{0069}', '\u{030c}']), ('\u{01d1}', &['\u{004f}', '\u{030c}']), ('\u{01d2}', &['\u{006f}', '\u{030c}']),
+    ('\u{01d3}', &['\u{0055}', '\u{030c}']), ('\u{01d4}', &['\u{0075}', '\u{030c}']), ('\u{01d5}', &['\u{0055}', '\u{0308}', '\u{0304}']),
+    ('\u{01d6}', &['\u{0075}', '\u{0308}', '\u{0304}']), ('\u{01d7}', &['\u{0055}', '\u{0308}', '\u{0301}']), ('\u{01d8}', &['\u{0075}', '\u{0308}', '\u{0301}']),
+    ('\u{01d9}', &['\u{0055}', '\u{0308}', '\u{030c}']), ('\u{01da}', &['\u{0075}', '\u{0308}', '\u{030c}']), ('\u{01db}', &['\u{0055}', '\u{0308}', '\u{0300}']),
+    ('\u{01dc}', &['\u{0075}', '\u{0308}', '\u{0300}']), ('\u{01de}', &['\u{0041}', '\u{0308}', '\u{0304}']), ('\u{01df}', &['\u{0061}', '\u{0308}', '\u{0304}']),
+    ('\u{01e0}', &['\u{0041}', '\u{0307}', '\u{0304}']), ('\u{01e1}', &['\u{0061}', '\u{0307}', '\u{0304}']), ('\u{01e2}', &['\u{00c6}', '\u{0304}']),
+    ('\u{01e3}', &['\u{00e6}', '\u{0304}']), ('\u{01e6}', &['\u{0047}', '\u{030c}']), ('\u{01e7}', &['\u{0067}', '\u{030c}']),
+    ('\u{01e8}', &['\u{004b}', '\u{030c}']), ('\u{01e9}', &['\u{006b}', '\u{030c}']), ('\u{01ea}', &['\u{004f}', '\u{0328}']),
+    ('\u{01eb}', &['\u{006f}', '\u{0328}']), ('\u{01ec}', &['\u{004f}', '\u{0328}', '\u{0304}']), ('\u{01ed}', &['\u{006f}', '\u{0328}', '\u{0304}']),
+    ('\u{01ee}', &['\u{01b7}', '\u{030c}']), ('\u{01ef}', &['\u{0292}', '\u{030c}']), ('\u{01f0}', &['\u{006a}', '\u{030c}']),
+    ('\u{01f4}', &['\u{0047}', '\u{0301}']), ('\u{01f5}', &['\u{0067}', '\u{0301}']), ('\u{01f8}', &['\u{004e}', '\u{0300}']),
+    ('\u{01f9}', &['\u{006e}', '\u{0300}']), ('\u{01fa}', &['\u{0041}', '\u{030a}', '\u{0301}']), ('\u{01fb}', &['\u{0061}', '\u{030a}', '\u{0301}']),
+    ('\u{01fc}', &['\u{00c6}', '\u{0301}']), ('\u{01fd}', &['\u{00e6}', '\u{0301}']), ('\u{01fe}', &['\u{00d8}', '\u{0301}']),
+    ('\u{01ff}', &['\u{00f8}', '\u{0301}']), ('\u{0200}', &['\u{0041}', '\u{030f}']), ('\u{0201}', &['\u{0061}', '\u{030f}']),
+    ('\u{0202}', &['\u{0041}', '\u{0311}']), ('\u{0203}', &['\u{0061}', '\u{0311}']), ('\u{0204}', &['\u{0045}', '\u{030f}']),
+    ('\u{0205}', &['\u{0065}', '\u{030f}']), ('\u{0206}', &['\u{0045}', '\u{0311}']), ('\u{0207}', &['\u{0065}', '\u{0311}']),
+    ('\u{0208}', &['\u{0049}', '\u{030f}']), ('\u{0209}', &['\u{0069}', '\u{030f}']), ('\u{020a}', &['\u{0049}', '\u{0311}']),
+    ('\u{020b}', &['\u{0069}', '\u{0311}']), ('\u{020c}', &['\u{004f}', '\u{030f}']), ('\u{020d}', &['\u{006f}', '\u{030f}']),
+    ('\u{020e}', &['\u{004f}', '\u{0311}']), ('\u{020f}', &['\u{006f}', '\u{0311}']), ('\u{0210}', &['\u{0052}', '\u{030f}']),
+    ('\u{0211}', &['\u{0072}', '\u{030f}']), ('\u{0212}', &['\u{0052}', '\u{0311}']), ('\u{0213}', &['\u{0072}', '\u{0311}']),
+    ('\u{0214}', &['\u{0055}', '\u{030f}']), ('\u{0215}', &['\u{0075}', '\u{030f}']), ('\u{0216}', &['\u{0055}', '\u{0311}']),
+    ('\u{0217}', &['\u{0075}', '\u{0311}']), ('\u{0218}', &['\u{0053}', '\u{0326}']), ('\u{0219}', &['\u{0073}', '\u{0326}']),
+    ('\u{021a}', &['\u{0054}', '\u{0326}']), ('\u{021b}', &['\u{0074}', '\u{0326}']), ('\u{021e}', &['\u{0048}', '\u{030c}']),
+    ('\u{021f}', &['\u{0068}', '\u{030c}']), ('\u{0226}', &['\u{0041}', '\u{0307}']), ('\u{0227}', &['\u{0061}', '\u{0307}']),
+    ('\u{0228}', &['\u{0045}', '\u{0327}']), ('\u{0229}', &['\u{0065}', '\u{0327}']), ('\u{022a}', &['\u{004f}', '\u{0308}', '\u{0304}']),
+    ('\u{022b}', &['\u{006f}', '\u{0308}', '\u{0304}']), ('\u{022c}', &['\u{004f}', '\u{0303}', '\u{0304}']), ('\u{022d}', &['\u{006f}', '\u{0303}', '\u{0304}']),
+    ('\u{022e}', &['\u{004f}', '\u{0307}']), ('\u{022f}', &['\u{006f}', '\u{0307}']), ('\u{0230}', &['\u{004f}', '\u{0307}', '\u{0304}']),
+    ('\u{0231}', &['\u{006f}', '\u{0307}', '\u{0304}']), ('\u{0232}', &['\u{0059}', '\u{0304}']), ('\u{0233}', &['\u{0079}', '\u{0304}']),
+    ('\u{0340}', &['\u{0300}']), ('\u{0341}', &['\u{0301}']), ('\u{0343}', &['\u{0313}']),
+    ('\u{0344}', &['\u{0308}', '\u{0301}']), ('\u{0374}', &['\u{02b9}']), ('\u{037e}', &['\u{003b}']),
+    ('\u{0385}', &['\u{00a8}', '\u{0301}']), ('\u{0386}', &['\u{0391}', '\u{0301}']), ('\u{0387}', &['\u{00b7}']),
+    ('\u{0388}', &['\u{0395}', '\u{0301}']), ('\u{0389}', &['\u{0397}', '\u{0301}']), ('\u{038a}', &['\u{0399}', '\u{0301}']),
+    ('\u{038c}', &['\u{039f}', '\u{0301}']), ('\u{038e}', &['\u{03a5}', '\u{0301}']), ('\u{038f}', &['\u{03a9}', '\u{0301}']),
+    ('\u{0390}', &['\u{03b9}', '\u{0308}', '\u{0301}']), ('\u{03aa}', &['\u{0399}', '\u{0308}']), ('\u{03ab}', &['\u{03a5}', '\u{0308}']),
+    ('\u{03ac}', &['\u{03b1}', '\u{0301}']), ('\u{03ad}', &['\u{03b5}', '\u{0301}']), ('\u{03ae}', &['\u{03b7}', '\u{0301}']),
+    ('\u{03af}', &['\u{03b9}', '\u{0301}']), ('\u{03b0}', &['\u{03c5}', '\u{0308}', '\u{0301}']), ('\u{03ca}', &['\u{03b9}', '\u{0308}']),
+    ('\u{03cb}', &['\u{03c5}', '\u{0308}']), ('\u{03cc}', &['\u{03bf}', '\u{0301}']), ('\u{03cd}', &['\u{03c5}', '\u{0301}']),
+    ('\u{03ce}', &['\u{03c9}', '\u{0301}']), ('\u{03d3}', &['\u{03d2}', '\u{0301}']), ('\u{03d4}', &['\u{03d2}', '\u{0308}']),
+    ('\u{0400}', &['\u{0415}', '\u{0300}']), ('\u{0401}', &['\u{0415}', '\u{0308}']), ('\u{0403}', &['\u{0413}', '\u{0301}']),
+    ('\u{0407}', &['\u{0406}', '\u{0308}']), ('\u{040c}', &['\u{041a}', '\u{0301}']), ('\u{040d}', &['\u{0418}', '\u{0300}']),
+    ('\u{040e}', &['\u{0423}', '\u{0306}']), ('\u{0419}', &['\u{0418}', '\u{0306}']), ('\u{0439}', &['\u{0438}', '\u{0306}']),
+    ('\u{0450}', &['\u{0435}', '\u{0300}']), ('\u{0451}', &['\u{0435}', '\u{0308}']), ('\u{0453}', &['\u{0433}', '\u{0301}']),
+    ('\u{0457}', &['\u{0456}', '\u{0308}']), ('\u{045c}', &['\u{043a}', '\u{0301}']), ('\u{045d}', &['\u{0438}', '\u{0300}']),
+    ('\u{045e}', &['\u{0443}', '\u{0306}']), ('\u{0476}', &['\u{0474}', '\u{030f}']), ('\u{0477}', &['\u{0475}', '\u{030f}']),
+    ('\u{04c1}', &['\u{0416}', '\u{0306}']), ('\u{04c2}', &['\u{0436}', '\u{0306}']), ('\u{04d0}', &['\u{0410}', '\u{0306}']),
+    ('\u{04d1}', &['\u{0430}', '\u{0306}']), ('\u{04d2}', &['\u{0410}', '\u{0308}']), ('\u{04d3}', &['\u{0430}', '\u{0308}']),
+    ('\u{04d6}', &['\u{0415}', '\u{0306}']), ('\u{04d7}', &['\u{0435}', '\u{0306}']), ('\u{04da}', &['\u{04d8}', '\u{0308}']),
+    ('\u{04db}', &['\u{04d9}', '\u{0308}']), ('\u{04dc}', &['\u{0416}', '\u{0308}']), ('\u{04dd}', &['\u{0436}', '\u{0308}']),
+    ('\u{04de}', &['\u{0417}', '\u{0308}']), ('\u{04df}', &['\u{0437}', '\u{0308}']), ('\u{04e2}', &['\u{0418}', '\u{0304}']),
+    ('\u{04e3}', &['\u{0438}', '\u{0304}']), ('\u{04e4}', &['\u{0418}', '\u{0308}']), ('\u{04e5}', &['\u{0438}', '\u{0308}']),
+    ('\u{04e6}', &['\u{041e}', '\u{0308}']), ('\u{04e7}', &['\u{043e}', '\u{0308}']), ('\u{04ea}', &['\u{04e8}', '\u{0308}']),
+    ('\u{04eb}', &['\u{04e9}', '\u{0308}']), ('\u{04ec}', &['\u{042d}', '\u{0308}']), ('\u{04ed}', &['\u{044d}', '\u{0308}']),
+    ('\u{04ee}', &['\u{0423}', '\u{0304}']), ('\u{04ef}', &['\u{0443}', '\u{0304}']), ('\u{04f0}', &['\u{0423}', '\u{0308}']),
+    ('\u{04f1}', &['\u{0443}', '\u{0308}']), ('\u{04f2}', &['\u{0423}', '\u{030b}']), ('\u{04f3}', &['\u{0443}', '\u{030b}']),
+    ('\u{04f4}', &['\u{0427}', '\u{0308}']), ('\u{04f5}', &['\u{0447}', '\u{0308}']), ('\u{04f8}', &['\u{042b}', '\u{0308}']),
+    ('\u{04f9}', &['\u{044b}', '\u{0308}']), ('\u{0622}', &['\u{0627}', '\u{0653}']), ('\u{0623}', &['\u{0627}', '\u{0654}']),
+    ('\u{0624}', &['\u{0648}', '\u{0654}']), ('\u{0625}', &['\u{0627}', '\u{0655}']), ('\u{0626}', &['\u{064a}', '\u{0654}']),
+    ('\u{06c0}', &['\u{06d5}', '\u{0654}']), ('\u{06c2}', &['\u{06c1}', '\u{0654}']), ('\u{06d3}', &['\u{06d2}', '\u{0654}']),
+    ('\u{0929}', &['\u{0928}', '\u{093c}']), ('\u{0931}', &['\u{0930}', '\u{093c}']), ('\u{0934}', &['\u{0933}', '\u{093c}']),
+    ('\u{0958}', &['\u{0915}', '\u{093c}']), ('\u{0959}', &['\u{0916}', '\u{093c}']), ('\u{095a}', &['\u{0917}', '\u{093c}']),
+    ('\u{095b}', &['\u{091c}', '\u{093c}']), ('\u{095c}', &['\u{0921}', '\u{093c}']), ('\u{095d}', &['\u{0922}', '\u{093c}']),
+    ('\u{095e}', &['\u{092b}', '\u{093c}']), ('\u{095f}', &['\u{092f}', '\u{093c}']), ('\u{09cb}', &['\u{09c7}', '\u{09be}']),
+    ('\u{09cc}', &['\u{09c7}', '\u{09d7}']), ('\u{09dc}', &['\u{09a1}', '\u{09bc}']), ('\u{09dd}', &['\u{09a2}', '\u{09bc}']),
+    ('\u{09df}', &['\u{09af}', '\u{09bc}']), ('\u{0a33}', &['\u{0a32}', '\u{0a3c}']), ('\u{0a36}', &['\u{0a38}', '\u{0a3c}']),
+    ('\u{0a59}', &['\u{0a16}', '\u{0a3c}']), ('\u{0a5a}', &['\u{0a17}', '\u{0a3c}']), ('\u{0a5b}', &['\u{0a1c}', '\u{0a3c}']),
+    ('\u{0a5e}', &['\u{0a2b}', '\u{0a3c}']), ('\u{0b48}', &['\u{0b47}', '\u{0b56}']), ('\u{0b4b}', &['\u{0b47}', '\u{0b3e}']),
+    ('\u{0b4c}', &['\u{0b47}', '\u{0b57}']), ('\u{0b5c}', &['\u{0b21}', '\u{0b3c}']), ('\u{0b5d}', &['\u{0b22}', '\u{0b3c}']),
+    ('\u{0b94}', &['\u{0b92}', '\u{0bd7}']), ('\u{0bca}', &['\u{0bc6}', '\u{0bbe}']), ('\u{0bcb}', &['\u{0bc7}', '\u{0bbe}']),
+    ('\u{0bcc}', &['\u{0bc6}', '\u{0bd7}']), ('\u{0c48}', &['\u{0c46}', '\u{0c56}']), ('\u{0cc0}', &['\u{0cbf}', '\u{0cd5}']),
+    ('\u{0cc7}', &['\u{0cc6}', '\u{0cd5}']), ('\u{0cc8}', &['\u{0cc6}', '\u{0cd6}']), ('\u{0cca}', &['\u{0cc6}', '\u{0cc2}']),
+    ('\u{0ccb}', &['\u{0cc6}', '\u{0cc2}', '\u{0cd5}']), ('\u{0d4a}', &['\u{0d46}', '\u{0d3e}']), ('\u{0d4b}', &['\u{0d47}', '\u{0d3e}']),
+    ('\u{0d4c}', &['\u{0d46}', '\u{0d57}']), ('\u{0dda}', &['\u{0dd9}', '\u{0dca}']), ('\u{0ddc}', &['\u{0dd9}', '\u{0dcf}']),
+    ('\u{0ddd}', &['\u{0dd9}', '\u{0dcf}', '\u{0dca}']), ('\u{0dde}', &['\u{0dd9}', '\u{0ddf}']), ('\u{0f43}', &['\u{0f42}', '\u{0fb7}']),
+    ('\u{0f4d}', &['\u{0f4c}', '\u{0fb7}']), ('\u{0f52}', &['\u{0f51}', '\u{0fb7}']), ('\u{0f57}', &['\u{0f56}', '\u{0fb7}']),
+    ('\u{0f5c}', &['\u{0f5b}', '\u{0fb7}']), ('\u{0f69}', &['\u{0f40}', '\u{0fb5}']), ('\u{0f73}', &['\u{0f71}', '\u{0f72}']),
+    ('\u{0f75}', &['\u{0f71}', '\u{0f74}']), ('\u{0f76}', &['\u{0fb2}', '\u{0f80}']), ('\u{0f78}', &['\u{0fb3}', '\u{0f80}']),
+    ('\u{0f81}', &['\u{0f71}', '\u{0f80}']), ('\u{0f93}', &['\u{0f92}', '\u{0fb7}']), ('\u{0f9d}', &['\u{0f9c}', '\u{0fb7}']),
+    ('\u{0fa2}', &['\u{0fa1}', '\u{0fb7}']), ('\u{0fa7}', &['\u{0fa6}', '\u{0fb7}']), ('\u{0fac}', &['\u{0fab}', '\u{0fb7}']),
+    ('\u{0fb9}', &['\u{0f90}', '\u{0fb5}']), ('\u{1026}', &['\u{1025}', '\u{102e}']), ('\u{1b06}', &['\u{1b05}', '\u{1b35}']),
+    ('\u{1b08}', &['\u{1b07}', '\u{1b35}']), ('\u{1b0a}', &['\u{1b09}', '\u{1b35}']), ('\u{1b0c}', &['\u{1b0b}', '\u{1b35}']),
+    ('\u{1b0e}', &['\u{1b0d}', '\u{1b35}']), ('\u{1b12}', &['\u{1b11}', '\u{1b35}']), ('\u{1b3b}', &['\u{1b3a}', '\u{1b35}']),
+    ('\u{1b3d}', &['\u{1b3c}', '\u{1b35}']), ('\u{1b40}', &['\u{1b3e}', '\u{1b35}']), ('\u{1b41}', &['\u{1b3f}', '\u{1b35}']),
+    ('\u{1b43}', &['\u{1b42}', '\u{1b35}']), ('\u{1e00}', &['\u{0041}', '\u{0325}']), ('\u{1e01}', &['\u{0061}', '\u{0325}']),
+    ('\u{1e02}', &['\u{0042}', '\u{0307}']), ('\u{1e03}', &['\u{0062}', '\u{0307}']), ('\u{1e04}', &['\u{0042}', '\u{0323}']),
+    ('\u{1e05}', &['\u{0062}', '\u{0323}']), ('\u{1e06}', &['\u{0042}', '\u{0331}']), ('\u{1e07}', &['\u{0062}', '\u{0331}']),
+    ('\u{1e08}', &['\u{0043}', '\u{0327}', '\u{0301}']), ('\u{1e09}', &['\u{0063}', '\u{0327}', '\u{0301}']), ('\u{1e0a}', &['\u{0044}', '\u{0307}']),
+    ('\u{1e0b}', &['\u{0064}', '\u{0307}']), ('\u{1e0c}', &['\u{0044}', '\u{0323}']), ('\u{1e0d}', &['\u{0064}', '\u{0323}']),
+    ('\u{1e0e}', &['\u{0044}', '\u{0331}']), ('\u{1e0f}', &['\u{0064}', '\u{0331}']), ('\u{1e10}', &['\u{0044}', '\u{0327}']),
+    ('\u{1e11}', &['\u{0064}', '\u{0327}']), ('\u{1e12}', &['\u{0044}', '\u{032d}']), ('\u{1e13}', &['\u{0064}', '\u{032d}']),
+    ('\u{1e14}', &['\u{0045}', '\u{0304}', '\u{0300}']), ('\u{1e15}', &['\u{0065}', '\u{0304}', '\u{0300}']), ('\u{1e16}', &['\u{0045}', '\u{0304}', '\u{0301}']),
+    ('\u{1e17}', &['\u{0065}', '\u{0304}', '\u{0301}']), ('\u{1e18}', &['\u{0045}', '\u{032d}']), ('\u{1e19}', &['\u{0065}', '\u{032d}']),
+    ('\u{1e1a}', &['\u{0045}', '\u{0330}']), ('\u{1e1b}', &['\u{0065}', '\u{0330}']), ('\u{1e1c}', &['\u{0045}', '\u{0327}', '\u{0306}']),
+    ('\u{1e1d}', &['\u{0065}', '\u{0327}', '\u{0306}']), ('\u{1e1e}', &['\u{0046}', '\u{0307}']), ('\u{1e1f}', &['\u{0066}', '\u{0307}']),
+    ('\u{1e20}', &['\u{0047}', '\u{0304}']), ('\u{1e21}', &['\u{0067}', '\u{0304}']), ('\u{1e22}', &['\u{0048}', '\u{0307}']),
+    ('\u{1e23}', &['\u{0068}', '\u{0307}']), ('\u{1e24}', &['\u{0048}', '\u{0323}']), ('\u{1e25}', &['\u{0068}', '\u{0323}']),
+    ('\u{1e26}', &['\u{0048}', '\u{0308}']), ('\u{1e27}', &['\u{0068}', '\u{0308}']), ('\u{1e28}', &['\u{0048}', '\u{0327}']),
+    ('\u{1e29}', &['\u{0068}', '\u{0327}']), ('\u{1e2a}', &['\u{0048}', '\u{032e}']), ('\u{1e2b}', &['\u{0068}', '\u{032e}']),
+    ('\u{1e2c}', &['\u{0049}', '\u{0330}']), ('\u{1e2d}', &['\u{0069}', '\u{0330}']), ('\u{1e2e}', &['\u{0049}', '\u{0308}', '\u{0301}']),
+    ('\u{1e2f}', &['\u{0069}', '\u{0308}', '\u{0301}']), ('\u{1e30}', &['\u{004b}', '\u{0301}']), ('\u{1e31}', &['\u{006b}', '\u{0301}']),
+    ('\u{1e32}', &['\u{004b}', '\u{0323}']), ('\u{1e33}', &['\u{006b}', '\u{0323}']), ('\u{1e34}', &['\u{004b}', '\u{0331}']),
+    ('\u{1e35}', &['\u{006b}', '\u{0331}']), ('\u{1e36}', &['\u{004c}', '\u{0323}']), ('\u{1e37}', &['\u{006c}', '\u{0323}']),
+    ('\u{1e38}', &['\u{004c}', '\u{0323}', '\u{0304}']), ('\u{1e39}', &['\u{006c}', '\u{0323}', '\u{0304}']), ('\u{1e3a}', &['\u{004c}', '\u{0331}']),
+    ('\u{1e3b}', &['\u{006c}', '\u{0331}']), ('\u{1e3c}', &['\u{004c}', '\u{032d}']), ('\u{1e3d}', &['\u{006c}', '\u{032d}']),
+    ('\u{1e3e}', &['\u{004d}', '\u{0301}']), ('\u{1e3f}', &['\u{006d}', '\u{0301}']), ('\u{1e40}', &['\u{004d}', '\u{0307}']),
+    ('\u{1e41}', &['\u{006d}', '\u{0307}']), ('\u{1e42}', &['\u{004d}', '\u{0323}']), ('\u{1e43}', &['\u{006d}', '\u{0323}']),
+    ('\u{1e44}', &['\u{004e}', '\u{0307}']), ('\u{1e45}', &['\u{006e}', '\u{0307}']), ('\u{1e46}', &['\u{004e}', '\u{0323}']),
+    ('\u{1e47}', &['\u{006e}', '\u{0323}']), ('\u{1e48}', &['\u{004e}', '\u{0331}']), ('\u{1e49}', &['\u{006e}', '\u{0331}']),
+    ('\u{1e4a}', &['\u{004e}', '\u{032d}']), ('\u{1e4b}', &['\u{006e}', '\u{032d}']), ('\u{1e4c}', &['\u{004f}', '\u{0303}', '\u{0301}']),
+    ('\u{1e4d}', &['\u{006f}', '\u{0303}', '\u{0301}']), ('\u{1e4e}', &['\u{004f}', '\u{0303}', '\u{0308}']), ('\u{1e4f}', &['\u{006f}', '\u{0303}', '\u{0308}']),
+    ('\u{1e50}', &['\u{004f}', '\u{0304}', '\u{0300}']), ('\u{1e51}', &['\u{006f}', '\u{0304}', '\u{0300}']), ('\u{1e52}', &['\u{004f}', '\u{0304}', '\u{0301}']),
+    ('\u{1e53}', &['\u{006f}', '\u{0304}', '\u{0301}']), ('\u{1e54}', &['\u{0050}', '\u{0301}']), ('\u{1e55}', &['\u{0070}', '\u{0301}']),
+    ('\u{1e56}', &['\u{0050}', '\u{0307}']), ('\u{1e57}', &['\u{0070}', '\u{0307}']), ('\u{1e58}', &['\u{0052}', '\u{0307}']),
+    ('\u{1e59}', &['\u{0072}', '\u{0307}']), ('\u{1e5a}', &['\u{0052}', '\u{0323}']), ('\u{1e5b}', &['\u{0072}', '\u{0323}']),
+    ('\u{1e5c}', &['\u{0052}', '\u{0323}', '\u{0304}']), ('\u{1e5d}', &['\u{0072}', '\u{0323}', '\u{0304}']), ('\u{1e5e}', &['\u{0052}', '\u{0331}']),
+    ('\u{1e5f}', &['\u{0072}', '\u{0331}']), ('\u{1e60}', &['\u{0053}', '\u{0307}']), ('\u{1e61}', &['\u{0073}', '\u{0307}']),
+    ('\u{1e62}', &['\u{0053}', '\u{0323}']), ('\u{1e63}', &['\u{0073}', '\u{0323}']), ('\u{1e64}', &['\u{0053}', '\u{0301}', '\u{0307}']),
+    ('\u{1e65}', &['\u{0073}', '\u{0301}', '\u{0307}']), ('\u{1e66}', &['\u{0053}', '\u{030c}', '\u{0307}']), ('\u{1e67}', &['\u{0073}', '\u{030c}', '\u{0307}']),
+    ('\u{1e68}', &['\u{0053}', '\u{0323}', '\u{0307}']), ('\u{1e69}', &['\u{0073}', '\u{0323}', '\u{0307}']), ('\u{1e6a}', &['\u{0054}', '\u{0307}']),
+    ('\u{1e6b}', &['\u{0074}', '\u{0307}']), ('\u{1e6c}', &['\u{0054}', '\u{0323}']), ('\u{1e6d}', &['\u{0074}', '\u{0323}']),
+    ('\u{1e6e}', &['\u{0054}', '\u{0331}']), ('\u{1e6f}', &['\u{0074}', '\u{0331}']), ('\u{1e70}', &['\u{0054}', '\u{032d}']),
+    ('\u{1e71}', &['\u{0074}', '\u{032d}']), ('\u{1e72}', &['\u{0055}', '\u{0324}']), ('\u{1e73}', &['\u{0075}', '\u{0324}']),
+    ('\u{1e74}', &['\u{0055}', '\u{0330}']), ('\u{1e75}', &['\u{0075}', '\u{0330}']), ('\u{1e76}', &['\u{0055}', '\u{032d}']),
+    ('\u{1e77}', &['\u{0075}', '\u{032d}']), ('\u{1e78}', &['\u{0055}', '\u{0303}', '\u{0301}']), ('\u{1e79}', &['\u{0075}', '\u{0303}', '\u{0301}']),
+    ('\u{1e7a}', &['\u{0055}', '\u{0304}', '\u{0308}']), ('\u{1e7b}', &['\u{0075}', '\u{0304}', '\u{0308}']), ('\u{1e7c}', &['\u{0056}', '\u{0303}']),
+    ('\u{1e7d}', &['\u{0076}', '\u{0303}']), ('\u{1e7e}', &['\u{0056}', '\u{0323}']), ('\u{1e7f}', &['\u{0076}', '\u{0323}']),
+    ('\u{1e80}', &['\u{0057}', '\u{0300}']), ('\u{1e81}', &['\u{0077}', '\u{0300}']), ('\u{1e82}', &['\u{0057}', '\u{0301}']),
+    ('\u{1e83}', &['\u{0077}', '\u{0301}']), ('\u{1e84}', &['\u{0057}', '\u{0308}']), ('\u{1e85}', &['\u{0077}', '\u{0308}']),
+    ('\u{1e86}', &['\u{0057}', '\u{0307}']), ('\u{1e87}', &['\u{0077}', '\u{0307}']), ('\u{1e88}', &['\u{0057}', '\u{0323}']),
+    ('\u{1e89}', &['\u{0077}', '\u{0323}']), ('\u{1e8a}', &['\u{0058}', '\u{0307}']), ('\u{1e8b}', &['\u{0078}', '\u{0307}']),
+    ('\u{1e8c}', &['\u{0058}', '\u{0308}']), ('\u{1e8d}', &['\u{0078}', '\u{0308}']), ('\u{1e8e}', &['\u{0059}', '\u{0307}']),
+    ('\u{1e8f}', &['\u{0079}', '\u{0307}']), ('\u{1e90}', &['\u{005a}', '\u{0302}']), ('\u{1e91}', &['\u{007a}', '\u{0302}']),
+    ('\u{1e92}', &['\u{005a}', '\u{0323}']), ('\u{1e93}', &['\u{007a}', '\u{0323}']), ('\u{1e94}', &['\u{005a}', '\u{0331}']),
+    ('\u{1e95}', &['\u{007a}', '\u{0331}']), ('\u{1e96}', &['\u{0068}', '\u{0331}']), ('\u{1e97}', &['\u{0074}', '\u{0308}']),
+    ('\u{1e98}', &['\u{0077}', '\u{030a}']), ('\u{1e99}', &['\u{0079}', '\u{030a}']), ('\u{1e9b}', &['\u{017f}', '\u{0307}']),
+    ('\u{1ea0}', &['\u{0041}', '\u{0323}']), ('\u{1ea1}', &['\u{0061}', '\u{0323}']), ('\u{1ea2}', &['\u{0041}', '\u{0309}']),
+    ('\u{1ea3}', &['\u{0061}', '\u{0309}']), ('\u{1ea4}', &['\u{0041}', '\u{0302}', '\u{0301}']), ('\u{1ea5}', &['\u{0061}', '\u{0302}', '\u{0301}']),
+    ('\u{1ea6}', &['\u{0041}', '\u{0302}', '\u{0300}']), ('\u{1ea7}', &['\u{0061}', '\u{0302}', '\u{0300}']), ('\u{1ea8}', &['\u{0041}', '\u{0302}', '\u{0309}']),
+    ('\u{1ea9}', &['\u{0061}', '\u{0302}', '\u{0309}']), ('\u{1eaa}', &['\u{0041}', '\u{0302}', '\u{0303}']), ('\u{1eab}', &['\u{0061}', '\u{0302}', '\u{0303}']),
+    ('\u{1eac}', &['\u{0041}', '\u{0323}', '\u{0302}']), ('\u{1ead}', &['\u{0061}', '\u{0323}', '\u{0302}']), ('\u{1eae}', &['\u{0041}', '\u{0306}', '\u{0301}']),
+    ('\u{1eaf}', &['\u{0061}', '\u{0306}', '\u{0301}']), ('\u{1eb0}', &['\u{0041}', '\u{0306}', '\u{0300}']), ('\u{1eb1}', &['\u{0061}', '\u{0306}', '\u{0300}']),
+    ('\u{1eb2}', &['\u{0041}', '\u{0306}', '\u{0309}']), ('\u{1eb3}', &['\u{0061}', '\u{0306}', '\u{0309}']), ('\u{1eb4}', &['\u{0041}', '\u{0306}', '\u{0303}']),
+    ('\u{1eb5}', &['\u{0061}', '\u{0306}', '\u{0303}']), ('\u{1eb6}', &['\u{0041}', '\u{0323}', '\u{0306}']), ('\u{1eb7}', &['\u{0061}', '\u{0323}', '\u{0306}']),
+    ('\u{1eb8}', &['\u{0045}', '\u{0323}']), ('\u{1eb9}', &['\u{0065}', '\u{0323}']), ('\u{1eba}', &['\u{0045}', '\u{0309}']),
+    ('\u{1ebb}', &['\u{0065}', '\u{0309}']), ('\u{1ebc}', &['\u{0045}', '\u{0303}']), ('\u{1ebd}', &['\u{0065}', '\u{0303}']),
+    ('\u{1ebe}', &['\u{0045}', '\u{0302}', '\u{0301}']), ('\u{1ebf}', &['\u{0065}', '\u{0302}', '\u{0301}']), ('\u{1ec0}', &['\u{0045}', '\u{0302}', '\u{0300}']),
+    ('\u{1ec1}', &['\u{0065}', '\u{0302}', '\u{0300}']), ('\u{1ec2}', &['\u{0045}', '\u{0302}', '\u{0309}']), ('\u{1ec3}', &['\u{0065}', '\u{0302}', '\u{0309}']),
+    ('\u{1ec4}', &['\u{0045}', '\u{0302}', '\u{0303}']), ('\u{1ec5}', &['\u{0065}', '\u{0302}', '\u{0303}']), ('\u{1ec6}', &['\u{0045}', '\u{0323}', '\u{0302}']),
+    ('\u{1ec7}', &['\u{0065}', '\u{0323}', '\u{0302}']), ('\u{1ec8}', &['\u{0049}', '\u{0309}']), ('\u{1ec9}', &['\u{0069}', '\u{0309}']),
+    ('\u{1eca}', &['\u{0049}', '\u{0323}']), ('\u{1ecb}', &['\u{0069}', '\u{0323}']), ('\u{1ecc}', &['\u{004f}', '\u{0323}']),
+    ('\u{1ecd}', &['\u{006f}', '\u{0323}']), ('\u{1ece}', &['\u{004f}', '\u{0309}']), ('\u{1ecf}', &['\u{006f}', '\u{0309}']),
+    ('\u{1ed0}', &['\u{004f}', '\u{0302}', '\u{0301}']), ('\u{1ed1}', &['\u{006f}', '\u{0302}', '\u{0301}']), ('\u{1ed2}', &['\u{004f}', '\u{0302}', '\u{0300}']),
+    ('\u{1ed3}', &['\u{006f}', '\u{0302}', '\u{0300}']), ('\u{1ed4}', &['\u{004f}', '\u{0302}', '\u{0309}']), ('\u{1ed5}', &['\u{006f}', '\u{0302}', '\u{0309}']),
+    ('\u{1ed6}', &['\u{004f}', '\u{0302}', '\u{0303}']), ('\u{1ed7}', &['\u{006f}', '\u{0302}', '\u{0303}']), ('\u{1ed8}', &['\u{004f}', '\u{0323}', '\u{0302}']),
+    ('\u{1ed9}', &['\u{006f}', '\u{0323}', '\u{0302}']), ('\u{1eda}', &['\u{004f}', '\u{031b}', '\u{0301}']), ('\u{1edb}', &['\u{006f}', '\u{031b}', '\u{0301}']),
+    ('\u{1edc}', &['\u{004f}', '\u{031b}', '\u{0300}']), ('\u{1edd}', &['\u{006f}', '\u{031b}', '\u{0300}']), ('\u{1ede}', &['\u{004f}', '\u{031b}', '\u{0309}']),
+    ('\u{1edf}', &['\u{006f}', '\u{031b}', '\u{0309}']), ('\u{1ee0}', &['\u{004f}', '\u{031b}', '\u{0303}']), ('\u{1ee1}', &['\u{006f}', '\u{031b}', '\u{0303}']),
+    ('\u{1ee2}', &['\u{004f}', '\u{031b}', '\u{0323}']), ('\u{1ee3}', &['\u{006f}', '\u{031b}', '\u{0323}']), ('\u{1ee4}', &['\u{0055}', '\u{0323}']),
+    ('\u{1ee5}', &['\u{0075}', '\u{0323}']), ('\u{1ee6}', &['\u{0055}', '\u{0309}']), ('\u{1ee7}', &['\u{0075}', '\u{0309}']),
+    ('\u{1ee8}', &['\u{0055}', '\u{031b}', '\u{0301}']), ('\u{1ee9}', &['\u{0075}', '\u{031b}', '\u{0301}']), ('\u{1eea}', &['\u{0055}', '\u{031b}', '\u{0300}']),
+    ('\u{1eeb}', &['\u{0075}', '\u{031b}', '\u{0300}']), ('\u{1eec}', &['\u{0055}', '\u{031b}', '\u{0309}']), ('\u{1eed}', &['\u{0075}', '\u{031b}', '\u{0309}']),
+    ('\u{1eee}', &['\u{0055}', '\u{031b}', '\u{0303}']), ('\u{1eef}', &['\u{0075}', '\u{031b}', '\u{0303}']), ('\u{1ef0}', &['\u{0055}', '\u{031b}', '\u{0323}']),
+    ('\u{1ef1}', &['\u{0075}', '\u{031b}', '\u{0323}']), ('\u{1ef2}', &['\u{0059}', '\u{0300}']), ('\u{1ef3}', &['\u{0079}', '\u{0300}']),
+    ('\u{1ef4}', &['\u{0059}', '\u{0323}']), ('\u{1ef5}', &['\u{0079}', '\u{0323}']), ('\u{1ef6}', &['\u{0059}', '\u{0309}']),
+    ('\u{1ef7}', &['\u{0079}', '\u{0309}']), ('\u{1ef8}', &['\u{0059}', '\u{0303}']), ('\u{1ef9}', &['\u{0079}', '\u{0303}']),
+    ('\u{1f00}', &['\u{03b1}', '\u{0313}']), ('\u{1f01}', &['\u{03b1}', '\u{0314}']), ('\u{1f02}', &['\u{03b1}', '\u{0313}', '\u{0300}']),
+    ('\u{1f03}', &['\u{03b1}', '\u{0314}', '\u{0300}']), ('\u{1f04}', &['\u{03b1}', '\u{0313}', '\u{0301}']), ('\u{1f05}', &['\u{03b1}', '\u{0314}', '\u{0301}']),
+    ('\u{1f06}', &['\u{03b1}', '\u{0313}', '\u{0342}']), ('\u{1f07}', &['\u{03b1}', '\u{0314}', '\u{0342}']), ('\u{1f08}', &['\u{0391}', '\u{0313}']),
+    ('\u{1f09}', &['\u{0391}', '\u{0314}']), ('\u{1f0a}', &['\u{0391}', '\u{0313}', '\u{0300}']), ('\u{1f0b}', &['\u{0391}', '\u{0314}', '\u{0300}']),
+    ('\u{1f0c}', &['\u{0391}', '\u{0313}', '\u{0301}']), ('\u{1f0d}', &['\u{0391}', '\u{0314}', '\u{0301}']), ('\u{1f0e}', &['\u{0391}', '\u{0313}', '\u{0342}']),
+    ('\u{1f0f}', &['\u{0391}', '\u{0314}', '\u{0342}']), ('\u{1f10}', &['\u{03b5}', '\u{0313}']), ('\u{1f11}', &['\u{03b5}', '\u{0314}']),
+    ('\u{1f12}', &['\u{03b5}', '\u{0313}', '\u{0300}']), ('\u{1f13}', &['\u{03b5}', '\u{0314}', '\u{0300}']), ('\u{1f14}', &['\u{03b5}', '\u{0313}', '\u{0301}']),
+    ('\u{1f15}', &['\u{03b5}', '\u{0314}', '\u{0301}']), ('\u{1f18}', &['\u{0395}', '\u{0313}']), ('\u{1f19}', &['\u{0395}', '\u{0314}']),
+    ('\u{1f1a}', &['\u{0395}', '\u{0313}', '\u{0300}']), ('\u{1f1b}', &['\u{0395}', '\u{0314}', '\u{0300}']), ('\u{1f1c}', &['\u{0395}', '\u{0313}', '\u{0301}']),
+    ('\u{1f1d}', &['\u{0395}', '\u{0314}', '\u{0301}']), ('\u{1f20}', &['\u{03b7}', '\u{0313}']), ('\u{1f21}', &['\u{03b7}', '\u{0314}']),
+    ('\u{1f22}', &['\u{03b7}', '\u{0313}', '\u{0300}']), ('\u{1f23}', &['\u{03b7}', '\u{0314}', '\u{0300}']), ('\u{1f24}', &['\u{03b7}', '\u{0313}', '\u{0301}']),
+    ('\u{1f25}', &['\u{03b7}', '\u{0314}', '\u{0301}']), ('\u{1f26}', &['\u{03b7}', '\u{0313}', '\u{0342}']), ('\u{1f27}', &['\u{03b7}', '\u{0314}', '\u{0342}']),
+    ('\u{1f28}', &['\u{0397}', '\u{0313}']), ('\u{1f29}', &['\u{0397}', '\u{0314}']), ('\u{1f2a}', &['\u{0397}', '\u{0313}', '\u{0300}']),
+    ('\u{1f2b}', &['\u{0397}', '\u{0314}', '\u{0300}']), ('\u{1f2c}', &['\u{0397}', '\u{0313}', '\u{0301}']), ('\u{1f2d}', &['\u{0397}', '\u{0314}', '\u{0301}']),
+    ('\u{1f2e}', &['\u{0397}', '\u{0313}', '\u{0342}']), ('\u{1f2f}', &['\u{0397}', '\u{0314}', '\u{0342}']), ('\u{1f30}', &['\u{03b9}', '\u{0313}']),
+    ('\u{1f31}', &['\u{03b9}', '\u{0314}']), ('\u{1f32}', &['\u{03b9}', '\u{0313}', '\u{0300}']), ('\u{1f33}', &['\u{03b9}', '\u{0314}', '\u{0300}']),
+    ('\u{1f34}', &['\u{03b9}', '\u{0313}', '\u{0301}']), ('\u{1f35}', &['\u{03b9}', '\u{0314}', '\u{0301}']), ('\u{1f36}', &['\u{03b9}', '\u{0313}', '\u{0342}']),
+    ('\u{1f37}', &['\u{03b9}', '\u{0314}', '\u{0342}']), ('\u{1f38}', &['\u{0399}', '\u{0313}']), ('\u{1f39}', &['\u{0399}', '\u{0314}']),
+    ('\u{1f3a}', &['\u{0399}', '\u{0313}', '\u{0300}']), ('\u{1f3b}', &['\u{0399}', '\u{0314}', '\u{0300}']), ('\u{1f3c}', &['\u{0399}', '\u{0313}', '\u{0301}']),
+    ('\u{1f3d}', &['\u{0399}', '\u{0314}', '\u{0301}']), ('\u{1f3e}', &['\u{0399}', '\u{0313}', '\u{0342}']), ('\u{1f3f}', &['\u{0399}', '\u{0314}', '\u{0342}']),
+    ('\u{1f40}', &['\u{03bf}', '\u{0313}']), ('\u{1f41}', &['\u{03bf}', '\u{0314}']), ('\u{1f42}', &['\u{03bf}', '\u{0313}', '\u{0300}']),
+    ('\u{1f43}', &['\u{03bf}', '\u{0314}', '\u{0300}']), ('\u{1f44}', &['\u{03bf}', '\u{0313}', '\u{0301}']), ('\u{1f45}', &['\u{03bf}', '\u{0314}', '\u{0301}']),
+    ('\u{1f48}', &['\u{039f}', '\u{0313}']), ('\u{1f49}', &['\u{039f}', '\u{0314}']), ('\u{1f4a}', &['\u{039f}', '\u{0313}', '\u{0300}']),
+    ('\u{1f4b}', &['\u{039f}', '\u{0314}', '\u{0300}']), ('\u{1f4c}', &['\u{039f}', '\u{0313}', '\u{0301}']), ('\u{1f4d}', &['\u{039f}', '\u{0314}', '\u{0301}']),
+    ('\u{1f50}', &['\u{03c5}', '\u{0313}']), ('\u{1f51}', &['\u{03c5}', '\u{0314}']), ('\u{1f52}', &['\u{03c5}', '\u{0313}', '\u{0300}']),
+    ('\u{1f53}', &['\u{03c5}', '\u{0314}', '\u{0300}']), ('\u{1f54}', &['\u{03c5}', '\u{0313}', '\u{0301}']), ('\u{1f55}', &['\u{03c5}', '\u{0314}', '\u{0301}']),
+    ('\u{1f56}', &['\u{03c5}', '\u{0313}', '\u{0342}']), ('\u{1f57}', &['\u{03c5}', '\u{0314}', '\u{0342}']), ('\u{1f59}', &['\u{03a5}', '\u{0314}']),
+    ('\u{1f5b}', &['\u{03a5}', '\u{0314}', '\u{0300}']), ('\u{1f5d}', &['\u{03a5}', '\u{0314}', '\u{0301}']), ('\u{1f5f}', &['\u{03a5}', '\u{0314}', '\u{0342}']),
+    ('\u{1f60}', &['\u{03c9}', '\u{0313}']), ('\u{1f61}', &['\u{03c9}', '\u{0314}']), ('\u{1f62}', &['\u{03c9}', '\u{0313}', '\u{0300}']),
+    ('\u{1f63}', &['\u{03c9}', '\u{0314}', '\u{0300}']), ('\u{1f64}', &['\u{03c9}', '\u{0313}', '\u{0301}']), ('\u{1f65}', &['\u{03c9}', '\u{0314}', '\u{0301}']),
+    ('\u{1f66}', &['\u{03c9}', '\u{0313}', '\u{0342}']), ('\u{1f67}', &['\u{03c9}', '\u{0314}', '\u{0342}']), ('\u{1f68}', &['\u{03a9}', '\u{0313}']),
+    ('\u{1f69}', &['\u{03a9}', '\u{0314}']), ('\u{1f6a}', &['\u{03a9}', '\u{0313}', '\u{0300}']), ('\u{1f6b}', &['\u{03a9}', '\u{0314}', '\u{0300}']),
+    ('\u{1f6c}', &['\u{03a9}', '\u{0313}', '\u{0301}']), ('\u{1f6d}', &['\u{03a9}', '\u{0314}', '\u{0301}']), ('\u{1f6e}', &['\u{03a9}', '\u{0313}', '\u{0342}']),
+    ('\u{1f6f}', &['\u{03a9}', '\u{0314}', '\u{0342}']), ('\u{1f70}', &['\u{03b1}', '\u{0300}']), ('\u{1f71}', &['\u{03b1}', '\u{0301}']),
+    ('\u{1f72}', &['\u{03b5}', '\u{0300}']), ('\u{1f73}', &['\u{03b5}', '\u{0301}']), ('\u{1f74}', &['\u{03b7}', '\u{0300}']),
+    ('\u{1f75}', &['\u{03b7}', '\u{0301}']), ('\u{1f76}', &['\u{03b9}', '\u{0300}']), ('\u{1f77}', &['\u{03b9}', '\u{0301}']),
+    ('\u{1f78}', &['\u{03bf}', '\u{0300}']), ('\u{1f79}', &['\u{03bf}', '\u{0301}']), ('\u{1f7a}', &['\u{03c5}', '\u{0300}']),
+    ('\u{1f7b}', &['\u{03c5}', '\u{0301}']), ('\u{1f7c}', &['\u{03c9}', '\u{0300}']), ('\u{1f7d}', &['\u{03c9}', '\u{0301}']),
+    ('\u{1f80}', &['\u{03b1}', '\u{0313}', '\u{0345}']), ('\u{1f81}', &['\u{03b1}', '\u{0314}', '\u{0345}']), ('\u{1f82}', &['\u{03b1}', '\u{0313}', '\u{0300}', '\u{0345}']),
+    ('\u{1f83}', &['\u{03b1}', '\u{0314}', '\u{0300}', '\u{0345}']), ('\u{1f84}', &['\u{03b1}', '\u{0313}', '\u{0301}', '\u{0345}']), ('\u{1f85}', &['\u{03b1}', '\u{0314}', '\u{0301}', '\u{0345}']),
+    ('\u{1f86}', &['\u{03b1}', '\u{0313}', '\u{0342}', '\u{0345}']), ('\u{1f87}', &['\u{03b1}', '\u{0314}', '\u{0342}', '\u{0345}']), ('\u{1f88}', &['\u{0391}', '\u{0313}', '\u{0345}']),
+    ('\u{1f89}', &['\u{0391}', '\u{0314}', '\u{0345}']), ('\u{1f8a}', &['\u{0391}', '\u{0313}', '\u{0300}', '\u{0345}']), ('\u{1f8b}', &['\u{0391}', '\u{0314}', '\u{0300}', '\u{0345}']),
+    ('\u{1f8c}', &['\u{0391}', '\u{0313}', '\u{0301}', '\u{0345}']), ('\u{1f8d}', &['\u{0391}', '\u{0314}', '\u{0301}', '\u{0345}']), ('\u{1f8e}', &['\u{0391}', '\u{0313}', '\u{0342}', '\u{0345}']),
+    ('\u{1f8f}', &['\u{0391}', '\u{0314}', '\u{0342}', '\u{0345}']), ('\u{1f90}', &['\u{03b7}', '\u{0313}', '\u{0345}']), ('\u{1f91}', &['\u{03b7}', '\u{0314}', '\u{0345}']),
+    ('\u{1f92}', &['\u{03b7}', '\u{0313}', '\u{0300}', '\u{0345}']), ('\u{1f93}', &['\u{03b7}', '\u{0314}', '\u{0300}', '\u{0345}']), ('\u{1f94}', &['\u{03b7}', '\u{0313}', '\u{0301}', '\u{0345}']),
+    ('\u{1f95}', &['\u{03b7}', '\u{0314}', '\u{0301}', '\u{0345}']), ('\u{1f96}', &['\u{03b7}', '\u{0313}', '\u{0342}', '\u{0345}']), ('\u{1f97}', &['\u{03b7}', '\u{0314}', '\u{0342}', '\u{0345}']),
+    ('\u{1f98}', &['\u{0397}', '\u{0313}', '\u{0345}']), ('\u{1f99}', &['\u{0397}', '\u{0314}', '\u{0345}']), ('\u{1f9a}', &['\u{0397}', '\u{0313}', '\u{0300}', '\u{0345}']),
+    ('\u{1f9b}', &['\u{0397}', '\u{0314}', '\u{0300}', '\u{0345}']), ('\u{1f9c}', &['\u{0397}', '\u{0313}', '\u{0301}', '\u{0345}']), ('\u{1f9d}', &['\u{0397}', '\u{0314}', '\u{0301}', '\u{0345}']),
+    ('\u{1f9e}', &['\u{0397}', '\u{0313}', '\u{0342}', '\u{0345}']), ('\u{1f9f}', &['\u{0397}', '\u{0314}', '\u{0342}', '\u{0345}']), ('\u{1fa0}', &['\u{03c9}', '\u{0313}', '\u{0345}']),
+    ('\u{1fa1}', &['\u{03c9}', '\u{0314}', '\u{0345}']), ('\u{1fa2}', &['\u{03c9}', '\u{0313}', '\u{0300}', '\u{0345}']), ('\u{1fa3}', &['\u{03c9}', '\u{0314}', '\u{0300}', '\u{0345}']),
+    ('\u{1fa4}', &['\u{03c9}', '\u{0313}', '\u{0301}', '\u{0345}']), ('\u{1fa5}', &['\u{03c9}', '\u{0314}', '\u{0301}', '\u{0345}']), ('\u{1fa6}', &['\u{03c9}', '\u{0313}', '\u{0342}', '\u{0345}']),
+    ('\u{1fa7}', &['\u{03c9}', '\u{0314}', '\u{0342}', '\u{0345}']), ('\u{1fa8}', &['\u{03a9}', '\u{0313}', '\u{0345}']), ('\u{1fa9}', &['\u{03a9}', '\u{0314}', '\u{0345}']),
+    ('\u{1faa}', &['\u{03a9}', '\u{0313}', '\u{0300}', '\u{0345}']), ('\u{1fab}', &['\u{03a9}', '\u{0314}', '\u{0300}', '\u{0345}']), ('\u{1fac}', &['\u{03a9}', '\u{0313}', '\u{0301}', '\u{0345}']),
+    ('\u{1fad}', &['\u{03a9}', '\u{0314}', '\u{0301}', '\u{0345}']), ('\u{1fae}', &['\u{03a9}', '\u{0313}', '\u{0342}', '\u{0345}']), ('\u{1faf}', &['\u{03a9}', '\u{0314}', '\u{0342}', '\u{0345}']),
+    ('\u{1fb0}', &['\u{03b1}', '\u{0306}']), ('\u{1fb1}', &['\u{03b1}', '\u{0304}']), ('\u{1fb2}', &['\u{03b1}', '\u{0300}', '\u{0345}']),
+    ('\u{1fb3}', &['\u{03b1}', '\u{0345}']), ('\u{1fb4}', &['\u{03b1}', '\u{0301}', '\u{0345}']), ('\u{1fb6}', &['\u{03b1}', '\u{0342}']),
+    ('\u{1fb7}', &['\u{03b1}', '\u{0342}', '\u{0345}']), ('\u{1fb8}', &['\u{0391}', '\u{0306}']), ('\u{1fb9}', &['\u{0391}', '\u{0304}']),
+    ('\u{1fba}', &['\u{0391}', '\u{0300}']), ('\u{1fbb}', &['\u{0391}', '\u{0301}']), ('\u{1fbc}', &['\u{0391}', '\u{0345}']),
+    ('\u{1fbe}', &['\u{03b9}']), ('\u{1fc1}', &['\u{00a8}', '\u{0342}']), ('\u{1fc2}', &['\u{03b7}', '\u{0300}', '\u{0345}']),
+    ('\u{1fc3}', &['\u{03b7}', '\u{0345}']), ('\u{1fc4}', &['\u{03b7}', '\u{0301}', '\u{0345}']), ('\u{1fc6}', &['\u{03b7}', '\u{0342}']),
+    ('\u{1fc7}', &['\u{03b7}', '\u{0342}', '\u{0345}']), ('\u{1fc8}', &['\u{0395}', '\u{0300}']), ('\u{1fc9}', &['\u{0395}', '\u{0301}']),
+    ('\u{1fca}', &['\u{0397}', '\u{0300}']), ('\u{1fcb}', &['\u{0397}', '\u{0301}']), ('\u{1fcc}', &['\u{0397}', '\u{0345}']),
+    ('\u{1fcd}', &['\u{1fbf}', '\u{0300}']), ('\u{1fce}', &['\u{1fbf}', '\u{0301}']), ('\u{1fcf}', &['\u{1fbf}', '\u{0342}']),
+    ('\u{1fd0}', &['\u{03b9}', '\u{0306}']), ('\u{1fd1}', &['\u{03b9}', '\u{0304}']), ('\u{1fd2}', &['\u{03b9}', '\u{0308}', '\u{0300}']),
+    ('\u{1fd3}', &['\u{03b9}', '\u{0308}', '\u{0301}']), ('\u{1fd6}', &['\u{03b9}', '\u{0342}']), ('\u{1fd7}', &['\u{03b9}', '\u{0308}', '\u{0342}']),
+    ('\u{1fd8}', &['\u{0399}', '\u{0306}']), ('\u{1fd9}', &['\u{0399}', '\u{0304}']), ('\u{1fda}', &['\u{0399}', '\u{0300}']),
+    ('\u{1fdb}', &['\u{0399}', '\u{0301}']), ('\u{1fdd}', &['\u{1ffe}', '\u{0300}']), ('\u{1fde}', &['\u{1ffe}', '\u{0301}']),
+    ('\u{1fdf}', &['\u{1ffe}', '\u{0342}']), ('\u{1fe0}', &['\u{03c5}', '\u{0306}']), ('\u{1fe1}', &['\u{03c5}', '\u{0304}']),
+    ('\u{1fe2}', &['\u{03c5}', '\u{0308}', '\u{0300}']), ('\u{1fe3}', &['\u{03c5}', '\u{0308}', '\u{0301}']), ('\u{1fe4}', &['\u{03c1}', '\u{0313}']),
+    ('\u{1fe5}', &['\u{03c1}', '\u{0314}']), ('\u{1fe6}', &['\u{03c5}', '\u{0342}']), ('\u{1fe7}', &['\u{03c5}', '\u{0308}', '\u{0342}']),
+    ('\u{1fe8}', &['\u{03a5}', '\u{0306}']), ('\u{1fe9}', &['\u{03a5}', '\u{0304}']), ('\u{1fea}', &['\u{03a5}', '\u{0300}']),
+    ('\u{1feb}', &['\u{03a5}', '\u{0301}']), ('\u{1fec}', &['\u{03a1}', '\u{0314}']), ('\u{1fed}', &['\u{00a8}', '\u{0300}']),
+    ('\u{1fee}', &['\u{00a8}', '\u{0301}']), ('\u{1fef}', &['\u{0060}']), ('\u{1ff2}', &['\u{03c9}', '\u{0300}', '\u{0345}']),
+    ('\u{1ff3}', &['\u{03c9}', '\u{0345}']), ('\u{1ff4}', &['\u{03c9}', '\u{0301}', '\u{0345}']), ('\u{1ff6}', &['\u{03c9}', '\u{0342}']),
+    ('\u{1ff7}', &['\u{03c9}', '\u{0342}', '\u{0345}']), ('\u{1ff8}', &['\u{039f}', '\u{0300}']), ('\u{1ff9}', &['\u{039f}', '\u{0301}']),
+    ('\u{1ffa}', &['\u{03a9}', '\u{0300}']), ('\u{1ffb}', &['\u{03a9}', '\u{0301}']), ('\u{1ffc}', &['\u{03a9}', '\u{0345}']),
+    ('\u{1ffd}', &['\u{00b4}']), ('\u{2000}', &['\u{2002}']), ('\u{2001}', &['\u{2003}']),
+    ('\u{2126}', &['\u{03a9}']), ('\u{212a}', &['\u{004b}']), ('\u{212b}', &['\u{0041}', '\u{030a}']),
+    ('\u{219a}', &['\u{2190}', '\u{0338}']), ('\u{219b}', &['\u{2192}', '\u{0338}']), ('\u{21ae}', &['\u{2194}', '\u{0338}']),
+    ('\u{21cd}', &['\u{21d0}', '\u{0338}']), ('\u{21ce}', &['\u{21d4}', '\u{0338}']), ('\u{21cf}', &['\u{21d2}', '\u{0338}']),
+    ('\u{2204}', &['\u{2203}', '\u{0338}']), ('\u{2209}', &['\u{2208}', '\u{0338}']), ('\u{220c}', &['\u{220b}', '\u{0338}']),
+    ('\u{2224}', &['\u{2223}', '\u{0338}']), ('\u{2226}', &['\u{2225}', '\u{0338}']), ('\u{2241}', &['\u{223c}', '\u{0338}']),
+    ('\u{2244}', &['\u{2243}', '\u{0338}']), ('\u{2247}', &['\u{2245}', '\u{0338}']), ('\u{2249}', &['\u{2248}', '\u{0338}']),
+    ('\u{2260}', &['\u{003d}', '\u{0338}']), ('\u{2262}', &['\u{2261}', '\u{0338}']), ('\u{226d}', &['\u{224d}', '\u{0338}']),
+    ('\u{226e}', &['\u{003c}', '\u{0338}']), ('\u{226f}', &['\u{003e}', '\u{0338}']), ('\u{2270}', &['\u{2264}', '\u{0338}']),
+    ('\u{2271}', &['\u{2265}', '\u{0338}']), ('\u{2274}', &['\u{2272}', '\u{0338}']), ('\u{2275}', &['\u{2273}', '\u{0338}']),
+    ('\u{2278}', &['\u{2276}', '\u{0338}']), ('\u{2279}', &['\u{2277}', '\u{0338}']), ('\u{2280}', &['\u{227a}', '\u{0338}']),
+    ('\u{2281}', &['\u{227b}', '\u{0338}']), ('\u{2284}', &['\u{2282}', '\u{0338}']), ('\u{2285}', &['\u{2283}', '\u{0338}']),
+    ('\u{2288}', &['\u{2286}', '\u{0338}']), ('\u{2289}', &['\u{2287}', '\u{0338}']), ('\u{22ac}', &['\u{22a2}', '\u{0338}']),
+    ('\u{22ad}', &['\u{22a8}', '\u{0338}']), ('\u{22ae}', &['\u{22a9}', '\u{0338}']), ('\u{22af}', &['\u{22ab}', '\u{0338}']),
+    ('\u{22e0}', &['\u{227c}', '\u{0338}']), ('\u{22e1}', &['\u{227d}', '\u{0338}']), ('\u{22e2}', &['\u{2291}', '\u{0338}']),
+    ('\u{22e3}', &['\u{2292}', '\u{0338}']), ('\u{22ea}', &['\u{22b2}', '\u{0338}']), ('\u{22eb}', &['\u{22b3}', '\u{0338}']),
+    ('\u{22ec}', &['\u{22b4}', '\u{0338}']), ('\u{22ed}', &['\u{22b5}', '\u{0338}']), ('\u{2329}', &['\u{3008}']),
+    ('\u{232a}', &['\u{3009}']), ('\u{2adc}', &['\u{2add}', '\u{0338}']), ('\u{304c}', &['\u{304b}', '\u{3099}']),
+    ('\u{304e}', &['\u{304d}', '\u{3099}']), ('\u{3050}', &['\u{304f}', '\u{3099}']), ('\u{3052}', &['\u{3051}', '\u{3099}']),
+    ('\u{3054}', &['\u{3053}', '\u{3099}']), ('\u{3056}', &['\u{3055}', '\u{3099}']), ('\u{3058}', &['\u{3057}', '\u{3099}']),
+    ('\u{305a}', &['\u{3059}', '\u{3099}']), ('\u{305c}', &['\u{305b}', '\u{3099}']), ('\u{305e}', &['\u{305d}', '\u{3099}']),
+    ('\u{3060}', &['\u{305f}', '\u{3099}']), ('\u{3062}', &['\u{3061}', '\u{3099}']), ('\u{3065}', &['\u{3064}', '\u{3099}']),
+    ('\u{3067}', &['\u{3066}', '\u{3099}']), ('\u{3069}', &['\u{3068}', '\u{3099}']), ('\u{3070}', &['\u{306f}', '\u{3099}']),
+    ('\u{3071}', &['\u{306f}', '\u{309a}']), ('\u{3073}', &['\u{3072}', '\u{3099}']), ('\u{3074}', &['\u{3072}', '\u{309a}']),
+    ('\u{3076}', &['\u{3075}', '\u{3099}']), ('\u{3077}', &['\u{3075}', '\u{309a}']), ('\u{3079}', &['\u{3078}', '\u{3099}']),
+    ('\u{307a}', &['\u{3078}', '\u{309a}']), ('\u{307c}', &['\u{307b}', '\u{3099}']), ('\u{307d}', &['\u{307b}', '\u{309a}']),
+    ('\u{3094}', &['\u{3046}', '\u{3099}']), ('\u{309e}', &['\u{309d}', '\u{3099}']), ('\u{30ac}', &['\u{30ab}', '\u{3099}']),
+    ('\u{30ae}', &['\u{30ad}', '\u{3099}']), ('\u{30b0}', &['\u{30af}', '\u{3099}']), ('\u{30b2}', &['\u{30b1}', '\u{3099}']),
+    ('\u{30b4}', &['\u{30b3}', '\u{3099}']), ('\u{30b6}', &['\u{30b5}', '\u{3099}']), ('\u{30b8}', &['\u{30b7}', '\u{3099}']),
+    ('\u{30ba}', &['\u{30b9}', '\u{3099}']), ('\u{30bc}', &['\u{30bb}', '\u{3099}']), ('\u{30be}', &['\u{30bd}', '\u{3099}']),
+    ('\u{30c0}', &['\u{30bf}', '\u{3099}']), ('\u{30c2}', &['\u{30c1}', '\u{3099}']), ('\u{30c5}', &['\u{30c4}', '\u{3099}']),
+    ('\u{30c7}', &['\u{30c6}', '\u{3099}']), ('\u{30c9}', &['\u{30c8}', '\u{3099}']), ('\u{30d0}', &['\u{30cf}', '\u{3099}']),
+    ('\u{30d1}', &['\u{30cf}', '\u{309a}']), ('\u{30d3}', &['\u{30d2}', '\u{3099}']), ('\u{30d4}', &['\u{30d2}', '\u{309a}']),
+    ('\u{30d6}', &['\u{30d5}', '\u{3099}']), ('\u{30d7}', &['\u{30d5}', '\u{309a}']), ('\u{30d9}', &['\u{30d8}', '\u{3099}']),
+    ('\u{30da}', &['\u{30d8}', '\u{309a}']), ('\u{30dc}', &['\u{30db}', '\u{3099}']), ('\u{30dd}', &['\u{30db}', '\u{309a}']),
+    ('\u{30f4}', &['\u{30a6}', '\u{3099}']), ('\u{30f7}', &['\u{30ef}', '\u{3099}']), ('\u{30f8}', &['\u{30f0}', '\u{3099}']),
+    ('\u{30f9}', &['\u{30f1}', '\u{3099}']), ('\u{30fa}', &['\u{30f2}', '\u{3099}']), ('\u{30fe}', &['\u{30fd}', '\u{3099}']),
+    ('\u{f900}', &['\u{8c48}']), ('\u{f901}', &['\u{66f4}']), ('\u{f902}', &['\u{8eca}']),
+    ('\u{f903}', &['\u{8cc8}']), ('\u{f904}', &['\u{6ed1}']), ('\u{f905}', &['\u{4e32}']),
+    ('\u{f906}', &['\u{53e5}']), ('\u{f907}', &['\u{9f9c}']), ('\u{f908}', &['\u{9f9c}']),
+    ('\u{f909}', &['\u{5951}']), ('\u{f90a}', &['\u{91d1}']), ('\u{f90b}', &['\u{5587}']),
+    ('\u{f90c}', &['\u{5948}']), ('\u{f90d}', &['\u{61f6}']), ('\u{f90e}', &['\u{7669}']),
+    ('\u{f90f}', &['\u{7f85}']), ('\u{f910}', &['\u{863f}']), ('\u{f911}', &['\u{87ba}']),
+    ('\u{f912}', &['\u{88f8}']), ('\u{f913}', &['\u{908f}']), ('\u{f914}', &['\u{6a02}']),
+    ('\u{f915}', &['\u{6d1b}']), ('\u{f916}', &['\u{70d9}']), ('\u{f917}', &['\u{73de}']),
+    ('\u{f918}', &['\u{843d}']), ('\u{f919}', &['\u{916a}']), ('\u{f91a}', &['\u{99f1}']),
+    ('\u{f91b}', &['\u{4e82}']), ('\u{f91c}', &['\u{5375}']), ('\u{f91d}', &['\u{6b04}']),
+    ('\u{f91e}', &['\u{721b}']), ('\u{f91f}', &['\u{862d}']), ('\u{f920}', &['\u{9e1e}']),
+    ('\u{f921}', &['\u{5d50}']), ('\u{f922}', &['\u{6feb}']), ('\u{f923}', &['\u{85cd}']),
+    ('\u{f924}', &['\u{8964}']), ('\u{f925}', &['\u{62c9}']), ('\u{f926}', &['\u{81d8}']),
+    ('\u{f927}', &['\u{881f}']), ('\u{f928}', &['\u{5eca}']), ('\u{f929}', &['\u{6717}']),
+    ('\u{f92a}', &['\u{6d6a}']), ('\u{f92b}', &['\u{72fc}']), ('\u{f92c}', &['\u{90ce}']),
+    ('\u{f92d}', &['\u{4f86}']), ('\u{f92e}', &['\u{51b7}']), ('\u{f92f}', &['\u{52de}']),
+    ('\u{f930}', &['\u{64c4}']), ('\u{f931}', &['\u{6ad3}']), ('\u{f932}', &['\u{7210}']),
+    ('\u{f933}', &['\u{76e7}']), ('\u{f934}', &['\u{8001}']), ('\u{f935}', &['\u{8606}']),
+    ('\u{f936}', &['\u{865c}']), ('\u{f937}', &['\u{8def}']), ('\u{f938}', &['\u{9732}']),
+    ('\u{f939}', &['\u{9b6f}']), ('\u{f93a}', &['\u{9dfa}']), ('\u{f93b}', &['\u{788c}']),
+    ('\u{f93c}', &['\u{797f}']), ('\u{f93d}', &['\u{7da0}']), ('\u{f93e}', &['\u{83c9}']),
+    ('\u{f93f}', &['\u{9304}']), ('\u{f940}', &['\u{9e7f}']), ('\u{f941}', &['\u{8ad6}']),
+    ('\u{f942}', &['\u{58df}']), ('\u{f943}', &['\u{5f04}']), ('\u{f944}', &['\u{7c60}']),
+    ('\u{f945}', &['\u{807e}']), ('\u{f946}', &['\u{7262}']), ('\u{f947}', &['\u{78ca}']),
+    ('\u{f948}', &['\u{8cc2}']), ('\u{f949}', &['\u{96f7}']), ('\u{f94a}', &['\u{58d8}']),
+    ('\u{f94b}', &['\u{5c62}']), ('\u{f94c}', &['\u{6a13}']), ('\u{f94d}', &['\u{6dda}']),
+    ('\u{f94e}', &['\u{6f0f}']), ('\u{f94f}', &['\u{7d2f}']), ('\u{f950}', &['\u{7e37}']),
+    ('\u{f951}', &['\u{964b}']), ('\u{f952}', &['\u{52d2}']), ('\u{f953}', &['\u{808b}']),
+    ('\u{f954}', &['\u{51dc}']), ('\u{f955}', &['\u{51cc}']), ('\u{f956}', &['\u{7a1c}']),
+    ('\u{f957}', &['\u{7dbe}']), ('\u{f958}', &['\u{83f1}']), ('\u{f959}', &['\u{9675}']),
+    ('\u{f95a}', &['\u{8b80}']), ('\u{f95b}', &['\u{62cf}']), ('\u{f95c}', &['\u{6a02}']),
+    ('\u{f95d}', &['\u{8afe}']), ('\u{f95e}', &['\u{4e39}']), ('\u{f95f}', &['\u{5be7}']),
+    ('\u{f960}', &['\u{6012}']), ('\u{f961}', &['\u{7387}']), ('\u{f962}', &['\u{7570}']),
+    ('\u{f963}', &['\u{5317}']), ('\u{f964}', &['\u{78fb}']), ('\u{f965}', &['\u{4fbf}']),
+    ('\u{f966}', &['\u{5fa9}']), ('\u{f967}', &['\u{4e0d}']), ('\u{f968}', &['\u{6ccc}']),
+    ('\u{f969}', &['\u{6578}']), ('\u{f96a}', &['\u{7d22}']), ('\u{f96b}', &['\u{53c3}']),
+    ('\u{f96c}', &['\u{585e}']), ('\u{f96d}', &['\u{7701}']), ('\u{f96e}', &['\u{8449}']),
+    ('\u{f96f}', &['\u{8aaa}']), ('\u{f970}', &['\u{6bba}']), ('\u{f971}', &['\u{8fb0}']),
+    ('\u{f972}', &['\u{6c88}']), ('\u{f973}', &['\u{62fe}']), ('\u{f974}', &['\u{82e5}']),
+    ('\u{f975}', &['\u{63a0}']), ('\u{f976}', &['\u{7565}']), ('\u{f977}', &['\u{4eae}']),
+    ('\u{f978}', &['\u{5169}']), ('\u{f979}', &['\u{51c9}']), ('\u{f97a}', &['\u{6881}']),
+    ('\u{f97b}', &['\u{7ce7}']), ('\u{f97c}', &['\u{826f}']), ('\u{f97d}', &['\u{8ad2}']),
+    ('\u{f97e}', &['\u{91cf}']), ('\u{f97f}', &['\u{52f5}']), ('\u{f980}', &['\u{5442}']),
+    ('\u{f981}', &['\u{5973}']), ('\u{f982}', &['\u{5eec}']), ('\u{f983}', &['\u{65c5}']),
+    ('\u{f984}', &['\u{6ffe}']), ('\u{f985}', &['\u{792a}']), ('\u{f986}', &['\u{95ad}']),
+    ('\u{f987}', &['\u{9a6a}']), ('\u{f988}', &['\u{9e97}']), ('\u{f989}', &['\u{9ece}']),
+    ('\u{f98a}', &['\u{529b}']), ('\u{f98b}', &['\u{66c6}']), ('\u{f98c}', &['\u{6b77}']),
+    ('\u{f98d}', &['\u{8f62}']), ('\u{f98e}', &['\u{5e74}']), ('\u{f98f}', &['\u{6190}']),
+    ('\u{f990}', &['\u{6200}']), ('\u{f991}', &['\u{649a}']), ('\u{f992}', &['\u{6f23}']),
+    ('\u{f993}', &['\u{7149}']), ('\u{f994}', &['\u{7489}']), ('\u{f995}', &['\u{79ca}']),
+    ('\u{f996}', &['\u{7df4}']), ('\u{f997}', &['\u{806f}']), ('\u{f998}', &['\u{8f26}']),
+    ('\u{f999}', &['\u{84ee}']), ('\u{f99a}', &['\u{9023}']), ('\u{f99b}', &['\u{934a}']),
+    ('\u{f99c}', &['\u{5217}']), ('\u{f99d}', &['\u{52a3}']), ('\u{f99e}', &['\u{54bd}']),
+    ('\u{f99f}', &['\u{70c8}']), ('\u{f9a0}', &['\u{88c2}']), ('\u{f9a1}', &['\u{8aaa}']),
+    ('\u{f9a2}', &['\u{5ec9}']), ('\u{f9a3}', &['\u{5ff5}']), ('\u{f9a4}', &['\u{637b}']),
+    ('\u{f9a5}', &['\u{6bae}']), ('\u{f9a6}', &['\u{7c3e}']), ('\u{f9a7}', &['\u{7375}']),
+    ('\u{f9a8}', &['\u{4ee4}']), ('\u{f9a9}', &['\u{56f9}']), ('\u{f9aa}', &['\u{5be7}']),
+    ('\u{f9ab}', &['\u{5dba}']), ('\u{f9ac}', &['\u{601c}']), ('\u{f9ad}', &['\u{73b2}']),
+    ('\u{f9ae}', &['\u{7469}']), ('\u{f9af}', &['\u{7f9a}']), ('\u{f9b0}', &['\u{8046}']),
+    ('\u{f9b1}', &['\u{9234}']), ('\u{f9b2}', &['\u{96f6}']), ('\u{f9b3}', &['\u{9748}']),
+    ('\u{f9b4}', &['\u{9818}']), ('\u{f9b5}', &['\u{4f8b}']), ('\u{f9b6}', &['\u{79ae}']),
+    ('\u{f9b7}', &['\u{91b4}']), ('\u{f9b8}', &['\u{96b8}']), ('\u{f9b9}', &['\u{60e1}']),
+    ('\u{f9ba}', &['\u{4e86}']), ('\u{f9bb}', &['\u{50da}']), ('\u{f9bc}', &['\u{5bee}']),
+    ('\u{f9bd}', &['\u{5c3f}']), ('\u{f9be}', &['\u{6599}']), ('\u{f9bf}', &['\u{6a02}']),
+    ('\u{f9c0}', &['\u{71ce}']), ('\u{f9c1}', &['\u{7642}']), ('\u{f9c2}', &['\u{84fc}']),
+    ('\u{f9c3}', &['\u{907c}']), ('\u{f9c4}', &['\u{9f8d}']), ('\u{f9c5}', &['\u{6688}']),
+    ('\u{f9c6}', &['\u{962e}']), ('\u{f9c7}', &['\u{5289}']), ('\u{f9c8}', &['\u{677b}']),
+    ('\u{f9c9}', &['\u{67f3}']), ('\u{f9ca}', &['\u{6d41}']), ('\u{f9cb}', &['\u{6e9c}']),
+    ('\u{f9cc}', &['\u{7409}']), ('\u{f9cd}', &['\u{7559}']), ('\u{f9ce}', &['\u{786b}']),
+    ('\u{f9cf}', &['\u{7d10}']), ('\u{f9d0}', &['\u{985e}']), ('\u{f9d1}', &['\u{516d}']),
+    ('\u{f9d2}', &['\u{622e}']), ('\u{f9d3}', &['\u{9678}']), ('\u{f9d4}', &['\u{502b}']),
+    ('\u{f9d5}', &['\u{5d19}']), ('\u{f9d6}', &['\u{6dea}']), ('\u{f9d7}', &['\u{8f2a}']),
+    ('\u{f9d8}', &['\u{5f8b}']), ('\u{f9d9}', &['\u{6144}']), ('\u{f9da}', &['\u{6817}']),
+    ('\u{f9db}', &['\u{7387}']), ('\u{f9dc}', &['\u{9686}']), ('\u{f9dd}', &['\u{5229}']),
+    ('\u{f9de}', &['\u{540f}']), ('\u{f9df}', &['\u{5c65}']), ('\u{f9e0}', &['\u{6613}']),
+    ('\u{f9e1}', &['\u{674e}']), ('\u{f9e2}', &['\u{68a8}']), ('\u{f9e3}', &['\u{6ce5}']),
+    ('\u{f9e4}', &['\u{7406}']), ('\u{f9e5}', &['\u{75e2}']), ('\u{f9e6}', &['\u{7f79}']),
+    ('\u{f9e7}', &['\u{88cf}']), ('\u{f9e8}', &['\u{88e1}']), ('\u{f9e9}', &['\u{91cc}']),
+    ('\u{f9ea}', &['\u{96e2}']), ('\u{f9eb}', &['\u{533f}']), ('\u{f9ec}', &['\u{6eba}']),
+    ('\u{f9ed}', &['\u{541d}']), ('\u{f9ee}', &['\u{71d0}']), ('\u{f9ef}', &['\u{7498}']),
+    ('\u{f9f0}', &['\u{85fa}']), ('\u{f9f1}', &['\u{96a3}']), ('\u{f9f2}', &['\u{9c57}']),
+    ('\u{f9f3}', &['\u{9e9f}']), ('\u{f9f4}', &['\u{6797}']), ('\u{f9f5}', &['\u{6dcb}']),
+    ('\u{f9f6}', &['\u{81e8}']), ('\u{f9f7}', &['\u{7acb}']), ('\u{f9f8}', &['\u{7b20}']),
+    ('\u{f9f9}', &['\u{7c92}']), ('\u{f9fa}', &['\u{72c0}']), ('\u{f9fb}', &['\u{7099}']),
+    ('\u{f9fc}', &['\u{8b58}']), ('\u{f9fd}', &['\u{4ec0}']), ('\u{f9fe}', &['\u{8336}']),
+    ('\u{f9ff}', &['\u{523a}']), ('\u{fa00}', &['\u{5207}']), ('\u{fa01}', &['\u{5ea6}']),
+    ('\u{fa02}', &['\u{62d3}']), ('\u{fa03}', &['\u{7cd6}']), ('\u{fa04}', &['\u{5b85}']),
+    ('\u{fa05}', &['\u{6d1e}']), ('\u{fa06}', &['\u{66b4}']), ('\u{fa07}', &['\u{8f3b}']),
+    ('\u{fa08}', &['\u{884c}']), ('\u{fa09}', &['\u{964d}']), ('\u{fa0a}', &['\u{898b}']),
+    ('\u{fa0b}', &['\u{5ed3}']), ('\u{fa0c}', &['\u{5140}']), ('\u{fa0d}', &['\u{55c0}']),
+    ('\u{fa10}', &['\u{585a}']), ('\u{fa12}', &['\u{6674}']), ('\u{fa15}', &['\u{51de}']),
+    ('\u{fa16}', &['\u{732a}']), ('\u{fa17}', &['\u{76ca}']), ('\u{fa18}', &['\u{793c}']),
+    ('\u{fa19}', &['\u{795e}']), ('\u{fa1a}', &['\u{7965}']), ('\u{fa1b}', &['\u{798f}']),
+    ('\u{fa1c}', &['\u{9756}']), ('\u{fa1d}', &['\u{7cbe}']), ('\u{fa1e}', &['\u{7fbd}']),
+    ('\u{fa20}', &['\u{8612}']), ('\u{fa22}', &['\u{8af8}']), ('\u{fa25}', &['\u{9038}']),
+    ('\u{fa26}', &['\u{90fd}']), ('\u{fa2a}', &['\u{98ef}']), ('\u{fa2b}', &['\u{98fc}']),
+    ('\u{fa2c}', &['\u{9928}']), ('\u{fa2d}', &['\u{9db4}']), ('\u{fa2e}', &['\u{90de}']),
+    ('\u{fa2f}', &['\u{96b7}']), ('\u{fa30}', &['\u{4fae}']), ('\u{fa31}', &['\u{50e7}']),
+    ('\u{fa32}', &['\u{514d}']), ('\u{fa33}', &['\u{52c9}']), ('\u{fa34}', &['\u{52e4}']),
+    ('\u{fa35}', &['\u{5351}']), ('\u{fa36}', &['\u{559d}']), ('\u{fa37}', &['\u{5606}']),
+    ('\u{fa38}', &['\u{5668}']), ('\u{fa39}', &['\u{5840}']), ('\u{fa3a}', &['\u{58a8}']),
+    ('\u{fa3b}', &['\u{5c64}']), ('\u{fa3c}', &['\u{5c6e}']), ('\u{fa3d}', &['\u{6094}']),
+    ('\u{fa3e}', &['\u{6168}']), ('\u{fa3f}', &['\u{618e}']), ('\u{fa40}', &['\u{61f2}']),
+    ('\u{fa41}', &['\u{654f}']), ('\u{fa42}', &['\u{65e2}']), ('\u{fa43}', &['\u{6691}']),
+    ('\u{fa44}', &['\u{6885}']), ('\u{fa45}', &['\u{6d77}']), ('\u{fa46}', &['\u{6e1a}']),
+    ('\u{fa47}', &['\u{6f22}']), ('\u{fa48}', &['\u{716e}']), ('\u{fa49}', &['\u{722b}']),
+    ('\u{fa4a}', &['\u{7422}']), ('\u{fa4b}', &['\u{7891}']), ('\u{fa4c}', &['\u{793e}']),
+    ('\u{fa4d}', &['\u{7949}']), ('\u{fa4e}', &['\u{7948}']), ('\u{fa4f}', &['\u{7950}']),
+    ('\u{fa50}', &['\u{7956}']), ('\u{fa51}', &['\u{795d}']), ('\u{fa52}', &['\u{798d}']),
+    ('\u{fa53}', &['\u{798e}']), ('\u{fa54}', &['\u{7a40}']), ('\u{fa55}', &['\u{7a81}']),
+    ('\u{fa56}', &['\u{7bc0}']), ('\u{fa57}', &['\u{7df4}']), ('\u{fa58}', &['\u{7e09}']),
+    ('\u{fa59}', &['\u{7e41}']), ('\u{fa5a}', &['\u{7f72}']), ('\u{fa5b}', &['\u{8005}']),
+    ('\u{fa5c}', &['\u{81ed}']), ('\u{fa5d}', &['\u{8279}']), ('\u{fa5e}', &['\u{8279}']),
+    ('\u{fa5f}', &['\u{8457}']), ('\u{fa60}', &['\u{8910}']), ('\u{fa61}', &['\u{8996}']),
+    ('\u{fa62}', &['\u{8b01}']), ('\u{fa63}', &['\u{8b39}']), ('\u{fa64}', &['\u{8cd3}']),
+    ('\u{fa65}', &['\u{8d08}']), ('\u{fa66}', &['\u{8fb6}']), ('\u{fa67}', &['\u{9038}']),
+    ('\u{fa68}', &['\u{96e3}']), ('\u{fa69}', &['\u{97ff}']), ('\u{fa6a}', &['\u{983b}']),
+    ('\u{fa6b}', &['\u{6075}']), ('\u{fa6c}', &['\u{242ee}']), ('\u{fa6d}', &['\u{8218}']),
+    ('\u{fa70}', &['\u{4e26}']), ('\u{fa71}', &['\u{51b5}']), ('\u{fa72}', &['\u{5168}']),
+    ('\u{fa73}', &['\u{4f80}']), ('\u{fa74}', &['\u{5145}']), ('\u{fa75}', &['\u{5180}']),
+    ('\u{fa76}', &['\u{52c7}']), ('\u{fa77}', &['\u{52fa}']), ('\u{fa78}', &['\u{559d}']),
+    ('\u{fa79}', &['\u{5555}']), ('\u{fa7a}', &['\u{5599}']), ('\u{fa7b}', &['\u{55e2}']),
+    ('\u{fa7c}', &['\u{585a}']), ('\u{fa7d}', &['\u{58b3}']), ('\u{fa7e}', &['\u{5944}']),
+    ('\u{fa7f}', &['\u{5954}']), ('\u{fa80}', &['\u{5a62}']), ('\u{fa81}', &['\u{5b28}']),
+    ('\u{fa82}', &['\u{5ed2}']), ('\u{fa83}', &['\u{5ed9}']), ('\u{fa84}', &['\u{5f69}']),
+    ('\u{fa85}', &['\u{5fad}']), ('\u{fa86}', &['\u{60d8}']), ('\u{fa87}', &['\u{614e}']),
+    ('\u{fa88}', &['\u{6108}']), ('\u{fa89}', &['\u{618e}']), ('\u{fa8a}', &['\u{6160}']),
+    ('\u{fa8b}', &['\u{61f2}']), ('\u{fa8c}', &['\u{6234}']), ('\u{fa8d}', &['\u{63c4}']),
+    ('\u{fa8e}', &['\u{641c}']), ('\u{fa8f}', &['\u{6452}']), ('\u{fa90}', &['\u{6556}']),
+    ('\u{fa91}', &['\u{6674}']), ('\u{fa92}', &['\u{6717}']), ('\u{fa93}', &['\u{671b}']),
+    ('\u{fa94}', &['\u{6756}']), ('\u{fa95}', &['\u{6b79}']), ('\u{fa96}', &['\u{6bba}']),
+    ('\u{fa97}', &['\u{6d41}']), ('\u{fa98}', &['\u{6edb}']), ('\u{fa99}', &['\u{6ecb}']),
+    ('\u{fa9a}', &['\u{6f22}']), ('\u{fa9b}', &['\u{701e}']), ('\u{fa9c}', &['\u{716e}']),
+    ('\u{fa9d}', &['\u{77a7}']), ('\u{fa9e}', &['\u{7235}']), ('\u{fa9f}', &['\u{72af}']),
+    ('\u{faa0}', &['\u{732a}']), ('\u{faa1}', &['\u{7471}']), ('\u{faa2}', &['\u{7506}']),
+    ('\u{faa3}', &['\u{753b}']), ('\u{faa4}', &['\u{761d}']), ('\u{faa5}', &['\u{761f}']),
+    ('\u{faa6}', &['\u{76ca}']), ('\u{faa7}', &['\u{76db}']), ('\u{faa8}', &['\u{76f4}']),
+    ('\u{faa9}', &['\u{774a}']), ('\u{faaa}', &['\u{7740}']), ('\u{faab}', &['\u{78cc}']),
+    ('\u{faac}', &['\u{7ab1}']), ('\u{faad}', &['\u{7bc0}']), ('\u{faae}', &['\u{7c7b}']),
+    ('\u{faaf}', &['\u{7d5b}']), ('\u{fab0}', &['\u{7df4}']), ('\u{fab1}', &['\u{7f3e}']),
+    ('\u{fab2}', &['\u{8005}']), ('\u{fab3}', &['\u{8352}']), ('\u{fab4}', &['\u{83ef}']),
+    ('\u{fab5}', &['\u{8779}']), ('\u{fab6}', &['\u{8941}']), ('\u{fab7}', &['\u{8986}']),
+    ('\u{fab8}', &['\u{8996}']), ('\u{fab9}', &['\u{8abf}']), ('\u{faba}', &['\u{8af8}']),
+    ('\u{fabb}', &['\u{8acb}']), ('\u{fabc}', &['\u{8b01}']), ('\u{fabd}', &['\u{8afe}']),
+    ('\u{fabe}', &['\u{8aed}']), ('\u{fabf}', &['\u{8b39}']), ('\u{fac0}', &['\u{8b8a}']),
+    ('\u{fac1}', &['\u{8d08}']), ('\u{fac2}', &['\u{8f38}']), ('\u{fac3}', &['\u{9072}']),
+    ('\u{fac4}', &['\u{9199}']), ('\u{fac5}', &['\u{9276}']), ('\u{fac6}', &['\u{967c}']),
+    ('\u{fac7}', &['\u{96e3}']), ('\u{fac8}', &['\u{9756}']), ('\u{fac9}', &['\u{97db}']),
+    ('\u{faca}', &['\u{97ff}']), ('\u{facb}', &['\u{980b}']), ('\u{facc}', &['\u{983b}']),
+    ('\u{facd}', &['\u{9b12}']), ('\u{face}', &['\u{9f9c}']), ('\u{facf}', &['\u{2284a}']),
+    ('\u{fad0}', &['\u{22844}']), ('\u{fad1}', &['\u{233d5}']), ('\u{fad2}', &['\u{3b9d}']),
+    ('\u{fad3}', &['\u{4018}']), ('\u{fad4}', &['\u{4039}']), ('\u{fad5}', &['\u{25249}']),
+    ('\u{fad6}', &['\u{25cd0}']), ('\u{fad7}', &['\u{27ed3}']), ('\u{fad8}', &['\u{9f43}']),
+    ('\u{fad9}', &['\u{9f8e}']), ('\u{fb1d}', &['\u{05d9}', '\u{05b4}']), ('\u{fb1f}', &['\u{05f2}', '\u{05b7}']),
+    ('\u{fb2a}', &['\u{05e9}', '\u{05c1}']), ('\u{fb2b}', &['\u{05e9}', '\u{05c2}']), ('\u{fb2c}', &['\u{05e9}', '\u{05bc}', '\u{05c1}']),
+    ('\u{fb2d}', &['\u{05e9}', '\u{05bc}', '\u{05c2}']), ('\u{fb2e}', &['\u{05d0}', '\u{05b7}']), ('\u{fb2f}', &['\u{05d0}', '\u{05b8}']),
+    ('\u{fb30}', &['\u{05d0}', '\u{05bc}']), ('\u{fb31}', &['\u{05d1}', '\u{05bc}']), ('\u{fb32}', &['\u{05d2}', '\u{05bc}']),
+    ('\u{fb33}', &['\u{05d3}', '\u{05bc}']), ('\u{fb34}', &['\u{05d4}', '\u{05bc}']), ('\u{fb35}', &['\u{05d5}', '\u{05bc}']),
+    ('\u{fb36}', &['\u{05d6}', '\u{05bc}']), ('\u{fb38}', &['\u{05d8}', '\u{05bc}']), ('\u{fb39}', &['\u{05d9}', '\u{05bc}']),
+    ('\u{fb3a}', &['\u{05da}', '\u{05bc}']), ('\u{fb3b}', &['\u{05db}', '\u{05bc}']), ('\u{fb3c}', &['\u{05dc}', '\u{05bc}']),
+    ('\u{fb3e}', &['\u{05de}', '\u{05bc}']), ('\u{fb40}', &['\u{05e0}', '\u{05bc}']), ('\u{fb41}', &['\u{05e1}', '\u{05bc}']),
+    ('\u{fb43}', &['\u{05e3}', '\u{05bc}']), ('\u{fb44}', &['\u{05e4}', '\u{05bc}']), ('\u{fb46}', &['\u{05e6}', '\u{05bc}']),
+    ('\u{fb47}', &['\u{05e7}', '\u{05bc}']), ('\u{fb48}', &['\u{05e8}', '\u{05bc}']), ('\u{fb49}', &['\u{05e9}', '\u{05bc}']),
+    ('\u{fb4a}', &['\u{05ea}', '\u{05bc}']), ('\u{fb4b}', &['\u{05d5}', '\u{05b9}']), ('\u{fb4c}', &['\u{05d1}', '\u{05bf}']),
+    ('\u{fb4d}', &['\u{05db}', '\u{05bf}']), ('\u{fb4e}', &['\u{05e4}', '\u{05bf}']), ('\u{1109a}', &['\u{11099}', '\u{110ba}']),
+    ('\u{1109c}', &['\u{1109b}', '\u{110ba}']), ('\u{110ab}', &['\u{110a5}', '\u{110ba}']), ('\u{1112e}', &['\u{11131}', '\u{11127}']),
+    ('\u{1112f}', &['\u{11132}', '\u{11127}']), ('\u{1134b}', &['\u{11347}', '\u{1133e}']), ('\u{1134c}', &['\u{11347}', '\u{11357}']),
+    ('\u{114bb}', &['\u{114b9}', '\u{114ba}']), ('\u{114bc}', &['\u{114b9}', '\u{114b0}']), ('\u{114be}', &['\u{114b9}', '\u{114bd}']),
+    ('\u{115ba}', &['\u{115b8}', '\u{115af}']), ('\u{115bb}', &['\u{115b9}', '\u{115af}']), ('\u{11938}', &['\u{11935}', '\u{11930}']),
+    ('\u{1d15e}', &['\u{1d157}', '\u{1d165}']), ('\u{1d15f}', &['\u{1d158}', '\u{1d165}']), ('\u{1d160}', &['\u{1d158}', '\u{1d165}', '\u{1d16e}']),
+    ('\u{1d161}', &['\u{1d158}', '\u{1d165}', '\u{1d16f}']), ('\u{1d162}', &['\u{1d158}', '\u{1d165}', '\u{1d170}']), ('\u{1d163}', &['\u{1d158}', '\u{1d165}', '\u{1d171}']),
+    ('\u{1d164}', &['\u{1d158}', '\u{1d165}', '\u{1d172}']), ('\u{1d1bb}', &['\u{1d1b9}', '\u{1d165}']), ('\u{1d1bc}', &['\u{1d1ba}', '\u{1d165}']),
+    ('\u{1d1bd}', &['\u{1d1b9}', '\u{1d165}', '\u{1d16e}']), ('\u{1d1be}', &['\u{1d1ba}', '\u{1d165}', '\u{1d16e}']), ('\u{1d1bf}', &['\u{1d1b9}', '\u{1d165}', '\u{1d16f}']),
+    ('\u{1d1c0}', &['\u{1d1ba}', '\u{1d165}', '\u{1d16f}']), ('\u{2f800}', &['\u{4e3d}']), ('\u{2f801}', &['\u{4e38}']),
+    ('\u{2f802}', &['\u{4e41}']), ('\u{2f803}', &['\u{20122}']), ('\u{2f804}', &['\u{4f60}']),
+    ('\u{2f805}', &['\u{4fae}']), ('\u{2f806}', &['\u{4fbb}']), ('\u{2f807}', &['\u{5002}']),
+    ('\u{2f808}', &['\u{507a}']), ('\u{2f809}', &['\u{5099}']), ('\u{2f80a}', &['\u{50e7}']),
+    ('\u{2f80b}', &['\u{50cf}']), ('\u{2f80c}', &['\u{349e}']), ('\u{2f80d}', &['\u{2063a}']),
+    ('\u{2f80e}', &['\u{514d}']), ('\u{2f80f}', &['\u{5154}']), ('\u{2f810}', &['\u{5164}']),
+    ('\u{2f811}', &['\u{5177}']), ('\u{2f812}', &['\u{2051c}']), ('\u{2f813}', &['\u{34b9}']),
+    ('\u{2f814}', &['\u{5167}']), ('\u{2f815}', &['\u{518d}']), ('\u{2f816}', &['\u{2054b}']),
+    ('\u{2f817}', &['\u{5197}']), ('\u{2f818}', &['\u{51a4}']), ('\u{2f819}', &['\u{4ecc}']),
+    ('\u{2f81a}', &['\u{51ac}']), ('\u{2f81b}', &['\u{51b5}']), ('\u{2f81c}', &['\u{291df}']),
+    ('\u{2f81d}', &['\u{51f5}']), ('\u{2f81e}', &['\u{5203}']), ('\u{2f81f}', &['\u{34df}']),
+    ('\u{2f820}', &['\u{523b}']), ('\u{2f821}', &['\u{5246}']), ('\u{2f822}', &['\u{5272}']),
+    ('\u{2f823}', &['\u{5277}']), ('\u{2f824}', &['\u{3515}']), ('\u{2f825}', &['\u{52c7}']),
+    ('\u{2f826}', &['\u{52c9}']), ('\u{2f827}', &['\u{52e4}']), ('\u{2f828}', &['\u{52fa}']),
+    ('\u{2f829}', &['\u{5305}']), ('\u{2f82a}', &['\u{5306}']), ('\u{2f82b}', &['\u{5317}']),
+    ('\u{2f82c}', &['\u{5349}']), ('\u{2f82d}', &['\u{5351}']), ('\u{2f82e}', &['\u{535a}']),
+    ('\u{2f82f}', &['\u{5373}']), ('\u{2f830}', &['\u{537d}']), ('\u{2f831}', &['\u{537f}']),
+    ('\u{2f832}', &['\u{537f}']), ('\u{2f833}', &['\u{537f}']), ('\u{2f834}', &['\u{20a2c}']),
+    ('\u{2f835}', &['\u{7070}']), ('\u{2f836}', &['\u{53ca}']), ('\u{2f837}', &['\u{53df}']),
+    ('\u{2f838}', &['\u{20b63}']), ('\u{2f839}', &['\u{53eb}']), ('\u{2f83a}', &['\u{53f1}']),
+    ('\u{2f83b}', &['\u{5406}']), ('\u{2f83c}', &['\u{549e}']), ('\u{2f83d}', &['\u{5438}']),
+    ('\u{2f83e}', &['\u{5448}']), ('\u{2f83f}', &['\u{5468}']), ('\u{2f840}', &['\u{54a2}']),
+    ('\u{2f841}', &['\u{54f6}']), ('\u{2f842}', &['\u{5510}']), ('\u{2f843}', &['\u{5553}']),
+    ('\u{2f844}', &['\u{5563}']), ('\u{2f845}', &['\u{5584}']), ('\u{2f846}', &['\u{5584}']),
+    ('\u{2f847}', &['\u{5599}']), ('\u{2f848}', &['\u{55ab}']), ('\u{2f849}', &['\u{55b3}']),
+    ('\u{2f84a}', &['\u{55c2}']), ('\u{2f84b}', &['\u{5716}']), ('\u{2f84c}', &['\u{5606}']),
+    ('\u{2f84d}', &['\u{5717}']), ('\u{2f84e}', &['\u{5651}']), ('\u{2f84f}', &['\u{5674}']),
+    ('\u{2f850}', &['\u{5207}']), ('\u{2f851}', &['\u{58ee}']), ('\u{2f852}', &['\u{57ce}']),
+    ('\u{2f853}', &['\u{57f4}']), ('\u{2f854}', &['\u{580d}']), ('\u{2f855}', &['\u{578b}']),
+    ('\u{2f856}', &['\u{5832}']), ('\u{2f857}', &['\u{5831}']), ('\u{2f858}', &['\u{58ac}']),
+    ('\u{2f859}', &['\u{214e4}']), ('\u{2f85a}', &['\u{58f2}']), ('\u{2f85b}', &['\u{58f7}']),
+    ('\u{2f85c}', &['\u{5906}']), ('\u{2f85d}', &['\u{591a}']), ('\u{2f85e}', &['\u{5922}']),
+    ('\u{2f85f}', &['\u{5962}']), ('\u{2f860}', &['\u{216a8}']), ('\u{2f861}', &['\u{216ea}']),
+    ('\u{2f862}', &['\u{59ec}']), ('\u{2f863}', &['\u{5a1b}']), ('\u{2f864}', &['\u{5a27}']),
+    ('\u{2f865}', &['\u{59d8}']), ('\u{2f866}', &['\u{5a66}']), ('\u{2f867}', &['\u{36ee}']),
+    ('\u{2f868}', &['\u{36fc}']), ('\u{2f869}', &['\u{5b08}']), ('\u{2f86a}', &['\u{5b3e}']),
+    ('\u{2f86b}', &['\u{5b3e}']), ('\u{2f86c}', &['\u{219c8}']), ('\u{2f86d}', &['\u{5bc3}']),
+    ('\u{2f86e}', &['\u{5bd8}']), ('\u{2f86f}', &['\u{5be7}']), ('\u{2f870}', &['\u{5bf3}']),
+    ('\u{2f871}', &['\u{21b18}']), ('\u{2f872}', &['\u{5bff}']), ('\u{2f873}', &['\u{5c06}']),
+    ('\u{2f874}', &['\u{5f53}']), ('\u{2f875}', &['\u{5c22}']), ('\u{2f876}', &['\u{3781}']),
+    ('\u{2f877}', &['\u{5c60}']), ('\u{2f878}', &['\u{5c6e}']), ('\u{2f879}', &['\u{5cc0}']),
+    ('\u{2f87a}', &['\u{5c8d}']), ('\u{2f87b}', &['\u{21de4}']), ('\u{2f87c}', &['\u{5d43}']),
+    ('\u{2f87d}', &['\u{21de6}']), ('\u{2f87e}', &['\u{5d6e}']), ('\u{2f87f}', &['\u{5d6b}']),
+    ('\u{2f880}', &['\u{5d7c}']), ('\u{2f881}', &['\u{5de1}']), ('\u{2f882}', &['\u{5de2}']),
+    ('\u{2f883}', &['\u{382f}']), ('\u{2f884}', &['\u{5dfd}']), ('\u{2f885}', &['\u{5e28}']),
+    ('\u{2f886}', &['\u{5e3d}']), ('\u{2f887}', &['\u{5e69}']), ('\u{2f888}', &['\u{3862}']),
+    ('\u{2f889}', &['\u{22183}']), ('\u{2f88a}', &['\u{387c}']), ('\u{2f88b}', &['\u{5eb0}']),
+    ('\u{2f88c}', &['\u{5eb3}']), ('\u{2f88d}', &['\u{5eb6}']), ('\u{2f88e}', &['\u{5eca}']),
+    ('\u{2f88f}', &['\u{2a392}']), ('\u{2f890}', &['\u{5efe}']), ('\u{2f891}', &['\u{22331}']),
+    ('\u{2f892}', &['\u{22331}']), ('\u{2f893}', &['\u{8201}']), ('\u{2f894}', &['\u{5f22}']),
+    ('\u{2f895}', &['\u{5f22}']), ('\u{2f896}', &['\u{38c7}']), ('\u{2f897}', &['\u{232b8}']),
+    ('\u{2f898}', &['\u{261da}']), ('\u{2f899}', &['\u{5f62}']), ('\u{2f89a}', &['\u{5f6b}']),
+    ('\u{2f89b}', &['\u{38e3}']), ('\u{2f89c}', &['\u{5f9a}']), ('\u{2f89d}', &['\u{5fcd}']),
+    ('\u{2f89e}', &['\u{5fd7}']), ('\u{2f89f}', &['\u{5ff9}']), ('\u{2f8a0}', &['\u{6081}']),
+    ('\u{2f8a1}', &['\u{393a}']), ('\u{2f8a2}', &['\u{391c}']), ('\u{2f8a3}', &['\u{6094}']),
+    ('\u{2f8a4}', &['\u{226d4}']), ('\u{2f8a5}', &['\u{60c7}']), ('\u{2f8a6}', &['\u{6148}']),
+    ('\u{2f8a7}', &['\u{614c}']), ('\u{2f8a8}', &['\u{614e}']), ('\u{2f8a9}', &['\u{614c}']),
+    ('\u{2f8aa}', &['\u{617a}']), ('\u{2f8ab}', &['\u{618e}']), ('\u{2f8ac}', &['\u{61b2}']),
+    ('\u{2f8ad}', &['\u{61a4}']), ('\u{2f8ae}', &['\u{61af}']), ('\u{2f8af}', &['\u{61de}']),
+    ('\u{2f8b0}', &['\u{61f2}']), ('\u{2f8b1}', &['\u{61f6}']), ('\u{2f8b2}', &['\u{6210}']),
+    ('\u{2f8b3}', &['\u{621b}']), ('\u{2f8b4}', &['\u{625d}']), ('\u{2f8b5}', &['\u{62b1}']),
+    ('\u{2f8b6}', &['\u{62d4}']), ('\u{2f8b7}', &['\u{6350}']), ('\u{2f8b8}', &['\u{22b0c}']),
+    ('\u{2f8b9}', &['\u{633d}']), ('\u{2f8ba}', &['\u{62fc}']), ('\u{2f8bb}', &['\u{6368}']),
+    ('\u{2f8bc}', &['\u{6383}']), ('\u{2f8bd}', &['\u{63e4}']), ('\u{2f8be}', &['\u{22bf1}']),
+    ('\u{2f8bf}', &['\u{6422}']), ('\u{2f8c0}', &['\u{63c5}']), ('\u{2f8c1}', &['\u{63a9}']),
+    ('\u{2f8c2}', &['\u{3a2e}']), ('\u{2f8c3}', &['\u{6469}']), ('\u{2f8c4}', &['\u{647e}']),
+    ('\u{2f8c5}', &['\u{649d}']), ('\u{2f8c6}', &['\u{6477}']), ('\u{2f8c7}', &['\u{3a6c}']),
+    ('\u{2f8c8}', &['\u{654f}']), ('\u{2f8c9}', &['\u{656c}']), ('\u{2f8ca}', &['\u{2300a}']),
+    ('\u{2f8cb}', &['\u{65e3}']), ('\u{2f8cc}', &['\u{66f8}']), ('\u{2f8cd}', &['\u{6649}']),
+    ('\u{2f8ce}', &['\u{3b19}']), ('\u{2f8cf}', &['\u{6691}']), ('\u{2f8d0}', &['\u{3b08}']),
+    ('\u{2f8d1}', &['\u{3ae4}']), ('\u{2f8d2}', &['\u{5192}']), ('\u{2f8d3}', &['\u{5195}']),
+    ('\u{2f8d4}', &['\u{6700}']), ('\u{2f8d5}', &['\u{669c}']), ('\u{2f8d6}', &['\u{80ad}']),
+    ('\u{2f8d7}', &['\u{43d9}']), ('\u{2f8d8}', &['\u{6717}']), ('\u{2f8d9}', &['\u{671b}']),
+    ('\u{2f8da}', &['\u{6721}']), ('\u{2f8db}', &['\u{675e}']), ('\u{2f8dc}', &['\u{6753}']),
+    ('\u{2f8dd}', &['\u{233c3}']), ('\u{2f8de}', &['\u{3b49}']), ('\u{2f8df}', &['\u{67fa}']),
+    ('\u{2f8e0}', &['\u{6785}']), ('\u{2f8e1}', &['\u{6852}']), ('\u{2f8e2}', &['\u{6885}']),
+    ('\u{2f8e3}', &['\u{2346d}']), ('\u{2f8e4}', &['\u{688e}']), ('\u{2f8e5}', &['\u{681f}']),
+    ('\u{2f8e6}', &['\u{6914}']), ('\u{2f8e7}', &['\u{3b9d}']), ('\u{2f8e8}', &['\u{6942}']),
+    ('\u{2f8e9}', &['\u{69a3}']), ('\u{2f8ea}', &['\u{69ea}']), ('\u{2f8eb}', &['\u{6aa8}']),
+    ('\u{2f8ec}', &['\u{236a3}']), ('\u{2f8ed}', &['\u{6adb}']), ('\u{2f8ee}', &['\u{3c18}']),
+    ('\u{2f8ef}', &['\u{6b21}']), ('\u{2f8f0}', &['\u{238a7}']), ('\u{2f8f1}', &['\u{6b54}']),
+    ('\u{2f8f2}', &['\u{3c4e}']), ('\u{2f8f3}', &['\u{6b72}']), ('\u{2f8f4}', &['\u{6b9f}']),
+    ('\u{2f8f5}', &['\u{6bba}']), ('\u{2f8f6}', &['\u{6bbb}']), ('\u{2f8f7}', &['\u{23a8d}']),
+    ('\u{2f8f8}', &['\u{21d0b}']), ('\u{2f8f9}', &['\u{23afa}']), ('\u{2f8fa}', &['\u{6c4e}']),
+    ('\u{2f8fb}', &['\u{23cbc}']), ('\u{2f8fc}', &['\u{6cbf}']), ('\u{2f8fd}', &['\u{6ccd}']),
+    ('\u{2f8fe}', &['\u{6c67}']), ('\u{2f8ff}', &['\u{6d16}']), ('\u{2f900}', &['\u{6d3e}']),
+    ('\u{2f901}', &['\u{6d77}']), ('\u{2f902}', &['\u{6d41}']), ('\u{2f903}', &['\u{6d69}']),
+    ('\u{2f904}', &['\u{6d78}']), ('\u{2f905}', &['\u{6d85}']), ('\u{2f906}', &['\u{23d1e}']),
+    ('\u{2f907}', &['\u{6d34}']), ('\u{2f908}', &['\u{6e2f}']), ('\u{2f909}', &['\u{6e6e}']),
+    ('\u{2f90a}', &['\u{3d33}']), ('\u{2f90b}', &['\u{6ecb}']), ('\u{2f90c}', &['\u{6ec7}']),
+    ('\u{2f90d}', &['\u{23ed1}']), ('\u{2f90e}', &['\u{6df9}']), ('\u{2f90f}', &['\u{6f6e}']),
+    ('\u{2f910}', &['\u{23f5e}']), ('\u{2f911}', &['\u{23f8e}']), ('\u{2f912}', &['\u{6fc6}']),
+    ('\u{2f913}', &['\u{7039}']), ('\u{2f914}', &['\u{701e}']), ('\u{2f915}', &['\u{701b}']),
+    ('\u{2f916}', &['\u{3d96}']), ('\u{2f917}', &['\u{704a}']), ('\u{2f918}', &['\u{707d}']),
+    ('\u{2f919}', &['\u{7077}']), ('\u{2f91a}', &['\u{70ad}']), ('\u{2f91b}', &['\u{20525}']),
+    ('\u{2f91c}', &['\u{7145}']), ('\u{2f91d}', &['\u{24263}']), ('\u{2f91e}', &['\u{719c}']),
+    ('\u{2f91f}', &['\u{243ab}']), ('\u{2f920}', &['\u{7228}']), ('\u{2f921}', &['\u{7235}']),
+    ('\u{2f922}', &['\u{7250}']), ('\u{2f923}', &['\u{24608}']), ('\u{2f924}', &['\u{7280}']),
+    ('\u{2f925}', &['\u{7295}']), ('\u{2f926}', &['\u{24735}']), ('\u{2f927}', &['\u{24814}']),
+    ('\u{2f928}', &['\u{737a}']), ('\u{2f929}', &['\u{738b}']), ('\u{2f92a}', &['\u{3eac}']),
+    ('\u{2f92b}', &['\u{73a5}']), ('\u{2f92c}', &['\u{3eb8}']), ('\u{2f92d}', &['\u{3eb8}']),
+    ('\u{2f92e}', &['\u{7447}']), ('\u{2f92f}', &['\u{745c}']), ('\u{2f930}', &['\u{7471}']),
+    ('\u{2f931}', &['\u{7485}']), ('\u{2f932}', &['\u{74ca}']), ('\u{2f933}', &['\u{3f1b}']),
+    ('\u{2f934}', &['\u{7524}']), ('\u{2f935}', &['\u{24c36}']), ('\u{2f936}', &['\u{753e}']),
+    ('\u{2f937}', &['\u{24c92}']), ('\u{2f938}', &['\u{7570}']), ('\u{2f939}', &['\u{2219f}']),
+    ('\u{2f93a}', &['\u{7610}']), ('\u{2f93b}', &['\u{24fa1}']), ('\u{2f93c}', &['\u{24fb8}']),
+    ('\u{2f93d}', &['\u{25044}']), ('\u{2f93e}', &['\u{3ffc}']), ('\u{2f93f}', &['\u{4008}']),
+    ('\u{2f940}', &['\u{76f4}']), ('\u{2f941}', &['\u{250f3}']), ('\u{2f942}', &['\u{250f2}']),
+    ('\u{2f943}', &['\u{25119}']), ('\u{2f944}', &['\u{25133}']), ('\u{2f945}', &['\u{771e}']),
+    ('\u{2f946}', &['\u{771f}']), ('\u{2f947}', &['\u{771f}']), ('\u{2f948}', &['\u{774a}']),
+    ('\u{2f949}', &['\u{4039}']), ('\u{2f94a}', &['\u{778b}']), ('\u{2f94b}', &['\u{4046}']),
+    ('\u{2f94c}', &['\u{4096}']), ('\u{2f94d}', &['\u{2541d}']), ('\u{2f94e}', &['\u{784e}']),
+    ('\u{2f94f}', &['\u{788c}']), ('\u{2f950}', &['\u{78cc}']), ('\u{2f951}', &['\u{40e3}']),
+    ('\u{2f952}', &['\u{25626}']), ('\u{2f953}', &['\u{7956}']), ('\u{2f954}', &['\u{2569a}']),
+    ('\u{2f955}', &['\u{256c5}']), ('\u{2f956}', &['\u{798f}']), ('\u{2f957}', &['\u{79eb}']),
+    ('\u{2f958}', &['\u{412f}']), ('\u{2f959}', &['\u{7a40}']), ('\u{2f95a}', &['\u{7a4a}']),
+    ('\u{2f95b}', &['\u{7a4f}']), ('\u{2f95c}', &['\u{2597c}']), ('\u{2f95d}', &['\u{25aa7}']),
+    ('\u{2f95e}', &['\u{25aa7}']), ('\u{2f95f}', &['\u{7aee}']), ('\u{2f960}', &['\u{4202}']),
+    ('\u{2f961}', &['\u{25bab}']), ('\u{2f962}', &['\u{7bc6}']), ('\u{2f963}', &['\u{7bc9}']),
+    ('\u{2f964}', &['\u{4227}']), ('\u{2f965}', &['\u{25c80}']), ('\u{2f966}', &['\u{7cd2}']),
+    ('\u{2f967}', &['\u{42a0}']), ('\u{2f968}', &['\u{7ce8}']), ('\u{2f969}', &['\u{7ce3}']),
+    ('\u{2f96a}', &['\u{7d00}']), ('\u{2f96b}', &['\u{25f86}']), ('\u{2f96c}', &['\u{7d63}']),
+    ('\u{2f96d}', &['\u{4301}']), ('\u{2f96e}', &['\u{7dc7}']), ('\u{2f96f}', &['\u{7e02}']),
+    ('\u{2f970}', &['\u{7e45}']), ('\u{2f971}', &['\u{4334}']), ('\u{2f972}', &['\u{26228}']),
+    ('\u{2f973}', &['\u{26247}']), ('\u{2f974}', &['\u{4359}']), ('\u{2f975}', &['\u{262d9}']),
+    ('\u{2f976}', &['\u{7f7a}']), ('\u{2f977}', &['\u{2633e}']), ('\u{2f978}', &['\u{7f95}']),
+    ('\u{2f979}', &['\u{7ffa}']), ('\u{2f97a}', &['\u{8005}']), ('\u{2f97b}', &['\u{264da}']),
+    ('\u{2f97c}', &['\u{26523}']), ('\u{2f97d}', &['\u{8060}']), ('\u{2f97e}', &['\u{265a8}']),
+    ('\u{2f97f}', &['\u{8070}']), ('\u{2f980}', &['\u{2335f}']), ('\u{2f981}', &['\u{43d5}']),
+    ('\u{2f982}', &['\u{80b2}']), ('\u{2f983}', &['\u{8103}']), ('\u{2f984}', &['\u{440b}']),
+    ('\u{2f985}', &['\u{813e}']), ('\u{2f986}', &['\u{5ab5}']), ('\u{2f987}', &['\u{267a7}']),
+    ('\u{2f988}', &['\u{267b5}']), ('\u{2f989}', &['\u{23393}']), ('\u{2f98a}', &['\u{2339c}']),
+    ('\u{2f98b}', &['\u{8201}']), ('\u{2f98c}', &['\u{8204}']), ('\u{2f98d}', &['\u{8f9e}']),
+    ('\u{2f98e}', &['\u{446b}']), ('\u{2f98f}', &['\u{8291}']), ('\u{2f990}', &['\u{828b}']),
+    ('\u{2f991}', &['\u{829d}']), ('\u{2f992}', &['\u{52b3}']), ('\u{2f993}', &['\u{82b1}']),
+    ('\u{2f994}', &['\u{82b3}']), ('\u{2f995}', &['\u{82bd}']), ('\u{2f996}', &['\u{82e6}']),
+    ('\u{2f997}', &['\u{26b3c}']), ('\u{2f998}', &['\u{82e5}']), ('\u{2f999}', &['\u{831d}']),
+    ('\u{2f99a}', &['\u{8363}']), ('\u{2f99b}', &['\u{83ad}']), ('\u{2f99c}', &['\u{8323}']),
+    ('\u{2f99d}', &['\u{83bd}']), ('\u{2f99e}', &['\u{83e7}']), ('\u{2f99f}', &['\u{8457}']),
+    ('\u{2f9a0}', &['\u{8353}']), ('\u{2f9a1}', &['\u{83ca}']), ('\u{2f9a2}', &['\u{83cc}']),
+    ('\u{2f9a3}', &['\u{83dc}']), ('\u{2f9a4}', &['\u{26c36}']), ('\u{2f9a5}', &['\u{26d6b}']),
+    ('\u{2f9a6}', &['\u{26cd5}']), ('\u{2f9a7}', &['\u{452b}']), ('\u{2f9a8}', &['\u{84f1}']),
+    ('\u{2f9a9}', &['\u{84f3}']), ('\u{2f9aa}', &['\u{8516}']), ('\u{2f9ab}', &['\u{273ca}']),
+    ('\u{2f9ac}', &['\u{8564}']), ('\u{2f9ad}', &['\u{26f2c}']), ('\u{2f9ae}', &['\u{455d}']),
+    ('\u{2f9af}', &['\u{4561}']), ('\u{2f9b0}', &['\u{26fb1}']), ('\u{2f9b1}', &['\u{270d2}']),
+    ('\u{2f9b2}', &['\u{456b}']), ('\u{2f9b3}', &['\u{8650}']), ('\u{2f9b4}', &['\u{865c}']),
+    ('\u{2f9b5}', &['\u{8667}']), ('\u{2f9b6}', &['\u{8669}']), ('\u{2f9b7}', &['\u{86a9}']),
+    ('\u{2f9b8}', &['\u{8688}']), ('\u{2f9b9}', &['\u{870e}']), ('\u{2f9ba}', &['\u{86e2}']),
+    ('\u{2f9bb}', &['\u{8779}']), ('\u{2f9bc}', &['\u{8728}']), ('\u{2f9bd}', &['\u{876b}']),
+    ('\u{2f9be}', &['\u{8786}']), ('\u{2f9bf}', &['\u{45d7}']), ('\u{2f9c0}', &['\u{87e1}']),
+    ('\u{2f9c1}', &['\u{8801}']), ('\u{2f9c2}', &['\u{45f9}']), ('\u{2f9c3}', &['\u{8860}']),
+    ('\u{2f9c4}', &['\u{8863}']), ('\u{2f9c5}', &['\u{27667}']), ('\u{2f9c6}', &['\u{88d7}']),
+    ('\u{2f9c7}', &['\u{88de}']), ('\u{2f9c8}', &['\u{4635}']), ('\u{2f9c9}', &['\u{88fa}']),
+    ('\u{2f9ca}', &['\u{34bb}']), ('\u{2f9cb}', &['\u{278ae}']), ('\u{2f9cc}', &['\u{27966}']),
+    ('\u{2f9cd}', &['\u{46be}']), ('\u{2f9ce}', &['\u{46c7}']), ('\u{2f9cf}', &['\u{8aa0}']),
+    ('\u{2f9d0}', &['\u{8aed}']), ('\u{2f9d1}', &['\u{8b8a}']), ('\u{2f9d2}', &['\u{8c55}']),
+    ('\u{2f9d3}', &['\u{27ca8}']), ('\u{2f9d4}', &['\u{8cab}']), ('\u{2f9d5}', &['\u{8cc1}']),
+    ('\u{2f9d6}', &['\u{8d1b}']), ('\u{2f9d7}', &['\u{8d77}']), ('\u{2f9d8}', &['\u{27f2f}']),
+    ('\u{2f9d9}', &['\u{20804}']), ('\u{2f9da}', &['\u{8dcb}']), ('\u{2f9db}', &['\u{8dbc}']),
+    ('\u{2f9dc}', &['\u{8df0}']), ('\u{2f9dd}', &['\u{208de}']), ('\u{2f9de}', &['\u{8ed4}']),
+    ('\u{2f9df}', &['\u{8f38}']), ('\u{2f9e0}', &['\u{285d2}']), ('\u{2f9e1}', &['\u{285ed}']),
+    ('\u{2f9e2}', &['\u{9094}']), ('\u{2f9e3}', &['\u{90f1}']), ('\u{2f9e4}', &['\u{9111}']),
+    ('\u{2f9e5}', &['\u{2872e}']), ('\u{2f9e6}', &['\u{911b}']), ('\u{2f9e7}', &['\u{9238}']),
+    ('\u{2f9e8}', &['\u{92d7}']), ('\u{2f9e9}', &['\u{92d8}']), ('\u{2f9ea}', &['\u{927c}']),
+    ('\u{2f9eb}', &['\u{93f9}']), ('\u{2f9ec}', &['\u{9415}']), ('\u{2f9ed}', &['\u{28bfa}']),
+    ('\u{2f9ee}', &['\u{958b}']), ('\u{2f9ef}', &['\u{4995}']), ('\u{2f9f0}', &['\u{95b7}']),
+    ('\u{2f9f1}', &['\u{28d77}']), ('\u{2f9f2}', &['\u{49e6}']), ('\u{2f9f3}', &['\u{96c3}']),
+    ('\u{2f9f4}', &['\u{5db2}']), ('\u{2f9f5}', &['\u{9723}']), ('\u{2f9f6}', &['\u{29145}']),
+    ('\u{2f9f7}', &['\u{2921a}']), ('\u{2f9f8}', &['\u{4a6e}']), ('\u{2f9f9}', &['\u{4a76}']),
+    ('\u{2f9fa}', &['\u{97e0}']), ('\u{2f9fb}', &['\u{2940a}']), ('\u{2f9fc}', &['\u{4ab2}']),
+    ('\u{2f9fd}', &['\u{29496}']), ('\u{2f9fe}', &['\u{980b}']), ('\u{2f9ff}', &['\u{980b}']),
+    ('\u{2fa00}', &['\u{9829}']), ('\u{2fa01}', &['\u{295b6}']), ('\u{2fa02}', &['\u{98e2}']),
+    ('\u{2fa03}', &['\u{4b33}']), ('\u{2fa04}', &['\u{9929}']), ('\u{2fa05}', &['\u{99a7}']),
+    ('\u{2fa06}', &['\u{99c2}']), ('\u{2fa07}', &['\u{99fe}']), ('\u{2fa08}', &['\u{4bce}']),
+    ('\u{2fa09}', &['\u{29b30}']), ('\u{2fa0a}', &['\u{9b12}']), ('\u{2fa0b}', &['\u{9c40}']),
+    ('\u{2fa0c}', &['\u{9cfd}']), ('\u{2fa0d}', &['\u{4cce}']), ('\u{2fa0e}', &['\u{4ced}']),
+    ('\u{2fa0f}', &['\u{9d67}']), ('\u{2fa10}', &['\u{2a0ce}']), ('\u{2fa11}', &['\u{4cf8}']),
+    ('\u{2fa12}', &['\u{2a105}']), ('\u{2fa13}', &['\u{2a20e}']), ('\u{2fa14}', &['\u{2a291}']),
+    ('\u{2fa15}', &['\u{9ebb}']), ('\u{2fa16}', &['\u{4d56}']), ('\u{2fa17}', &['\u{9ef9}']),
+    ('\u{2fa18}', &['\u{9efe}']), ('\u{2fa19}', &['\u{9f05}']), ('\u{2fa1a}', &['\u{9f0f}']),
+    ('\u{2fa1b}', &['\u{9f16}']), ('\u{2fa1c}', &['\u{9f3b}']), ('\u{2fa1d}', &['\u{2a600}']),
+];
+
+/// The canonical combining classes of characters whose class isn't zero, by character.
+#[rustfmt::skip]
+pub(super) const COMBINING_CLASSES: &[(char, u8)] = &[
+    ('\u{0300}', 230), ('\u{0301}', 230), ('\u{0302}', 230), ('\u{0303}', 230), ('\u{0304}', 230), ('\u{0305}', 230),
+    ('\u{0306}', 230), ('\u{0307}', 230), ('\u{0308}', 230), ('\u{0309}', 230), ('\u{030a}', 230), ('\u{030b}', 230),
+    ('\u{030c}', 230), ('\u{030d}', 230), ('\u{030e}', 230), ('\u{030f}', 230), ('\u{0310}', 230), ('\u{0311}', 230),
+    ('\u{0312}', 230), ('\u{0313}', 230), ('\u{0314}', 230), ('\u{0315}', 232), ('\u{0316}', 220), ('\u{0317}', 220),
+    ('\u{0318}', 220), ('\u{0319}', 220), ('\u{031a}', 232), ('\u{031b}', 216), ('\u{031c}', 220), ('\u{031d}', 220),
+    ('\u{031e}', 220), ('\u{031f}', 220), ('\u{0320}', 220), ('\u{0321}', 202), ('\u{0322}', 202), ('\u{0323}', 220),
+    ('\u{0324}', 220), ('\u{0325}', 220), ('\u{0326}', 220), ('\u{0327}', 202), ('\u{0328}', 202), ('\u{0329}', 220),
+    ('\u{032a}', 220), ('\u{032b}', 220), ('\u{032c}', 220), ('\u{032d}', 220), ('\u{032e}', 220), ('\u{032f}', 220),
+    ('\u{0330}', 220), ('\u{0331}', 220), ('\u{0332}', 220), ('\u{0333}', 220), ('\u{0334}', 1), ('\u{0335}', 1),
+    ('\u{0336}', 1), ('\u{0337}', 1), ('\u{0338}', 1), ('\u{0339}', 220), ('\u{033a}', 220), ('\u{033b}', 220),
+    ('\u{033c}', 220), ('\u{033d}', 230), ('\u{033e}', 230), ('\u{033f}', 230), ('\u{0340}', 230), ('\u{0341}', 230),
+    ('\u{0342}', 230), ('\u{0343}', 230), ('\u{0344}', 230), ('\u{0345}', 240), ('\u{0346}', 230), ('\u{0347}', 220),
+    ('\u{0348}', 220), ('\u{0349}', 220), ('\u{034a}', 230), ('\u{034b}', 230), ('\u{034c}', 230), ('\u{034d}', 220),
+    ('\u{034e}', 220), ('\u{0350}', 230), ('\u{0351}', 230), ('\u{0352}', 230), ('\u{0353}', 220), ('\u{0354}', 220),
+    ('\u{0355}', 220), ('\u{0356}', 220), ('\u{0357}', 230), ('\u{0358}', 232), ('\u{0359}', 220), ('\u{035a}', 220),
+    ('\u{035b}', 230), ('\u{035c}', 233), ('\u{035d}', 234), ('\u{035e}', 234), ('\u{035f}', 233), ('\u{0360}', 234),
+    ('\u{0361}', 234), ('\u{0362}', 233), ('\u{0363}', 230), ('\u{0364}', 230), ('\u{0365}', 230), ('\u{0366}', 230),
+    ('\u{0367}', 230), ('\u{0368}', 230), ('\u{0369}', 230), ('\u{036a}', 230), ('\u{036b}', 230), ('\u{036c}', 230),
+    ('\u{036d}', 230), ('\u{036e}', 230), ('\u{036f}', 230), ('\u{0483}', 230), ('\u{0484}', 230), ('\u{0485}', 230),
+    ('\u{0486}', 230), ('\u{0487}', 230), ('\u{0591}', 220), ('\u{0592}', 230), ('\u{0593}', 230), ('\u{0594}', 230),
+    ('\u{0595}', 230), ('\u{0596}', 220), ('\u{0597}', 230), ('\u{0598}', 230), ('\u{0599}', 230), ('\u{059a}', 222),
+    ('\u{059b}', 220), ('\u{059c}', 230), ('\u{059d}', 230), ('\u{059e}', 230), ('\u{059f}', 230), ('\u{05a0}', 230),
+    ('\u{05a1}', 230), ('\u{05a2}', 220), ('\u{05a3}', 220), ('\u{05a4}', 220), ('\u{05a5}', 220), ('\u{05a6}', 220),
+    ('\u{05a7}', 220), ('\u{05a8}', 230), ('\u{05a9}', 230), ('\u{05aa}', 220), ('\u{05ab}', 230), ('\u{05ac}', 230),
+    ('\u{05ad}', 222), ('\u{05ae}', 228), ('\u{05af}', 230), ('\u{05b0}', 10), ('\u{05b1}', 11), ('\u{05b2}', 12),
+    ('\u{05b3}', 13), ('\u{05b4}', 14), ('\u{05b5}', 15), ('\u{05b6}', 16), ('\u{05b7}', 17), ('\u{05b8}', 18),
+    ('\u{05b9}', 19), ('\u{05ba}', 19), ('\u{05bb}', 20), ('\u{05bc}', 21), ('\u{05bd}', 22), ('\u{05bf}', 23),
+    ('\u{05c1}', 24), ('\u{05c2}', 25), ('\u{05c4}', 230), ('\u{05c5}', 220), ('\u{05c7}', 18), ('\u{0610}', 230),
+    ('\u{0611}', 230), ('\u{0612}', 230), ('\u{0613}', 230), ('\u{0614}', 230), ('\u{0615}', 230), ('\u{0616}', 230),
+    ('\u{0617}', 230), ('\u{0618}', 30), ('\u{0619}', 31), ('\u{061a}', 32), ('\u{064b}', 27), ('\u{064c}', 28),
+    ('\u{064d}', 29), ('\u{064e}', 30), ('\u{064f}', 31), ('\u{0650}', 32), ('\u{0651}', 33), ('\u{0652}', 34),
+    ('\u{0653}', 230), ('\u{0654}', 230), ('\u{0655}', 220), ('\u{0656}', 220), ('\u{0657}', 230), ('\u{0658}', 230),
+    ('\u{0659}', 230), ('\u{065a}', 230), ('\u{065b}', 230), ('\u{065c}', 220), ('\u{065d}', 230), ('\u{065e}', 230),
+    ('\u{065f}', 220), ('\u{0670}', 35), ('\u{06d6}', 230), ('\u{06d7}', 230), ('\u{06d8}', 230), ('\u{06d9}', 230),
+    ('\u{06da}', 230), ('\u{06db}', 230), ('\u{06dc}', 230), ('\u{06df}', 230), ('\u{06e0}', 230), ('\u{06e1}', 230),
+    ('\u{06e2}', 230), ('\u{06e3}', 220), ('\u{06e4}', 230), ('\u{06e7}', 230), ('\u{06e8}', 230), ('\u{06ea}', 220),
+    ('\u{06eb}', 230), ('\u{06ec}', 230), ('\u{06ed}', 220), ('\u{0711}', 36), ('\u{0730}', 230), ('\u{0731}', 220),
+    ('\u{0732}', 230), ('\u{0733}', 230), ('\u{0734}', 220), ('\u{0735}', 230), ('\u{0736}', 230), ('\u{0737}', 220),
+    ('\u{0738}', 220), ('\u{0739}', 220), ('\u{073a}', 230), ('\u{073b}', 220), ('\u{073c}', 220), ('\u{073d}', 230),
+    ('\u{073e}', 220), ('\u{073f}', 230), ('\u{0740}', 230), ('\u{0741}', 230), ('\u{0742}', 220), ('\u{0743}', 230),
+    ('\u{0744}', 220), ('\u{0745}', 230), ('\u{0746}', 220), ('\u{0747}', 230), ('\u{0748}', 220), ('\u{0749}', 230),
+    ('\u{074a}', 230), ('\u{07eb}', 230), ('\u{07ec}', 230), ('\u{07ed}', 230), ('\u{07ee}', 230), ('\u{07ef}', 230),
+    ('\u{07f0}', 230), ('\u{07f1}', 230), ('\u{07f2}', 220), ('\u{07f3}', 230), ('\u{07fd}', 220), ('\u{0816}', 230),
+    ('\u{0817}', 230), ('\u{0818}', 230), ('\u{0819}', 230), ('\u{081b}', 230), ('\u{081c}', 230), ('\u{081d}', 230),
+    ('\u{081e}', 230), ('\u{081f}', 230), ('\u{0820}', 230), ('\u{0821}', 230), ('\u{0822}', 230), ('\u{0823}', 230),
+    ('\u{0825}', 230), ('\u{0826}', 230), ('\u{0827}', 230), ('\u{0829}', 230), ('\u{082a}', 230), ('\u{082b}', 230),
+    ('\u{082c}', 230), ('\u{082d}', 230), ('\u{0859}', 220), ('\u{085a}', 220), ('\u{085b}', 220), ('\u{0898}', 230),
+    ('\u{0899}', 220), ('\u{089a}', 220), ('\u{089b}', 220), ('\u{089c}', 230), ('\u{089d}', 230), ('\u{089e}', 230),
+    ('\u{089f}', 230), ('\u{08ca}', 230), ('\u{08cb}', 230), ('\u{08cc}', 230), ('\u{08cd}', 230), ('\u{08ce}', 230),
+    ('\u{08cf}', 220), ('\u{08d0}', 220), ('\u{08d1}', 220), ('\u{08d2}', 220), ('\u{08d3}', 220), ('\u{08d4}', 230),
+    ('\u{08d5}', 230), ('\u{08d6}', 230), ('\u{08d7}', 230), ('\u{08d8}', 230), ('\u{08d9}', 230), ('\u{08da}', 230),
+    ('\u{08db}', 230), ('\u{08dc}', 230), ('\u{08dd}', 230), ('\u{08de}', 230), ('\u{08df}', 230), ('\u{08e0}', 230),
+    ('\u{08e1}', 230), ('\u{08e3}', 220), ('\u{08e4}', 230), ('\u{08e5}', 230), ('\u{08e6}', 220), ('\u{08e7}', 230),
+    ('\u{08e8}', 230), ('\u{08e9}', 220), ('\u{08ea}', 230), ('\u{08eb}', 230), ('\u{08ec}', 230), ('\u{08ed}', 220),
+    ('\u{08ee}', 220), ('\u{08ef}', 220), ('\u{08f0}', 27), ('\u{08f1}', 28), ('\u{08f2}', 29), ('\u{08f3}', 230),
+    ('\u{08f4}', 230), ('\u{08f5}', 230), ('\u{08f6}', 220), ('\u{08f7}', 230), ('\u{08f8}', 230), ('\u{08f9}', 220),
+    ('\u{08fa}', 220), ('\u{08fb}', 230), ('\u{08fc}', 230), ('\u{08fd}', 230), ('\u{08fe}', 230), ('\u{08ff}', 230),
+    ('\u{093c}', 7), ('\u{094d}', 9), ('\u{0951}', 230), ('\u{0952}', 220), ('\u{0953}', 230), ('\u{0954}', 230),
+    ('\u{09bc}', 7), ('\u{09cd}', 9), ('\u{09fe}', 230), ('\u{0a3c}', 7), ('\u{0a4d}', 9), ('\u{0abc}', 7),
+    ('\u{0acd}', 9), ('\u{0b3c}', 7), ('\u{0b4d}', 9), ('\u{0bcd}', 9), ('\u{0c3c}', 7), ('\u{0c4d}', 9),
+    ('\u{0c55}', 84), ('\u{0c56}', 91), ('\u{0cbc}', 7), ('\u{0ccd}', 9), ('\u{0d3b}', 9), ('\u{0d3c}', 9),
+    ('\u{0d4d}', 9), ('\u{0dca}', 9), ('\u{0e38}', 103), ('\u{0e39}', 103), ('\u{0e3a}', 9), ('\u{0e48}', 107),
+    ('\u{0e49}', 107), ('\u{0e4a}', 107), ('\u{0e4b}', 107), ('\u{0eb8}', 118), ('\u{0eb9}', 118), ('\u{0eba}', 9),
+    ('\u{0ec8}', 122), ('\u{0ec9}', 122), ('\u{0eca}', 122), ('\u{0ecb}', 122), ('\u{0f18}', 220), ('\u{0f19}', 220),
+    ('\u{0f35}', 220), ('\u{0f37}', 220), ('\u{0f39}', 216), ('\u{0f71}', 129), ('\u{0f72}', 130), ('\u{0f74}', 132),
+    ('\u{0f7a}', 130), ('\u{0f7b}', 130), ('\u{0f7c}', 130), ('\u{0f7d}', 130), ('\u{0f80}', 130), ('\u{0f82}', 230),
+    ('\u{0f83}', 230), ('\u{0f84}', 9), ('\u{0f86}', 230), ('\u{0f87}', 230), ('\u{0fc6}', 220), ('\u{1037}', 7),
+    ('\u{1039}', 9), ('\u{103a}', 9), ('\u{108d}', 220), ('\u{135d}', 230), ('\u{135e}', 230), ('\u{135f}', 230),
+    ('\u{1714}', 9), ('\u{1715}', 9), ('\u{1734}', 9), ('\u{17d2}', 9), ('\u{17dd}', 230), ('\u{18a9}', 228),
+    ('\u{1939}', 222), ('\u{193a}', 230), ('\u{193b}', 220), ('\u{1a17}', 230), ('\u{1a18}', 220), ('\u{1a60}', 9),
+    ('\u{1a75}', 230), ('\u{1a76}', 230), ('\u{1a77}', 230), ('\u{1a78}', 230), ('\u{1a79}', 230), ('\u{1a7a}', 230),
+    ('\u{1a7b}', 230), ('\u{1a7c}', 230), ('\u{1a7f}', 220), ('\u{1ab0}', 230), ('\u{1ab1}', 230), ('\u{1ab2}', 230),
+    ('\u{1ab3}', 230), ('\u{1ab4}', 230), ('\u{1ab5}', 220), ('\u{1ab6}', 220), ('\u{1ab7}', 220), ('\u{1ab8}', 220),
+    ('\u{1ab9}', 220), ('\u{1aba}', 220), ('\u{1abb}', 230), ('\u{1abc}', 230), ('\u{1abd}', 220), ('\u{1abf}', 220),
+    ('\u{1ac0}', 220), ('\u{1ac1}', 230), ('\u{1ac2}', 230), ('\u{1ac3}', 220), ('\u{1ac4}', 220), ('\u{1ac5}', 230),
+    ('\u{1ac6}', 230), ('\u{1ac7}', 230), ('\u{1ac8}', 230), ('\u{1ac9}', 230), ('\u{1aca}', 220), ('\u{1acb}', 230),
+    ('\u{1acc}', 230), ('\u{1acd}', 230), ('\u{1ace}', 230), ('\u{1b34}', 7), ('\u{1b44}', 9), ('\u{1b6b}', 230),
+    ('\u{1b6c}', 220), ('\u{1b6d}', 230), ('\u{1b6e}', 230), ('\u{1b6f}', 230), ('\u{1b70}', 230), ('\u{1b71}', 230),
+    ('\u{1b72}', 230), ('\u{1b73}', 230), ('\u{1baa}', 9), ('\u{1bab}', 9), ('\u{1be6}', 7), ('\u{1bf2}', 9),
+    ('\u{1bf3}', 9), ('\u{1c37}', 7), ('\u{1cd0}', 230), ('\u{1cd1}', 230), ('\u{1cd2}', 230), ('\u{1cd4}', 1),
+    ('\u{1cd5}', 220), ('\u{1cd6}', 220), ('\u{1cd7}', 220), ('\u{1cd8}', 220), ('\u{1cd9}', 220), ('\u{1cda}', 230),
+    ('\u{1cdb}', 230), ('\u{1cdc}', 220), ('\u{1cdd}', 220), ('\u{1cde}', 220), ('\u{1cdf}', 220), ('\u{1ce0}', 230),
+    ('\u{1ce2}', 1), ('\u{1ce3}', 1), ('\u{1ce4}', 1), ('\u{1ce5}', 1), ('\u{1ce6}', 1), ('\u{1ce7}', 1),
+    ('\u{1ce8}', 1), ('\u{1ced}', 220), ('\u{1cf4}', 230), ('\u{1cf8}', 230), ('\u{1cf9}', 230), ('\u{1dc0}', 230),
+    ('\u{1dc1}', 230), ('\u{1dc2}', 220), ('\u{1dc3}', 230), ('\u{1dc4}', 230), ('\u{1dc5}', 230), ('\u{1dc6}', 230),
+    ('\u{1dc7}', 230), ('\u{1dc8}', 230), ('\u{1dc9}', 230), ('\u{1dca}', 220), ('\u{1dcb}', 230), ('\u{1dcc}', 230),
+    ('\u{1dcd}', 234), ('\u{1dce}', 214), ('\u{1dcf}', 220), ('\u{1dd0}', 202), ('\u{1dd1}', 230), ('\u{1dd2}', 230),
+    ('\u{1dd3}', 230), ('\u{1dd4}', 230), ('\u{1dd5}', 230), ('\u{1dd6}', 230), ('\u{1dd7}', 230), ('\u{1dd8}', 230),
+    ('\u{1dd9}', 230), ('\u{1dda}', 230), ('\u{1ddb}', 230), ('\u{1ddc}', 230), ('\u{1ddd}', 230), ('\u{1dde}', 230),
+    ('\u{1ddf}', 230), ('\u{1de0}', 230), ('\u{1de1}', 230), ('\u{1de2}', 230), ('\u{1de3}', 230), ('\u{1de4}', 230),
+    ('\u{1de5}', 230), ('\u{1de6}', 230), ('\u{1de7}', 230), ('\u{1de8}', 230), ('\u{1de9}', 230), ('\u{1dea}', 230),
+    ('\u{1deb}', 230), ('\u{1dec}', 230), ('\u{1ded}', 230), ('\u{1dee}', 230), ('\u{1def}', 230), ('\u{1df0}', 230),
+    ('\u{1df1}', 230), ('\u{1df2}', 230), ('\u{1df3}', 230), ('\u{1df4}', 230), ('\u{1df5}', 230), ('\u{1df6}', 232),
+    ('\u{1df7}', 228), ('\u{1df8}', 228), ('\u{1df9}', 220), ('\u{1dfa}', 218), ('\u{1dfb}', 230), ('\u{1dfc}', 233),
+    ('\u{1dfd}', 220), ('\u{1dfe}', 230), ('\u{1dff}', 220), ('\u{20d0}', 230), ('\u{20d1}', 230), ('\u{20d2}', 1),
+    ('\u{20d3}', 1), ('\u{20d4}', 230), ('\u{20d5}', 230), ('\u{20d6}', 230), ('\u{20d7}', 230), ('\u{20d8}', 1),
+    ('\u{20d9}', 1), ('\u{20da}', 1), ('\u{20db}', 230), ('\u{20dc}', 230), ('\u{20e1}', 230), ('\u{20e5}', 1),
+    ('\u{20e6}', 1), ('\u{20e7}', 230), ('\u{20e8}', 220), ('\u{20e9}', 230), ('\u{20ea}', 1), ('\u{20eb}', 1),
+    ('\u{20ec}', 220), ('\u{20ed}', 220), ('\u{20ee}', 220), ('\u{20ef}', 220), ('\u{20f0}', 230), ('\u{2cef}', 230),
+    ('\u{2cf0}', 230), ('\u{2cf1}', 230), ('\u{2d7f}', 9), ('\u{2de0}', 230), ('\u{2de1}', 230), ('\u{2de2}', 230),
+    ('\u{2de3}', 230), ('\u{2de4}', 230), ('\u{2de5}', 230), ('\u{2de6}', 230), ('\u{2de7}', 230), ('\u{2de8}', 230),
+    ('\u{2de9}', 230), ('\u{2dea}', 230), ('\u{2deb}', 230), ('\u{2dec}', 230), ('\u{2ded}', 230), ('\u{2dee}', 230),
+    ('\u{2def}', 230), ('\u{2df0}', 230), ('\u{2df1}', 230), ('\u{2df2}', 230), ('\u{2df3}', 230), ('\u{2df4}', 230),
+    ('\u{2df5}', 230), ('\u{2df6}', 230), ('\u{2df7}', 230), ('\u{2df8}', 230), ('\u{2df9}', 230), ('\u{2dfa}', 230),
+    ('\u{2dfb}', 230), ('\u{2dfc}', 230), ('\u{2dfd}', 230), ('\u{2dfe}', 230), ('\u{2dff}', 230), ('\u{302a}', 218),
+    ('\u{302b}', 228), ('\u{302c}', 232), ('\u{302d}', 222), ('\u{302e}', 224), ('\u{302f}', 224), ('\u{3099}', 8),
+    ('\u{309a}', 8), ('\u{a66f}', 230), ('\u{a674}', 230), ('\u{a675}', 230), ('\u{a676}', 230), ('\u{a677}', 230),
+    ('\u{a678}', 230), ('\u{a679}', 230), ('\u{a67a}', 230), ('\u{a67b}', 230), ('\u{a67c}', 230), ('\u{a67d}', 230),
+    ('\u{a69e}', 230), ('\u{a69f}', 230), ('\u{a6f0}', 230), ('\u{a6f1}', 230), ('\u{a806}', 9), ('\u{a82c}', 9),
+    ('\u{a8c4}', 9), ('\u{a8e0}', 230), ('\u{a8e1}', 230), ('\u{a8e2}', 230), ('\u{a8e3}', 230), ('\u{a8e4}', 230),
+    ('\u{a8e5}', 230), ('\u{a8e6}', 230), ('\u{a8e7}', 230), ('\u{a8e8}', 230), ('\u{a8e9}', 230), ('\u{a8ea}', 230),
+    ('\u{a8eb}', 230), ('\u{a8ec}', 230), ('\u{a8ed}', 230), ('\u{a8ee}', 230), ('\u{a8ef}', 230), ('\u{a8f0}', 230),
+    ('\u{a8f1}', 230), ('\u{a92b}', 220), ('\u{a92c}', 220), ('\u{a92d}', 220), ('\u{a953}', 9), ('\u{a9b3}', 7),
+    ('\u{a9c0}', 9), ('\u{aab0}', 230), ('\u{aab2}', 230), ('\u{aab3}', 230), ('\u{aab4}', 220), ('\u{aab7}', 230),
+    ('\u{aab8}', 230), ('\u{aabe}', 230), ('\u{aabf}', 230), ('\u{aac1}', 230), ('\u{aaf6}', 9), ('\u{abed}', 9),
+    ('\u{fb1e}', 26), ('\u{fe20}', 230), ('\u{fe21}', 230), ('\u{fe22}', 230), ('\u{fe23}', 230), ('\u{fe24}', 230),
+    ('\u{fe25}', 230), ('\u{fe26}', 230), ('\u{fe27}', 220), ('\u{fe28}', 220), ('\u{fe29}', 220), ('\u{fe2a}', 220),
+    ('\u{fe2b}', 220), ('\u{fe2c}', 220), ('\u{fe2d}', 220), ('\u{fe2e}', 230), ('\u{fe2f}', 230), ('\u{101fd}', 220),
+    ('\u{102e0}', 220), ('\u{10376}', 230), ('\u{10377}', 230), ('\u{10378}', 230), ('\u{10379}', 230), ('\u{1037a}', 230),
+    ('\u{10a0d}', 220), ('\u{10a0f}', 230), ('\u{10a38}', 230), ('\u{10a39}', 1), ('\u{10a3a}', 220), ('\u{10a3f}', 9),
+    ('\u{10ae5}', 230), ('\u{10ae6}', 220), ('\u{10d24}', 230), ('\u{10d25}', 230), ('\u{10d26}', 230), ('\u{10d27}', 230),
+    ('\u{10eab}', 230), ('\u{10eac}', 230), ('\u{10f46}', 220), ('\u{10f47}', 220), ('\u{10f48}', 230), ('\u{10f49}', 230),
+    ('\u{10f4a}', 230), ('\u{10f4b}', 220), ('\u{10f4c}', 230), ('\u{10f4d}', 220), ('\u{10f4e}', 220), ('\u{10f4f}', 220),
+    ('\u{10f50}', 220), ('\u{10f82}', 230), ('\u{10f83}', 220), ('\u{10f84}', 230), ('\u{10f85}', 220), ('\u{11046}', 9),
+    ('\u{11070}', 9), ('\u{1107f}', 9), ('\u{110b9}', 9), ('\u{110ba}', 7), ('\u{11100}', 230), ('\u{11101}', 230),
+    ('\u{11102}', 230), ('\u{11133}', 9), ('\u{11134}', 9), ('\u{11173}', 7), ('\u{111c0}', 9), ('\u{111ca}', 7),
+    ('\u{11235}', 9), ('\u{11236}', 7), ('\u{112e9}', 7), ('\u{112ea}', 9), ('\u{1133b}', 7), ('\u{1133c}', 7),
+    ('\u{1134d}', 9), ('\u{11366}', 230), ('\u{11367}', 230), ('\u{11368}', 230), ('\u{11369}', 230), ('\u{1136a}', 230),
+    ('\u{1136b}', 230), ('\u{1136c}', 230), ('\u{11370}', 230), ('\u{11371}', 230), ('\u{11372}', 230), ('\u{11373}', 230),
+    ('\u{11374}', 230), ('\u{11442}', 9), ('\u{11446}', 7), ('\u{1145e}', 230), ('\u{114c2}', 9), ('\u{114c3}', 7),
+    ('\u{115bf}', 9), ('\u{115c0}', 7), ('\u{1163f}', 9), ('\u{116b6}', 9), ('\u{116b7}', 7), ('\u{1172b}', 9),
+    ('\u{11839}', 9), ('\u{1183a}', 7), ('\u{1193d}', 9), ('\u{1193e}', 9), ('\u{11943}', 7), ('\u{119e0}', 9),
+    ('\u{11a34}', 9), ('\u{11a47}', 9), ('\u{11a99}', 9), ('\u{11c3f}', 9), ('\u{11d42}', 7), ('\u{11d44}', 9),
+    ('\u{11d45}', 9), ('\u{11d97}', 9), ('\u{16af0}', 1), ('\u{16af1}', 1), ('\u{16af2}', 1), ('\u{16af3}', 1),
+    ('\u{16af4}', 1), ('\u{16b30}', 230), ('\u{16b31}', 230), ('\u{16b32}', 230), ('\u{16b33}', 230), ('\u{16b34}', 230),
+    ('\u{16b35}', 230), ('\u{16b36}', 230), ('\u{16ff0}', 6), ('\u{16ff1}', 6), ('\u{1bc9e}', 1), ('\u{1d165}', 216),
+    ('\u{1d166}', 216), ('\u{1d167}', 1), ('\u{1d168}', 1), ('\u{1d169}', 1), ('\u{1d16d}', 226), ('\u{1d16e}', 216),
+    ('\u{1d16f}', 216), ('\u{1d170}', 216), ('\u{1d171}', 216), ('\u{1d172}', 216), ('\u{1d17b}', 220), ('\u{1d17c}', 220),
+    ('\u{1d17d}', 220), ('\u{1d17e}', 220), ('\u{1d17f}', 220), ('\u{1d180}', 220), ('\u{1d181}', 220), ('\u{1d182}', 220),
+    ('\u{1d185}', 230), ('\u{1d186}', 230), ('\u{1d187}', 230), ('\u{1d188}', 230), ('\u{1d189}', 230), ('\u{1d18a}', 220),
+    ('\u{1d18b}', 220), ('\u{1d1aa}', 230), ('\u{1d1ab}', 230), ('\u{1d1ac}', 230), ('\u{1d1ad}', 230), ('\u{1d242}', 230),
+    ('\u{1d243}', 230), ('\u{1d244}', 230), ('\u{1e000}', 230), ('\u{1e001}', 230), ('\u{1e002}', 230), ('\u{1e003}', 230),
+    ('\u{1e004}', 230), ('\u{1e005}', 230), ('\u{1e006}', 230), ('\u{1e008}', 230), ('\u{1e009}', 230), ('\u{1e00a}', 230),
+    ('\u{1e00b}', 230), ('\u{1e00c}', 230), ('\u{1e00d}', 230), ('\u{1e00e}', 230), ('\u{1e00f}', 230), ('\u{1e010}', 230),
+    ('\u{1e011}', 230), ('\u{1e012}', 230), ('\u{1e013}', 230), ('\u{1e014}', 230), ('\u{1e015}', 230), ('\u{1e016}', 230),
+    ('\u{1e017}', 230), ('\u{1e018}', 230), ('\u{1e01b}', 230), ('\u{1e01c}', 230), ('\u{1e01d}', 230), ('\u{1e01e}', 230),
+    ('\u{1e01f}', 230), ('\u{1e020}', 230), ('\u{1e021}', 230), ('\u{1e023}', 230), ('\u{1e024}', 230), ('\u{1e026}', 230),
+    ('\u{1e027}', 230), ('\u{1e028}', 230), ('\u{1e029}', 230), ('\u{1e02a}', 230), ('\u{1e130}', 230), ('\u{1e131}', 230),
+    ('\u{1e132}', 230), ('\u{1e133}', 230), ('\u{1e134}', 230), ('\u{1e135}', 230), ('\u{1e136}', 230), ('\u{1e2ae}', 230),
+    ('\u{1e2ec}', 230), ('\u{1e2ed}', 230), ('\u{1e2ee}', 230), ('\u{1e2ef}', 230), ('\u{1e8d0}', 220), ('\u{1e8d1}', 220),
+    ('\u{1e8d2}', 220), ('\u{1e8d3}', 220), ('\u{1e8d4}', 220), ('\u{1e8d5}', 220), ('\u{1e8d6}', 220), ('\u{1e944}', 230),
+    ('\u{1e945}', 230), ('\u{1e946}', 230), ('\u{1e947}', 230), ('\u{1e948}', 230), ('\u{1e949}', 230), ('\u{1e94a}', 7),
+];
+
+/// The primary composites of pairs of characters, other than Hangul syllables, by pair.
+#[rustfmt::skip]
+pub(super) const COMPOSITIONS: &[((char, char), char)] = &[
+    (('\u{003c}', '\u{0338}'), '\u{226e}'), (('\u{003d}', '\u{0338}'), '\u{2260}'), (('\u{003e}', '\u{0338}'), '\u{226f}'),
+    (('\u{0041}', '\u{0300}'), '\u{00c0}'), (('\u{0041}', '\u{0301}'), '\u{00c1}'), (('\u{0041}', '\u{0302}'), '\u{00c2}'),
+    (('\u{0041}', '\u{0303}'), '\u{00c3}'), (('\u{0041}', '\u{0304}'), '\u{0100}'), (('\u{0041}', '\u{0306}'), '\u{0102}'),
+    (('\u{0041}', '\u{0307}'), '\u{0226}'), (('\u{0041}', '\u{0308}'), '\u{00c4}'), (('\u{0041}', '\u{0309}'), '\u{1ea2}'),
+    (('\u{0041}', '\u{030a}'), '\u{00c5}'), (('\u{0041}', '\u{030c}'), '\u{01cd}'), (('\u{0041}', '\u{030f}'), '\u{0200}'),
+    (('\u{0041}', '\u{0311}'), '\u{0202}'), (('\u{0041}', '\u{0323}'), '\u{1ea0}'), (('\u{0041}', '\u{0325}'), '\u{1e00}'),
+    (('\u{0041}', '\u{0328}'), '\u{0104}'), (('\u{0042}', '\u{0307}'), '\u{1e02}'), (('\u{0042}', '\u{0323}'), '\u{1e04}'),
+    (('\u{0042}', '\u{0331}'), '\u{1e06}'), (('\u{0043}', '\u{0301}'), '\u{0106}'), (('\u{0043}', '\u{0302}'), '\u{0108}'),
+    (('\u{0043}', '\u{0307}'), '\u{010a}'), (('\u{0043}', '\u{030c}'), '\u{010c}'), (('\u{0043}', '\u{0327}'), '\u{00c7}'),
+    (('\u{0044}', '\u{0307}'), '\u{1e0a}'), (('\u{0044}', '\u{030c}'), '\u{010e}'), (('\u{0044}', '\u{0323}'), '\u{1e0c}'),
+    (('\u{0044}', '\u{0327}'), '\u{1e10}'), (('\u{0044}', '\u{032d}'), '\u{1e12}'), (('\u{0044}', '\u{0331}'), '\u{1e0e}'),
+    (('\u{0045}', '\u{0300}'), '\u{00c8}'), (('\u{0045}', '\u{0301}'), '\u{00c9}'), (('\u{0045}', '\u{0302}'), '\u{00ca}'),
+    (('\u{0045}', '\u{0303}'), '\u{1ebc}'), (('\u{0045}', '\u{0304}'), '\u{0112}'), (('\u{0045}', '\u{0306}'), '\u{0114}'),
+    (('\u{0045}', '\u{0307}'), '\u{0116}'), (('\u{0045}', '\u{0308}'), '\u{00cb}'), (('\u{0045}', '\u{0309}'), '\u{1eba}'),
+    (('\u{0045}', '\u{030c}'), '\u{011a}'), (('\u{0045}', '\u{030f}'), '\u{0204}'), (('\u{0045}', '\u{0311}'), '\u{0206}'),
+    (('\u{0045}', '\u{0323}'), '\u{1eb8}'), (('\u{0045}', '\u{0327}'), '\u{0228}'), (('\u{0045}', '\u{0328}'), '\u{0118}'),
+    (('\u{0045}', '\u{032d}'), '\u{1e18}'), (('\u{0045}', '\u{0330}'), '\u{1e1a}'), (('\u{0046}', '\u{0307}'), '\u{1e1e}'),
+    (('\u{0047}', '\u{0301}'), '\u{01f4}'), (('\u{0047}', '\u{0302}'), '\u{011c}'), (('\u{0047}', '\u{0304}'), '\u{1e20}'),
+    (('\u{0047}', '\u{0306}'), '\u{011e}'), (('\u{0047}', '\u{0307}'), '\u{0120}'), (('\u{0047}', '\u{030c}'), '\u{01e6}'),
+    (('\u{0047}', '\u{0327}'), '\u{0122}'), (('\u{0048}', '\u{0302}'), '\u{0124}'), (('\u{0048}', '\u{0307}'), '\u{1e22}'),
+    (('\u{0048}', '\u{0308}'), '\u{1e26}'), (('\u{0048}', '\u{030c}'), '\u{021e}'), (('\u{0048}', '\u{0323}'), '\u{1e24}'),
+    (('\u{0048}', '\u{0327}'), '\u{1e28}'), (('\u{0048}', '\u{032e}'), '\u{1e2a}'), (('\u{0049}', '\u{0300}'), '\u{00cc}'),
+    (('\u{0049}', '\u{0301}'), '\u{00cd}'), (('\u{0049}', '\u{0302}'), '\u{00ce}'), (('\u{0049}', '\u{0303}'), '\u{0128}'),
+    (('\u{0049}', '\u{0304}'), '\u{012a}'), (('\u{0049}', '\u{0306}'), '\u{012c}'), (('\u{0049}', '\u{0307}'), '\u{0130}'),
+    (('\u{0049}', '\u{0308}'), '\u{00cf}'), (('\u{0049}', '\u{0309}'), '\u{1ec8}'), (('\u{0049}', '\u{030c}'), '\u{01cf}'),
+    (('\u{0049}', '\u{030f}'), '\u{0208}'), (('\u{0049}', '\u{0311}'), '\u{020a}'), (('\u{0049}', '\u{0323}'), '\u{1eca}'),
+    (('\u{0049}', '\u{0328}'), '\u{012e}'), (('\u{0049}', '\u{0330}'), '\u{1e2c}'), (('\u{004a}', '\u{0302}'), '\u{0134}'),
+    (('\u{004b}', '\u{0301}'), '\u{1e30}'), (('\u{004b}', '\u{030c}'), '\u{01e8}'), (('\u{004b}', '\u{0323}'), '\u{1e32}'),
+    (('\u{004b}', '\u{0327}'), '\u{0136}'), (('\u{004b}', '\u{0331}'), '\u{1e34}'), (('\u{004c}', '\u{0301}'), '\u{0139}'),
+    (('\u{004c}', '\u{030c}'), '\u{013d}'), (('\u{004c}', '\u{0323}'), '\u{1e36}'), (('\u{004c}', '\u{0327}'), '\u{013b}'),
+    (('\u{004c}', '\u{032d}'), '\u{1e3c}'), (('\u{004c}', '\u{0331}'), '\u{1e3a}'), (('\u{004d}', '\u{0301}'), '\u{1e3e}'),
+    (('\u{004d}', '\u{0307}'), '\u{1e40}'), (('\u{004d}', '\u{0323}'), '\u{1e42}'), (('\u{004e}', '\u{0300}'), '\u{01f8}'),
+    (('\u{004e}', '\u{0301}'), '\u{0143}'), (('\u{004e}', '\u{0303}'), '\u{00d1}'), (('\u{004e}', '\u{0307}'), '\u{1e44}'),
+    (('\u{004e}', '\u{030c}'), '\u{0147}'), (('\u{004e}', '\u{0323}'), '\u{1e46}'), (('\u{004e}', '\u{0327}'), '\u{0145}'),
+    (('\u{004e}', '\u{032d}'), '\u{1e4a}'), (('\u{004e}', '\u{0331}'), '\u{1e48}'), (('\u{004f}', '\u{0300}'), '\u{00d2}'),
+    (('\u{004f}', '\u{0301}'), '\u{00d3}'), (('\u{004f}', '\u{0302}'), '\u{00d4}'), (('\u{004f}', '\u{0303}'), '\u{00d5}'),
+    (('\u{004f}', '\u{0304}'), '\u{014c}'), (('\u{004f}', '\u{0306}'), '\u{014e}'), (('\u{004f}', '\u{0307}'), '\u{022e}'),
+    (('\u{004f}', '\u{0308}'), '\u{00d6}'), (('\u{004f}', '\u{0309}'), '\u{1ece}'), (('\u{004f}', '\u{030b}'), '\u{0150}'),
+    (('\u{004f}', '\u{030c}'), '\u{01d1}'), (('\u{004f}', '\u{030f}'), '\u{020c}'), (('\u{004f}', '\u{0311}'), '\u{020e}'),
+    (('\u{004f}', '\u{031b}'), '\u{01a0}'), (('\u{004f}', '\u{0323}'), '\u{1ecc}'), (('\u{004f}', '\u{0328}'), '\u{01ea}'),
+    (('\u{0050}', '\u{0301}'), '\u{1e54}'), (('\u{0050}', '\u{0307}'), '\u{1e56}'), (('\u{0052}', '\u{0301}'), '\u{0154}'),
+    (('\u{0052}', '\u{0307}'), '\u{1e58}'), (('\u{0052}', '\u{030c}'), '\u{0158}'), (('\u{0052}', '\u{030f}'), '\u{0210}'),
+    (('\u{0052}', '\u{0311}'), '\u{0212}'), (('\u{0052}', '\u{0323}'), '\u{1e5a}'), (('\u{0052}', '\u{0327}'), '\u{0156}'),
+    (('\u{0052}', '\u{0331}'), '\u{1e5e}'), (('\u{0053}', '\u{0301}'), '\u{015a}'), (('\u{0053}', '\u{0302}'), '\u{015c}'),
+    (('\u{0053}', '\u{0307}'), '\u{1e60}'), (('\u{0053}', '\u{030c}'), '\u{0160}'), (('\u{0053}', '\u{0323}'), '\u{1e62}'),
+    (('\u{0053}', '\u{0326}'), '\u{0218}'), (('\u{0053}', '\u{0327}'), '\u{015e}'), (('\u{0054}', '\u{0307}'), '\u{1e6a}'),
+    (('\u{0054}', '\u{030c}'), '\u{0164}'), (('\u{0054}', '\u{0323}'), '\u{1e6c}'), (('\u{0054}', '\u{0326}'), '\u{021a}'),
+    (('\u{0054}', '\u{0327}'), '\u{0162}'), (('\u{0054}', '\u{032d}'), '\u{1e70}'), (('\u{0054}', '\u{0331}'), '\u{1e6e}'),
+    (('\u{0055}', '\u{0300}'), '\u{00d9}'), (('\u{0055}', '\u{0301}'), '\u{00da}'), (('\u{0055}', '\u{0302}'), '\u{00db}'),
+    (('\u{0055}', '\u{0303}'), '\u{0168}'), (('\u{0055}', '\u{0304}'), '\u{016a}'), (('\u{0055}', '\u{0306}'), '\u{016c}'),
+    (('\u{0055}', '\u{0308}'), '\u{00dc}'), (('\u{0055}', '\u{0309}'), '\u{1ee6}'), (('\u{0055}', '\u{030a}'), '\u{016e}'),
+    (('\u{0055}', '\u{030b}'), '\u{0170}'), (('\u{0055}', '\u{030c}'), '\u{01d3}'), (('\u{0055}', '\u{030f}'), '\u{0214}'),
+    (('\u{0055}', '\u{0311}'), '\u{0216}'), (('\u{0055}', '\u{031b}'), '\u{01af}'), (('\u{0055}', '\u{0323}'), '\u{1ee4}'),
+    (('\u{0055}', '\u{0324}'), '\u{1e72}'), (('\u{0055}', '\u{0328}'), '\u{0172}'), (('\u{0055}', '\u{032d}'), '\u{1e76}'),
+    (('\u{0055}', '\u{0330}'), '\u{1e74}'), (('\u{0056}', '\u{0303}'), '\u{1e7c}'), (('\u{0056}', '\u{0323}'), '\u{1e7e}'),
+    (('\u{0057}', '\u{0300}'), '\u{1e80}'), (('\u{0057}', '\u{0301}'), '\u{1e82}'), (('\u{0057}', '\u{0302}'), '\u{0174}'),
+    (('\u{0057}', '\u{0307}'), '\u{1e86}'), (('\u{0057}', '\u{0308}'), '\u{1e84}'), (('\u{0057}', '\u{0323}'), '\u{1e88}'),
+    (('\u{0058}', '\u{0307}'), '\u{1e8a}'), (('\u{0058}', '\u{0308}'), '\u{1e8c}'), (('\u{0059}', '\u{0300}'), '\u{1ef2}'),
+    (('\u{0059}', '\u{0301}'), '\u{00dd}'), (('\u{0059}', '\u{0302}'), '\u{0176}'), (('\u{0059}', '\u{0303}'), '\u{1ef8}'),
+    (('\u{0059}', '\u{0304}'), '\u{0232}'), (('\u{0059}', '\u{0307}'), '\u{1e8e}'), (('\u{0059}', '\u{0308}'), '\u{0178}'),
+    (('\u{0059}', '\u{0309}'), '\u{1ef6}'), (('\u{0059}', '\u{0323}'), '\u{1ef4}'), (('\u{005a}', '\u{0301}'), '\u{0179}'),
+    (('\u{005a}', '\u{0302}'), '\u{1e90}'), (('\u{005a}', '\u{0307}'), '\u{017b}'), (('\u{005a}', '\u{030c}'), '\u{017d}'),
+    (('\u{005a}', '\u{0323}'), '\u{1e92}'), (('\u{005a}', '\u{0331}'), '\u{1e94}'), (('\u{0061}', '\u{0300}'), '\u{00e0}'),
+    (('\u{0061}', '\u{0301}'), '\u{00e1}'), (('\u{0061}', '\u{0302}'), '\u{00e2}'), (('\u{0061}', '\u{0303}'), '\u{00e3}'),
+    (('\u{0061}', '\u{0304}'), '\u{0101}'), (('\u{0061}', '\u{0306}'), '\u{0103}'), (('\u{0061}', '\u{0307}'), '\u{0227}'),
+    (('\u{0061}', '\u{0308}'), '\u{00e4}'), (('\u{0061}', '\u{0309}'), '\u{1ea3}'), (('\u{0061}', '\u{030a}'), '\u{00e5}'),
+    (('\u{0061}', '\u{030c}'), '\u{01ce}'), (('\u{0061}', '\u{030f}'), '\u{0201}'), (('\u{0061}', '\u{0311}'), '\u{0203}'),
+    (('\u{0061}', '\u{0323}'), '\u{1ea1}'), (('\u{0061}', '\u{0325}'), '\u{1e01}'), (('\u{0061}', '\u{0328}'), '\u{0105}'),
+    (('\u{0062}', '\u{0307}'), '\u{1e03}'), (('\u{0062}', '\u{0323}'), '\u{1e05}'), (('\u{0062}', '\u{0331}'), '\u{1e07}'),
+    (('\u{0063}', '\u{0301}'), '\u{0107}'), (('\u{0063}', '\u{0302}'), '\u{0109}'), (('\u{0063}', '\u{0307}'), '\u{010b}'),
+    (('\u{0063}', '\u{030c}'), '\u{010d}'), (('\u{0063}', '\u{0327}'), '\u{00e7}'), (('\u{0064}', '\u{0307}'), '\u{1e0b}'),
+    (('\u{0064}', '\u{030c}'), '\u{010f}'), (('\u{0064}', '\u{0323}'), '\u{1e0d}'), (('\u{0064}', '\u{0327}'), '\u{1e11}'),
+    (('\u{0064}', '\u{032d}'), '\u{1e13}'), (('\u{0064}', '\u{0331}'), '\u{1e0f}'), (('\u{0065}', '\u{0300}'), '\u{00e8}'),
+    (('\u{0065}', '\u{0301}'), '\u{00e9}'), (('\u{0065}', '\u{0302}'), '\u{00ea}'), (('\u{0065}', '\u{0303}'), '\u{1ebd}'),
+    (('\u{0065}', '\u{0304}'), '\u{0113}'), (('\u{0065}', '\u{0306}'), '\u{0115}'), (('\u{0065}', '\u{0307}'), '\u{0117}'),
+    (('\u{0065}', '\u{0308}'), '\u{00eb}'), (('\u{0065}', '\u{0309}'), '\u{1ebb}'), (('\u{0065}', '\u{030c}'), '\u{011b}'),
+    (('\u{0065}', '\u{030f}'), '\u{0205}'), (('\u{0065}', '\u{0311}'), '\u{0207}'), (('\u{0065}', '\u{0323}'), '\u{1eb9}'),
+    (('\u{0065}', '\u{0327}'), '\u{0229}'), (('\u{0065}', '\u{0328}'), '\u{0119}'), (('\u{0065}', '\u{032d}'), '\u{1e19}'),
+    (('\u{0065}', '\u{0330}'), '\u{1e1b}'), (('\u{0066}', '\u{0307}'), '\u{1e1f}'), (('\u{0067}', '\u{0301}'), '\u{01f5}'),
+    (('\u{0067}', '\u{0302}'), '\u{011d}'), (('\u{0067}', '\u{0304}'), '\u{1e21}'), (('\u{0067}', '\u{0306}'), '\u{011f}'),
+    (('\u{0067}', '\u{0307}'), '\u{0121}'), (('\u{0067}', '\u{030c}'), '\u{01e7}'), (('\u{0067}', '\u{0327}'), '\u{0123}'),
+    (('\u{0068}', '\u{0302}'), '\u{0125}'), (('\u{0068}', '\u{0307}'), '\u{1e23}'), (('\u{0068}', '\u{0308}'), '\u{1e27}'),
+    (('\u{0068}', '\u{030c}'), '\u{021f}'), (('\u{0068}', '\u{0323}'), '\u{1e25}'), (('\u{0068}', '\u{0327}'), '\u{1e29}'),
+    (('\u{0068}', '\u{032e}'), '\u{1e2b}'), (('\u{0068}', '\u{0331}'), '\u{1e96}'), (('\u{0069}', '\u{0300}'), '\u{00ec}'),
+    (('\u{0069}', '\u{0301}'), '\u{00ed}'), (('\u{0069}', '\u{0302}'), '\u{00ee}'), (('\u{0069}', '\u{0303}'), '\u{0129}'),
+    (('\u{0069}', '\u{0304}'), '\u{012b}'), (('\u{0069}', '\u{0306}'), '\u{012d}'), (('\u{0069}', '\u{0308}'), '\u{00ef}'),
+    (('\u{0069}', '\u{0309}'), '\u{1ec9}'), (('\u{0069}', '\u{030c}'), '\u{01d0}'), (('\u{0069}', '\u{030f}'), '\u{0209}'),
+    (('\u{0069}', '\u{0311}'), '\u{020b}'), (('\u{0069}', '\u{0323}'), '\u{1ecb}'), (('\u{0069}', '\u{0328}'), '\u{012f}'),
+    (('\u{0069}', '\u{0330}'), '\u{1e2d}'), (('\u{006a}', '\u{0302}'), '\u{0135}'), (('\u{006a}', '\u{030c}'), '\u{01f0}'),
+    (('\u{006b}', '\u{0301}'), '\u{1e31}'), (('\u{006b}', '\u{030c}'), '\u{01e9}'), (('\u{006b}', '\u{0323}'), '\u{1e33}'),
+    (('\u{006b}', '\u{0327}'), '\u{0137}'), (('\u{006b}', '\u{0331}'), '\u{1e35}'), (('\u{006c}', '\u{0301}'), '\u{013a}'),
+    (('\u{006c}', '\u{030c}'), '\u{013e}'), (('\u{006c}', '\u{0323}'), '\u{1e37}'), (('\u{006c}', '\u{0327}'), '\u{013c}'),
+    (('\u{006c}', '\u{032d}'), '\u{1e3d}'), (('\u{006c}', '\u{0331}'), '\u{1e3b}'), (('\u{006d}', '\u{0301}'), '\u{1e3f}'),
+    (('\u{006d}', '\u{0307}'), '\u{1e41}'), (('\u{006d}', '\u{0323}'), '\u{1e43}'), (('\u{006e}', '\u{0300}'), '\u{01f9}'),
+    (('\u{006e}', '\u{0301}'), '\u{0144}'), (('\u{006e}', '\u{0303}'), '\u{00f1}'), (('\u{006e}', '\u{0307}'), '\u{1e45}'),
+    (('\u{006e}', '\u{030c}'), '\u{0148}'), (('\u{006e}', '\u{0323}'), '\u{1e47}'), (('\u{006e}', '\u{0327}'), '\u{0146}'),
+    (('\u{006e}', '\u{032d}'), '\u{1e4b}'), (('\u{006e}', '\u{0331}'), '\u{1e49}'), (('\u{006f}', '\u{0300}'), '\u{00f2}'),
+    (('\u{006f}', '\u{0301}'), '\u{00f3}'), (('\u{006f}', '\u{0302}'), '\u{00f4}'), (('\u{006f}', '\u{0303}'), '\u{00f5}'),
+    (('\u{006f}', '\u{0304}'), '\u{014d}'), (('\u{006f}', '\u{0306}'), '\u{014f}'), (('\u{006f}', '\u{0307}'), '\u{022f}'),
+    (('\u{006f}', '\u{0308}'), '\u{00f6}'), (('\u{006f}', '\u{0309}'), '\u{1ecf}'), (('\u{006f}', '\u{030b}'), '\u{0151}'),
+    (('\u{006f}', '\u{030c}'), '\u{01d2}'), (('\u{006f}', '\u{030f}'), '\u{020d}'), (('\u{006f}', '\u{0311}'), '\u{020f}'),
+    (('\u{006f}', '\u{031b}'), '\u{01a1}'), (('\u{006f}', '\u{0323}'), '\u{1ecd}'), (('\u{006f}', '\u{0328}'), '\u{01eb}'),
+    (('\u{0070}', '\u{0301}'), '\u{1e55}'), (('\u{0070}', '\u{0307}'), '\u{1e57}'), (('\u{0072}', '\u{0301}'), '\u{0155}'),
+    (('\u{0072}', '\u{0307}'), '\u{1e59}'), (('\u{0072}', '\u{030c}'), '\u{0159}'), (('\u{0072}', '\u{030f}'), '\u{0211}'),
+    (('\u{0072}', '\u{0311}'), '\u{0213}'), (('\u{0072}', '\u{0323}'), '\u{1e5b}'), (('\u{0072}', '\u{0327}'), '\u{0157}'),
+    (('\u{0072}', '\u{0331}'), '\u{1e5f}'), (('\u{0073}', '\u{0301}'), '\u{015b}'), (('\u{0073}', '\u{0302}'), '\u{015d}'),
+    (('\u{0073}', '\u{0307}'), '\u{1e61}'), (('\u{0073}', '\u{030c}'), '\u{0161}'), (('\u{0073}', '\u{0323}'), '\u{1e63}'),
+    (('\u{0073}', '\u{0326}'), '\u{0219}'), (('\u{0073}', '\u{0327}'), '\u{015f}'), (('\u{0074}', '\u{0307}'), '\u{1e6b}'),
+    (('\u{0074}', '\u{0308}'), '\u{1e97}'), (('\u{0074}', '\u{030c}'), '\u{0165}'), (('\u{0074}', '\u{0323}'), '\u{1e6d}'),
+    (('\u{0074}', '\u{0326}'), '\u{021b}'), (('\u{0074}', '\u{0327}'), '\u{0163}'), (('\u{0074}', '\u{032d}'), '\u{1e71}'),
+    (('\u{0074}', '\u{0331}'), '\u{1e6f}'), (('\u{0075}', '\u{0300}'), '\u{00f9}'), (('\u{0075}', '\u{0301}'), '\u{00fa}'),
+    (('\u{0075}', '\u{0302}'), '\u{00fb}'), (('\u{0075}', '\u{0303}'), '\u{0169}'), (('\u{0075}', '\u{0304}'), '\u{016b}'),
+    (('\u{0075}', '\u{0306}'), '\u{016d}'), (('\u{0075}', '\u{0308}'), '\u{00fc}'), (('\u{0075}', '\u{0309}'), '\u{1ee7}'),
+    (('\u{0075}', '\u{030a}'), '\u{016f}'), (('\u{0075}', '\u{030b}'), '\u{0171}'), (('\u{0075}', '\u{030c}'), '\u{01d4}'),
+    (('\u{0075}', '\u{030f}'), '\u{0215}'), (('\u{0075}', '\u{0311}'), '\u{0217}'), (('\u{0075}', '\u{031b}'), '\u{01b0}'),
+    (('\u{0075}', '\u{0323}'), '\u{1ee5}'), (('\u{0075}', '\u{0324}'), '\u{1e73}'), (('\u{0075}', '\u{0328}'), '\u{0173}'),
+    (('\u{0075}', '\u{032d}'), '\u{1e77}'), (('\u{0075}', '\u{0330}'), '\u{1e75}'), (('\u{0076}', '\u{0303}'), '\u{1e7d}'),
+    (('\u{0076}', '\u{0323}'), '\u{1e7f}'), (('\u{0077}', '\u{0300}'), '\u{1e81}'), (('\u{0077}', '\u{0301}'), '\u{1e83}'),
+    (('\u{0077}', '\u{0302}'), '\u{0175}'), (('\u{0077}', '\u{0307}'), '\u{1e87}'), (('\u{0077}', '\u{0308}'), '\u{1e85}'),
+    (('\u{0077}', '\u{030a}'), '\u{1e98}'), (('\u{0077}', '\u{0323}'), '\u{1e89}'), (('\u{0078}', '\u{0307}'), '\u{1e8b}'),
+    (('\u{0078}', '\u{0308}'), '\u{1e8d}'), (('\u{0079}', '\u{0300}'), '\u{1ef3}'), (('\u{0079}', '\u{0301}'), '\u{00fd}'),
+    (('\u{0079}', '\u{0302}'), '\u{0177}'), (('\u{0079}', '\u{0303}'), '\u{1ef9}'), (('\u{0079}', '\u{0304}'), '\u{0233}'),
+    (('\u{0079}', '\u{0307}'), '\u{1e8f}'), (('\u{0079}', '\u{0308}'), '\u{00ff}'), (('\u{0079}', '\u{0309}'), '\u{1ef7}'),
+    (('\u{0079}', '\u{030a}'), '\u{1e99}'), (('\u{0079}', '\u{0323}'), '\u{1ef5}'), (('\u{007a}', '\u{0301}'), '\u{017a}'),
+    (('\u{007a}', '\u{0302}'), '\u{1e91}'), (('\u{007a}', '\u{0307}'), '\u{017c}'), (('\u{007a}', '\u{030c}'), '\u{017e}'),
+    (('\u{007a}', '\u{0323}'), '\u{1e93}'), (('\u{007a}', '\u{0331}'), '\u{1e95}'), (('\u{00a8}', '\u{0300}'), '\u{1fed}'),
+    (('\u{00a8}', '\u{0301}'), '\u{0385}'), (('\u{00a8}', '\u{0342}'), '\u{1fc1}'), (('\u{00c2}', '\u{0300}'), '\u{1ea6}'),
+    (('\u{00c2}', '\u{0301}'), '\u{1ea4}'), (('\u{00c2}', '\u{0303}'), '\u{1eaa}'), (('\u{00c2}', '\u{0309}'), '\u{1ea8}'),
+    (('\u{00c4}', '\u{0304}'), '\u{01de}'), (('\u{00c5}', '\u{0301}'), '\u{01fa}'), (('\u{00c6}', '\u{0301}'), '\u{01fc}'),
+    (('\u{00c6}', '\u{0304}'), '\u{01e2}'), (('\u{00c7}', '\u{0301}'), '\u{1e08}'), (('\u{00ca}', '\u{0300}'), '\u{1ec0}'),
+    (('\u{00ca}', '\u{0301}'), '\u{1ebe}'), (('\u{00ca}', '\u{0303}'), '\u{1ec4}'), (('\u{00ca}', '\u{0309}'), '\u{1ec2}'),
+    (('\u{00cf}', '\u{0301}'), '\u{1e2e}'), (('\u{00d4}', '\u{0300}'), '\u{1ed2}'), (('\u{00d4}', '\u{0301}'), '\u{1ed0}'),
+    (('\u{00d4}', '\u{0303}'), '\u{1ed6}'), (('\u{00d4}', '\u{0309}'), '\u{1ed4}'), (('\u{00d5}', '\u{0301}'), '\u{1e4c}'),
+    (('\u{00d5}', '\u{0304}'), '\u{022c}'), (('\u{00d5}', '\u{0308}'), '\u{1e4e}'), (('\u{00d6}', '\u{0304}'), '\u{022a}'),
+    (('\u{00d8}', '\u{0301}'), '\u{01fe}'), (('\u{00dc}', '\u{0300}'), '\u{01db}'), (('\u{00dc}', '\u{0301}'), '\u{01d7}'),
+    (('\u{00dc}', '\u{0304}'), '\u{01d5}'), (('\u{00dc}', '\u{030c}'), '\u{01d9}'), (('\u{00e2}', '\u{0300}'), '\u{1ea7}'),
+    (('\u{00e2}', '\u{0301}'), '\u{1ea5}'), (('\u{00e2}', '\u{0303}'), '\u{1eab}'), (('\u{00e2}', '\u{0309}'), '\u{1ea9}'),
+    (('\u{00e4}', '\u{0304}'), '\u{01df}'), (('\u{00e5}', '\u{0301}'), '\u{01fb}'), (('\u{00e6}', '\u{0301}'), '\u{01fd}'),
+    (('\u{00e6}', '\u{0304}'), '\u{01e3}'), (('\u{00e7}', '\u{0301}'), '\u{1e09}'), (('\u{00ea}', '\u{0300}'), '\u{1ec1}'),
+    (('\u{00ea}', '\u{0301}'), '\u{1ebf}'), (('\u{00ea}', '\u{0303}'), '\u{1ec5}'), (('\u{00ea}', '\u{0309}'), '\u{1ec3}'),
+    (('\u{00ef}', '\u{0301}'), '\u{1e2f}'), (('\u{00f4}', '\u{0300}'), '\u{1ed3}'), (('\u{00f4}', '\u{0301}'), '\u{1ed1}'),
+    (('\u{00f4}', '\u{0303}'), '\u{1ed7}'), (('\u{00f4}', '\u{0309}'), '\u{1ed5}'), (('\u{00f5}', '\u{0301}'), '\u{1e4d}'),
+    (('\u{00f5}', '\u{0304}'), '\u{022d}'), (('\u{00f5}', '\u{0308}'), '\u{1e4f}'), (('\u{00f6}', '\u{0304}'), '\u{022b}'),
+    (('\u{00f8}', '\u{0301}'), '\u{01ff}'), (('\u{00fc}', '\u{0300}'), '\u{01dc}'), (('\u{00fc}', '\u{0301}'), '\u{01d8}'),
+    (('\u{00fc}', '\u{0304}'), '\u{01d6}'), (('\u{00fc}', '\u{030c}'), '\u{01da}'), (('\u{0102}', '\u{0300}'), '\u{1eb0}'),
+    (('\u{0102}', '\u{0301}'), '\u{1eae}'), (('\u{0102}', '\u{0303}'), '\u{1eb4}'), (('\u{0102}', '\u{0309}'), '\u{1eb2}'),
+    (('\u{0103}', '\u{0300}'), '\u{1eb1}'), (('\u{0103}', '\u{0301}'), '\u{1eaf}'), (('\u{0103}', '\u{0303}'), '\u{1eb5}'),
+    (('\u{0103}', '\u{0309}'), '\u{1eb3}'), (('\u{0112}', '\u{0300}'), '\u{1e14}'), (('\u{0112}', '\u{0301}'), '\u{1e16}'),
+    (('\u{0113}', '\u{0300}'), '\u{1e15}'), (('\u{0113}', '\u{0301}'), '\u{1e17}'), (('\u{014c}', '\u{0300}'), '\u{1e50}'),
+    (('\u{014c}', '\u{0301}'), '\u{1e52}'), (('\u{014d}', '\u{0300}'), '\u{1e51}'), (('\u{014d}', '\u{0301}'), '\u{1e53}'),
+    (('\u{015a}', '\u{0307}'), '\u{1e64}'), (('\u{015b}', '\u{0307}'), '\u{1e65}'), (('\u{0160}', '\u{0307}'), '\u{1e66}'),
+    (('\u{0161}', '\u{0307}'), '\u{1e67}'), (('\u{0168}', '\u{0301}'), '\u{1e78}'), (('\u{0169}', '\u{0301}'), '\u{1e79}'),
+    (('\u{016a}', '\u{0308}'), '\u{1e7a}'), (('\u{016b}', '\u{0308}'), '\u{1e7b}'), (('\u{017f}', '\u{0307}'), '\u{1e9b}'),
+    (('\u{01a0}', '\u{0300}'), '\u{1edc}'), (('\u{01a0}', '\u{0301}'), '\u{1eda}'), (('\u{01a0}', '\u{0303}'), '\u{1ee0}'),
+    (('\u{01a0}', '\u{0309}'), '\u{1ede}'), (('\u{01a0}', '\u{0323}'), '\u{1ee2}'), (('\u{01a1}', '\u{0300}'), '\u{1edd}'),
+    (('\u{01a1}', '\u{0301}'), '\u{1edb}'), (('\u{01a1}', '\u{0303}'), '\u{1ee1}'), (('\u{01a1}', '\u{0309}'), '\u{1edf}'),
+    (('\u{01a1}', '\u{0323}'), '\u{1ee3}'), (('\u{01af}', '\u{0300}'), '\u{1eea}'), (('\u{01af}', '\u{0301}'), '\u{1ee8}'),
+    (('\u{01af}', '\u{0303}'), '\u{1eee}'), (('\u{01af}', '\u{0309}'), '\u{1eec}'), (('\u{01af}', '\u{0323}'), '\u{1ef0}'),
+    (('\u{01b0}', '\u{0300}'), '\u{1eeb}'), (('\u{01b0}', '\u{0301}'), '\u{1ee9}'), (('\u{01b0}', '\u{0303}'), '\u{1eef}'),
+    (('\u{01b0}', '\u{0309}'), '\u{1eed}'), (('\u{01b0}', '\u{0323}'), '\u{1ef1}'), (('\u{01b7}', '\u{030c}'), '\u{01ee}'),
+    (('\u{01ea}', '\u{0304}'), '\u{01ec}'), (('\u{01eb}', '\u{0304}'), '\u{01ed}'), (('\u{0226}', '\u{0304}'), '\u{01e0}'),
+    (('\u{0227}', '\u{0304}'), '\u{01e1}'), (('\u{0228}', '\u{0306}'), '\u{1e1c}'), (('\u{0229}', '\u{0306}'), '\u{1e1d}'),
+    (('\u{022e}', '\u{0304}'), '\u{0230}'), (('\u{022f}', '\u{0304}'), '\u{0231}'), (('\u{0292}', '\u{030c}'), '\u{01ef}'),
+    (('\u{0391}', '\u{0300}'), '\u{1fba}'), (('\u{0391}', '\u{0301}'), '\u{0386}'), (('\u{0391}', '\u{0304}'), '\u{1fb9}'),
+    (('\u{0391}', '\u{0306}'), '\u{1fb8}'), (('\u{0391}', '\u{0313}'), '\u{1f08}'), (('\u{0391}', '\u{0314}'), '\u{1f09}'),
+    (('\u{0391}', '\u{0345}'), '\u{1fbc}'), (('\u{0395}', '\u{0300}'), '\u{1fc8}'), (('\u{0395}', '\u{0301}'), '\u{0388}'),
+    (('\u{0395}', '\u{0313}'), '\u{1f18}'), (('\u{0395}', '\u{0314}'), '\u{1f19}'), (('\u{0397}', '\u{0300}'), '\u{1fca}'),
+    (('\u{0397}', '\u{0301}'), '\u{0389}'), (('\u{0397}', '\u{0313}'), '\u{1f28}'), (('\u{0397}', '\u{0314}'), '\u{1f29}'),
+    (('\u{0397}', '\u{0345}'), '\u{1fcc}'), (('\u{0399}', '\u{0300}'), '\u{1fda}'), (('\u{0399}', '\u{0301}'), '\u{038a}'),
+    (('\u{0399}', '\u{0304}'), '\u{1fd9}'), (('\u{0399}', '\u{0306}'), '\u{1fd8}'), (('\u{0399}', '\u{0308}'), '\u{03aa}'),
+    (('\u{0399}', '\u{0313}'), '\u{1f38}'), (('\u{0399}', '\u{0314}'), '\u{1f39}'), (('\u{039f}', '\u{0300}'), '\u{1ff8}'),
+    (('\u{039f}', '\u{0301}'), '\u{038c}'), (('\u{039f}', '\u{0313}'), '\u{1f48}'), (('\u{039f}', '\u{0314}'), '\u{1f49}'),
+    (('\u{03a1}', '\u{0314}'), '\u{1fec}'), (('\u{03a5}', '\u{0300}'), '\u{1fea}'), (('\u{03a5}', '\u{0301}'), '\u{038e}'),
+    (('\u{03a5}', '\u{0304}'), '\u{1fe9}'), (('\u{03a5}', '\u{0306}'), '\u{1fe8}'), (('\u{03a5}', '\u{0308}'), '\u{03ab}'),
+    (('\u{03a5}', '\u{0314}'), '\u{1f59}'), (('\u{03a9}', '\u{0300}'), '\u{1ffa}'), (('\u{03a9}', '\u{0301}'), '\u{038f}'),
+    (('\u{03a9}', '\u{0313}'), '\u{1f68}'), (('\u{03a9}', '\u{0314}'), '\u{1f69}'), (('\u{03a9}', '\u{0345}'), '\u{1ffc}'),
+    (('\u{03ac}', '\u{0345}'), '\u{1fb4}'), (('\u{03ae}', '\u{0345}'), '\u{1fc4}'), (('\u{03b1}', '\u{0300}'), '\u{1f70}'),
+    (('\u{03b1}', '\u{0301}'), '\u{03ac}'), (('\u{03b1}', '\u{0304}'), '\u{1fb1}'), (('\u{03b1}', '\u{0306}'), '\u{1fb0}'),
+    (('\u{03b1}', '\u{0313}'), '\u{1f00}'), (('\u{03b1}', '\u{0314}'), '\u{1f01}'), (('\u{03b1}', '\u{0342}'), '\u{1fb6}'),
+    (('\u{03b1}', '\u{0345}'), '\u{1fb3}'), (('\u{03b5}', '\u{0300}'), '\u{1f72}'), (('\u{03b5}', '\u{0301}'), '\u{03ad}'),
+    (('\u{03b5}', '\u{0313}'), '\u{1f10}'), (('\u{03b5}', '\u{0314}'), '\u{1f11}'), (('\u{03b7}', '\u{0300}'), '\u{1f74}'),
+    (('\u{03b7}', '\u{0301}'), '\u{03ae}'), (('\u{03b7}', '\u{0313}'), '\u{1f20}'), (('\u{03b7}', '\u{0314}'), '\u{1f21}'),
+    (('\u{03b7}', '\u{0342}'), '\u{1fc6}'), (('\u{03b7}', '\u{0345}'), '\u{1fc3}'), (('\u{03b9}', '\u{0300}'), '\u{1f76}'),
+    (('\u{03b9}', '\u{0301}'), '\u{03af}'), (('\u{03b9}', '\u{0304}'), '\u{1fd1}'), (('\u{03b9}', '\u{0306}'), '\u{1fd0}'),
+    (('\u{03b9}', '\u{0308}'), '\u{03ca}'), (('\u{03b9}', '\u{0313}'), '\u{1f30}'), (('\u{03b9}', '\u{0314}'), '\u{1f31}'),
+    (('\u{03b9}', '\u{0342}'), '\u{1fd6}'), (('\u{03bf}', '\u{0300}'), '\u{1f78}'), (('\u{03bf}', '\u{0301}'), '\u{03cc}'),
+    (('\u{03bf}', '\u{0313}'), '\u{1f40}'), (('\u{03bf}', '\u{0314}'), '\u{1f41}'), (('\u{03c1}', '\u{0313}'), '\u{1fe4}'),
+    (('\u{03c1}', '\u{0314}'), '\u{1fe5}'), (('\u{03c5}', '\u{0300}'), '\u{1f7a}'), (('\u{03c5}', '\u{0301}'), '\u{03cd}'),
+    (('\u{03c5}', '\u{0304}'), '\u{1fe1}'), (('\u{03c5}', '\u{0306}'), '\u{1fe0}'), (('\u{03c5}', '\u{0308}'), '\u{03cb}'),
+    (('\u{03c5}', '\u{0313}'), '\u{1f50}'), (('\u{03c5}', '\u{0314}'), '\u{1f51}'), (('\u{03c5}', '\u{0342}'), '\u{1fe6}'),
+    (('\u{03c9}', '\u{0300}'), '\u{1f7c}'), (('\u{03c9}', '\u{0301}'), '\u{03ce}'), (('\u{03c9}', '\u{0313}'), '\u{1f60}'),
+    (('\u{03c9}', '\u{0314}'), '\u{1f61}'), (('\u{03c9}', '\u{0342}'), '\u{1ff6}'), (('\u{03c9}', '\u{0345}'), '\u{1ff3}'),
+    (('\u{03ca}', '\u{0300}'), '\u{1fd2}'), (('\u{03ca}', '\u{0301}'), '\u{0390}'), (('\u{03ca}', '\u{0342}'), '\u{1fd7}'),
+    (('\u{03cb}', '\u{0300}'), '\u{1fe2}'), (('\u{03cb}', '\u{0301}'), '\u{03b0}'), (('\u{03cb}', '\u{0342}'), '\u{1fe7}'),
+    (('\u{03ce}', '\u{0345}'), '\u{1ff4}'), (('\u{03d2}', '\u{0301}'), '\u{03d3}'), (('\u{03d2}', '\u{0308}'), '\u{03d4}'),
+    (('\u{0406}', '\u{0308}'), '\u{0407}'), (('\u{0410}', '\u{0306}'), '\u{04d0}'), (('\u{0410}', '\u{0308}'), '\u{04d2}'),
+    (('\u{0413}', '\u{0301}'), '\u{0403}'), (('\u{0415}', '\u{0300}'), '\u{0400}'), (('\u{0415}', '\u{0306}'), '\u{04d6}'),
+    (('\u{0415}', '\u{0308}'), '\u{0401}'), (('\u{0416}', '\u{0306}'), '\u{04c1}'), (('\u{0416}', '\u{0308}'), '\u{04dc}'),
+    (('\u{0417}', '\u{0308}'), '\u{04de}'), (('\u{0418}', '\u{0300}'), '\u{040d}'), (('\u{0418}', '\u{0304}'), '\u{04e2}'),
+    (('\u{0418}', '\u{0306}'), '\u{0419}'), (('\u{0418}', '\u{0308}'), '\u{04e4}'), (('\u{041a}', '\u{0301}'), '\u{040c}'),
+    (('\u{041e}', '\u{0308}'), '\u{04e6}'), (('\u{0423}', '\u{0304}'), '\u{04ee}'), (('\u{0423}', '\u{0306}'), '\u{040e}'),
+    (('\u{0423}', '\u{0308}'), '\u{04f0}'), (('\u{0423}', '\u{030b}'), '\u{04f2}'), (('\u{0427}', '\u{0308}'), '\u{04f4}'),
+    (('\u{042b}', '\u{0308}'), '\u{04f8}'), (('\u{042d}', '\u{0308}'), '\u{04ec}'), (('\u{0430}', '\u{0306}'), '\u{04d1}'),
+    (('\u{0430}', '\u{0308}'), '\u{04d3}'), (('\u{0433}', '\u{0301}'), '\u{0453}'), (('\u{0435}', '\u{0300}'), '\u{0450}'),
+    (('\u{0435}', '\u{0306}'), '\u{04d7}'), (('\u{0435}', '\u{0308}'), '\u{0451}'), (('\u{0436}', '\u{0306}'), '\u{04c2}'),
+    (('\u{0436}', '\u{0308}'), '\u{04dd}'), (('\u{0437}', '\u{0308}'), '\u{04df}'), (('\u{0438}', '\u{0300}'), '\u{045d}'),
+    (('\u{0438}', '\u{0304}'), '\u{04e3}'), (('\u{0438}', '\u{0306}'), '\u{0439}'), (('\u{0438}', '\u{0308}'), '\u{04e5}'),
+    (('\u{043a}', '\u{0301}'), '\u{045c}'), (('\u{043e}', '\u{0308}'), '\u{04e7}'), (('\u{0443}', '\u{0304}'), '\u{04ef}'),
+    (('\u{0443}', '\u{0306}'), '\u{045e}'), (('\u{0443}', '\u{0308}'), '\u{04f1}'), (('\u{0443}', '\u{030b}'), '\u{04f3}'),
+    (('\u{0447}', '\u{0308}'), '\u{04f5}'), (('\u{044b}', '\u{0308}'), '\u{04f9}'), (('\u{044d}', '\u{0308}'), '\u{04ed}'),
+    (('\u{0456}', '\u{0308}'), '\u{0457}'), (('\u{0474}', '\u{030f}'), '\u{0476}'), (('\u{0475}', '\u{030f}'), '\u{0477}'),
+    (('\u{04d8}', '\u{0308}'), '\u{04da}'), (('\u{04d9}', '\u{0308}'), '\u{04db}'), (('\u{04e8}', '\u{0308}'), '\u{04ea}'),
+    (('\u{04e9}', '\u{0308}'), '\u{04eb}'), (('\u{0627}', '\u{0653}'), '\u{0622}'), (('\u{0627}', '\u{0654}'), '\u{0623}'),
+    (('\u{0627}', '\u{0655}'), '\u{0625}'), (('\u{0648}', '\u{0654}'), '\u{0624}'), (('\u{064a}', '\u{0654}'), '\u{0626}'),
+    (('\u{06c1}', '\u{0654}'), '\u{06c2}'), (('\u{06d2}', '\u{0654}'), '\u{06d3}'), (('\u{06d5}', '\u{0654}'), '\u{06c0}'),
+    (('\u{0928}', '\u{093c}'), '\u{0929}'), (('\u{0930}', '\u{093c}'), '\u{0931}'), (('\u{0933}', '\u{093c}'), '\u{0934}'),
+    (('\u{09c7}', '\u{09be}'), '\u{09cb}'), (('\u{09c7}', '\u{09d7}'), '\u{09cc}'), (('\u{0b47}', '\u{0b3e}'), '\u{0b4b}'),
+    (('\u{0b47}', '\u{0b56}'), '\u{0b48}'), (('\u{0b47}', '\u{0b57}'), '\u{0b4c}'), (('\u{0b92}', '\u{0bd7}'), '\u{0b94}'),
+    (('\u{0bc6}', '\u{0bbe}'), '\u{0bca}'), (('\u{0bc6}', '\u{0bd7}'), '\u{0bcc}'), (('\u{0bc7}', '\u{0bbe}'), '\u{0bcb}'),
+    (('\u{0c46}', '\u{0c56}'), '\u{0c48}'), (('\u{0cbf}', '\u{0cd5}'), '\u{0cc0}'), (('\u{0cc6}', '\u{0cc2}'), '\u{0cca}'),
+    (('\u{0cc6}', '\u{0cd5}'), '\u{0cc7}'), (('\u{0cc6}', '\u{0cd6}'), '\u{0cc8}'), (('\u{0cca}', '\u{0cd5}'), '\u{0ccb}'),
+    (('\u{0d46}', '\u{0d3e}'), '\u{0d4a}'), (('\u{0d46}', '\u{0d57}'), '\u{0d4c}'), (('\u{0d47}', '\u{0d3e}'), '\u{0d4b}'),
+    (('\u{0dd9}', '\u{0dca}'), '\u{0dda}'), (('\u{0dd9}', '\u{0dcf}'), '\u{0ddc}'), (('\u{0dd9}', '\u{0ddf}'), '\u{0dde}'),
+    (('\u{0ddc}', '\u{0dca}'), '\u{0ddd}'), (('\u{1025}', '\u{102e}'), '\u{1026}'), (('\u{1b05}', '\u{1b35}'), '\u{1b06}'),
+    (('\u{1b07}', '\u{1b35}'), '\u{1b08}'), (('\u{1b09}', '\u{1b35}'), '\u{1b0a}'), (('\u{1b0b}', '\u{1b35}'), '\u{1b0c}'),
+    (('\u{1b0d}', '\u{1b35}'), '\u{1b0e}'), (('\u{1b11}', '\u{1b35}'), '\u{1b12}'), (('\u{1b3a}', '\u{1b35}'), '\u{1b3b}'),
+    (('\u{1b3c}', '\u{1b35}'), '\u{1b3d}'), (('\u{1b3e}', '\u{1b35}'), '\u{1b40}'), (('\u{1b3f}', '\u{1b35}'), '\u{1b41}'),
+    (('\u{1b42}', '\u{1b35}'), '\u{1b43}'), (('\u{1e36}', '\u{0304}'), '\u{1e38}'), (('\u{1e37}', '\u{0304}'), '\u{1e39}'),
+    (('\u{1e5a}', '\u{0304}'), '\u{1e5c}'), (('\u{1e5b}', '\u{0304}'), '\u{1e5d}'), (('\u{1e62}', '\u{0307}'), '\u{1e68}'),
+    (('\u{1e63}', '\u{0307}'), '\u{1e69}'), (('\u{1ea0}', '\u{0302}'), '\u{1eac}'), (('\u{1ea0}', '\u{0306}'), '\u{1eb6}'),
+    (('\u{1ea1}', '\u{0302}'), '\u{1ead}'), (('\u{1ea1}', '\u{0306}'), '\u{1eb7}'), (('\u{1eb8}', '\u{0302}'), '\u{1ec6}'),
+    (('\u{1eb9}', '\u{0302}'), '\u{1ec7}'), (('\u{1ecc}', '\u{0302}'), '\u{1ed8}'), (('\u{1ecd}', '\u{0302}'), '\u{1ed9}'),
+    (('\u{1f00}', '\u{0300}'), '\u{1f02}'), (('\u{1f00}', '\u{0301}'), '\u{1f04}'), (('\u{1f00}', '\u{0342}'), '\u{1f06}'),
+    (('\u{1f00}', '\u{0345}'), '\u{1f80}'), (('\u{1f01}', '\u{0300}'), '\u{1f03}'), (('\u{1f01}', '\u{0301}'), '\u{1f05}'),
+    (('\u{1f01}', '\u{0342}'), '\u{1f07}'), (('\u{1f01}', '\u{0345}'), '\u{1f81}'), (('\u{1f02}', '\u{0345}'), '\u{1f82}'),
+    (('\u{1f03}', '\u{0345}'), '\u{1f83}'), (('\u{1f04}', '\u{0345}'), '\u{1f84}'), (('\u{1f05}', '\u{0345}'), '\u{1f85}'),
+    (('\u{1f06}', '\u{0345}'), '\u{1f86}'), (('\u{1f07}', '\u{0345}'), '\u{1f87}'), (('\u{1f08}', '\u{0300}'), '\u{1f0a}'),
+    (('\u{1f08}', '\u{0301}'), '\u{1f0c}'), (('\u{1f08}', '\u{0342}'), '\u{1f0e}'), (('\u{1f08}', '\u{0345}'), '\u{1f88}'),
+    (('\u{1f09}', '\u{0300}'), '\u{1f0b}'), (('\u{1f09}', '\u{0301}'), '\u{1f0d}'), (('\u{1f09}', '\u{0342}'), '\u{1f0f}'),
+    (('\u{1f09}', '\u{0345}'), '\u{1f89}'), (('\u{1f0a}', '\u{0345}'), '\u{1f8a}'), (('\u{1f0b}', '\u{0345}'), '\u{1f8b}'),
+    (('\u{1f0c}', '\u{0345}'), '\u{1f8c}'), (('\u{1f0d}', '\u{0345}'), '\u{1f8d}'), (('\u{1f0e}', '\u{0345}'), '\u{1f8e}'),
+    (('\u{1f0f}', '\u{0345}'), '\u{1f8f}'), (('\u{1f10}', '\u{0300}'), '\u{1f12}'), (('\u{1f10}', '\u{0301}'), '\u{1f14}'),
+    (('\u{1f11}', '\u{0300}'), '\u{1f13}'), (('\u{1f11}', '\u{0301}'), '\u{1f15}'), (('\u{1f18}', '\u{0300}'), '\u{1f1a}'),
+    (('\u{1f18}', '\u{0301}'), '\u{1f1c}'), (('\u{1f19}', '\u{0300}'), '\u{1f1b}'), (('\u{1f19}', '\u{0301}'), '\u{1f1d}'),
+    (('\u{1f20}', '\u{0300}'), '\u{1f22}'), (('\u{1f20}', '\u{0301}'), '\u{1f24}'), (('\u{1f20}', '\u{0342}'), '\u{1f26}'),
+    (('\u{1f20}', '\u{0345}'), '\u{1f90}'), (('\u{1f21}', '\u{0300}'), '\u{1f23}'), (('\u{1f21}', '\u{0301}'), '\u{1f25}'),
+    (('\u{1f21}', '\u{0342}'), '\u{1f27}'), (('\u{1f21}', '\u{0345}'), '\u{1f91}'), (('\u{1f22}', '\u{0345}'), '\u{1f92}'),
+    (('\u{1f23}', '\u{0345}'), '\u{1f93}'), (('\u{1f24}', '\u{0345}'), '\u{1f94}'), (('\u{1f25}', '\u{0345}'), '\u{1f95}'),
+    (('\u{1f26}', '\u{0345}'), '\u{1f96}'), (('\u{1f27}', '\u{0345}'), '\u{1f97}'), (('\u{1f28}', '\u{0300}'), '\u{1f2a}'),
+    (('\u{1f28}', '\u{0301}'), '\u{1f2c}'), (('\u{1f28}', '\u{0342}'), '\u{1f2e}'), (('\u{1f28}', '\u{0345}'), '\u{1f98}'),
+    (('\u{1f29}', '\u{0300}'), '\u{1f2b}'), (('\u{1f29}', '\u{0301}'), '\u{1f2d}'), (('\u{1f29}', '\u{0342}'), '\u{1f2f}'),
+    (('\u{1f29}', '\u{0345}'), '\u{1f99}'), (('\u{1f2a}', '\u{0345}'), '\u{1f9a}'), (('\u{1f2b}', '\u{0345}'), '\u{1f9b}'),
+    (('\u{1f2c}', '\u{0345}'), '\u{1f9c}'), (('\u{1f2d}', '\u{0345}'), '\u{1f9d}'), (('\u{1f2e}', '\u{0345}'), '\u{1f9e}'),
+    (('\u{1f2f}', '\u{0345}'), '\u{1f9f}'), (('\u{1f30}', '\u{0300}'), '\u{1f32}'), (('\u{1f30}', '\u{0301}'), '\u{1f34}'),
+    (('\u{1f30}', '\u{0342}'), '\u{1f36}'), (('\u{1f31}', '\u{0300}'), '\u{1f33}'), (('\u{1f31}', '\u{0301}'), '\u{1f35}'),
+    (('\u{1f31}', '\u{0342}'), '\u{1f37}'), (('\u{1f38}', '\u{0300}'), '\u{1f3a}'), (('\u{1f38}', '\u{0301}'), '\u{1f3c}'),
+    (('\u{1f38}', '\u{0342}'), '\u{1f3e}'), (('\u{1f39}', '\u{0300}'), '\u{1f3b}'), (('\u{1f39}', '\u{0301}'), '\u{1f3d}'),
+    (('\u{1f39}', '\u{0342}'), '\u{1f3f}'), (('\u{1f40}', '\u{0300}'), '\u{1f42}'), (('\u{1f40}', '\u{0301}'), '\u{1f44}'),
+    (('\u{1f41}', '\u{0300}'), '\u{1f43}'), (('\u{1f41}', '\u{0301}'), '\u{1f45}'), (('\u{1f48}', '\u{0300}'), '\u{1f4a}'),
+    (('\u{1f48}', '\u{0301}'), '\u{1f4c}'), (('\u{1f49}', '\u{0300}'), '\u{1f4b}'), (('\u{1f49}', '\u{0301}'), '\u{1f4d}'),
+    (('\u{1f50}', '\u{0300}'), '\u{1f52}'), (('\u{1f50}', '\u{0301}'), '\u{1f54}'), (('\u{1f50}', '\u{0342}'), '\u{1f56}'),
+    (('\u{1f51}', '\u{0300}'), '\u{1f53}'), (('\u{1f51}', '\u{0301}'), '\u{1f55}'), (('\u{1f51}', '\u{0342}'), '\u{1f57}'),
+    (('\u{1f59}', '\u{0300}'), '\u{1f5b}'), (('\u{1f59}', '\u{0301}'), '\u{1f5d}'), (('\u{1f59}', '\u{0342}'), '\u{1f5f}'),
+    (('\u{1f60}', '\u{0300}'), '\u{1f62}'), (('\u{1f60}', '\u{0301}'), '\u{1f64}'), (('\u{1f60}', '\u{0342}'), '\u{1f66}'),
+    (('\u{1f60}', '\u{0345}'), '\u{1fa0}'), (('\u{1f61}', '\u{0300}'), '\u{1f63}'), (('\u{1f61}', '\u{0301}'), '\u{1f65}'),
+    (('\u{1f61}', '\u{0342}'), '\u{1f67}'), (('\u{1f61}', '\u{0345}'), '\u{1fa1}'), (('\u{1f62}', '\u{0345}'), '\u{1fa2}'),
+    (('\u{1f63}', '\u{0345}'), '\u{1fa3}'), (('\u{1f64}', '\u{0345}'), '\u{1fa4}'), (('\u{1f65}', '\u{0345}'), '\u{1fa5}'),
+    (('\u{1f66}', '\u{0345}'), '\u{1fa6}'), (('\u{1f67}', '\u{0345}'), '\u{1fa7}'), (('\u{1f68}', '\u{0300}'), '\u{1f6a}'),
+    (('\u{1f68}', '\u{0301}'), '\u{1f6c}'), (('\u{1f68}', '\u{0342}'), '\u{1f6e}'), (('\u{1f68}', '\u{0345}'), '\u{1fa8}'),
+    (('\u{1f69}', '\u{0300}'), '\u{1f6b}'), (('\u{1f69}', '\u{0301}'), '\u{1f6d}'), (('\u{1f69}', '\u{0342}'), '\u{1f6f}'),
+    (('\u{1f69}', '\u{0345}'), '\u{1fa9}'), (('\u{1f6a}', '\u{0345}'), '\u{1faa}'), (('\u{1f6b}', '\u{0345}'), '\u{1fab}'),
+    (('\u{1f6c}', '\u{0345}'), '\u{1fac}'), (('\u{1f6d}', '\u{0345}'), '\u{1fad}'), (('\u{1f6e}', '\u{0345}'), '\u{1fae}'),
+    (('\u{1f6f}', '\u{0345}'), '\u{1faf}'), (('\u{1f70}', '\u{0345}'), '\u{1fb2}'), (('\u{1f74}', '\u{0345}'), '\u{1fc2}'),
+    (('\u{1f7c}', '\u{0345}'), '\u{1ff2}'), (('\u{1fb6}', '\u{0345}'), '\u{1fb7}'), (('\u{1fbf}', '\u{0300}'), '\u{1fcd}'),
+    (('\u{1fbf}', '\u{0301}'), '\u{1fce}'), (('\u{1fbf}', '\u{0342}'), '\u{1fcf}'), (('\u{1fc6}', '\u{0345}'), '\u{1fc7}'),
+    (('\u{1ff6}', '\u{0345}'), '\u{1ff7}'), (('\u{1ffe}', '\u{0300}'), '\u{1fdd}'), (('\u{1ffe}', '\u{0301}'), '\u{1fde}'),
+    (('\u{1ffe}', '\u{0342}'), '\u{1fdf}'), (('\u{2190}', '\u{0338}'), '\u{219a}'), (('\u{2192}', '\u{0338}'), '\u{219b}'),
+    (('\u{2194}', '\u{0338}'), '\u{21ae}'), (('\u{21d0}', '\u{0338}'), '\u{21cd}'), (('\u{21d2}', '\u{0338}'), '\u{21cf}'),
+    (('\u{21d4}', '\u{0338}'), '\u{21ce}'), (('\u{2203}', '\u{0338}'), '\u{2204}'), (('\u{2208}', '\u{0338}'), '\u{2209}'),
+    (('\u{220b}', '\u{0338}'), '\u{220c}'), (('\u{2223}', '\u{0338}'), '\u{2224}'), (('\u{2225}', '\u{0338}'), '\u{2226}'),
+    (('\u{223c}', '\u{0338}'), '\u{2241}'), (('\u{2243}', '\u{0338}'), '\u{2244}'), (('\u{2245}', '\u{0338}'), '\u{2247}'),
+    (('\u{2248}', '\u{0338}'), '\u{2249}'), (('\u{224d}', '\u{0338}'), '\u{226d}'), (('\u{2261}', '\u{0338}'), '\u{2262}'),
+    (('\u{2264}', '\u{0338}'), '\u{2270}'), (('\u{2265}', '\u{0338}'), '\u{2271}'), (('\u{2272}', '\u{0338}'), '\u{2274}'),
+    (('\u{2273}', '\u{0338}'), '\u{2275}'), (('\u{2276}', '\u{0338}'), '\u{2278}'), (('\u{2277}', '\u{0338}'), '\u{2279}'),
+    (('\u{227a}', '\u{0338}'), '\u{2280}'), (('\u{227b}', '\u{0338}'), '\u{2281}'), (('\u{227c}', '\u{0338}'), '\u{22e0}'),
+    (('\u{227d}', '\u{0338}'), '\u{22e1}'), (('\u{2282}', '\u{0338}'), '\u{2284}'), (('\u{2283}', '\u{0338}'), '\u{2285}'),
+    (('\u{2286}', '\u{0338}'), '\u{2288}'), (('\u{2287}', '\u{0338}'), '\u{2289}'), (('\u{2291}', '\u{0338}'), '\u{22e2}'),
+    (('\u{2292}', '\u{0338}'), '\u{22e3}'), (('\u{22a2}', '\u{0338}'), '\u{22ac}'), (('\u{22a8}', '\u{0338}'), '\u{22ad}'),
+    (('\u{22a9}', '\u{0338}'), '\u{22ae}'), (('\u{22ab}', '\u{0338}'), '\u{22af}'), (('\u{22b2}', '\u{0338}'), '\u{22ea}'),
+    (('\u{22b3}', '\u{0338}'), '\u{22eb}'), (('\u{22b4}', '\u{0338}'), '\u{22ec}'), (('\u{22b5}', '\u{0338}'), '\u{22ed}'),
+    (('\u{3046}', '\u{3099}'), '\u{3094}'), (('\u{304b}', '\u{3099}'), '\u{304c}'), (('\u{304d}', '\u{3099}'), '\u{304e}'),
+    (('\u{304f}', '\u{3099}'), '\u{3050}'), (('\u{3051}', '\u{3099}'), '\u{3052}'), (('\u{3053}', '\u{3099}'), '\u{3054}'),
+    (('\u{3055}', '\u{3099}'), '\u{3056}'), (('\u{3057}', '\u{3099}'), '\u{3058}'), (('\u{3059}', '\u{3099}'), '\u{305a}'),
+    (('\u{305b}', '\u{3099}'), '\u{305c}'), (('\u{305d}', '\u{3099}'), '\u{305e}'), (('\u{305f}', '\u{3099}'), '\u{3060}'),
+    (('\u{3061}', '\u{3099}'), '\u{3062}'), (('\u{3064}', '\u{3099}'), '\u{3065}'), (('\u{3066}', '\u{3099}'), '\u{3067}'),
+    (('\u{3068}', '\u{3099}'), '\u{3069}'), (('\u{306f}', '\u{3099}'), '\u{3070}'), (('\u{306f}', '\u{309a}'), '\u{3071}'),
+    (('\u{3072}', '\u{3099}'), '\u{3073}'), (('\u{3072}', '\u{309a}'), '\u{3074}'), (('\u{3075}', '\u{3099}'), '\u{3076}'),
+    (('\u{3075}', '\u{309a}'), '\u{3077}'), (('\u{3078}', '\u{3099}'), '\u{3079}'), (('\u{3078}', '\u{309a}'), '\u{307a}'),
+    (('\u{307b}', '\u{3099}'), '\u{307c}'), (('\u{307b}', '\u{309a}'), '\u{307d}'), (('\u{309d}', '\u{3099}'), '\u{309e}'),
+    (('\u{30a6}', '\u{3099}'), '\u{30f4}'), (('\u{30ab}', '\u{3099}'), '\u{30ac}'), (('\u{30ad}', '\u{3099}'), '\u{30ae}'),
+    (('\u{30af}', '\u{3099}'), '\u{30b0}'), (('\u{30b1}', '\u{3099}'), '\u{30b2}'), (('\u{30b3}', '\u{3099}'), '\u{30b4}'),
+    (('\u{30b5}', '\u{3099}'), '\u{30b6}'), (('\u{30b7}', '\u{3099}'), '\u{30b8}'), (('\u{30b9}', '\u{3099}'), '\u{30ba}'),
+    (('\u{30bb}', '\u{3099}'), '\u{30bc}'), (('\u{30bd}', '\u{3099}'), '\u{30be}'), (('\u{30bf}', '\u{3099}'), '\u{30c0}'),
+    (('\u{30c1}', '\u{3099}'), '\u{30c2}'), (('\u{30c4}', '\u{3099}'), '\u{30c5}'), (('\u{30c6}', '\u{3099}'), '\u{30c7}'),
+    (('\u{30c8}', '\u{3099}'), '\u{30c9}'), (('\u{30cf}', '\u{3099}'), '\u{30d0}'), (('\u{30cf}', '\u{309a}'), '\u{30d1}'),
+    (('\u{30d2}', '\u{3099}'), '\u{30d3}'), (('\u{30d2}', '\u{309a}'), '\u{30d4}'), (('\u{30d5}', '\u{3099}'), '\u{30d6}'),
+    (('\u{30d5}', '\u{309a}'), '\u{30d7}'), (('\u{30d8}', '\u{3099}'), '\u{30d9}'), (('\u{30d8}', '\u{309a}'), '\u{30da}'),
+    (('\u{30db}', '\u{3099}'), '\u{30dc}'), (('\u{30db}', '\u{309a}'), '\u{30dd}'), (('\u{30ef}', '\u{3099}'), '\u{30f7}'),
+    (('\u{30f0}', '\u{3099}'), '\u{30f8}'), (('\u{30f1}', '\u{3099}'), '\u{30f9}'), (('\u{30f2}', '\u{3099}'), '\u{30fa}'),
+    (('\u{30fd}', '\u{3099}'), '\u{30fe}'), (('\u{11099}', '\u{110ba}'), '\u{1109a}'), (('\u{1109b}', '\u{110ba}'), '\u{1109c}'),
+    (('\u{110a5}', '\u{110ba}'), '\u{110ab}'), (('\u{11131}', '\u{11127}'), '\u{1112e}'), (('\u{11132}', '\u{11127}'), '\u{1112f}'),
+    (('\u{11347}', '\u{1133e}'), '\u{1134b}'), (('\u{11347}', '\u{11357}'), '\u{1134c}'), (('\u{114b9}', '\u{114b0}'), '\u{114bc}'),
+    (('\u{114b9}', '\u{114ba}'), '\u{114bb}'), (('\u{114b9}', '\u{114bd}'), '\u{114be}'), (('\u{115b8}', '\u{115af}'), '\u{115ba}'),
+    (('\u{115b9}', '\u{115af}'), '\u{115bb}'), (('\u{11935}', '\u{11930}'), '\u{11938}'),
+];
@@ -0,0 +1,247 @@
+/*!
+Unified diffs of text files, in the format of `diff -u`.
+*/
+
+use std::{fmt::Write, path::Path};
+
+use crate::glob::slash_separated;
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+/// A line-level edit, by the indices of the lines in the old and new text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// The bytes as text, if they're UTF-8 without any NUL bytes.
+pub(crate) fn as_text(bytes: &[u8]) -> Option<&str> {
+    if bytes.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()
+}
+
+/// A unified diff of two versions of the text file at the given path.
+pub(crate) fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edit_script(&old, &new);
+
+    let path = slash_separated(path);
+    let mut diff = format!("--- a/{path}\n+++ b/{path}\n");
+
+    for hunk in hunks(&edits) {
+        let (old_start, old_length, new_start, new_length) = span(hunk);
+        writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            range(old_start, old_length),
+            range(new_start, new_length)
+        )
+        .expect("writing to a string can't fail");
+
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Keep(index, _) => (' ', old[index]),
+                Edit::Delete(index) => ('-', old[index]),
+                Edit::Insert(index) => ('+', new[index]),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    diff
+}
+
+/// The shortest edit script turning `old` into `new`, by Myers' algorithm.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+
+    // The furthest `x` reached on each diagonal `k = x - y`, saved before each round of edits.
+    let mut furthest = vec![0isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(furthest.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)]) {
+                furthest[index(k + 1)]
+            } else {
+                furthest[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index(k)] = x;
+
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, furthest) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = furthest[index(previous_k)];
+        let previous_y = previous_x - previous_k;
+
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Keep(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == previous_x {
+                edits.push(Edit::Insert(previous_y as usize));
+            } else {
+                edits.push(Edit::Delete(previous_x as usize));
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+/// Group the edits into hunks of changes with up to [`CONTEXT`] unchanged lines around them.
+fn hunks(edits: &[Edit]) -> Vec<&[Edit]> {
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Keep(..)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut hunks = Vec::new();
+    let mut changes = changes.into_iter().peekable();
+    while let Some(first) = changes.next() {
+        let mut last = first;
+        while let Some(&next) = changes.peek() {
+            if next - last - 1 > 2 * CONTEXT {
+                break;
+            }
+            last = next;
+            changes.next();
+        }
+
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(edits.len());
+        hunks.push(&edits[start..end]);
+    }
+
+    hunks
+}
+
+/// The zero-based start and the length of a hunk in the old text, then in the new text.
+fn span(hunk: &[Edit]) -> (usize, usize, usize, usize) {
+    let (mut old_start, mut new_start) = (None, None);
+    let (mut old_length, mut new_length) = (0, 0);
+    let (mut old_next, mut new_next) = (0, 0);
+
+    for edit in hunk {
+        match *edit {
+            Edit::Keep(old, new) => {
+                old_start.get_or_insert(old);
+                new_start.get_or_insert(new);
+                (old_next, new_next) = (old + 1, new + 1);
+                old_length += 1;
+                new_length += 1;
+            }
+            Edit::Delete(old) => {
+                old_start.get_or_insert(old);
+                old_next = old + 1;
+                old_length += 1;
+            }
+            Edit::Insert(new) => {
+                new_start.get_or_insert(new);
+                new_next = new + 1;
+                new_length += 1;
+            }
+        }
+    }
+
+    // A side without lines in the hunk starts where the other side's lines are inserted.
+    (
+        old_start.unwrap_or(old_next),
+        old_length,
+        new_start.unwrap_or(new_next),
+        new_length,
+    )
+}
+
+/// A range of lines as written in a hunk header.
+fn range(start: usize, length: usize) -> String {
+    match length {
+        // An empty range names the line before it.
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{length}", start + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::unified_diff;
+
+    #[test]
+    fn changed_lines_should_be_shown_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+
+        assert_eq!(
+            unified_diff(Path::new("numbers.txt"), old, new),
+            "\
+--- a/numbers.txt
++++ b/numbers.txt
+@@ -2,7 +2,7 @@
+ 2
+ 3
+ 4
+-5
++five
+ 6
+ 7
+ 8
+"
+        );
+    }
+
+    #[test]
+    fn insertions_into_empty_files_should_be_shown() {
+        assert_eq!(
+            unified_diff(Path::new("new.txt"), "", "a\nb"),
+            "\
+--- a/new.txt
++++ b/new.txt
+@@ -0,0 +1,2 @@
++a
++b
+\\ No newline at end of file
+"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+/*!
+Navigating trees in memory: looking up entries by their paths, and walking every entry in turn.
+*/
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::{Content, Entry};
+
+impl Content {
+    /**
+    The content at the given path relative to this tree's root, if there's any. The empty path is
+    the root itself.
+
+    ```
+    use dir_compare::Content;
+
+    let content = Content::of("fixtures/equivalent/dir-a")?;
+
+    let Some(Content::File(bytes)) = content.get("subdir/test2.txt") else {
+        panic!("subdir/test2.txt should be a file");
+    };
+    assert_eq!(bytes, b"more test data\n");
+    assert!(content.get("subdir/missing.txt").is_none());
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Self> {
+        path.as_ref()
+            .components()
+            .try_fold(self, |content, component| {
+                Some(&content.child(component)?.content)
+            })
+    }
+
+    /**
+    Walk every entry in the tree, the root first, yielding each with its path relative to the root.
+    A directory's children follow it, ordered by name, so the paths come in order.
+
+    ```
+    use dir_compare::Content;
+
+    let content = Content::of("fixtures/equivalent/dir-a")?;
+    let files: Vec<_> = content
+        .walk()
+        .filter(|(_, content)| matches!(content, Content::File(_)))
+        .map(|(path, _)| path)
+        .collect();
+
+    assert_eq!(files, ["subdir/test2.txt", "test.txt"].map(std::path::PathBuf::from));
+
+    # Ok::<(), Box<dyn std::error::Error>>(())
+    ```
+    */
+    #[must_use]
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: vec![(PathBuf::new(), self)],
+        }
+    }
+
+    /// The child entry with the name of the given component, if this is a directory that has one.
+    fn child(&self, component: Component<'_>) -> Option<&Entry> {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let Self::Entries(entries) = self else {
+            return None;
+        };
+        // Entries are ordered by the keys they're compared by, which are their names unless options
+        // normalized or lowercased them, in which case the entry is searched for by name.
+        match entries.binary_search_by(|entry| entry.key().cmp(name)) {
+            Ok(index) if entries[index].name == name => Some(&entries[index]),
+            _ => entries.iter().find(|entry| entry.name == name),
+        }
+    }
+}
+
+impl Entry {
+    /// The entry's content.
+    #[must_use]
+    pub fn content(&self) -> &Content {
+        &self.content
+    }
+
+    /**
+    The entry at the given path relative to this one, if there's any. The empty path is this entry
+    itself. See [`Content::get`].
+    */
+    #[must_use]
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Self> {
+        path.as_ref()
+            .components()
+            .try_fold(self, |entry, component| entry.content.child(component))
+    }
+}
+
+/**
+An iterator over every entry in a tree, with its path relative to the root. See [`Content::walk`].
+*/
+#[derive(Debug, Clone)]
+pub struct Walk<'a> {
+    stack: Vec<(PathBuf, &'a Content)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (PathBuf, &'a Content);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, content) = self.stack.pop()?;
+        if let Content::Entries(entries) = content {
+            // Push in reverse so that the children are popped in order.
+            self.stack.extend(
+                entries
+                    .iter()
+                    .rev()
+                    .map(|entry| (path.join(&entry.name), &entry.content)),
+            );
+        }
+
+        Some((path, content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{Content, Entry};
+
+    #[test]
+    fn entries_should_be_found_by_relative_paths() {
+        let entry = Entry::at("fixtures/equivalent/dir-a").unwrap();
+
+        assert_eq!(entry.get("").unwrap().name(), "dir-a");
+        assert_eq!(entry.get("subdir/test2.txt").unwrap().name(), "test2.txt");
+        assert!(entry.get("test.txt/nothing").is_none());
+        assert!(entry.get("../dir-b").is_none());
+        assert_eq!(
+            entry.content().get(Path::new("subdir")),
+            entry.get("subdir").map(Entry::content)
+        );
+    }
+
+    #[test]
+    fn walks_should_visit_every_entry_in_order() {
+        let content = Content::of("fixtures/equivalent/dir-a").unwrap();
+        let paths: Vec<_> = content.walk().map(|(path, _)| path).collect();
+
+        assert_eq!(
+            paths,
+            ["", "subdir", "subdir/test2.txt", "test.txt"].map(Path::new)
+        );
+    }
+}
@@ -0,0 +1,427 @@
+/*!
+Continuous comparison of two trees on disk, reporting the paths that drift apart or converge as the
+trees change.
+*/
+
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use crate::{Cache, Comparison, DiffReport, Difference, Options};
+
+/// How long the trees must be left alone after a change before they're compared again, so that a
+/// burst of changes is compared once.
+#[cfg(target_os = "linux")]
+const SETTLE: Duration = Duration::from_millis(50);
+
+/**
+A change in how two watched trees differ.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The path started to differ, or now differs in another way.
+    Diverged(Difference),
+    /// The path no longer differs.
+    Converged(PathBuf),
+}
+
+impl WatchEvent {
+    /// The path of the entry the event is about, relative to the roots of the trees.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Diverged(difference) => difference.path(),
+            Self::Converged(path) => path,
+        }
+    }
+}
+
+/**
+Two trees on disk under watch. Iterating over it blocks until the trees change, then compares them
+again and yields a [`WatchEvent`] for each path whose difference changed. Iteration never ends.
+
+On Linux, changes are waited for with inotify. Elsewhere, and for trees on file systems that don't
+report their changes, such as network file systems, the trees are compared at an interval instead;
+see [`Watch::polling`]. Either way, files are compared through a [`Cache`], so only the files that
+changed are read again.
+
+```no_run
+use dir_compare::{Options, Watch, WatchEvent};
+
+let watch = Watch::new("/srv/replica-a", "/srv/replica-b", &Options::new())?;
+println!("{} paths differ", watch.report().differences().len());
+
+for event in watch {
+    match event? {
+        WatchEvent::Diverged(difference) => println!("{:?} differs", difference.path()),
+        WatchEvent::Converged(path) => println!("{path:?} is back in sync"),
+    }
+}
+
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+*/
+#[derive(Debug)]
+pub struct Watch {
+    a: PathBuf,
+    b: PathBuf,
+    options: Options,
+    cache: Cache,
+    report: DiffReport,
+    trigger: Trigger,
+    pending: VecDeque<WatchEvent>,
+}
+
+impl Watch {
+    /**
+    Start watching the files or directories at the given paths, comparing them as configured by the
+    given [`Options`]. They're compared once right away; see [`Watch::report`].
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn new(a: impl AsRef<Path>, b: impl AsRef<Path>, options: &Options) -> io::Result<Self> {
+        Self::start(a.as_ref(), b.as_ref(), options, Trigger::new()?)
+    }
+
+    /**
+    Start watching the files or directories at the given paths, as with [`Watch::new`], but compare
+    them every `interval` instead of waiting to be notified of changes.
+
+    # Errors
+
+    Will bubble I/O errors.
+    */
+    pub fn polling(
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        options: &Options,
+        interval: Duration,
+    ) -> io::Result<Self> {
+        Self::start(a.as_ref(), b.as_ref(), options, Trigger::Interval(interval))
+    }
+
+    fn start(a: &Path, b: &Path, options: &Options, trigger: Trigger) -> io::Result<Self> {
+        let mut watch = Self {
+            a: a.to_path_buf(),
+            b: b.to_path_buf(),
+            options: options.clone(),
+            cache: Cache::new(),
+            report: DiffReport::default(),
+            trigger,
+            pending: VecDeque::new(),
+        };
+        // Watch before comparing, so that no change made during the comparison is missed.
+        watch.trigger.watch(&watch.a)?;
+        watch.trigger.watch(&watch.b)?;
+        watch.report = watch.compare()?;
+
+        Ok(watch)
+    }
+
+    /// The differences between the trees as of their latest comparison.
+    #[must_use]
+    pub fn report(&self) -> &DiffReport {
+        &self.report
+    }
+
+    fn compare(&mut self) -> io::Result<DiffReport> {
+        Comparison::between_cached(&self.a, &self.b, &self.options, &mut self.cache)
+            .map(Comparison::into_report)
+    }
+
+    /// Wait for the trees to change, then compare them again, queueing the events.
+    fn update(&mut self) -> io::Result<()> {
+        self.trigger.wait()?;
+        self.trigger.watch(&self.a)?;
+        self.trigger.watch(&self.b)?;
+
+        let report = self.compare()?;
+        self.pending.extend(events(&self.report, &report));
+        self.report = report;
+        Ok(())
+    }
+}
+
+impl Iterator for Watch {
+    type Item = io::Result<WatchEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Err(error) = self.update() {
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+/// The events that turn one report into another, ordered by path.
+fn events(before: &DiffReport, after: &DiffReport) -> Vec<WatchEvent> {
+    let mut events = Vec::new();
+    let mut before = before.iter().peekable();
+    let mut after = after.iter().peekable();
+
+    loop {
+        let order = match (before.peek(), after.peek()) {
+            (None, None) => return events,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(old), Some(new)) => old.path().cmp(new.path()),
+        };
+
+        match order {
+            Ordering::Less => {
+                let old = before.next().expect("the peeked difference exists");
+                events.push(WatchEvent::Converged(old.path().to_path_buf()));
+            }
+            Ordering::Greater => {
+                let new = after.next().expect("the peeked difference exists");
+                events.push(WatchEvent::Diverged(new.clone()));
+            }
+            Ordering::Equal => {
+                let old = before.next().expect("the peeked difference exists");
+                let new = after.next().expect("the peeked difference exists");
+                if old.kind() != new.kind() {
+                    events.push(WatchEvent::Diverged(new.clone()));
+                }
+            }
+        }
+    }
+}
+
+/// What prompts the trees to be compared again.
+#[derive(Debug)]
+enum Trigger {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Inotify),
+    Interval(Duration),
+}
+
+impl Trigger {
+    #[cfg(target_os = "linux")]
+    fn new() -> io::Result<Self> {
+        inotify::Inotify::new().map(Self::Inotify)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new() -> io::Result<Self> {
+        Ok(Self::Interval(Duration::from_secs(1)))
+    }
+
+    /// Make sure every directory of the tree at the given path is watched.
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn watch(&mut self, root: &Path) -> io::Result<()> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::Inotify(inotify) => inotify.add_tree(root),
+            Self::Interval(_) => Ok(()),
+        }
+    }
+
+    /// Block until the trees have changed and settled, or until the interval has elapsed.
+    fn wait(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::Inotify(inotify) => {
+                inotify.wait(None)?;
+                while inotify.wait(Some(SETTLE))? {}
+                Ok(())
+            }
+            Self::Interval(interval) => {
+                thread::sleep(*interval);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::{
+        ffi::CString,
+        fs::{self, File},
+        io::{self, Read},
+        os::{
+            fd::{AsRawFd, FromRawFd},
+            raw::{c_char, c_int, c_short, c_ulong},
+            unix::ffi::OsStrExt,
+        },
+        path::Path,
+        time::Duration,
+    };
+
+    extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(descriptor: c_int, path: *const c_char, mask: u32) -> c_int;
+        fn poll(descriptors: *mut PollDescriptor, count: c_ulong, timeout: c_int) -> c_int;
+    }
+
+    #[repr(C)]
+    struct PollDescriptor {
+        descriptor: c_int,
+        events: c_short,
+        returned_events: c_short,
+    }
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2_000_000;
+    const POLLIN: c_short = 1;
+
+    /// The changes to be notified of: modification, metadata changes, creation, deletion, and
+    /// moves, of watched directories' children and of the directories themselves.
+    const MASK: u32 = 0x2 | 0x4 | 0x8 | 0x40 | 0x80 | 0x100 | 0x200 | 0x400 | 0x800;
+
+    /// An inotify instance, whose events are only waited for, never inspected.
+    #[derive(Debug)]
+    pub(super) struct Inotify {
+        file: File,
+    }
+
+    impl Inotify {
+        pub(super) fn new() -> io::Result<Self> {
+            // SAFETY: The call has no preconditions.
+            let descriptor = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+            if descriptor < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // SAFETY: The descriptor was just opened, and nothing else owns it.
+            let file = unsafe { File::from_raw_fd(descriptor) };
+            Ok(Self { file })
+        }
+
+        /**
+        Watch every directory of the tree at the given path, or the file at it. The root's parent
+        is watched too, to notice the root being replaced.
+        */
+        pub(super) fn add_tree(&mut self, root: &Path) -> io::Result<()> {
+            if let Some(parent) = root.parent().filter(|p| !p.as_os_str().is_empty()) {
+                self.add(parent)?;
+            }
+
+            let mut stack = vec![root.to_path_buf()];
+            while let Some(path) = stack.pop() {
+                self.add(&path)?;
+                let Ok(entries) = fs::read_dir(&path) else {
+                    continue;
+                };
+                for entry in entries.filter_map(Result::ok) {
+                    if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                        stack.push(entry.path());
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Watch the file or directory at the given path. Watching it again has no effect, and
+        /// entries that have disappeared in the meantime are ignored.
+        fn add(&mut self, path: &Path) -> io::Result<()> {
+            let path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+
+            // SAFETY: The path is a valid C string for the duration of the call.
+            let watch = unsafe { inotify_add_watch(self.file.as_raw_fd(), path.as_ptr(), MASK) };
+            if watch < 0 {
+                let error = io::Error::last_os_error();
+                if !matches!(
+                    error.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+                ) {
+                    return Err(error);
+                }
+            }
+            Ok(())
+        }
+
+        /// Wait for events, up to the given timeout, discarding them. Returns whether any arrived.
+        pub(super) fn wait(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+            let mut descriptor = PollDescriptor {
+                descriptor: self.file.as_raw_fd(),
+                events: POLLIN,
+                returned_events: 0,
+            };
+            let timeout = timeout.map_or(-1, |timeout| {
+                c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX)
+            });
+
+            // SAFETY: The pointer refers to a single descriptor for the duration of the call.
+            let ready = unsafe { poll(&mut descriptor, 1, timeout) };
+            if ready < 0 {
+                let error = io::Error::last_os_error();
+                return match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(false),
+                    _ => Err(error),
+                };
+            }
+
+            let mut buffer = [0; 4096];
+            loop {
+                match self.file.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Err(error) => return Err(error),
+                }
+            }
+
+            Ok(ready > 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use crate::{testing::TempDir, DifferenceKind, Options, Watch, WatchEvent};
+
+    fn drift_and_converge(start: impl Fn(&Path, &Path) -> Watch) {
+        let temp = TempDir::new();
+        let (a, b) = (temp.path().join("a"), temp.path().join("b"));
+        for dir in [&a, &b] {
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("sub/file.txt"), "same").unwrap();
+        }
+
+        let mut watch = start(&a, &b);
+        assert!(watch.report().is_empty());
+
+        fs::write(b.join("sub/file.txt"), "drifted").unwrap();
+        let event = watch.next().unwrap().unwrap();
+        let WatchEvent::Diverged(difference) = &event else {
+            panic!("expected a divergence, got {event:?}");
+        };
+        assert_eq!(difference.path(), Path::new("sub/file.txt"));
+        assert_eq!(difference.kind(), DifferenceKind::Modified);
+
+        fs::write(b.join("sub/file.txt"), "same").unwrap();
+        assert_eq!(
+            watch.next().unwrap().unwrap(),
+            WatchEvent::Converged("sub/file.txt".into())
+        );
+    }
+
+    #[test]
+    fn changes_should_be_noticed() {
+        drift_and_converge(|a, b| Watch::new(a, b, &Options::new()).unwrap());
+    }
+
+    #[test]
+    fn changes_should_be_noticed_by_polling() {
+        drift_and_converge(|a, b| {
+            Watch::polling(a, b, &Options::new(), Duration::from_millis(10)).unwrap()
+        });
+    }
+}
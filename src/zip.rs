@@ -0,0 +1,94 @@
+/*!
+A reader of zip archives, enough to extract the files of stored or deflated entries.
+
+Zip64 archives, encryption, and compression methods other than deflate aren't supported.
+*/
+
+use std::io;
+
+use crate::inflate::{crc32, inflate, invalid};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// The entries of a zip archive, as their names and decompressed bytes, in the archive's order.
+pub(crate) fn entries(archive: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let end = end_of_central_directory(archive)?;
+    let count = usize::from(read_u16(archive, end + 10)?);
+    let mut offset = read_u32(archive, end + 16)? as usize;
+    let mut entries = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        if read_u32(archive, offset)? != CENTRAL_HEADER {
+            return Err(invalid(
+                "a zip central directory entry has an invalid signature",
+            ));
+        }
+        let method = read_u16(archive, offset + 10)?;
+        let crc = read_u32(archive, offset + 16)?;
+        let compressed_size = read_u32(archive, offset + 20)? as usize;
+        let size = read_u32(archive, offset + 24)? as usize;
+        let name_length = usize::from(read_u16(archive, offset + 28)?);
+        let extra_length = usize::from(read_u16(archive, offset + 30)?);
+        let comment_length = usize::from(read_u16(archive, offset + 32)?);
+        let local = read_u32(archive, offset + 42)? as usize;
+        let name = slice(archive, offset + 46, name_length)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_length + extra_length + comment_length;
+
+        if read_u32(archive, local)? != LOCAL_HEADER {
+            return Err(invalid("a zip local header has an invalid signature"));
+        }
+        let start = local
+            + 30
+            + usize::from(read_u16(archive, local + 26)?)
+            + usize::from(read_u16(archive, local + 28)?);
+        let data = slice(archive, start, compressed_size)?;
+
+        let bytes = match method {
+            STORED => data.to_vec(),
+            DEFLATED => inflate(data)?.0,
+            _ => return Err(invalid("a zip entry has an unsupported compression method")),
+        };
+        if bytes.len() != size || crc32(&bytes) != crc {
+            return Err(invalid("a zip entry is corrupt"));
+        }
+
+        entries.push((name, bytes));
+    }
+
+    Ok(entries)
+}
+
+/// The offset of the end of central directory record, which follows the central directory and
+/// precedes a comment of up to 65535 bytes.
+fn end_of_central_directory(archive: &[u8]) -> io::Result<usize> {
+    let last = archive
+        .len()
+        .checked_sub(22)
+        .ok_or_else(|| invalid("the data is not a zip archive"))?;
+
+    (last.saturating_sub(0xffff)..=last)
+        .rev()
+        .find(|&offset| read_u32(archive, offset).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| invalid("the data is not a zip archive"))
+}
+
+fn slice(bytes: &[u8], offset: usize, length: usize) -> io::Result<&[u8]> {
+    bytes
+        .get(offset..offset + length)
+        .ok_or_else(|| invalid("a zip archive is truncated"))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    slice(bytes, offset, 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    slice(bytes, offset, 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
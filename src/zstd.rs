@@ -0,0 +1,873 @@
+/*!
+A decoder for Zstandard frames ([RFC 8878]), so that zstd-compressed files can be compared without
+taking on a dependency.
+
+Like the DEFLATE decoder, it favours simplicity over speed, in the manner of the reference's
+educational decoder. Frames that need a dictionary aren't supported.
+
+[RFC 8878]: https://www.rfc-editor.org/rfc/rfc8878
+*/
+
+use std::io;
+
+use crate::inflate::invalid;
+
+const MAGIC: u32 = 0xfd2f_b528;
+/// Skippable frames have any magic number of this form.
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+const MAX_HUFFMAN_BITS: u32 = 11;
+
+/// The base values and numbers of extra bits of the literals length codes.
+const LITERALS_LENGTH_BASE: [u32; 36] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 18, 20, 22, 24, 28, 32, 40, 48, 64,
+    128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536,
+];
+const LITERALS_LENGTH_EXTRA: [u8; 36] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 4, 6, 7, 8, 9, 10, 11,
+    12, 13, 14, 15, 16,
+];
+/// The base values and numbers of extra bits of the match length codes.
+const MATCH_LENGTH_BASE: [u32; 53] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+    28, 29, 30, 31, 32, 33, 34, 35, 37, 39, 41, 43, 47, 51, 59, 67, 83, 99, 131, 259, 515, 1027,
+    2051, 4099, 8195, 16387, 32771, 65539,
+];
+const MATCH_LENGTH_EXTRA: [u8; 53] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    1, 1, 1, 1, 2, 2, 3, 3, 4, 4, 5, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+];
+/// The largest offset code, whose offsets are of up to 31 bits.
+const MAX_OFFSET_CODE: usize = 31;
+
+/// The distributions of the predefined codes, and their accuracy logs.
+const LITERALS_LENGTH_DEFAULT: ([i16; 36], u32) = (
+    [
+        4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1,
+        1, 1, -1, -1, -1, -1,
+    ],
+    6,
+);
+const MATCH_LENGTH_DEFAULT: ([i16; 53], u32) = (
+    [
+        1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+    ],
+    6,
+);
+const OFFSET_DEFAULT: ([i16; 29], u32) = (
+    [
+        1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+    ],
+    5,
+);
+
+/// Decompress Zstandard data of any number of frames, skipping skippable ones.
+pub(crate) fn unzstd(mut input: &[u8]) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+
+    loop {
+        let magic = read_u32(input, 0).ok_or_else(|| invalid("the zstd frame is truncated"))?;
+        let consumed = if magic == MAGIC {
+            decode_frame(&input[4..], &mut output)? + 4
+        } else if magic & 0xffff_fff0 == SKIPPABLE_MAGIC {
+            let size = read_u32(input, 4).ok_or_else(|| invalid("the zstd frame is truncated"))?;
+            usize::try_from(size)
+                .ok()
+                .and_then(|size| size.checked_add(8))
+                .filter(|&end| end <= input.len())
+                .ok_or_else(|| invalid("the zstd frame is truncated"))?
+        } else {
+            return Err(invalid("the data is not zstd-compressed"));
+        };
+
+        input = &input[consumed..];
+        if input.is_empty() {
+            return Ok(output);
+        }
+    }
+}
+
+/// Decode a frame, after its magic number, onto the output, returning the number of bytes read.
+fn decode_frame(input: &[u8], output: &mut Vec<u8>) -> io::Result<usize> {
+    let truncated = || invalid("the zstd frame is truncated");
+    let descriptor = *input.first().ok_or_else(truncated)?;
+    let single_segment = descriptor & 0b10_0000 != 0;
+    let has_checksum = descriptor & 0b100 != 0;
+    if descriptor & 0b1000 != 0 {
+        return Err(invalid("the zstd frame header has a reserved bit set"));
+    }
+
+    let mut position = 1 + usize::from(!single_segment);
+    let dictionary_size = [0, 1, 2, 4][usize::from(descriptor & 0b11)];
+    let dictionary = read_le(input, position, dictionary_size).ok_or_else(truncated)?;
+    if dictionary != 0 {
+        return Err(invalid("the zstd frame needs a dictionary"));
+    }
+    position += dictionary_size;
+    let content_size = match descriptor >> 6 {
+        0 if single_segment => Some(read_le(input, position, 1).ok_or_else(truncated)?),
+        0 => None,
+        1 => Some(read_le(input, position, 2).ok_or_else(truncated)? + 256),
+        2 => Some(read_le(input, position, 4).ok_or_else(truncated)?),
+        _ => Some(read_le(input, position, 8).ok_or_else(truncated)?),
+    };
+    position += match descriptor >> 6 {
+        0 => usize::from(single_segment),
+        size_flag => 1 << size_flag,
+    };
+
+    let start = output.len();
+    let mut state = FrameState::default();
+    loop {
+        let header = read_le(input, position, 3).ok_or_else(truncated)?;
+        position += 3;
+        let last = header & 1 != 0;
+        let size = (header >> 3) as usize;
+        if size > MAX_BLOCK_SIZE {
+            return Err(invalid("a zstd block is too large"));
+        }
+        // An RLE block holds one byte, whatever its size.
+        let kind = (header >> 1) & 0b11;
+        let stored = if kind == 1 { 1 } else { size };
+        let block = input
+            .get(position..position + stored)
+            .ok_or_else(truncated)?;
+        match kind {
+            0 => output.extend_from_slice(block),
+            1 => output.resize(output.len() + size, block[0]),
+            2 => decode_block(block, output, start, &mut state)?,
+            _ => return Err(invalid("a zstd block is of a reserved type")),
+        }
+        position += stored;
+        if last {
+            break;
+        }
+    }
+
+    let content = &output[start..];
+    if content_size.is_some_and(|size| size != content.len() as u64) {
+        return Err(invalid(
+            "the zstd frame's content isn't of its declared size",
+        ));
+    }
+    if has_checksum {
+        let checksum = read_u32(input, position).ok_or_else(truncated)?;
+        if checksum != xxh64(content) as u32 {
+            return Err(invalid("the zstd data is corrupt"));
+        }
+        position += 4;
+    }
+
+    Ok(position)
+}
+
+/// What later blocks of a frame may reuse from earlier ones.
+struct FrameState {
+    huffman: Option<Huffman>,
+    literals_length: Option<Fse>,
+    offset: Option<Fse>,
+    match_length: Option<Fse>,
+    repeat_offsets: [usize; 3],
+}
+
+impl Default for FrameState {
+    fn default() -> Self {
+        Self {
+            huffman: None,
+            literals_length: None,
+            offset: None,
+            match_length: None,
+            repeat_offsets: [1, 4, 8],
+        }
+    }
+}
+
+/// Decode a compressed block onto the output, whose frame started at `start`.
+fn decode_block(
+    block: &[u8],
+    output: &mut Vec<u8>,
+    start: usize,
+    state: &mut FrameState,
+) -> io::Result<()> {
+    let (literals, consumed) = decode_literals(block, &mut state.huffman)?;
+    let sequences = decode_sequences(&block[consumed..], state)?;
+
+    let mut literals = literals.as_slice();
+    for (literals_length, offset, match_length) in sequences {
+        let (copied, rest) = literals
+            .split_at_checked(literals_length)
+            .ok_or_else(|| invalid("a zstd sequence overruns its literals"))?;
+        output.extend_from_slice(copied);
+        literals = rest;
+
+        if offset == 0 || offset > output.len() - start {
+            return Err(invalid(
+                "a zstd match reaches before the start of the frame",
+            ));
+        }
+        if match_length > MAX_BLOCK_SIZE {
+            return Err(invalid("a zstd match is too long"));
+        }
+        // Matches may overlap the bytes they produce, so they're copied a byte at a time.
+        let from = output.len() - offset;
+        for index in from..from + match_length {
+            output.push(output[index]);
+        }
+    }
+    output.extend_from_slice(literals);
+
+    Ok(())
+}
+
+/// Decode the literals section of a block, returning the literals and the bytes read.
+fn decode_literals(block: &[u8], huffman: &mut Option<Huffman>) -> io::Result<(Vec<u8>, usize)> {
+    let truncated = || invalid("the zstd literals section is truncated");
+    let first = *block.first().ok_or_else(truncated)?;
+    let kind = first & 0b11;
+    let size_format = (first >> 2) & 0b11;
+
+    if kind < 2 {
+        // Raw or RLE literals, with a header of 1 to 3 bytes.
+        let (header_size, size) = match size_format {
+            0 | 2 => (1, usize::from(first >> 3)),
+            1 => (
+                2,
+                (read_le(block, 0, 2).ok_or_else(truncated)? >> 4) as usize,
+            ),
+            _ => (
+                3,
+                (read_le(block, 0, 3).ok_or_else(truncated)? >> 4) as usize,
+            ),
+        };
+        if kind == 0 {
+            let literals = block
+                .get(header_size..header_size + size)
+                .ok_or_else(truncated)?;
+            return Ok((literals.to_vec(), header_size + size));
+        }
+        let byte = *block.get(header_size).ok_or_else(truncated)?;
+        return Ok((vec![byte; size], header_size + 1));
+    }
+
+    // Huffman-compressed literals, in one stream or four.
+    let (header_size, bits, streams) = match size_format {
+        0 => (3, 10, 1),
+        1 => (3, 10, 4),
+        2 => (4, 14, 4),
+        _ => (5, 18, 4),
+    };
+    let header = read_le(block, 0, header_size).ok_or_else(truncated)?;
+    let size = ((header >> 4) & ((1 << bits) - 1)) as usize;
+    let compressed_size = ((header >> (4 + bits)) & ((1 << bits) - 1)) as usize;
+    if size > MAX_BLOCK_SIZE {
+        return Err(invalid("the zstd literals are too large"));
+    }
+    let mut compressed = block
+        .get(header_size..header_size + compressed_size)
+        .ok_or_else(truncated)?;
+    if kind == 2 {
+        let (table, consumed) = Huffman::read(compressed)?;
+        *huffman = Some(table);
+        compressed = &compressed[consumed..];
+    }
+    let huffman = huffman
+        .as_ref()
+        .ok_or_else(|| invalid("zstd literals reuse a Huffman table that doesn't exist"))?;
+
+    let mut literals = Vec::with_capacity(size);
+    if streams == 1 {
+        huffman.decode(compressed, size, &mut literals)?;
+    } else {
+        let jumps = compressed.get(..6).ok_or_else(truncated)?;
+        let mut sizes = [0; 4];
+        for (stream, size) in sizes.iter_mut().take(3).enumerate() {
+            *size = usize::from(u16::from_le_bytes([
+                jumps[stream * 2],
+                jumps[stream * 2 + 1],
+            ]));
+        }
+        sizes[3] = (compressed.len() - 6)
+            .checked_sub(sizes[..3].iter().sum())
+            .ok_or_else(truncated)?;
+        let stream_size = size.div_ceil(4);
+        let last_size = size
+            .checked_sub(stream_size * 3)
+            .ok_or_else(|| invalid("the zstd literals are split unevenly"))?;
+
+        let mut rest = &compressed[6..];
+        for (stream, &compressed_size) in sizes.iter().enumerate() {
+            let (stream_bytes, remaining) = rest.split_at(compressed_size);
+            let count = if stream == 3 { last_size } else { stream_size };
+            huffman.decode(stream_bytes, count, &mut literals)?;
+            rest = remaining;
+        }
+    }
+
+    Ok((literals, header_size + compressed_size))
+}
+
+/// Decode the sequences section of a block into literal lengths, offsets, and match lengths.
+fn decode_sequences(
+    section: &[u8],
+    state: &mut FrameState,
+) -> io::Result<Vec<(usize, usize, usize)>> {
+    let truncated = || invalid("the zstd sequences section is truncated");
+    let first = *section.first().ok_or_else(truncated)?;
+    let (count, mut position) = match first {
+        0 => return Ok(Vec::new()),
+        1..=127 => (usize::from(first), 1),
+        128..=254 => {
+            let second = *section.get(1).ok_or_else(truncated)?;
+            ((usize::from(first - 128) << 8) + usize::from(second), 2)
+        }
+        255 => (
+            read_le(section, 1, 2).ok_or_else(truncated)? as usize + 0x7f00,
+            3,
+        ),
+    };
+
+    let modes = *section.get(position).ok_or_else(truncated)?;
+    position += 1;
+    if modes & 0b11 != 0 {
+        return Err(invalid("the zstd sequences section has reserved bits set"));
+    }
+    for (shift, table, default, max_symbol, max_log) in [
+        (
+            6,
+            &mut state.literals_length,
+            (&LITERALS_LENGTH_DEFAULT.0[..], LITERALS_LENGTH_DEFAULT.1),
+            LITERALS_LENGTH_BASE.len() - 1,
+            9,
+        ),
+        (
+            4,
+            &mut state.offset,
+            (&OFFSET_DEFAULT.0[..], OFFSET_DEFAULT.1),
+            MAX_OFFSET_CODE,
+            8,
+        ),
+        (
+            2,
+            &mut state.match_length,
+            (&MATCH_LENGTH_DEFAULT.0[..], MATCH_LENGTH_DEFAULT.1),
+            MATCH_LENGTH_BASE.len() - 1,
+            9,
+        ),
+    ] {
+        let rest = section.get(position..).ok_or_else(truncated)?;
+        match (modes >> shift) & 0b11 {
+            0 => *table = Some(Fse::new(default.0, default.1)?),
+            1 => {
+                let symbol = *rest.first().ok_or_else(truncated)?;
+                if usize::from(symbol) > max_symbol {
+                    return Err(invalid("a zstd code is out of range"));
+                }
+                *table = Some(Fse::rle(symbol));
+                position += 1;
+            }
+            2 => {
+                let (fse, consumed) = Fse::read(rest, max_symbol, max_log)?;
+                *table = Some(fse);
+                position += consumed;
+            }
+            _ => {
+                if table.is_none() {
+                    return Err(invalid("zstd sequences reuse a table that doesn't exist"));
+                }
+            }
+        }
+    }
+    let (Some(literals_lengths), Some(offsets), Some(match_lengths)) =
+        (&state.literals_length, &state.offset, &state.match_length)
+    else {
+        unreachable!("every table was just set or checked");
+    };
+
+    let mut bits = Backward::new(section.get(position..).ok_or_else(truncated)?)?;
+    let mut literals_length_state = bits.read(literals_lengths.log);
+    let mut offset_state = bits.read(offsets.log);
+    let mut match_length_state = bits.read(match_lengths.log);
+    let repeat_offsets = &mut state.repeat_offsets;
+    let mut sequences = Vec::with_capacity(count);
+    for index in 0..count {
+        let offset_code = usize::from(offsets.symbols[offset_state as usize]);
+        let match_code = usize::from(match_lengths.symbols[match_length_state as usize]);
+        let literals_code = usize::from(literals_lengths.symbols[literals_length_state as usize]);
+        if offset_code > MAX_OFFSET_CODE {
+            return Err(invalid("a zstd offset code is out of range"));
+        }
+
+        let offset_value = (1u64 << offset_code) + bits.read(offset_code as u32);
+        let match_length_value = MATCH_LENGTH_BASE[match_code] as usize
+            + bits.read(u32::from(MATCH_LENGTH_EXTRA[match_code])) as usize;
+        let literals_length_value = LITERALS_LENGTH_BASE[literals_code] as usize
+            + bits.read(u32::from(LITERALS_LENGTH_EXTRA[literals_code])) as usize;
+
+        let offset_value = offset_value as usize;
+        let offset = if offset_value > 3 {
+            let offset = offset_value - 3;
+            *repeat_offsets = [offset, repeat_offsets[0], repeat_offsets[1]];
+            offset
+        } else {
+            // Without literals, each repeat offset stands for the next one.
+            match offset_value - 1 + usize::from(literals_length_value == 0) {
+                0 => repeat_offsets[0],
+                1 => {
+                    let offset = repeat_offsets[1];
+                    *repeat_offsets = [offset, repeat_offsets[0], repeat_offsets[2]];
+                    offset
+                }
+                2 => {
+                    let offset = repeat_offsets[2];
+                    *repeat_offsets = [offset, repeat_offsets[0], repeat_offsets[1]];
+                    offset
+                }
+                _ => {
+                    let offset = repeat_offsets[0]
+                        .checked_sub(1)
+                        .filter(|&offset| offset > 0)
+                        .ok_or_else(|| invalid("a zstd repeat offset is zero"))?;
+                    *repeat_offsets = [offset, repeat_offsets[0], repeat_offsets[1]];
+                    offset
+                }
+            }
+        };
+        sequences.push((literals_length_value, offset, match_length_value));
+
+        if index + 1 < count {
+            literals_length_state = literals_lengths.next(literals_length_state, &mut bits);
+            match_length_state = match_lengths.next(match_length_state, &mut bits);
+            offset_state = offsets.next(offset_state, &mut bits);
+        }
+    }
+    if bits.remaining() != 0 {
+        return Err(invalid("the zstd sequences don't fill their bitstream"));
+    }
+
+    Ok(sequences)
+}
+
+/// A table for decoding a finite state entropy code.
+struct Fse {
+    log: u32,
+    symbols: Vec<u8>,
+    bits: Vec<u32>,
+    bases: Vec<u64>,
+}
+
+impl Fse {
+    /// Build the decoding table of the given distribution, whose probabilities sum to 2^`log`.
+    fn new(distribution: &[i16], log: u32) -> io::Result<Self> {
+        let size = 1usize << log;
+        let mut symbols = vec![0; size];
+        let mut next = vec![0u32; distribution.len()];
+        // Symbols of "less than one" probability take the last states, one each.
+        let mut high = size;
+        for (symbol, &probability) in distribution.iter().enumerate() {
+            if probability == -1 {
+                high = high
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid("a zstd distribution is over-subscribed"))?;
+                symbols[high] = symbol as u8;
+                next[symbol] = 1;
+            } else {
+                next[symbol] = u32::try_from(probability)
+                    .map_err(|_| invalid("a zstd distribution has a negative probability"))?;
+            }
+        }
+
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mask = size - 1;
+        let mut position = 0;
+        for (symbol, &probability) in distribution.iter().enumerate() {
+            for _ in 0..probability.max(0) {
+                symbols[position] = symbol as u8;
+                position = (position + step) & mask;
+                while position >= high {
+                    position = (position + step) & mask;
+                }
+            }
+        }
+        if position != 0 {
+            return Err(invalid("a zstd distribution doesn't fill its table"));
+        }
+
+        let mut bits = vec![0; size];
+        let mut bases = vec![0; size];
+        for state in 0..size {
+            let symbol = usize::from(symbols[state]);
+            let descriptor = next[symbol];
+            next[symbol] += 1;
+            bits[state] = log - descriptor.ilog2();
+            bases[state] = (u64::from(descriptor) << bits[state]) - size as u64;
+        }
+
+        Ok(Self {
+            log,
+            symbols,
+            bits,
+            bases,
+        })
+    }
+
+    /// A table that always decodes the given symbol, without reading any bits.
+    fn rle(symbol: u8) -> Self {
+        Self {
+            log: 0,
+            symbols: vec![symbol],
+            bits: vec![0],
+            bases: vec![0],
+        }
+    }
+
+    /// Read a table's distribution, returning the table and the bytes read.
+    fn read(input: &[u8], max_symbol: usize, max_log: u32) -> io::Result<(Self, usize)> {
+        let truncated = || invalid("a zstd table description is truncated");
+        let mut position = 0;
+        let peek = |position: usize, count: u32| -> io::Result<u32> {
+            let mut value = 0;
+            for bit in 0..count as usize {
+                let index = position + bit;
+                let byte = *input.get(index / 8).ok_or_else(truncated)?;
+                value |= u32::from(byte >> (index % 8) & 1) << bit;
+            }
+            Ok(value)
+        };
+
+        let log = peek(position, 4)? + 5;
+        position += 4;
+        if log > max_log {
+            return Err(invalid("a zstd table is too accurate"));
+        }
+        let mut remaining = 1i32 << log;
+        let mut distribution = Vec::new();
+        while remaining > 0 {
+            if distribution.len() > max_symbol {
+                return Err(invalid("a zstd table has too many symbols"));
+            }
+            let bits = (remaining + 1).ilog2() + 1;
+            let value = peek(position, bits)?;
+            let lower_mask = (1 << (bits - 1)) - 1;
+            let threshold = (1 << bits) - 1 - (remaining as u32 + 1);
+            let value = if value & lower_mask < threshold {
+                position += bits as usize - 1;
+                value & lower_mask
+            } else if value > lower_mask {
+                position += bits as usize;
+                value - threshold
+            } else {
+                position += bits as usize;
+                value
+            };
+
+            let probability = value as i32 - 1;
+            remaining -= probability.abs();
+            distribution.push(probability as i16);
+            if probability == 0 {
+                loop {
+                    let repeat = peek(position, 2)?;
+                    position += 2;
+                    distribution.extend(std::iter::repeat_n(0, repeat as usize));
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+        }
+        if remaining != 0 || distribution.len() > max_symbol + 1 {
+            return Err(invalid("a zstd table's probabilities don't add up"));
+        }
+
+        Ok((Self::new(&distribution, log)?, position.div_ceil(8)))
+    }
+
+    /// The state that follows the given one, reading its bits from the stream.
+    fn next(&self, state: u64, bits: &mut Backward<'_>) -> u64 {
+        let state = state as usize;
+        self.bases[state] + bits.read(self.bits[state])
+    }
+}
+
+/// A Huffman table for decoding literals, indexed by the next `bits` bits of the stream.
+struct Huffman {
+    bits: u32,
+    /// The symbol, and the length of its code, of each index.
+    table: Vec<(u8, u32)>,
+}
+
+impl Huffman {
+    /// Read a table's description, returning the table and the bytes read.
+    fn read(input: &[u8]) -> io::Result<(Self, usize)> {
+        let truncated = || invalid("a zstd Huffman table description is truncated");
+        let header = *input.first().ok_or_else(truncated)?;
+        let (mut weights, consumed) = if header >= 128 {
+            // The weights are written directly, four bits each.
+            let count = usize::from(header - 127);
+            let bytes = input.get(1..1 + count.div_ceil(2)).ok_or_else(truncated)?;
+            let weights = (0..count)
+                .map(|index| match index % 2 {
+                    0 => bytes[index / 2] >> 4,
+                    _ => bytes[index / 2] & 0xf,
+                })
+                .collect();
+            (weights, 1 + count.div_ceil(2))
+        } else {
+            // The weights are compressed, with a finite state entropy code of two states.
+            let compressed = input
+                .get(1..1 + usize::from(header))
+                .ok_or_else(truncated)?;
+            let (fse, consumed) = Fse::read(compressed, MAX_HUFFMAN_BITS as usize, 6)?;
+            let mut bits = Backward::new(&compressed[consumed..])?;
+            let mut states = [bits.read(fse.log), bits.read(fse.log)];
+            let mut weights = Vec::new();
+            for turn in 0.. {
+                let (state, other) = match turn % 2 {
+                    0 => (states[0], states[1]),
+                    _ => (states[1], states[0]),
+                };
+                weights.push(fse.symbols[state as usize]);
+                states[turn % 2] = fse.next(state, &mut bits);
+                if bits.remaining() < 0 {
+                    // The stream ran out, so the other state holds the last weight.
+                    weights.push(fse.symbols[other as usize]);
+                    break;
+                }
+                if weights.len() > 255 {
+                    return Err(invalid("a zstd Huffman table has too many symbols"));
+                }
+            }
+            (weights, 1 + usize::from(header))
+        };
+        if weights.len() > 255 {
+            return Err(invalid("a zstd Huffman table has too many symbols"));
+        }
+
+        // The last symbol's weight is implied by the others, which must leave room for it.
+        let total: u32 = weights
+            .iter()
+            .filter(|&&weight| weight > 0)
+            .map(|&weight| 1 << (weight - 1))
+            .sum();
+        if total == 0
+            || weights
+                .iter()
+                .any(|&weight| u32::from(weight) > MAX_HUFFMAN_BITS)
+        {
+            return Err(invalid("a zstd Huffman table is malformed"));
+        }
+        let bits = total.ilog2() + 1;
+        let left = (1 << bits) - total;
+        if bits > MAX_HUFFMAN_BITS || !left.is_power_of_two() {
+            return Err(invalid("a zstd Huffman table is malformed"));
+        }
+        weights.push(left.ilog2() as u8 + 1);
+
+        // Codes are assigned from the longest to the shortest, in the order of the symbols.
+        let lengths: Vec<u32> = weights
+            .iter()
+            .map(|&weight| match weight {
+                0 => 0,
+                weight => bits + 1 - u32::from(weight),
+            })
+            .collect();
+        let mut starts = vec![0usize; bits as usize + 1];
+        for length in (1..=bits).rev() {
+            let count = lengths.iter().filter(|&&other| other == length).count();
+            if length > 1 {
+                starts[length as usize - 1] = starts[length as usize] + (count << (bits - length));
+            }
+        }
+        let mut table = vec![(0, 0); 1 << bits];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length == 0 {
+                continue;
+            }
+            let start = starts[length as usize];
+            let span = 1 << (bits - length);
+            table[start..start + span].fill((symbol as u8, length));
+            starts[length as usize] += span;
+        }
+
+        Ok((Self { bits, table }, consumed))
+    }
+
+    /// Decode a stream of the given number of symbols onto the output.
+    fn decode(&self, stream: &[u8], count: usize, output: &mut Vec<u8>) -> io::Result<()> {
+        let mut bits = Backward::new(stream)?;
+        let mask = (1 << self.bits) - 1;
+        let mut state = bits.read(self.bits) as usize;
+        for _ in 0..count {
+            let (symbol, length) = self.table[state];
+            output.push(symbol);
+            state = ((state << length) & mask) | bits.read(length) as usize;
+        }
+        // The last reads ran past the start of the stream by exactly the table's width.
+        if bits.remaining() != -i64::from(self.bits) {
+            return Err(invalid("a zstd Huffman stream doesn't hold its literals"));
+        }
+
+        Ok(())
+    }
+}
+
+/**
+Reads a byte slice backward, from its last bit to its first, as zstd writes its entropy-coded
+streams. The stream starts below the highest set bit of the last byte. Bits read past the start of
+the slice are zeros.
+*/
+struct Backward<'a> {
+    input: &'a [u8],
+    /// The number of bits left, which is negative once the start has been read past.
+    position: i64,
+}
+
+impl<'a> Backward<'a> {
+    fn new(input: &'a [u8]) -> io::Result<Self> {
+        let last = input
+            .last()
+            .filter(|&&last| last != 0)
+            .ok_or_else(|| invalid("a zstd bitstream has no start marker"))?;
+        let position = input.len() as i64 * 8 - i64::from(last.leading_zeros()) - 1;
+
+        Ok(Self { input, position })
+    }
+
+    fn read(&mut self, count: u32) -> u64 {
+        self.position -= i64::from(count);
+        let mut value = 0;
+        for bit in 0..count {
+            let index = self.position + i64::from(bit);
+            if let Ok(index) = usize::try_from(index) {
+                value |= u64::from(self.input[index / 8] >> (index % 8) & 1) << bit;
+            }
+        }
+        value
+    }
+
+    fn remaining(&self) -> i64 {
+        self.position
+    }
+}
+
+/// Read a little-endian integer of the given number of bytes, up to 8, at the given offset.
+fn read_le(input: &[u8], offset: usize, size: usize) -> Option<u64> {
+    let bytes = input.get(offset..offset.checked_add(size)?)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | u64::from(byte)),
+    )
+}
+
+fn read_u32(input: &[u8], offset: usize) -> Option<u32> {
+    read_le(input, offset, 4).map(|value| value as u32)
+}
+
+/// The XXH64 hash, with a seed of 0, whose low 32 bits are zstd's content checksums.
+fn xxh64(data: &[u8]) -> u64 {
+    const PRIMES: [u64; 5] = [
+        0x9e37_79b1_85eb_ca87,
+        0xc2b2_ae3d_27d4_eb4f,
+        0x1656_67b1_9e37_79f9,
+        0x85eb_ca77_c2b2_ae63,
+        0x27d4_eb2f_1656_67c5,
+    ];
+    let round = |accumulator: u64, lane: u64| {
+        accumulator
+            .wrapping_add(lane.wrapping_mul(PRIMES[1]))
+            .rotate_left(31)
+            .wrapping_mul(PRIMES[0])
+    };
+    let lane = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().expect("lanes are 8 bytes"));
+
+    let mut stripes = data.chunks_exact(32);
+    let mut hash = if data.len() >= 32 {
+        let mut accumulators = [
+            PRIMES[0].wrapping_add(PRIMES[1]),
+            PRIMES[1],
+            0,
+            0u64.wrapping_sub(PRIMES[0]),
+        ];
+        for stripe in &mut stripes {
+            for (accumulator, bytes) in accumulators.iter_mut().zip(stripe.chunks_exact(8)) {
+                *accumulator = round(*accumulator, lane(bytes));
+            }
+        }
+        let mut hash = accumulators[0]
+            .rotate_left(1)
+            .wrapping_add(accumulators[1].rotate_left(7))
+            .wrapping_add(accumulators[2].rotate_left(12))
+            .wrapping_add(accumulators[3].rotate_left(18));
+        for accumulator in accumulators {
+            hash = (hash ^ round(0, accumulator))
+                .wrapping_mul(PRIMES[0])
+                .wrapping_add(PRIMES[3]);
+        }
+        hash
+    } else {
+        PRIMES[4]
+    };
+    hash = hash.wrapping_add(data.len() as u64);
+
+    let mut rest = stripes.remainder();
+    while let Some((bytes, remaining)) = rest.split_first_chunk::<8>() {
+        hash = (hash ^ round(0, lane(bytes)))
+            .rotate_left(27)
+            .wrapping_mul(PRIMES[0])
+            .wrapping_add(PRIMES[3]);
+        rest = remaining;
+    }
+    if let Some((bytes, remaining)) = rest.split_first_chunk::<4>() {
+        hash = (hash ^ u64::from(u32::from_le_bytes(*bytes)).wrapping_mul(PRIMES[0]))
+            .rotate_left(23)
+            .wrapping_mul(PRIMES[1])
+            .wrapping_add(PRIMES[2]);
+        rest = remaining;
+    }
+    for &byte in rest {
+        hash = (hash ^ u64::from(byte).wrapping_mul(PRIMES[4]))
+            .rotate_left(11)
+            .wrapping_mul(PRIMES[0]);
+    }
+
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(PRIMES[1]);
+    hash ^= hash >> 29;
+    hash = hash.wrapping_mul(PRIMES[2]);
+    hash ^ hash >> 32
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn zstd_files_should_decompress() {
+        let original = std::fs::read("fixtures/encoded/plain.txt").unwrap();
+        for path in [
+            "fixtures/encoded/dir-a/data.txt.zst",
+            "fixtures/encoded/dir-b/data.txt.zst",
+        ] {
+            let compressed = std::fs::read(path).unwrap();
+            assert_eq!(super::unzstd(&compressed).unwrap(), original, "{path}");
+        }
+    }
+
+    #[test]
+    fn xxh64_should_match_the_reference() {
+        assert_eq!(super::xxh64(b""), 0xef46_db37_51d8_e999);
+        assert_eq!(super::xxh64(b"abc"), 0x44bc_2cf5_ad77_0999);
+    }
+
+    #[test]
+    fn corrupt_zstd_data_should_be_rejected() {
+        let compressed = std::fs::read("fixtures/encoded/dir-a/data.txt.zst").unwrap();
+        for end in [3, 6, compressed.len() / 2, compressed.len() - 1] {
+            assert!(super::unzstd(&compressed[..end]).is_err(), "{end}");
+        }
+        let mut flipped = compressed.clone();
+        let middle = flipped.len() / 2;
+        flipped[middle] ^= 0x10;
+        assert!(super::unzstd(&flipped).is_err());
+    }
+}